@@ -1,11 +1,21 @@
 pub mod db_cache;
+mod dump;
 pub mod fast_cache;
 pub mod msec;
+pub mod single_flight;
+
+#[cfg(feature = "redis")]
+pub mod remote_cache;
+#[cfg(not(feature = "redis"))]
+#[path = "cache/remote_cache_dummy.rs"]
+pub mod remote_cache;
+
+mod storage_format;
 
 #[cfg(all(feature = "uring", target_os = "linux"))]
+#[path = "cache/storage_cache_uring.rs"]
 pub mod storage_cache;
 #[cfg(not(all(feature = "uring", target_os = "linux")))]
-#[path = "cache/storage_cache_dummy.rs"]
 pub mod storage_cache;
 
 pub trait CycleCounter {
@@ -1,21 +1,30 @@
 use anyhow::Result;
 use byte_unit::Byte;
 use downcast_rs::{DowncastSync, impl_downcast};
-use fxhash::FxBuildHasher;
+use fxhash::{FxBuildHasher, FxHashMap, FxHashSet};
 use log::error;
-use moka::{future::Cache, notification::RemovalCause};
+use moka::{Expiry, future::Cache, notification::RemovalCause};
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use std::str::FromStr;
 use std::{
     fs,
     path::Path,
     sync::{
-        Arc,
-        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+        atomic::{AtomicI64, AtomicU64, Ordering},
     },
+    time::{Duration, Instant},
 };
 
 use crate::ShardId;
-use crate::{cache::fast_cache::FastCache, cache::storage_cache::StorageCache};
+use crate::{
+    cache::dump::{self, DumpConfig},
+    cache::fast_cache::FastCache,
+    cache::remote_cache::RemoteCache,
+    cache::single_flight::SingleFlight,
+    cache::storage_cache::StorageCache,
+};
 
 use super::msec::{MSEC_SHR, MSec, get_cache_time};
 
@@ -30,6 +39,7 @@ const DEFAULT_DISK_CACHE_INDEX_SIZE: &str = "8MiB";
 const DEFAULT_DISK_CACHE_FILE_NUM: &str = "1";
 const DEFAULT_DISK_CACHE_FILE_SIZE: &str = "100MiB";
 const DEFAULT_CACHE_TTL: &str = "86400";
+const DEFAULT_REMOTE_CACHE_TIMEOUT: &str = "100";
 const DISK_CACHE_FILE_NAME: &str = "cache-%Y%m%d%H%M%S";
 
 pub trait CacheVal: DowncastSync + std::fmt::Debug {
@@ -54,6 +64,199 @@ pub trait HashVal: Send + Sync {
     fn hash_val(&self, shard_id: ShardId) -> u128;
 }
 
+static TYPE_NAMES: Lazy<RwLock<FxHashMap<u64, &'static str>>> =
+    Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+/// Registers the name of the cached type shown in the metrics.
+pub fn register_type_name(type_id: u64, name: &'static str) {
+    TYPE_NAMES.write().unwrap().insert(type_id, name);
+}
+
+/// Settings of a cached type that override the defaults of the DB
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CachePolicy {
+    /// Time to live in seconds
+    pub ttl: Option<u64>,
+    /// Percentage applied to the size when weighing entries for eviction
+    pub weight: Option<u32>,
+    /// Upper limit of the bytes in the long cache per shard
+    pub limit: Option<u64>,
+    /// Seconds a value past its TTL is still returned while it is reloaded
+    pub stale: Option<u64>,
+}
+
+static POLICIES: Lazy<RwLock<FxHashMap<u64, CachePolicy>>> =
+    Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+/// Registers the cache settings of the type.
+pub fn register_type_policy(type_id: u64, policy: CachePolicy) {
+    POLICIES.write().unwrap().insert(type_id, policy);
+}
+
+fn policy_of(type_id: u64) -> Option<CachePolicy> {
+    POLICIES.read().unwrap().get(&type_id).copied()
+}
+
+fn weigh(value: &Arc<dyn CacheVal>) -> u32 {
+    let size = value._size().saturating_add(MOKA_BASE_MEMORY);
+    match policy_of(value._type_id()).and_then(|p| p.weight) {
+        Some(weight) => (size as u64 * weight as u64 / 100).clamp(1, u32::MAX as u64) as u32,
+        None => size,
+    }
+}
+
+/// Expires the long cache entries with the TTL of their type.
+struct TypeExpiry(Duration);
+
+impl TypeExpiry {
+    fn duration(&self, value: &Arc<dyn CacheVal>) -> Duration {
+        match policy_of(value._type_id()) {
+            Some(policy) => {
+                policy.ttl.map(Duration::from_secs).unwrap_or(self.0)
+                    + Duration::from_secs(policy.stale.unwrap_or_default())
+            }
+            None => self.0,
+        }
+    }
+}
+
+impl Expiry<u128, Arc<dyn CacheVal>> for TypeExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &u128,
+        value: &Arc<dyn CacheVal>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(self.duration(value))
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &u128,
+        value: &Arc<dyn CacheVal>,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(self.duration(value))
+    }
+}
+
+pub type Decoder = fn(&[u8]) -> Result<Arc<dyn CacheVal>>;
+
+static DECODERS: Lazy<RwLock<FxHashMap<u64, (u64, Decoder)>>> =
+    Lazy::new(|| RwLock::new(FxHashMap::default()));
+
+/// Registers the decoder used to restore the long cache from the dump file.
+/// Types without a decoder are not dumped.
+/// Dumped entries whose fingerprint differs from `fingerprint` are skipped, so change it when the encoded layout changes.
+pub fn register_decoder(type_id: u64, fingerprint: u64, decoder: Decoder) {
+    DECODERS
+        .write()
+        .unwrap()
+        .insert(type_id, (fingerprint, decoder));
+}
+
+/// Invalidations received before the dump file is loaded
+#[derive(Default)]
+struct Pending {
+    hashes: FxHashSet<u128>,
+    types: FxHashSet<u64>,
+    all: bool,
+}
+
+#[derive(Default)]
+struct TypeStats {
+    hit: AtomicU64,
+    miss: AtomicU64,
+    evicted: AtomicU64,
+    entries: AtomicI64,
+    bytes: AtomicI64,
+    long_bytes: AtomicI64,
+}
+
+/// Counters per type and shard
+#[derive(Default)]
+struct StatsMap(RwLock<FxHashMap<(u64, ShardId), Arc<TypeStats>>>);
+
+impl StatsMap {
+    fn get(&self, type_id: u64, shard_id: ShardId) -> Arc<TypeStats> {
+        if let Some(stats) = self.0.read().unwrap().get(&(type_id, shard_id)) {
+            return Arc::clone(stats);
+        }
+        Arc::clone(
+            self.0
+                .write()
+                .unwrap()
+                .entry((type_id, shard_id))
+                .or_default(),
+        )
+    }
+
+    fn inserted(&self, value: &Arc<dyn CacheVal>, long: bool) {
+        let stats = self.get(value._type_id(), value._shard_id());
+        stats.entries.fetch_add(1, Ordering::Relaxed);
+        stats
+            .bytes
+            .fetch_add(value._size() as i64, Ordering::Relaxed);
+        if long {
+            stats
+                .long_bytes
+                .fetch_add(value._size() as i64, Ordering::Relaxed);
+        }
+    }
+
+    fn removed(&self, value: &Arc<dyn CacheVal>, cause: RemovalCause, long: bool) {
+        let stats = self.get(value._type_id(), value._shard_id());
+        if cause == RemovalCause::Size {
+            stats.evicted.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.entries.fetch_sub(1, Ordering::Relaxed);
+        stats
+            .bytes
+            .fetch_sub(value._size() as i64, Ordering::Relaxed);
+        if long {
+            stats
+                .long_bytes
+                .fetch_sub(value._size() as i64, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CacheMetrics {
+    pub cache_request_count: u64,
+    pub fast_cache_hit: u64,
+    pub long_cache_hit: u64,
+    pub short_cache_hit: u64,
+    pub version_cache_hit: u64,
+    pub storage_cache_hit: u64,
+    pub remote_cache_hit: u64,
+    pub long_cache_evicted: u64,
+    pub short_cache_evicted: u64,
+    pub version_cache_evicted: u64,
+    /// Weighted size of the long cache in bytes
+    pub long_cache_size: u64,
+    /// Weighted size of the short cache in bytes
+    pub short_cache_size: u64,
+    pub types: Vec<TypeCacheMetrics>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TypeCacheMetrics {
+    pub type_id: u64,
+    /// Registered with register_type_name
+    pub name: Option<&'static str>,
+    pub shard_id: ShardId,
+    pub hit: u64,
+    pub miss: u64,
+    /// Entries evicted from the short and long caches due to capacity
+    pub evicted: u64,
+    /// Entries in the short, version and long caches
+    pub entries: u64,
+    /// Size of the entries in bytes
+    pub bytes: u64,
+}
+
 fn get_fast_cache(name: &str, time_to_live: u64) -> FastCache {
     let index_size = Byte::from_str(
         &std::env::var(format!("{}_FAST_CACHE_INDEX_SIZE", name))
@@ -67,6 +270,7 @@ fn get_fast_cache(name: &str, time_to_live: u64) -> FastCache {
 fn get_short_cache(
     name: &str,
     short_cache_evicted: Arc<AtomicU64>,
+    stats: Arc<StatsMap>,
 ) -> Cache<u128, Arc<dyn CacheVal>, FxBuildHasher> {
     let capacity = Byte::from_str(
         &std::env::var(format!("{}_SHORT_CACHE_CAPACITY", name))
@@ -81,16 +285,15 @@ fn get_short_cache(
         .unwrap_or_else(|e| panic!("{}_SHORT_CACHE_TIME has an error:{:?}", name, e));
 
     Cache::builder()
-        .weigher(|_key, value: &Arc<dyn CacheVal>| -> u32 {
-            value._size().saturating_add(MOKA_BASE_MEMORY)
-        })
+        .weigher(|_key, value: &Arc<dyn CacheVal>| -> u32 { weigh(value) })
         .max_capacity(capacity)
-        .time_to_live(std::time::Duration::from_secs(time_to_live))
+        .time_to_live(Duration::from_secs(time_to_live))
         .support_invalidation_closures()
-        .eviction_listener(move |_k, _v, cause| {
+        .eviction_listener(move |_k, v, cause| {
             if cause == RemovalCause::Size {
                 short_cache_evicted.fetch_add(1, Ordering::Relaxed);
             }
+            stats.removed(&v, cause, false);
         })
         .build_with_hasher(FxBuildHasher::default())
 }
@@ -99,6 +302,7 @@ fn get_long_cache(
     name: &str,
     long_cache_evicted: Arc<AtomicU64>,
     storage_cache: Option<Arc<StorageCache>>,
+    stats: Arc<StatsMap>,
 ) -> Cache<u128, Arc<dyn CacheVal>, FxBuildHasher> {
     let capacity = Byte::from_str(
         &std::env::var(format!("{}_LONG_CACHE_CAPACITY", name))
@@ -118,17 +322,16 @@ fn get_long_cache(
         .unwrap_or_else(|e| panic!("{}_LONG_CACHE_IDLE_TIME has an error:{:?}", name, e));
 
     Cache::builder()
-        .weigher(|_key, value: &Arc<dyn CacheVal>| -> u32 {
-            value._size().saturating_add(MOKA_BASE_MEMORY)
-        })
+        .weigher(|_key, value: &Arc<dyn CacheVal>| -> u32 { weigh(value) })
         .max_capacity(capacity)
-        .time_to_live(std::time::Duration::from_secs(time_to_live))
-        .time_to_idle(std::time::Duration::from_secs(time_to_idle))
+        .expire_after(TypeExpiry(Duration::from_secs(time_to_live)))
+        .time_to_idle(Duration::from_secs(time_to_idle))
         .support_invalidation_closures()
         .eviction_listener(move |k, v, cause| {
             if cause == RemovalCause::Size {
                 long_cache_evicted.fetch_add(1, Ordering::Relaxed);
             }
+            stats.removed(&v, cause, true);
             if cause.was_evicted()
                 && let Some(ref storage_cache) = storage_cache
                 && let Ok(buf) = v._encode()
@@ -181,21 +384,41 @@ fn get_storage_cache(
     StorageCache::start(path, index_size, file_num, file_size, time_to_live)
 }
 
+fn get_remote_cache(name: &str, ttl: u64) -> Result<Option<RemoteCache>> {
+    let Ok(url) = std::env::var(format!("{}_REMOTE_CACHE_URL", name)) else {
+        return Ok(None);
+    };
+    let prefix =
+        std::env::var(format!("{}_REMOTE_CACHE_PREFIX", name)).unwrap_or_else(|_| name.to_owned());
+    let timeout = std::env::var(format!("{}_REMOTE_CACHE_TIMEOUT", name))
+        .unwrap_or_else(|_| DEFAULT_REMOTE_CACHE_TIMEOUT.to_owned())
+        .parse::<u64>()
+        .unwrap_or_else(|e| panic!("{}_REMOTE_CACHE_TIMEOUT has an error:{:?}", name, e));
+    Ok(Some(RemoteCache::start(&url, prefix, timeout, ttl)?))
+}
+
 pub struct DbCache {
     fast_cache: Option<FastCache>,
     short_cache: Cache<u128, Arc<dyn CacheVal>, FxBuildHasher>,
     version_cache: Cache<u128, Arc<dyn CacheVal>, FxBuildHasher>,
     long_cache: Cache<u128, Arc<dyn CacheVal>, FxBuildHasher>,
     storage_cache: Option<Arc<StorageCache>>,
+    remote_cache: Option<Arc<RemoteCache>>,
     fast_cache_hit: AtomicU64,
     long_cache_hit: AtomicU64,
     short_cache_hit: AtomicU64,
     version_cache_hit: AtomicU64,
     storage_cache_hit: AtomicU64,
+    remote_cache_hit: AtomicU64,
     cache_request_count: AtomicU64,
     long_cache_evicted: Arc<AtomicU64>,
     short_cache_evicted: Arc<AtomicU64>,
     version_cache_evicted: Arc<AtomicU64>,
+    stats: Arc<StatsMap>,
+    dump: Option<DumpConfig>,
+    pending: Mutex<Option<Pending>>,
+    started: MSec,
+    single_flight: SingleFlight,
     ttl: u64,
 }
 
@@ -211,6 +434,9 @@ impl DbCache {
             .unwrap_or_else(|_| DEFAULT_CACHE_TTL.to_owned())
             .parse::<u64>()
             .unwrap_or_else(|e| panic!("{}_CACHE_TTL has an error:{:?}", name, e));
+        let remote_cache = get_remote_cache(name, ttl)?.map(Arc::new);
+        let dump = DumpConfig::from_env(name, path);
+        let pending = Mutex::new(dump.as_ref().map(|_| Pending::default()));
         let ttl = ttl.saturating_mul(1_000_000_000 / (1 << MSEC_SHR));
 
         let fast_cache = if use_fast_cache {
@@ -219,37 +445,46 @@ impl DbCache {
             None
         };
         let storage_cache = if use_storage_cache && let Some(path) = path {
-            Some(Arc::new(get_storage_cache(
-                name,
-                is_hot_deploy,
-                path,
-                ttl,
-            )?))
+            Some(Arc::new(get_storage_cache(name, is_hot_deploy, path, ttl)?))
         } else {
             None
         };
+        let stats = Arc::new(StatsMap::default());
         let short_cache_evicted = Arc::new(AtomicU64::new(0));
-        let short_cache = get_short_cache(name, Arc::clone(&short_cache_evicted));
+        let short_cache =
+            get_short_cache(name, Arc::clone(&short_cache_evicted), Arc::clone(&stats));
         let version_cache_evicted = Arc::new(AtomicU64::new(0));
-        let version_cache = get_short_cache(name, Arc::clone(&version_cache_evicted));
+        let version_cache =
+            get_short_cache(name, Arc::clone(&version_cache_evicted), Arc::clone(&stats));
         let long_cache_evicted = Arc::new(AtomicU64::new(0));
-        let long_cache =
-            get_long_cache(name, Arc::clone(&long_cache_evicted), storage_cache.clone());
+        let long_cache = get_long_cache(
+            name,
+            Arc::clone(&long_cache_evicted),
+            storage_cache.clone(),
+            Arc::clone(&stats),
+        );
         Ok(DbCache {
             fast_cache,
             short_cache,
             version_cache,
             long_cache,
             storage_cache,
+            remote_cache,
             fast_cache_hit: AtomicU64::new(0),
             long_cache_hit: AtomicU64::new(0),
             short_cache_hit: AtomicU64::new(0),
             version_cache_hit: AtomicU64::new(0),
             storage_cache_hit: AtomicU64::new(0),
+            remote_cache_hit: AtomicU64::new(0),
             cache_request_count: AtomicU64::new(0),
             long_cache_evicted,
             short_cache_evicted,
             version_cache_evicted,
+            stats,
+            dump,
+            pending,
+            started: MSec::now(),
+            single_flight: SingleFlight::default(),
             ttl,
         })
     }
@@ -260,13 +495,117 @@ impl DbCache {
         }
     }
 
+    /// Writes the long cache entries to the dump file and returns the number of entries.
+    /// Only types registered with register_decoder are written.
+    pub fn dump(&self) -> Result<usize> {
+        let Some(ref config) = self.dump else {
+            return Ok(0);
+        };
+        let decoders = DECODERS.read().unwrap();
+        let (_, msec) = get_cache_time();
+        let entries = self.long_cache.iter().filter_map(|(hash, value)| {
+            let (fingerprint, _) = decoders.get(&value._type_id())?;
+            if value
+                ._time()
+                .less_than_ttl(msec, self.ttl_of(value._type_id()))
+            {
+                return None;
+            }
+            match value._encode() {
+                Ok(data) => Some(dump::Entry {
+                    hash: *hash,
+                    type_id: value._type_id(),
+                    fingerprint: *fingerprint,
+                    data,
+                }),
+                Err(e) => {
+                    error!("{}", e);
+                    None
+                }
+            }
+        });
+        dump::write(&config.path, entries, config.size)
+    }
+
+    /// Restores the long cache from the dump file written by the old server during a hot deploy.
+    /// The dump is discarded on a cold start, because updates made while no server was running
+    /// have not been invalidated in it.
+    pub async fn load_dump(&self, is_hot_deploy: bool) -> Result<usize> {
+        let Some(ref config) = self.dump else {
+            return Ok(0);
+        };
+        let result = if is_hot_deploy {
+            self._load_dump(config).await
+        } else {
+            Ok(0)
+        };
+        let _ = fs::remove_file(&config.path);
+        *self.pending.lock().unwrap() = None;
+        result
+    }
+
+    /// Waits for the old server to write the dump file after this server started.
+    async fn _load_dump(&self, config: &DumpConfig) -> Result<usize> {
+        let deadline = tokio::time::Instant::now() + config.wait;
+        let time = loop {
+            match dump::read_time(&config.path) {
+                Ok(time) if !time.less_than(self.started) => break time,
+                _ if tokio::time::Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+                _ => return Ok(0),
+            }
+        };
+        if time
+            .add_sec(config.max_age.as_secs())
+            .less_than(MSec::now())
+        {
+            return Ok(0);
+        }
+        let path = config.path.clone();
+        let entries = tokio::task::spawn_blocking(move || dump::read(&path)).await??;
+        let mut count = 0;
+        for entry in entries {
+            let decoder = DECODERS.read().unwrap().get(&entry.type_id).copied();
+            let Some((fingerprint, decoder)) = decoder else {
+                continue;
+            };
+            if fingerprint != entry.fingerprint {
+                continue;
+            }
+            let value = match decoder(&entry.data) {
+                Ok(value) => value,
+                Err(e) => {
+                    error!("{}", e);
+                    continue;
+                }
+            };
+            {
+                let pending = self.pending.lock().unwrap();
+                match pending.as_ref() {
+                    Some(p) if p.all || p.types.contains(&entry.type_id) => continue,
+                    Some(p) if p.hashes.contains(&entry.hash) => continue,
+                    _ => {}
+                }
+            }
+            if self.long_cache.contains_key(&entry.hash) {
+                continue;
+            }
+            self.insert_long_cache(entry.hash, value).await;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     pub async fn insert_short(&self, id: &dyn HashVal, value: Arc<dyn CacheVal>) {
         let hash = id.hash_val(value._shard_id());
+        self.stats.inserted(&value, false);
         self.short_cache.insert(hash, value).await
     }
 
     pub async fn insert_version(&self, id: &dyn HashVal, value: Arc<dyn CacheVal>) {
         let hash = id.hash_val(value._shard_id());
+        self.stats.inserted(&value, false);
         self.version_cache.insert(hash, value).await
     }
 
@@ -277,13 +616,71 @@ impl DbCache {
         use_fast_cache: bool,
     ) {
         let hash = id.hash_val(value._shard_id());
+        if let Some(ref remote_cache) = self.remote_cache {
+            match value._encode() {
+                Ok(buf) => {
+                    let remote_cache = Arc::clone(remote_cache);
+                    let type_id = value._type_id();
+                    tokio::spawn(async move { remote_cache.write(hash, type_id, &buf).await });
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
         if use_fast_cache && let Some(ref fast_cache) = self.fast_cache {
             let old = fast_cache.insert(hash, value);
             if let Some(old) = old {
-                self.long_cache.insert(old.0, old.1).await;
+                self.insert_long_cache(old.0, old.1).await;
             }
             return;
         }
+        self.insert_long_cache(hash, value).await;
+    }
+
+    fn ttl_of(&self, type_id: u64) -> u64 {
+        policy_of(type_id)
+            .and_then(|p| p.ttl)
+            .map(|ttl| ttl.saturating_mul(1_000_000_000 / (1 << MSEC_SHR)))
+            .unwrap_or(self.ttl)
+    }
+
+    fn stale_of(&self, type_id: u64) -> u64 {
+        policy_of(type_id)
+            .and_then(|p| p.stale)
+            .map(|stale| stale.saturating_mul(1_000_000_000 / (1 << MSEC_SHR)))
+            .unwrap_or_default()
+    }
+
+    /// Whether the value is past its TTL and returned only within the stale-while-revalidate window
+    pub fn is_stale<T>(&self, value: &T) -> bool
+    where
+        T: CacheVal,
+    {
+        let (_, msec) = get_cache_time();
+        self.stale_of(T::__type_id()) > 0
+            && value
+                ._time()
+                .less_than_ttl(msec, self.ttl_of(T::__type_id()))
+    }
+
+    pub fn single_flight(&self) -> &SingleFlight {
+        &self.single_flight
+    }
+
+    async fn insert_long_cache(&self, hash: u128, value: Arc<dyn CacheVal>) {
+        // Entries over the limit of the type are not cached until others expire.
+        // The key is invalidated so that an update does not leave the old value.
+        if let Some(limit) = policy_of(value._type_id()).and_then(|p| p.limit)
+            && self
+                .stats
+                .get(value._type_id(), value._shard_id())
+                .long_bytes
+                .load(Ordering::Relaxed)
+                >= limit as i64
+        {
+            self.long_cache.invalidate(&hash).await;
+            return;
+        }
+        self.stats.inserted(&value, true);
         self.long_cache.insert(hash, value).await;
     }
 
@@ -294,16 +691,43 @@ impl DbCache {
         use_fast_cache: bool,
         from_memory: bool,
     ) -> Option<Arc<T>>
+    where
+        T: CacheVal,
+    {
+        let val = self
+            ._get::<T>(hash, shard_id, use_fast_cache, from_memory)
+            .await;
+        // Lookups only in memory are made when receiving updates, not by requests.
+        if !from_memory {
+            let stats = self.stats.get(T::__type_id(), shard_id);
+            if val.is_some() {
+                stats.hit.fetch_add(1, Ordering::Relaxed);
+            } else {
+                stats.miss.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        val
+    }
+
+    async fn _get<T>(
+        &self,
+        hash: u128,
+        shard_id: ShardId,
+        use_fast_cache: bool,
+        from_memory: bool,
+    ) -> Option<Arc<T>>
     where
         T: CacheVal,
     {
         let (now, msec) = get_cache_time();
+        let ttl = self.ttl_of(T::__type_id());
+        let stale_ttl = ttl.saturating_add(self.stale_of(T::__type_id()));
         self.cache_request_count.fetch_add(1, Ordering::Relaxed);
 
         if use_fast_cache && let Some(ref fast_cache) = self.fast_cache {
             let val = fast_cache
                 .get(hash, now, msec)
-                .filter(|v| v._shard_id() == shard_id)
+                .filter(|v| v._shard_id() == shard_id && !v._time().less_than_ttl(msec, stale_ttl))
                 .map(|v| v.downcast_arc::<T>().ok())
                 .unwrap_or(None);
             if val.is_some() {
@@ -320,7 +744,7 @@ impl DbCache {
             .map(|v| v.downcast_arc::<T>().ok())
             .unwrap_or(None);
         if let Some(val) = val {
-            if val._time().less_than_ttl(msec, self.ttl) {
+            if val._time().less_than_ttl(msec, stale_ttl) {
                 return None;
             }
             if use_fast_cache && let Some(ref fast_cache) = self.fast_cache {
@@ -339,7 +763,7 @@ impl DbCache {
             .unwrap_or(None);
         if let Some(val) = val {
             self.short_cache_hit.fetch_add(1, Ordering::Relaxed);
-            self.long_cache.insert(hash, val.clone()).await;
+            self.insert_long_cache(hash, val.clone()).await;
             return Some(val);
         }
 
@@ -357,7 +781,27 @@ impl DbCache {
                     if v._shard_id() == shard_id {
                         let val = Arc::new(v);
                         self.storage_cache_hit.fetch_add(1, Ordering::Relaxed);
-                        self.long_cache.insert(hash, val.clone()).await;
+                        self.insert_long_cache(hash, val.clone()).await;
+                        return Some(val);
+                    }
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        if let Some(ref remote_cache) = self.remote_cache
+            && let Some((buf, floor)) = remote_cache.read(hash, T::__type_id()).await
+        {
+            match T::_decode(&buf) {
+                Ok(v) => {
+                    // Values cached before the bulk invalidation are stale.
+                    if v._shard_id() == shard_id
+                        && !v._time().less_than(floor)
+                        && !v._time().less_than_ttl(msec, ttl)
+                    {
+                        let val = Arc::new(v);
+                        self.remote_cache_hit.fetch_add(1, Ordering::Relaxed);
+                        self.insert_long_cache(hash, val.clone()).await;
                         return Some(val);
                     }
                 }
@@ -380,11 +824,17 @@ impl DbCache {
     }
 
     pub async fn invalidate(&self, id: &dyn HashVal, shard_id: ShardId) {
+        if let Some(ref mut pending) = *self.pending.lock().unwrap() {
+            pending.hashes.insert(id.hash_val(shard_id));
+        }
         if let Some(ref fast_cache) = self.fast_cache {
             fast_cache.invalidate(id.hash_val(shard_id));
         }
         self.short_cache.invalidate(&id.hash_val(shard_id)).await;
         self.long_cache.invalidate(&id.hash_val(shard_id)).await;
+        if let Some(ref remote_cache) = self.remote_cache {
+            remote_cache.invalidate(id.hash_val(shard_id)).await;
+        }
     }
 
     pub async fn invalidate_version(&self, id: &dyn HashVal, shard_id: ShardId) {
@@ -395,6 +845,9 @@ impl DbCache {
     where
         T: CacheVal,
     {
+        if let Some(ref mut pending) = *self.pending.lock().unwrap() {
+            pending.types.insert(T::__type_id());
+        }
         self.short_cache
             .invalidate_entries_if(|_k, v| v.clone().downcast_arc::<T>().is_ok())
             .unwrap();
@@ -407,6 +860,12 @@ impl DbCache {
         if let Some(ref fast_cache) = self.fast_cache {
             fast_cache.invalidate_all_of(T::__type_id());
         }
+        if let Some(ref remote_cache) = self.remote_cache
+            && let Ok(handle) = tokio::runtime::Handle::try_current()
+        {
+            let remote_cache = Arc::clone(remote_cache);
+            handle.spawn(async move { remote_cache.invalidate_all_of(T::__type_id()).await });
+        }
     }
 
     pub fn invalidate_all_of_version<T>(&self)
@@ -419,6 +878,9 @@ impl DbCache {
     }
 
     pub fn invalidate_all(&self) {
+        if let Some(ref mut pending) = *self.pending.lock().unwrap() {
+            pending.all = true;
+        }
         self.short_cache.invalidate_all();
         self.version_cache.invalidate_all();
         self.long_cache.invalidate_all();
@@ -428,6 +890,49 @@ impl DbCache {
         if let Some(ref fast_cache) = self.fast_cache {
             fast_cache.invalidate_all();
         }
+        if let Some(ref remote_cache) = self.remote_cache
+            && let Ok(handle) = tokio::runtime::Handle::try_current()
+        {
+            let remote_cache = Arc::clone(remote_cache);
+            handle.spawn(async move { remote_cache.invalidate_all().await });
+        }
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        let names = TYPE_NAMES.read().unwrap();
+        let mut types: Vec<TypeCacheMetrics> = self
+            .stats
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(&(type_id, shard_id), stats)| TypeCacheMetrics {
+                type_id,
+                name: names.get(&type_id).copied(),
+                shard_id,
+                hit: stats.hit.load(Ordering::Relaxed),
+                miss: stats.miss.load(Ordering::Relaxed),
+                evicted: stats.evicted.load(Ordering::Relaxed),
+                entries: stats.entries.load(Ordering::Relaxed).max(0) as u64,
+                bytes: stats.bytes.load(Ordering::Relaxed).max(0) as u64,
+            })
+            .collect();
+        types.sort_by(|a, b| (a.name, a.type_id, a.shard_id).cmp(&(b.name, b.type_id, b.shard_id)));
+        CacheMetrics {
+            cache_request_count: self.cache_request_count(),
+            fast_cache_hit: self.fast_cache_hit(),
+            long_cache_hit: self.long_cache_hit(),
+            short_cache_hit: self.short_cache_hit(),
+            version_cache_hit: self.version_cache_hit(),
+            storage_cache_hit: self.storage_cache_hit(),
+            remote_cache_hit: self.remote_cache_hit(),
+            long_cache_evicted: self.long_cache_evicted(),
+            short_cache_evicted: self.short_cache_evicted(),
+            version_cache_evicted: self.version_cache_evicted(),
+            long_cache_size: self.long_cache.weighted_size(),
+            short_cache_size: self.short_cache.weighted_size(),
+            types,
+        }
     }
 
     pub fn fast_cache_hit(&self) -> u64 {
@@ -445,6 +950,9 @@ impl DbCache {
     pub fn storage_cache_hit(&self) -> u64 {
         self.storage_cache_hit.load(Ordering::Relaxed)
     }
+    pub fn remote_cache_hit(&self) -> u64 {
+        self.remote_cache_hit.load(Ordering::Relaxed)
+    }
     pub fn cache_request_count(&self) -> u64 {
         self.cache_request_count.load(Ordering::Relaxed)
     }
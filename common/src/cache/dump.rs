@@ -0,0 +1,186 @@
+use anyhow::{Result, ensure};
+use byte_unit::Byte;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::msec::MSec;
+
+const MAGIC: &[u8; 8] = b"SNXCDMP2";
+const DEFAULT_DUMP_SIZE: &str = "64MiB";
+const DEFAULT_DUMP_MAX_AGE: &str = "300";
+const DEFAULT_DUMP_WAIT: &str = "60";
+const HEADER_SIZE: u64 = 16;
+const ENTRY_HEADER_SIZE: u64 = 36;
+
+/// Settings of the long cache snapshot taken on shutdown
+pub(crate) struct DumpConfig {
+    pub path: PathBuf,
+    /// Upper limit of the encoded size
+    pub size: u64,
+    /// Snapshots older than this are not loaded.
+    pub max_age: Duration,
+    /// How long a hot-deployed server waits for the snapshot of the old server
+    pub wait: Duration,
+}
+
+impl DumpConfig {
+    pub fn from_env(name: &str, path: Option<&Path>) -> Option<DumpConfig> {
+        let enabled = std::env::var(format!("{}_CACHE_DUMP", name))
+            .map(|v| v == "true" || v == "1")
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        let path = path?.with_extension("dump");
+        let size = Byte::from_str(
+            &std::env::var(format!("{}_CACHE_DUMP_SIZE", name))
+                .unwrap_or_else(|_| DEFAULT_DUMP_SIZE.to_owned()),
+        )
+        .unwrap_or_else(|e| panic!("{}_CACHE_DUMP_SIZE has an error:{:?}", name, e))
+        .as_u64();
+        let max_age = std::env::var(format!("{}_CACHE_DUMP_MAX_AGE", name))
+            .unwrap_or_else(|_| DEFAULT_DUMP_MAX_AGE.to_owned())
+            .parse::<u64>()
+            .unwrap_or_else(|e| panic!("{}_CACHE_DUMP_MAX_AGE has an error:{:?}", name, e));
+        let wait = std::env::var(format!("{}_CACHE_DUMP_WAIT", name))
+            .unwrap_or_else(|_| DEFAULT_DUMP_WAIT.to_owned())
+            .parse::<u64>()
+            .unwrap_or_else(|e| panic!("{}_CACHE_DUMP_WAIT has an error:{:?}", name, e));
+        Some(DumpConfig {
+            path,
+            size,
+            max_age: Duration::from_secs(max_age),
+            wait: Duration::from_secs(wait),
+        })
+    }
+}
+
+pub(crate) struct Entry {
+    pub hash: u128,
+    pub type_id: u64,
+    /// Schema fingerprint of the type when the entry was encoded
+    pub fingerprint: u64,
+    pub data: Vec<u8>,
+}
+
+/// Writes the entries to a temporary file and renames it so that a reader never sees a partial file.
+pub(crate) fn write(path: &Path, entries: impl Iterator<Item = Entry>, size: u64) -> Result<usize> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("dump.tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&MSec::now().inner().to_le_bytes())?;
+    let mut total = 0u64;
+    let mut count = 0;
+    for entry in entries {
+        total += entry.data.len() as u64 + ENTRY_HEADER_SIZE;
+        if total > size {
+            break;
+        }
+        writer.write_all(&entry.hash.to_le_bytes())?;
+        writer.write_all(&entry.type_id.to_le_bytes())?;
+        writer.write_all(&entry.fingerprint.to_le_bytes())?;
+        writer.write_all(&(entry.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&entry.data)?;
+        count += 1;
+    }
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&tmp, path)?;
+    Ok(count)
+}
+
+/// Returns the time the snapshot was taken.
+pub(crate) fn read_time(path: &Path) -> Result<MSec> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_header(&mut reader)
+}
+
+fn read_header(reader: &mut impl Read) -> Result<MSec> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    ensure!(&magic == MAGIC, "invalid cache dump file");
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(MSec::from(u64::from_le_bytes(buf)))
+}
+
+pub(crate) fn read(path: &Path) -> Result<Vec<Entry>> {
+    let file = File::open(path)?;
+    let mut remaining = file.metadata()?.len().saturating_sub(HEADER_SIZE);
+    let mut reader = BufReader::new(file);
+    read_header(&mut reader)?;
+    let mut list = Vec::new();
+    loop {
+        let mut hash = [0u8; 16];
+        match reader.read_exact(&mut hash) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut type_id = [0u8; 8];
+        reader.read_exact(&mut type_id)?;
+        let mut fingerprint = [0u8; 8];
+        reader.read_exact(&mut fingerprint)?;
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as u64;
+        remaining = remaining.saturating_sub(ENTRY_HEADER_SIZE);
+        ensure!(len <= remaining, "invalid cache dump file");
+        remaining -= len;
+        let mut data = vec![0u8; len as usize];
+        reader.read_exact(&mut data)?;
+        list.push(Entry {
+            hash: u128::from_le_bytes(hash),
+            type_id: u64::from_le_bytes(type_id),
+            fingerprint: u64::from_le_bytes(fingerprint),
+            data,
+        });
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(i: u8) -> Entry {
+        Entry {
+            hash: i as u128,
+            type_id: 1,
+            fingerprint: 2,
+            data: vec![i; i as usize],
+        }
+    }
+
+    #[test]
+    fn read_written_entries() -> Result<()> {
+        let path = std::env::temp_dir().join("senax-cache-dump-read.dump");
+        assert_eq!(write(&path, (1..4).map(entry), 1000)?, 3);
+        let list = read(&path)?;
+        assert_eq!(list.len(), 3);
+        assert_eq!(list[2].hash, 3);
+        assert_eq!(list[2].fingerprint, 2);
+        assert_eq!(list[2].data, vec![3; 3]);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reject_invalid_length() -> Result<()> {
+        let path = std::env::temp_dir().join("senax-cache-dump-length.dump");
+        write(&path, (1..2).map(entry), 1000)?;
+        let mut bytes = fs::read(&path)?;
+        let pos = HEADER_SIZE as usize + 32;
+        bytes[pos..pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, bytes)?;
+        assert!(read(&path).is_err());
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
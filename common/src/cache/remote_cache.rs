@@ -0,0 +1,271 @@
+use anyhow::Result;
+use log::warn;
+use redis::aio::ConnectionManager;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::time::timeout;
+
+use super::msec::MSec;
+
+/// Cache shared between hosts on a server speaking the Redis protocol.
+///
+/// The bulk invalidation of a type and the invalidation of a key are recorded as floor times in the server,
+/// and values cached before the floor are treated as missing.
+/// Since writes are not awaited, a write that lands after the invalidation is rejected by the floor of the key.
+pub struct RemoteCache {
+    client: redis::Client,
+    conn: OnceCell<ConnectionManager>,
+    prefix: String,
+    timeout: Duration,
+    /// seconds
+    ttl: u64,
+}
+
+impl RemoteCache {
+    pub fn start(url: &str, prefix: String, timeout_ms: u64, ttl: u64) -> Result<RemoteCache> {
+        Ok(RemoteCache {
+            client: redis::Client::open(url)?,
+            conn: OnceCell::new(),
+            prefix,
+            timeout: Duration::from_millis(timeout_ms),
+            ttl,
+        })
+    }
+
+    async fn conn(&self) -> Result<ConnectionManager> {
+        let conn = self
+            .conn
+            .get_or_try_init(|| async {
+                timeout(self.timeout, ConnectionManager::new(self.client.clone()))
+                    .await
+                    .map_err(|_| io::Error::from(ErrorKind::TimedOut))?
+            })
+            .await?;
+        Ok(conn.clone())
+    }
+
+    fn key(&self, hash: u128) -> String {
+        format!("{}:{:032x}", self.prefix, hash)
+    }
+
+    fn key_floor_key(&self, hash: u128) -> String {
+        format!("{}:floor:{:032x}", self.prefix, hash)
+    }
+
+    fn floor_key(&self, type_id: Option<u64>) -> String {
+        match type_id {
+            Some(type_id) => format!("{}:floor:{:x}", self.prefix, type_id),
+            None => format!("{}:floor", self.prefix),
+        }
+    }
+
+    async fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T> {
+        let mut conn = self.conn().await?;
+        Ok(timeout(self.timeout, cmd.query_async(&mut conn)).await??)
+    }
+
+    pub async fn write(&self, hash: u128, type_id: u64, data: &[u8]) {
+        let mut buf = Vec::with_capacity(8 + data.len());
+        buf.extend_from_slice(&type_id.to_le_bytes());
+        buf.extend_from_slice(data);
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(hash)).arg(buf);
+        if self.ttl > 0 {
+            cmd.arg("EX").arg(self.ttl);
+        }
+        if let Err(e) = self.query::<()>(&cmd).await {
+            warn!("remote cache: {}", e);
+        }
+    }
+
+    /// Returns the data and the floor time of the key.
+    pub async fn read(&self, hash: u128, type_id: u64) -> Option<(Vec<u8>, MSec)> {
+        let mut cmd = redis::cmd("MGET");
+        cmd.arg(self.key(hash))
+            .arg(self.key_floor_key(hash))
+            .arg(self.floor_key(Some(type_id)))
+            .arg(self.floor_key(None));
+        let (data, key_floor, type_floor, all_floor) = match self
+            .query::<(Option<Vec<u8>>, Option<u64>, Option<u64>, Option<u64>)>(&cmd)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("remote cache: {}", e);
+                return None;
+            }
+        };
+        let floor = key_floor
+            .unwrap_or_default()
+            .max(type_floor.unwrap_or_default())
+            .max(all_floor.unwrap_or_default());
+        let mut data = data?;
+        if data.len() < 8 || data[0..8] != type_id.to_le_bytes() {
+            return None;
+        }
+        Some((data.split_off(8), MSec::from(floor)))
+    }
+
+    pub async fn invalidate(&self, hash: u128) {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key_floor_key(hash)).arg(MSec::now().inner());
+        if self.ttl > 0 {
+            cmd.arg("EX").arg(self.ttl);
+        }
+        if let Err(e) = self.query::<()>(&cmd).await {
+            warn!("remote cache: {}", e);
+        }
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(self.key(hash));
+        if let Err(e) = self.query::<()>(&cmd).await {
+            warn!("remote cache: {}", e);
+        }
+    }
+
+    pub async fn invalidate_all_of(&self, type_id: u64) {
+        self.set_floor(Some(type_id)).await;
+    }
+
+    pub async fn invalidate_all(&self) {
+        self.set_floor(None).await;
+    }
+
+    async fn set_floor(&self, type_id: Option<u64>) {
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.floor_key(type_id)).arg(MSec::now().inner());
+        if let Err(e) = self.query::<()>(&cmd).await {
+            warn!("remote cache: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    type Store = Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>;
+
+    /// Minimal server of the Redis protocol supporting SET, MGET and DEL.
+    async fn stand_in() -> (String, Store) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        let store = Store::default();
+        let store2 = store.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, store2.clone()));
+            }
+        });
+        (url, store)
+    }
+
+    async fn read_line(reader: &mut BufReader<TcpStream>) -> Option<String> {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.ok()? == 0 {
+            return None;
+        }
+        Some(line.trim_end().to_string())
+    }
+
+    async fn serve(stream: TcpStream, store: Store) -> Option<()> {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let num: usize = read_line(&mut reader)
+                .await?
+                .strip_prefix('*')?
+                .parse()
+                .ok()?;
+            let mut args = Vec::new();
+            for _ in 0..num {
+                let len: usize = read_line(&mut reader)
+                    .await?
+                    .strip_prefix('$')?
+                    .parse()
+                    .ok()?;
+                let mut buf = vec![0u8; len + 2];
+                reader.read_exact(&mut buf).await.ok()?;
+                buf.truncate(len);
+                args.push(buf);
+            }
+            let res = {
+                let mut store = store.lock().unwrap();
+                match args[0].to_ascii_uppercase().as_slice() {
+                    b"SET" => {
+                        store.insert(args[1].clone(), args[2].clone());
+                        b"+OK\r\n".to_vec()
+                    }
+                    b"DEL" => {
+                        let n = args[1..]
+                            .iter()
+                            .filter(|k| store.remove(*k).is_some())
+                            .count();
+                        format!(":{}\r\n", n).into_bytes()
+                    }
+                    b"MGET" => {
+                        let mut res = format!("*{}\r\n", args.len() - 1).into_bytes();
+                        for key in &args[1..] {
+                            match store.get(key) {
+                                Some(v) => {
+                                    res.extend_from_slice(format!("${}\r\n", v.len()).as_bytes());
+                                    res.extend_from_slice(v);
+                                    res.extend_from_slice(b"\r\n");
+                                }
+                                None => res.extend_from_slice(b"$-1\r\n"),
+                            }
+                        }
+                        res
+                    }
+                    _ => b"+OK\r\n".to_vec(),
+                }
+            };
+            reader.get_mut().write_all(&res).await.ok()?;
+        }
+    }
+
+    #[tokio::test]
+    async fn read_written_data() {
+        let (url, store) = stand_in().await;
+        let cache = RemoteCache::start(&url, "test".to_string(), 1000, 60).unwrap();
+        cache.write(1, 7, b"data").await;
+        let (data, floor) = cache.read(1, 7).await.unwrap();
+        assert_eq!(data, b"data");
+        assert_eq!(floor.inner(), 0);
+        assert!(cache.read(1, 8).await.is_none());
+        assert!(cache.read(2, 7).await.is_none());
+        assert!(store.lock().unwrap().contains_key(cache.key(1).as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn floor() {
+        let (url, _) = stand_in().await;
+        let cache = RemoteCache::start(&url, "test".to_string(), 1000, 60).unwrap();
+        let time = MSec::now();
+        cache.write(1, 7, b"data").await;
+        cache.write(2, 7, b"data").await;
+        cache.invalidate_all_of(7).await;
+        let (_, floor) = cache.read(1, 7).await.unwrap();
+        assert!(!floor.less_than(time));
+
+        cache.invalidate(2).await;
+        assert!(cache.read(2, 7).await.is_none());
+        // A write issued before the invalidation that lands late still has an older time than the floor.
+        cache.write(2, 7, b"late").await;
+        let (_, floor) = cache.read(2, 7).await.unwrap();
+        assert!(!floor.less_than(time));
+    }
+
+    #[tokio::test]
+    async fn unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let cache = RemoteCache::start(&url, "test".to_string(), 100, 60).unwrap();
+        cache.write(1, 7, b"data").await;
+        assert!(cache.read(1, 7).await.is_none());
+    }
+}
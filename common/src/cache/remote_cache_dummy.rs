@@ -0,0 +1,23 @@
+#![allow(unused_variables)]
+use anyhow::{Result, bail};
+
+use super::msec::MSec;
+
+pub struct RemoteCache;
+impl RemoteCache {
+    pub fn start(url: &str, prefix: String, timeout_ms: u64, ttl: u64) -> Result<RemoteCache> {
+        bail!("The remote cache requires the redis feature of senax-common.");
+    }
+
+    pub async fn write(&self, hash: u128, type_id: u64, data: &[u8]) {}
+
+    pub async fn read(&self, hash: u128, type_id: u64) -> Option<(Vec<u8>, MSec)> {
+        None
+    }
+
+    pub async fn invalidate(&self, hash: u128) {}
+
+    pub async fn invalidate_all_of(&self, type_id: u64) {}
+
+    pub async fn invalidate_all(&self) {}
+}
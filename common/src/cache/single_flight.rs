@@ -0,0 +1,98 @@
+use fxhash::{FxHashMap, FxHashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Coalesces concurrent loads of the same cache keys so that only one caller queries the database.
+#[derive(Default)]
+pub struct SingleFlight(Mutex<FxHashMap<u128, Arc<AsyncMutex<()>>>>);
+
+/// Marks the keys as being loaded until dropped.
+pub struct FlightGuard<'a> {
+    owner: &'a SingleFlight,
+    hashes: FxHashSet<u128>,
+    _locks: Vec<OwnedMutexGuard<()>>,
+}
+
+impl FlightGuard<'_> {
+    /// Whether the key is loaded by this guard
+    pub fn contains(&self, hash: u128) -> bool {
+        self.hashes.contains(&hash)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut map = self.owner.0.lock().unwrap();
+        for hash in &self.hashes {
+            map.remove(hash);
+        }
+    }
+}
+
+impl SingleFlight {
+    /// Takes the keys not yet being loaded and waits until the others finish loading.
+    /// Since the keys are taken at once, callers never wait for each other.
+    pub async fn begin(&self, hashes: &[u128]) -> FlightGuard<'_> {
+        let (guard, waits) = self.take(hashes);
+        for flight in waits {
+            let _ = flight.lock().await;
+        }
+        guard
+    }
+
+    /// Takes the keys not yet being loaded without waiting.
+    pub fn try_begin(&self, hashes: &[u128]) -> FlightGuard<'_> {
+        self.take(hashes).0
+    }
+
+    fn take(&self, hashes: &[u128]) -> (FlightGuard<'_>, Vec<Arc<AsyncMutex<()>>>) {
+        let mut guard = FlightGuard {
+            owner: self,
+            hashes: FxHashSet::default(),
+            _locks: Vec::new(),
+        };
+        let mut waits = Vec::new();
+        let mut map = self.0.lock().unwrap();
+        for hash in hashes {
+            if let Some(flight) = map.get(hash) {
+                waits.push(Arc::clone(flight));
+            } else if guard.hashes.insert(*hash) {
+                let flight = Arc::new(AsyncMutex::new(()));
+                guard
+                    ._locks
+                    .push(Arc::clone(&flight).try_lock_owned().unwrap());
+                map.insert(*hash, flight);
+            }
+        }
+        (guard, waits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn follower_waits_for_leader() {
+        let flight = Arc::new(SingleFlight::default());
+        let leader = flight.begin(&[1, 2]).await;
+        assert!(leader.contains(1) && leader.contains(2));
+        assert!(flight.try_begin(&[1]).is_empty());
+
+        let flight2 = Arc::clone(&flight);
+        let follower = tokio::spawn(async move {
+            let guard = flight2.begin(&[2, 3]).await;
+            (guard.contains(2), guard.contains(3))
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!follower.is_finished());
+        drop(leader);
+        assert_eq!(follower.await.unwrap(), (false, true));
+        assert!(flight.try_begin(&[1, 2, 3]).contains(1));
+    }
+}
@@ -0,0 +1,207 @@
+//! On-disk format and index of the storage cache shared by its implementations.
+//!
+//! The cache file is a ring buffer of aligned records. The index is a set-associative table of
+//! the upper bits of the hash and the record position.
+
+use anyhow::{Result, bail};
+use arc_swap::ArcSwapOption;
+use bytes::{Buf, BufMut, BytesMut};
+use fxhash::FxHashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::msec::MSec;
+
+pub(crate) const ALIGNMENT_SHIFT: usize = 3;
+pub(crate) const ALIGNMENT: u64 = 1 << ALIGNMENT_SHIFT;
+pub(crate) const SET_ASSOCIATIVE: u64 = 16;
+pub(crate) const HASH_SHIFT: usize = 40;
+pub(crate) const POS_MASK: u64 = (1 << HASH_SHIFT) - 1;
+pub(crate) const HEADER_SIZE: usize = 40;
+pub(crate) const MAX_FILE_SIZE: u64 = 4398046511104;
+
+pub(crate) type Index = Arc<ArcSwapOption<Vec<AtomicU64>>>;
+
+/// Builds an aligned record. Returns None if the data is too large.
+pub(crate) fn encode(hash: u128, type_id: u64, data: &[u8], time: MSec) -> Option<BytesMut> {
+    if data.len() >= u32::MAX as usize - HEADER_SIZE {
+        return None;
+    }
+    let mut buf = BytesMut::with_capacity(HEADER_SIZE + data.len());
+    buf.put_u128_le(hash);
+    buf.put_u64_le(type_id);
+    buf.put_u64_le(time.inner());
+    buf.put_u32_le(data.len() as u32);
+    buf.put_u32_le(checksum(data));
+    buf.put(data);
+    buf.resize(
+        (buf.len() + ALIGNMENT as usize - 1) & !(ALIGNMENT as usize - 1),
+        0,
+    );
+    Some(buf)
+}
+
+pub(crate) enum Parsed {
+    /// Bytes still to be read
+    Rest(usize),
+    /// Length of the data following the header
+    Done(usize),
+}
+
+#[derive(Debug, derive_more::Display)]
+pub(crate) struct OlderThanTtl;
+impl std::error::Error for OlderThanTtl {}
+
+/// Validates a record read from the head. The buffer must contain the header.
+pub(crate) fn parse(buf: &[u8], hash: u128, type_id: u64, time: MSec) -> Result<Parsed> {
+    let mut chunk = buf;
+    if chunk.get_u128_le() != hash || chunk.get_u64_le() != type_id {
+        bail!("hash or type_id error");
+    }
+    if MSec::from(chunk.get_u64_le()).less_than(time) {
+        bail!(OlderThanTtl);
+    }
+    let len = chunk.get_u32_le() as usize;
+    if buf.len() >= HEADER_SIZE + len {
+        if chunk.get_u32_le() != checksum(&chunk[..len]) {
+            bail!("checksum error");
+        }
+        return Ok(Parsed::Done(len));
+    }
+    Ok(Parsed::Rest(HEADER_SIZE + len - buf.len()))
+}
+
+/// Records written before this time are outdated.
+pub(crate) fn oldest_time(ttl: u64, outdated_map: &FxHashMap<u64, MSec>, type_id: u64) -> MSec {
+    let mut time = MSec::now().sub(ttl);
+    if let Some(outdated) = outdated_map.get(&type_id)
+        && time.less_than(*outdated)
+    {
+        time = *outdated;
+    }
+    time
+}
+
+/// Allocates the position of a record of the size, skipping to the head at the end of the file.
+pub(crate) fn alloc_pos(file_pos: &AtomicU64, size: u64, file_size: u64) -> u64 {
+    let mut pos = file_pos.load(Ordering::Relaxed);
+    if (pos % file_size) + size > file_size {
+        pos = pos.wrapping_add(file_size).saturating_sub(pos % file_size);
+    }
+    file_pos.store(
+        pos.wrapping_add((size + ALIGNMENT - 1) & !(ALIGNMENT - 1)),
+        Ordering::Relaxed,
+    );
+    pos
+}
+
+/// Rounds the byte size of the index down to the number of entries of a power of two.
+pub(crate) fn index_len(index_size: u64) -> usize {
+    let index_size = (index_size / std::mem::size_of::<AtomicU64>() as u64) as usize;
+    1usize << (std::mem::size_of::<usize>() as u32 * 8 - index_size.leading_zeros() - 1)
+}
+
+pub(crate) fn init_index(index: &Index, index_len: usize) {
+    if index.load().is_none() {
+        let mut vec = Vec::with_capacity(index_len);
+        for _i in 0..index_len {
+            vec.push(AtomicU64::new(0));
+        }
+        index.store(Some(Arc::new(vec)));
+    }
+}
+
+pub(crate) fn remove_index(index: &Index, hash: u128) {
+    let index = index.load();
+    if let Some(index) = index.as_ref() {
+        let index_mask = index.len() as u64 - 1;
+        let hash_idx = (hash as u64) & index_mask;
+        for i in 0..SET_ASSOCIATIVE {
+            let candidate = index[((hash_idx + i) & index_mask) as usize].load(Ordering::Relaxed);
+            if (candidate & !POS_MASK) == ((hash as u64) & !POS_MASK) {
+                index[((hash_idx + i) & index_mask) as usize].store(0, Ordering::Relaxed);
+                break;
+            }
+        }
+    }
+}
+
+pub(crate) fn set_index(index: &Index, hash: u128, file_pos: u64) {
+    let index = index.load();
+    if let Some(index) = index.as_ref() {
+        let index_mask = index.len() as u64 - 1;
+        let hash_idx = (hash as u64) & index_mask;
+        let mut idx = 0;
+        let mut pos = index[hash_idx as usize].load(Ordering::Relaxed) & POS_MASK;
+        for i in 0..SET_ASSOCIATIVE {
+            let candidate = index[((hash_idx + i) & index_mask) as usize].load(Ordering::Relaxed);
+            if candidate == 0 || (candidate & !POS_MASK) == ((hash as u64) & !POS_MASK) {
+                idx = i;
+                break;
+            }
+            if u40_less_than(candidate & POS_MASK, pos) {
+                pos = candidate & POS_MASK;
+                idx = i;
+            }
+        }
+        let hash_pos = ((hash as u64) & !POS_MASK) | ((file_pos >> ALIGNMENT_SHIFT) & POS_MASK);
+        index[((hash_idx + idx) & index_mask) as usize].store(hash_pos, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn get_index(
+    index: &Index,
+    hash: u128,
+    file_pos: &AtomicU64,
+    file_size: u64,
+    outdated_pos: &AtomicU64,
+) -> Option<u64> {
+    let index = index.load();
+    if let Some(index) = index.as_ref() {
+        let index_mask = index.len() as u64 - 1;
+        let hash_idx = (hash as u64) & index_mask;
+        for i in 0..SET_ASSOCIATIVE {
+            let candidate = index[((hash_idx + i) & index_mask) as usize].load(Ordering::Relaxed);
+            if (candidate & !POS_MASK) == ((hash as u64) & !POS_MASK) {
+                let pos = (candidate & POS_MASK) << ALIGNMENT_SHIFT;
+                if u40_less_than(outdated_pos.load(Ordering::Relaxed), pos)
+                    && u40_less_than(
+                        file_pos.load(Ordering::Relaxed).wrapping_sub(file_size),
+                        pos,
+                    )
+                {
+                    return Some(pos);
+                } else {
+                    return None;
+                }
+            }
+        }
+    }
+    None
+}
+
+pub(crate) fn u40_less_than(lhs: u64, rhs: u64) -> bool {
+    let lhs = lhs & 0xFFFFFFFFFF;
+    let rhs = rhs & 0xFFFFFFFFFF;
+    let lhs = lhs | ((lhs & 0x8000000000) * 0x1FFFFFE);
+    let rhs = rhs | ((rhs & 0x8000000000) * 0x1FFFFFE);
+    lhs.wrapping_sub(rhs) > u64::MAX / 2
+}
+
+fn checksum(v: &[u8]) -> u32 {
+    let h = fxhash::hash(v);
+    (h >> 32 ^ h) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_u40_less_than() {
+        assert!(u40_less_than(1, 2));
+        assert!(!u40_less_than(1, 1));
+        assert!(!u40_less_than(2, 1));
+        assert!(u40_less_than(0xffffffffff, 0));
+        assert!(u40_less_than(0xfffffffffe, 0xffffffffff));
+    }
+}
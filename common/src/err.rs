@@ -40,3 +40,19 @@ impl std::fmt::Display for LockFailed {
     }
 }
 impl std::error::Error for LockFailed {}
+
+#[derive(Debug)]
+pub struct LockLost {
+    pub name: String,
+}
+impl LockLost {
+    pub fn new(name: String) -> LockLost {
+        LockLost { name }
+    }
+}
+impl std::fmt::Display for LockLost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Lock Lost: {}", self.name)
+    }
+}
+impl std::error::Error for LockLost {}
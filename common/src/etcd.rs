@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use etcd_client::{
-    Client, ConnectOptions, EventType, GetOptions, TlsOptions, WatchOptions, WatchStream,
+    Client, Compare, CompareOp, ConnectOptions, EventType, GetOptions, PutOptions, TlsOptions, Txn,
+    TxnOp, WatchOptions, WatchStream,
 };
 use fxhash::FxHashMap;
 use std::{env, fs, sync::Arc};
@@ -100,6 +101,58 @@ pub async fn delete(key: &str) -> Result<()> {
     Ok(())
 }
 
+/// Creates `lock/{key}` attached to a new lease of `ttl` seconds unless it already exists.
+/// Returns the lease ID and the revision of the creation, which increases monotonically.
+pub async fn try_lock(key: &str, ttl: i64) -> Result<Option<(i64, i64)>> {
+    let key = format!("/senax/lock/{key}");
+    let mut etcd_client = CLIENT.lock().await;
+    let client = etcd_client
+        .as_mut()
+        .expect("etcd_client has not been initialized.");
+    let lease_id = client.lease_grant(ttl, None).await?.id();
+    let txn = Txn::new()
+        .when([Compare::create_revision(&*key, CompareOp::Equal, 0)])
+        .and_then([TxnOp::put(
+            &*key,
+            lease_id.to_string(),
+            Some(PutOptions::new().with_lease(lease_id)),
+        )]);
+    let res = client.txn(txn).await?;
+    if !res.succeeded() {
+        client.lease_revoke(lease_id).await?;
+        return Ok(None);
+    }
+    let revision = res.header().map(|h| h.revision()).unwrap_or_default();
+    Ok(Some((lease_id, revision)))
+}
+
+/// Refreshes the lease and returns the remaining TTL in seconds. Zero or less means it has expired.
+pub async fn keep_alive(lease_id: i64) -> Result<i64> {
+    let mut etcd_client = CLIENT.lock().await;
+    let (mut keeper, mut stream) = etcd_client
+        .as_mut()
+        .expect("etcd_client has not been initialized.")
+        .lease_keep_alive(lease_id)
+        .await?;
+    keeper.keep_alive().await?;
+    let res = stream
+        .message()
+        .await?
+        .context("no response to the lease keep alive")?;
+    Ok(res.ttl())
+}
+
+/// Revokes the lease and deletes the keys attached to it.
+pub async fn revoke(lease_id: i64) -> Result<()> {
+    let mut etcd_client = CLIENT.lock().await;
+    etcd_client
+        .as_mut()
+        .expect("etcd_client has not been initialized.")
+        .lease_revoke(lease_id)
+        .await?;
+    Ok(())
+}
+
 pub async fn watch(key: &str, with_prefix: bool) -> Result<WatchStream> {
     let mut opt = WatchOptions::new();
     let key = format!("/senax/{key}");
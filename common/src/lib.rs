@@ -4,7 +4,9 @@ pub mod err;
 pub mod etcd;
 pub mod fulltext;
 pub mod linker;
+pub mod lock;
 pub mod session;
+pub mod shard;
 pub mod types {
     pub mod blob;
     pub mod geo_point;
@@ -1,13 +1,16 @@
 use anyhow::{Context as _, Result, bail};
 use bytes::Bytes;
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
-#[cfg(all(feature = "uring", target_os = "linux"))]
 pub mod common;
 pub mod stream;
 #[cfg(all(feature = "uring", target_os = "linux"))]
 mod tcp_client;
+#[cfg(not(all(feature = "uring", target_os = "linux")))]
+mod tokio_client;
 #[cfg(all(feature = "uring", target_os = "linux"))]
 mod unix_client;
 
@@ -26,9 +29,38 @@ where
         Ok(())
     }
 }
+/// A message from the Linker numbered in the replay buffer of the stream.
+#[derive(Debug)]
+pub struct Packet {
+    pub epoch: u64,
+    pub seq: u64,
+    pub data: Bytes,
+}
+
+/// Position of the last packet acknowledged by the Receiver.
+/// The client resumes from here when it reconnects to the Linker.
+#[derive(Debug, Default)]
+pub struct ResumePoint {
+    epoch: AtomicU64,
+    seq: AtomicU64,
+}
+impl ResumePoint {
+    pub fn get(&self) -> (u64, u64) {
+        (
+            self.epoch.load(Ordering::Acquire),
+            self.seq.load(Ordering::Acquire),
+        )
+    }
+    fn set(&self, epoch: u64, seq: u64) {
+        self.seq.store(seq, Ordering::Release);
+        self.epoch.store(epoch, Ordering::Release);
+    }
+}
+
 #[derive(Debug)]
 pub struct Receiver<T> {
-    rx: UnboundedReceiver<Bytes>,
+    rx: UnboundedReceiver<Packet>,
+    resume: Arc<ResumePoint>,
     _phantom: PhantomData<T>,
 }
 impl<T> Receiver<T>
@@ -37,19 +69,30 @@ where
 {
     /// Receive data from the Linker.
     /// If the connection with the Linker is disconnected, return None.
-    /// If there is an abnormal disconnection between Linkers and it reconnects, return Some(None).
+    /// If there is an abnormal disconnection between Linkers and it reconnects,
+    /// or the missed data is no longer kept by the Linker, return Some(None).
     pub async fn recv(&mut self) -> Option<Option<Result<T>>> {
         match self.rx.recv().await {
             Some(mut v) => {
-                if v.is_empty() {
+                if v.epoch != 0 {
+                    self.resume.set(v.epoch, v.seq);
+                }
+                if v.data.is_empty() {
                     Some(None)
                 } else {
-                    Some(Some(senax_encoder::decode(&mut v).context("parse error")))
+                    Some(Some(
+                        senax_encoder::decode(&mut v.data).context("parse error"),
+                    ))
                 }
             }
             None => None,
         }
     }
+
+    /// The position to resume from after reconnecting.
+    pub fn resume_point(&self) -> (u64, u64) {
+        self.resume.get()
+    }
 }
 
 pub fn link<T>(
@@ -62,7 +105,9 @@ pub fn link<T>(
 where
     T: senax_encoder::Encoder + senax_encoder::Decoder,
 {
-    let (to_linker, from_linker) = LinkerClient::start(port, stream_id, pw, exit_tx, send_only)?;
+    let resume = Arc::new(ResumePoint::default());
+    let (to_linker, from_linker) =
+        LinkerClient::start(port, stream_id, pw, exit_tx, send_only, resume.clone())?;
     Ok((
         Sender {
             tx: to_linker,
@@ -70,6 +115,7 @@ where
         },
         Receiver {
             rx: from_linker,
+            resume,
             _phantom: Default::default(),
         },
     ))
@@ -85,7 +131,8 @@ impl LinkerClient {
         pw: &str,
         exit_tx: mpsc::Sender<i32>,
         send_only: bool,
-    ) -> Result<(UnboundedSender<Bytes>, UnboundedReceiver<Bytes>)> {
+        resume: Arc<ResumePoint>,
+    ) -> Result<(UnboundedSender<Bytes>, UnboundedReceiver<Packet>)> {
         let (to_linker, from_local) = mpsc::unbounded_channel();
         let (to_local, from_linker) = mpsc::unbounded_channel();
         if port.starts_with('/') {
@@ -97,6 +144,7 @@ impl LinkerClient {
                 pw.to_string(),
                 exit_tx,
                 send_only,
+                resume,
             ) {
                 Ok(_) => {
                     return Ok((to_linker, from_linker));
@@ -114,6 +162,7 @@ impl LinkerClient {
                 pw.to_string(),
                 exit_tx,
                 send_only,
+                resume,
             ) {
                 Ok(_) => {
                     return Ok((to_linker, from_linker));
@@ -128,16 +177,33 @@ impl LinkerClient {
 }
 
 #[cfg(not(all(feature = "uring", target_os = "linux")))]
-#[allow(unused_variables)]
 #[allow(clippy::type_complexity)]
 impl LinkerClient {
     pub fn start(
-        _port: &str,
-        _stream_id: u64,
-        _pw: &str,
-        _exit_tx: mpsc::Sender<i32>,
-        _send_only: bool,
-    ) -> Result<(UnboundedSender<Bytes>, UnboundedReceiver<Bytes>)> {
-        bail!("linker is not supported");
+        port: &str,
+        stream_id: u64,
+        pw: &str,
+        exit_tx: mpsc::Sender<i32>,
+        send_only: bool,
+        resume: Arc<ResumePoint>,
+    ) -> Result<(UnboundedSender<Bytes>, UnboundedReceiver<Packet>)> {
+        let (to_linker, from_local) = mpsc::unbounded_channel();
+        let (to_local, from_linker) = mpsc::unbounded_channel();
+        match tokio_client::run(
+            port,
+            stream_id,
+            from_local,
+            to_local,
+            pw.to_string(),
+            exit_tx,
+            send_only,
+            resume,
+        ) {
+            Ok(_) => Ok((to_linker, from_linker)),
+            Err(e) => {
+                log::warn!("{}", e);
+                bail!("linker connection failed");
+            }
+        }
     }
 }
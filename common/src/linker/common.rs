@@ -1,11 +1,22 @@
+#[cfg(all(feature = "uring", target_os = "linux"))]
 use bytes::BytesMut;
+#[cfg(all(feature = "uring", target_os = "linux"))]
 use tokio_uring::buf::{IoBuf, IoBufMut};
 
-pub const LINKER_VER: u16 = 1;
+/// Must match `LOCAL_VER` of senax-linker. Version 2 is not compatible with linkers of version 1.
+pub const LINKER_VER: u16 = 2;
 pub const SENDER: u16 = 0;
 pub const RECEIVER: u16 = 1;
+pub const RECONNECT_RETRY: u32 = 10;
 
+/// Interval before the next connection attempt, doubled after each failure up to 5 seconds.
+pub fn reconnect_delay(retry: u32) -> std::time::Duration {
+    std::time::Duration::from_millis(100 << retry.min(6)).min(std::time::Duration::from_secs(5))
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
 pub(crate) struct IoBytesMut(BytesMut, usize, usize);
+#[cfg(all(feature = "uring", target_os = "linux"))]
 impl IoBytesMut {
     pub fn new(capacity: usize) -> Self {
         Self(BytesMut::with_capacity(capacity), 0, capacity)
@@ -17,6 +28,7 @@ impl IoBytesMut {
         self.0
     }
 }
+#[cfg(all(feature = "uring", target_os = "linux"))]
 unsafe impl IoBuf for IoBytesMut {
     fn stable_ptr(&self) -> *const u8 {
         self.0.as_ptr()
@@ -30,6 +42,7 @@ unsafe impl IoBuf for IoBytesMut {
         self.2 - self.1
     }
 }
+#[cfg(all(feature = "uring", target_os = "linux"))]
 unsafe impl IoBufMut for IoBytesMut {
     fn stable_mut_ptr(&mut self) -> *mut u8 {
         unsafe { self.0.as_mut_ptr().add(self.1) }
@@ -0,0 +1,359 @@
+use anyhow::{Result, bail};
+use bytes::{BufMut, Bytes, BytesMut};
+use log::{error, info, warn};
+use sha2::{Digest, Sha512};
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout};
+
+use super::common::{LINKER_VER, RECEIVER, RECONNECT_RETRY, SENDER, reconnect_delay};
+use super::{Packet, ResumePoint};
+
+const LINKER_PORT: u16 = 25551;
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+/// Plain tokio implementation of the tcp and unix clients for environments without io_uring.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run(
+    port: &str,
+    stream_id: u64,
+    from_local: UnboundedReceiver<Bytes>,
+    to_local: UnboundedSender<Packet>,
+    pw: String,
+    exit_tx: mpsc::Sender<i32>,
+    send_only: bool,
+    resume: Arc<ResumePoint>,
+) -> Result<()> {
+    let port = if port.starts_with('/') {
+        if cfg!(not(unix)) {
+            bail!("unix domain socket is not supported");
+        }
+        port.to_owned()
+    } else if port
+        .rsplit_once(':')
+        .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+    {
+        port.to_owned()
+    } else {
+        format!("{}:{}", port, LINKER_PORT)
+    };
+    let _port = port.clone();
+    let _pw = pw.clone();
+    // The number of the sender connection, which the linker renumbers when it restarts
+    let (conn_no_tx, mut conn_no_rx) = watch::channel::<Option<u64>>(None);
+    let _exit_tx = exit_tx.clone();
+    tokio::spawn(async move {
+        let mut from_local = from_local;
+        let mut pending = None;
+        let mut retry = 0;
+        let result: Result<()> = async {
+            loop {
+                info!("connecting to {_port}");
+                let result = match connect(&_port).await {
+                    Ok(stream) => {
+                        let result = handle_sender_stream(
+                            stream,
+                            stream_id,
+                            &conn_no_tx,
+                            &mut from_local,
+                            &_pw,
+                            &mut pending,
+                        )
+                        .await;
+                        if conn_no_tx.send_replace(None).is_some() {
+                            retry = 0;
+                        }
+                        match result {
+                            Ok(_) => return Ok(()),
+                            Err(e) => {
+                                warn!("disconnected from {_port}: {}", e);
+                                Err(e)
+                            }
+                        }
+                    }
+                    Err(e) => Err(e),
+                };
+                retry += 1;
+                if retry > RECONNECT_RETRY {
+                    return result;
+                }
+                sleep(reconnect_delay(retry)).await;
+            }
+        }
+        .await;
+        if let Err(e) = result {
+            error!("{}", e);
+            let _ = _exit_tx.try_send(1);
+        }
+    });
+    if !send_only {
+        tokio::spawn(async move {
+            let result: Result<()> = async {
+                let mut retry = 0;
+                let mut reconnect = false;
+                loop {
+                    let Ok(conn_no) = conn_no_rx
+                        .wait_for(|v| v.is_some())
+                        .await
+                        .map(|v| v.unwrap())
+                    else {
+                        // The sender has stopped.
+                        return Ok(());
+                    };
+                    info!("connecting to {port}");
+                    match connect(&port).await {
+                        Ok(stream) => {
+                            retry = 0;
+                            let result = handle_receiver_stream(
+                                stream, stream_id, conn_no, &to_local, &pw, &resume, reconnect,
+                            )
+                            .await;
+                            if to_local.is_closed() {
+                                return Ok(());
+                            }
+                            match result {
+                                Ok(_) => warn!("disconnected from {port}"),
+                                Err(e) => warn!("disconnected from {port}: {}", e),
+                            }
+                            reconnect = true;
+                            // When the linker has restarted, the sender reconnects with a new number.
+                            let _ = timeout(Duration::from_secs(1), conn_no_rx.changed()).await;
+                            continue;
+                        }
+                        Err(e) => {
+                            retry += 1;
+                            if retry > RECONNECT_RETRY {
+                                return Err(e);
+                            }
+                        }
+                    }
+                    sleep(Duration::from_secs(1)).await;
+                }
+            }
+            .await;
+            if let Err(e) = result {
+                error!("{}", e);
+                let _ = exit_tx.try_send(1);
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn connect(port: &str) -> Result<Box<dyn Io>> {
+    #[cfg(unix)]
+    if port.starts_with('/') {
+        return Ok(Box::new(tokio::net::UnixStream::connect(port).await?));
+    }
+    Ok(Box::new(TcpStream::connect(port).await?))
+}
+
+/// Sends the local messages until the local channel is closed.
+/// A message whose write failed is kept in `pending` and sent again after reconnecting.
+async fn handle_sender_stream(
+    stream: Box<dyn Io>,
+    stream_id: u64,
+    conn_no_tx: &watch::Sender<Option<u64>>,
+    from_local: &mut UnboundedReceiver<Bytes>,
+    pw: &str,
+    pending: &mut Option<Bytes>,
+) -> Result<()> {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut buf = BytesMut::with_capacity(2 + 2 + 64 + 8);
+    buf.put_u16_le(LINKER_VER);
+    buf.put_u16_le(SENDER);
+    let mut hasher = Sha512::new();
+    hasher.update(pw);
+    buf.put(&*hasher.finalize());
+    buf.put_u64_le(stream_id);
+    writer.write_all(&buf).await?;
+    let conn_no = reader.read_u64_le().await?;
+    conn_no_tx.send_replace(Some(conn_no));
+    // The linker sends nothing more, so a read returns only when the connection is lost.
+    let closed = reader.read_u8();
+    tokio::pin!(closed);
+    loop {
+        let data = match pending.take() {
+            Some(data) => data,
+            None => tokio::select! {
+                data = from_local.recv() => match data {
+                    Some(data) => data,
+                    None => return Ok(()),
+                },
+                res = &mut closed => {
+                    res?;
+                    bail!("unexpected data from the linker");
+                }
+            },
+        };
+        *pending = Some(data.clone());
+        writer.write_u64_le(data.len() as u64).await?;
+        writer.write_all(&data).await?;
+        *pending = None;
+    }
+}
+
+async fn handle_receiver_stream(
+    mut stream: Box<dyn Io>,
+    stream_id: u64,
+    conn_no: u64,
+    to_local: &UnboundedSender<Packet>,
+    pw: &str,
+    resume: &ResumePoint,
+    reconnect: bool,
+) -> Result<()> {
+    let (mut epoch, seq) = resume.get();
+    if reconnect && epoch == 0 {
+        // Nothing was received before the disconnection, so the position is unknown.
+        epoch = u64::MAX;
+    }
+    let mut buf = BytesMut::with_capacity(2 + 2 + 64 + 8 + 8 + 8 + 8);
+    buf.put_u16_le(LINKER_VER);
+    buf.put_u16_le(RECEIVER);
+    let mut hasher = Sha512::new();
+    hasher.update(pw);
+    buf.put(&*hasher.finalize());
+    buf.put_u64_le(stream_id);
+    buf.put_u64_le(conn_no);
+    buf.put_u64_le(epoch);
+    buf.put_u64_le(seq);
+    stream.write_all(&buf).await?;
+    loop {
+        let epoch = match stream.read_u64_le().await {
+            Ok(epoch) => epoch,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        let seq = stream.read_u64_le().await?;
+        let len = stream.read_u64_le().await?;
+        let mut data = vec![0; len.try_into()?];
+        stream.read_exact(&mut data).await?;
+        to_local.send(Packet {
+            epoch,
+            seq,
+            data: data.into(),
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const PW: &str = "secret";
+
+    /// Accepts a connection and checks the handshake header common to the sender and the receiver.
+    async fn accept(listener: &TcpListener, mode: u16) -> TcpStream {
+        let (mut stream, _) = timeout(Duration::from_secs(10), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stream.read_u16_le().await.unwrap(), LINKER_VER);
+        assert_eq!(stream.read_u16_le().await.unwrap(), mode);
+        let mut hash = [0u8; 64];
+        stream.read_exact(&mut hash).await.unwrap();
+        assert_eq!(&hash[..], &*Sha512::digest(PW));
+        assert_eq!(stream.read_u64_le().await.unwrap(), 7);
+        stream
+    }
+
+    /// Stand-in of the linker handshake, returning the sender and receiver connections.
+    async fn handshake(
+        listener: &TcpListener,
+        conn_no: u64,
+        resume: (u64, u64),
+    ) -> (TcpStream, TcpStream) {
+        let mut sender = accept(listener, SENDER).await;
+        sender.write_u64_le(conn_no).await.unwrap();
+        let mut receiver = accept(listener, RECEIVER).await;
+        assert_eq!(receiver.read_u64_le().await.unwrap(), conn_no);
+        assert_eq!(receiver.read_u64_le().await.unwrap(), resume.0);
+        assert_eq!(receiver.read_u64_le().await.unwrap(), resume.1);
+        (sender, receiver)
+    }
+
+    async fn read_msg(stream: &mut TcpStream) -> Vec<u8> {
+        let len = stream.read_u64_le().await.unwrap();
+        let mut data = vec![0; len as usize];
+        stream.read_exact(&mut data).await.unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn framing() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().to_string();
+        let (to_linker, from_local) = mpsc::unbounded_channel();
+        let (to_local, mut from_linker) = mpsc::unbounded_channel();
+        let (exit_tx, _exit_rx) = mpsc::channel(1);
+        let resume = Arc::new(ResumePoint::default());
+        run(
+            &port,
+            7,
+            from_local,
+            to_local,
+            PW.to_string(),
+            exit_tx,
+            false,
+            resume,
+        )
+        .unwrap();
+        let (mut sender, mut receiver) = handshake(&listener, 3, (0, 0)).await;
+
+        to_linker.send(Bytes::from_static(b"hello")).unwrap();
+        assert_eq!(read_msg(&mut sender).await, b"hello");
+
+        receiver.write_u64_le(1).await.unwrap();
+        receiver.write_u64_le(2).await.unwrap();
+        receiver.write_u64_le(5).await.unwrap();
+        receiver.write_all(b"world").await.unwrap();
+        let packet = from_linker.recv().await.unwrap();
+        assert_eq!((packet.epoch, packet.seq), (1, 2));
+        assert_eq!(&packet.data[..], b"world");
+    }
+
+    #[tokio::test]
+    async fn resume_after_restart() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().to_string();
+        let (to_linker, from_local) = mpsc::unbounded_channel();
+        let (to_local, _from_linker) = mpsc::unbounded_channel();
+        let (exit_tx, _exit_rx) = mpsc::channel(1);
+        let resume = Arc::new(ResumePoint::default());
+        run(
+            &port,
+            7,
+            from_local,
+            to_local,
+            PW.to_string(),
+            exit_tx,
+            false,
+            resume.clone(),
+        )
+        .unwrap();
+        let (sender, receiver) = handshake(&listener, 3, (0, 0)).await;
+        resume.set(4, 10);
+
+        // The linker restarts and numbers the connections again.
+        drop(sender);
+        drop(receiver);
+        to_linker.send(Bytes::from_static(b"queued")).unwrap();
+        let (mut sender, _receiver) = handshake(&listener, 9, (4, 10)).await;
+        assert_eq!(read_msg(&mut sender).await, b"queued");
+    }
+
+    #[test]
+    fn reconnect_delay_is_capped() {
+        assert!(reconnect_delay(1) < reconnect_delay(2));
+        assert_eq!(reconnect_delay(RECONNECT_RETRY), Duration::from_secs(5));
+    }
+}
@@ -1,48 +1,77 @@
-use anyhow::{Error, Result};
+use anyhow::{Error, Result, bail};
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use log::{error, info};
+use log::{error, info, warn};
 use sha2::{Digest, Sha512};
 use std::path::Path;
+use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tokio::sync::oneshot;
-use tokio::time::sleep;
+use tokio::sync::watch;
+use tokio::time::{sleep, timeout};
 use tokio_uring::buf::IoBuf;
 use tokio_uring::net::UnixStream;
 
 use super::common::LINKER_VER;
-use super::common::{IoBytesMut, RECEIVER, SENDER};
+use super::common::{IoBytesMut, RECEIVER, RECONNECT_RETRY, SENDER, reconnect_delay};
+use super::{Packet, ResumePoint};
 
 pub(crate) fn run(
     unix_port: &str,
     stream_id: u64,
     from_local: UnboundedReceiver<Bytes>,
-    to_local: UnboundedSender<Bytes>,
+    to_local: UnboundedSender<Packet>,
     pw: String,
     exit_tx: mpsc::Sender<i32>,
     send_only: bool,
+    resume: Arc<ResumePoint>,
 ) -> Result<()> {
     let unix_port = unix_port.to_owned();
     let _unix_port = unix_port.clone();
     let _pw = pw.clone();
-    let (conn_no_sender, conn_no_receiver) = oneshot::channel::<u64>();
+    // The number of the sender connection, which the linker renumbers when it restarts
+    let (conn_no_tx, mut conn_no_rx) = watch::channel::<Option<u64>>(None);
     let _exit_tx = exit_tx.clone();
     thread::Builder::new()
         .name("unix adapter".to_string())
         .spawn(move || {
             tokio_uring::start(async move {
+                let mut from_local = from_local;
+                let mut pending = None;
                 let sock_file = Path::new(&_unix_port);
-                info!("connecting to {_unix_port}");
-                let stream = match UnixStream::connect(&sock_file).await {
-                    Ok(stream) => stream,
-                    Err(_) => {
-                        sleep(Duration::from_secs(1)).await;
-                        UnixStream::connect(&sock_file).await?
+                let mut retry = 0;
+                loop {
+                    info!("connecting to {_unix_port}");
+                    let result = match UnixStream::connect(&sock_file).await {
+                        Ok(stream) => {
+                            let result = handle_sender_stream(
+                                stream,
+                                stream_id,
+                                &conn_no_tx,
+                                &mut from_local,
+                                &_pw,
+                                &mut pending,
+                            )
+                            .await;
+                            if conn_no_tx.send_replace(None).is_some() {
+                                retry = 0;
+                            }
+                            match result {
+                                Ok(_) => return Ok(()),
+                                Err(e) => {
+                                    warn!("disconnected from {_unix_port}: {}", e);
+                                    Err(e)
+                                }
+                            }
+                        }
+                        Err(e) => Err(e.into()),
+                    };
+                    retry += 1;
+                    if retry > RECONNECT_RETRY {
+                        return result;
                     }
-                };
-                handle_sender_stream(stream, stream_id, conn_no_sender, from_local, _pw).await?;
-                Ok(())
+                    sleep(reconnect_delay(retry)).await;
+                }
             })
             .inspect_err(|e: &Error| {
                 error!("{}", e);
@@ -54,18 +83,47 @@ pub(crate) fn run(
             .name("unix adapter".to_string())
             .spawn(move || {
                 tokio_uring::start(async move {
-                    let conn_no = conn_no_receiver.await?;
                     let sock_file = Path::new(&unix_port);
-                    info!("connecting to {unix_port}");
-                    let stream = match UnixStream::connect(&sock_file).await {
-                        Ok(stream) => stream,
-                        Err(_) => {
-                            sleep(Duration::from_secs(1)).await;
-                            UnixStream::connect(&sock_file).await?
+                    let mut retry = 0;
+                    let mut reconnect = false;
+                    loop {
+                        let Ok(conn_no) = conn_no_rx
+                            .wait_for(|v| v.is_some())
+                            .await
+                            .map(|v| v.unwrap())
+                        else {
+                            // The sender has stopped.
+                            return Ok(());
+                        };
+                        info!("connecting to {unix_port}");
+                        match UnixStream::connect(&sock_file).await {
+                            Ok(stream) => {
+                                retry = 0;
+                                let result = handle_receiver_stream(
+                                    stream, stream_id, conn_no, &to_local, &pw, &resume, reconnect,
+                                )
+                                .await;
+                                if to_local.is_closed() {
+                                    return Ok(());
+                                }
+                                match result {
+                                    Ok(_) => warn!("disconnected from {unix_port}"),
+                                    Err(e) => warn!("disconnected from {unix_port}: {}", e),
+                                }
+                                reconnect = true;
+                                // When the linker has restarted, the sender reconnects with a new number.
+                                let _ = timeout(Duration::from_secs(1), conn_no_rx.changed()).await;
+                                continue;
+                            }
+                            Err(e) => {
+                                retry += 1;
+                                if retry > RECONNECT_RETRY {
+                                    return Err(e.into());
+                                }
+                            }
                         }
-                    };
-                    handle_receiver_stream(stream, stream_id, conn_no, to_local, pw).await?;
-                    Ok(())
+                        sleep(Duration::from_secs(1)).await;
+                    }
                 })
                 .inspect_err(|e: &Error| {
                     error!("{}", e);
@@ -76,12 +134,15 @@ pub(crate) fn run(
     Ok(())
 }
 
+/// Sends the local messages until the local channel is closed.
+/// A message whose write failed is kept in `pending` and sent again after reconnecting.
 async fn handle_sender_stream(
     stream: UnixStream,
     stream_id: u64,
-    conn_no_sender: oneshot::Sender<u64>,
-    mut from_local: UnboundedReceiver<Bytes>,
-    pw: String,
+    conn_no_tx: &watch::Sender<Option<u64>>,
+    from_local: &mut UnboundedReceiver<Bytes>,
+    pw: &str,
+    pending: &mut Option<Bytes>,
 ) -> Result<()> {
     let mut buf = BytesMut::with_capacity(2 + 2 + 64 + 8);
     buf.put_u16_le(LINKER_VER);
@@ -93,29 +154,50 @@ async fn handle_sender_stream(
     stream.write_all(buf.freeze()).await.0?;
     let buf = IoBytesMut::new(8);
     let conn_no = read_all(buf, &stream).await?.get_u64_le();
-    let _ = conn_no_sender.send(conn_no);
+    conn_no_tx.send_replace(Some(conn_no));
+    // The linker sends nothing more, so a read returns only when the connection is lost.
+    let closed = stream.read(IoBytesMut::new(1));
+    tokio::pin!(closed);
     loop {
-        tokio::select! {
-            Some(data) = from_local.recv() => {
-                let mut buf = BytesMut::with_capacity(8);
-                buf.put_u64_le(data.len() as u64);
-                stream.write_all(buf.freeze()).await.0?;
-                stream.write_all(data).await.0?;
-            }
-            else => break,
-        }
+        let data = match pending.take() {
+            Some(data) => data,
+            None => tokio::select! {
+                data = from_local.recv() => match data {
+                    Some(data) => data,
+                    None => return Ok(()),
+                },
+                (res, _) = &mut closed => {
+                    if res? == 0 {
+                        bail!("connection closed by the linker");
+                    }
+                    bail!("unexpected data from the linker");
+                }
+            },
+        };
+        *pending = Some(data.clone());
+        let mut buf = BytesMut::with_capacity(8);
+        buf.put_u64_le(data.len() as u64);
+        stream.write_all(buf.freeze()).await.0?;
+        stream.write_all(data).await.0?;
+        *pending = None;
     }
-    Ok(())
 }
 
 async fn handle_receiver_stream(
     stream: UnixStream,
     stream_id: u64,
     conn_no: u64,
-    to_local: UnboundedSender<Bytes>,
-    pw: String,
+    to_local: &UnboundedSender<Packet>,
+    pw: &str,
+    resume: &ResumePoint,
+    reconnect: bool,
 ) -> Result<()> {
-    let mut buf = BytesMut::with_capacity(2 + 2 + 64 + 8 + 8);
+    let (mut epoch, seq) = resume.get();
+    if reconnect && epoch == 0 {
+        // Nothing was received before the disconnection, so the position is unknown.
+        epoch = u64::MAX;
+    }
+    let mut buf = BytesMut::with_capacity(2 + 2 + 64 + 8 + 8 + 8 + 8);
     buf.put_u16_le(LINKER_VER);
     buf.put_u16_le(RECEIVER);
     let mut hasher = Sha512::new();
@@ -123,20 +205,25 @@ async fn handle_receiver_stream(
     buf.put(&*hasher.finalize());
     buf.put_u64_le(stream_id);
     buf.put_u64_le(conn_no);
+    buf.put_u64_le(epoch);
+    buf.put_u64_le(seq);
     stream.write_all(buf.freeze()).await.0?;
     loop {
-        let buf = IoBytesMut::new(8);
+        let buf = IoBytesMut::new(8 + 8);
         tokio::select! {
             (res, mut buf) = stream.read(buf) => {
                 let n = res?;
                 if n == 0 { break }
                 buf.advance(n);
-                let buf = read_msg(buf, &stream).await?;
-                if buf.is_empty() {
-                    to_local.send(Bytes::new())?;
-                } else {
-                    to_local.send(buf.freeze())?;
-                }
+                let mut header = read_all(buf, &stream).await?;
+                let epoch = header.get_u64_le();
+                let seq = header.get_u64_le();
+                let buf = read_msg(IoBytesMut::new(8), &stream).await?;
+                to_local.send(Packet {
+                    epoch,
+                    seq,
+                    data: buf.freeze(),
+                })?;
             },
             else => break,
         }
@@ -0,0 +1,318 @@
+use crate::err::{LockFailed, LockLost};
+use anyhow::Result;
+use async_trait::async_trait;
+use fxhash::FxHashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+
+/// Interval between attempts while waiting for a lock held by another holder.
+pub const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A lock held through a [`LockProvider`].
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub key: String,
+    /// Fencing token that increases every time the key is acquired.
+    /// Pass it to the protected resource and reject requests with an older token.
+    pub token: u64,
+    pub ttl: Duration,
+    /// Identifies this acquisition in the provider.
+    pub holder: String,
+    /// Local estimate of the expiration. The provider's clock decides the actual expiration.
+    pub expires_at: Instant,
+}
+
+impl Lease {
+    pub fn new(key: &str, token: u64, ttl: Duration, holder: String, start: Instant) -> Lease {
+        Lease {
+            key: key.to_string(),
+            token,
+            ttl,
+            holder,
+            expires_at: start + ttl,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Instant::now()
+    }
+}
+
+/// Backend of distributed locks.
+#[async_trait]
+pub trait LockProvider: Send + Sync {
+    /// Acquires the lock for `ttl`. A `timeout` of None waits indefinitely.
+    /// Fails with [`LockFailed`] when the timeout elapses.
+    async fn acquire(&self, key: &str, ttl: Duration, timeout: Option<Duration>) -> Result<Lease>;
+
+    /// Extends the lease by its TTL.
+    /// Fails with [`LockLost`] if the lease has been taken over or can no longer be confirmed.
+    async fn renew(&self, lease: &mut Lease) -> Result<()>;
+
+    async fn release(&self, lease: Lease) -> Result<()>;
+}
+
+/// Returns a random ID that identifies an acquisition.
+pub fn new_holder() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+/// Returns whether the timeout started at `start` has elapsed.
+pub fn is_timed_out(start: Instant, timeout: Option<Duration>) -> bool {
+    timeout.map(|t| start.elapsed() >= t).unwrap_or_default()
+}
+
+/// Keeps a lease alive in the background and releases it when dropped.
+/// Renews the lease every third of its TTL, so it is suitable for long jobs.
+pub struct LockGuard {
+    key: String,
+    token: u64,
+    lost: watch::Receiver<bool>,
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<Result<()>>>,
+}
+
+impl LockGuard {
+    pub async fn acquire(
+        provider: Arc<dyn LockProvider>,
+        key: &str,
+        ttl: Duration,
+        timeout: Option<Duration>,
+    ) -> Result<LockGuard> {
+        let mut lease = provider.acquire(key, ttl, timeout).await?;
+        let key = lease.key.clone();
+        let token = lease.token;
+        let (lost_tx, lost) = watch::channel(false);
+        let (stop, mut stop_rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(lease.ttl / 3) => {
+                        if let Err(e) = provider.renew(&mut lease).await {
+                            if e.downcast_ref::<LockLost>().is_some() || lease.is_expired() {
+                                log::warn!("{}", e);
+                                let _ = lost_tx.send(true);
+                                return Ok(());
+                            }
+                            log::warn!("lock renewal failed: {}", e);
+                        }
+                    }
+                    _ = &mut stop_rx => {
+                        return provider.release(lease).await;
+                    }
+                }
+            }
+        });
+        Ok(LockGuard {
+            key,
+            token,
+            lost,
+            stop: Some(stop),
+            task: Some(task),
+        })
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Whether the lease could not be renewed and may be held by another holder.
+    pub fn is_lost(&self) -> bool {
+        *self.lost.borrow()
+    }
+
+    /// Waits until the lease is lost.
+    pub async fn lost(&mut self) {
+        let _ = self.lost.wait_for(|lost| *lost).await;
+    }
+
+    /// Releases the lease. Fails with [`LockLost`] if the lease had already been lost.
+    pub async fn release(mut self) -> Result<()> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            task.await??;
+        }
+        if self.is_lost() {
+            return Err(LockLost::new(self.key.clone()).into());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        // The renewal task releases the lease when the sender is dropped.
+        self.stop.take();
+    }
+}
+
+/// Lock provider within a single process, for tests and single server deployments.
+#[derive(Default)]
+pub struct LocalLockProvider(Mutex<FxHashMap<String, (String, u64, Instant)>>);
+
+impl LocalLockProvider {
+    fn try_acquire(&self, key: &str, ttl: Duration) -> Option<Lease> {
+        let now = Instant::now();
+        let mut map = self.0.lock().unwrap();
+        let (holder, token, expires_at) = map
+            .entry(key.to_string())
+            .or_insert_with(|| (String::new(), 0, now));
+        if !holder.is_empty() && *expires_at > now {
+            return None;
+        }
+        *holder = new_holder();
+        *token += 1;
+        *expires_at = now + ttl;
+        Some(Lease::new(key, *token, ttl, holder.clone(), now))
+    }
+}
+
+#[async_trait]
+impl LockProvider for LocalLockProvider {
+    async fn acquire(&self, key: &str, ttl: Duration, timeout: Option<Duration>) -> Result<Lease> {
+        let start = Instant::now();
+        loop {
+            if let Some(lease) = self.try_acquire(key, ttl) {
+                return Ok(lease);
+            }
+            if is_timed_out(start, timeout) {
+                return Err(LockFailed::new(key.to_string()).into());
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    async fn renew(&self, lease: &mut Lease) -> Result<()> {
+        let now = Instant::now();
+        let mut map = self.0.lock().unwrap();
+        match map.get_mut(&lease.key) {
+            Some((holder, token, expires_at))
+                if *holder == lease.holder && *token == lease.token =>
+            {
+                *expires_at = now + lease.ttl;
+                lease.expires_at = now + lease.ttl;
+                Ok(())
+            }
+            _ => Err(LockLost::new(lease.key.clone()).into()),
+        }
+    }
+
+    async fn release(&self, lease: Lease) -> Result<()> {
+        let mut map = self.0.lock().unwrap();
+        if let Some((holder, token, _)) = map.get_mut(&lease.key)
+            && *holder == lease.holder
+            && *token == lease.token
+        {
+            holder.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Lock provider using etcd leases. The fencing token is the etcd revision at acquisition.
+#[cfg(feature = "etcd")]
+#[derive(Default)]
+pub struct EtcdLockProvider;
+
+#[cfg(feature = "etcd")]
+#[async_trait]
+impl LockProvider for EtcdLockProvider {
+    async fn acquire(&self, key: &str, ttl: Duration, timeout: Option<Duration>) -> Result<Lease> {
+        let start = Instant::now();
+        let ttl_secs = ttl.as_secs_f64().ceil().max(1.0) as i64;
+        loop {
+            let now = Instant::now();
+            if let Some((lease_id, revision)) = crate::etcd::try_lock(key, ttl_secs).await? {
+                let holder = lease_id.to_string();
+                return Ok(Lease::new(key, revision as u64, ttl, holder, now));
+            }
+            if is_timed_out(start, timeout) {
+                return Err(LockFailed::new(key.to_string()).into());
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    async fn renew(&self, lease: &mut Lease) -> Result<()> {
+        let now = Instant::now();
+        let ttl = crate::etcd::keep_alive(lease_id(lease)?).await?;
+        if ttl <= 0 {
+            return Err(LockLost::new(lease.key.clone()).into());
+        }
+        lease.expires_at = now + Duration::from_secs(ttl as u64).min(lease.ttl);
+        Ok(())
+    }
+
+    async fn release(&self, lease: Lease) -> Result<()> {
+        crate::etcd::revoke(lease_id(&lease)?).await
+    }
+}
+
+/// The holder of an etcd lock is its lease ID.
+#[cfg(feature = "etcd")]
+fn lease_id(lease: &Lease) -> Result<i64> {
+    use anyhow::Context;
+    lease
+        .holder
+        .parse()
+        .with_context(|| format!("invalid etcd lease: {}", lease.holder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local() -> Result<()> {
+        let provider = LocalLockProvider::default();
+        let ttl = Duration::from_millis(200);
+        let wait = Some(Duration::from_millis(50));
+        let lease = provider.acquire("a", ttl, None).await?;
+        assert_eq!(lease.token, 1);
+        let err = provider.acquire("a", ttl, wait).await.unwrap_err();
+        assert!(err.downcast_ref::<LockFailed>().is_some());
+        assert_eq!(provider.acquire("b", ttl, wait).await?.token, 1);
+        provider.release(lease).await?;
+        let mut lease = provider.acquire("a", ttl, wait).await?;
+        assert_eq!(lease.token, 2);
+
+        tokio::time::sleep(ttl).await;
+        let other = provider.acquire("a", ttl, wait).await?;
+        assert_eq!(other.token, 3);
+        let err = provider.renew(&mut lease).await.unwrap_err();
+        assert!(err.downcast_ref::<LockLost>().is_some());
+        provider.release(lease).await?;
+        assert!(provider.acquire("a", ttl, wait).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn guard() -> Result<()> {
+        let provider: Arc<dyn LockProvider> = Arc::new(LocalLockProvider::default());
+        let ttl = Duration::from_millis(150);
+        let wait = Some(Duration::from_millis(50));
+        let guard = LockGuard::acquire(provider.clone(), "a", ttl, None).await?;
+        assert_eq!(guard.token(), 1);
+        tokio::time::sleep(ttl * 3).await;
+        assert!(!guard.is_lost());
+        assert!(provider.acquire("a", ttl, wait).await.is_err());
+        guard.release().await?;
+        let lease = provider.acquire("a", ttl, wait).await?;
+        assert_eq!(lease.token, 2);
+        provider.release(lease).await?;
+
+        let guard = LockGuard::acquire(provider.clone(), "a", ttl, None).await?;
+        drop(guard);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(provider.acquire("a", ttl, wait).await?.token, 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+use anyhow::{Context as _, Result, ensure};
+use futures::{Stream, StreamExt as _, future};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{Read as _, Seek as _, SeekFrom, Write as _};
+use std::path::{Path, PathBuf};
+
+use crate::ShardId;
+
+/// Value that can be used as a shard key.
+///
+/// The hash must not change between processes or releases, since it decides
+/// which database a row is stored in.
+pub trait ShardKey {
+    fn shard_hash(&self) -> u64;
+    /// Value compared against the boundaries of a range map.
+    fn shard_range_value(&self) -> Option<i64>;
+}
+
+/// 64-bit FNV-1a. Integers are hashed as little-endian i64 and strings as UTF-8.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+macro_rules! impl_int_shard_key {
+    ($($t:ty),*) => {
+        $(
+            impl ShardKey for $t {
+                fn shard_hash(&self) -> u64 {
+                    fnv1a(&(*self as i64).to_le_bytes())
+                }
+                fn shard_range_value(&self) -> Option<i64> {
+                    Some(*self as i64)
+                }
+            }
+        )*
+    };
+}
+impl_int_shard_key!(i8, i16, i32, i64, u8, u16, u32, u64);
+
+impl ShardKey for str {
+    fn shard_hash(&self) -> u64 {
+        fnv1a(self.as_bytes())
+    }
+    fn shard_range_value(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl ShardKey for String {
+    fn shard_hash(&self) -> u64 {
+        self.as_str().shard_hash()
+    }
+    fn shard_range_value(&self) -> Option<i64> {
+        None
+    }
+}
+
+impl<T: ShardKey + ?Sized> ShardKey for &T {
+    fn shard_hash(&self) -> u64 {
+        (**self).shard_hash()
+    }
+    fn shard_range_value(&self) -> Option<i64> {
+        (**self).shard_range_value()
+    }
+}
+
+/// Computes the shard that stores the given key.
+///
+/// If `ranges` is empty, the key is distributed by hash. Otherwise `ranges`
+/// holds the ascending lower bounds of shards 1, 2, ..., and keys below the
+/// first bound go to shard 0.
+pub fn shard_id_of<K: ShardKey + ?Sized>(
+    key: &K,
+    ranges: &[i64],
+    shard_num: usize,
+) -> Result<ShardId> {
+    let shard_num = shard_num.max(1);
+    let shard_id = if ranges.is_empty() {
+        (key.shard_hash() % shard_num as u64) as usize
+    } else {
+        let value = key
+            .shard_range_value()
+            .context("A range map requires an integer shard key.")?;
+        ranges.partition_point(|bound| *bound <= value)
+    };
+    ensure!(
+        shard_id < shard_num,
+        "The shard key is mapped to shard {}, but only {} shards are configured.",
+        shard_id,
+        shard_num
+    );
+    Ok(shard_id as ShardId)
+}
+
+/// Compares column values in the same way as `ORDER BY`.
+pub fn cmp_value<T: PartialOrd>(a: &T, b: &T) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+/// Compares nullable column values in the same way as `ORDER BY`.
+/// MySQL and SQLite sort NULL first in ascending order, PostgreSQL sorts it last.
+pub fn cmp_nullable<T: PartialOrd>(a: &Option<T>, b: &Option<T>, nulls_first: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp_value(a, b),
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) if nulls_first => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) if nulls_first => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+    }
+}
+
+/// Merges rows that each shard returned in order and applies `offset` and
+/// `limit` to the merged rows.
+pub fn merge_sorted<T, F>(
+    lists: Vec<Vec<T>>,
+    cmp: F,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Vec<T>
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut list: Vec<T> = lists.into_iter().flatten().collect();
+    // The stable sort merges the already sorted runs of each shard.
+    list.sort_by(cmp);
+    list.into_iter()
+        .skip(offset.unwrap_or_default())
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Merges streams that each shard returns in order.
+/// Ties are taken from the stream of the lower shard first.
+pub fn merge_sorted_streams<T, S, F>(streams: Vec<S>, cmp: F) -> impl Stream<Item = T>
+where
+    S: Stream<Item = T> + Unpin,
+    F: Fn(&T, &T) -> Ordering,
+{
+    futures::stream::unfold(
+        (streams, None::<Vec<Option<T>>>, cmp),
+        |(mut streams, heads, cmp)| async move {
+            let mut heads = match heads {
+                Some(heads) => heads,
+                None => future::join_all(streams.iter_mut().map(|s| s.next())).await,
+            };
+            let (index, _) = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(i, head)| head.as_ref().map(|head| (i, head)))
+                .min_by(|a, b| cmp(a.1, b.1))?;
+            let item = heads[index].take()?;
+            heads[index] = streams[index].next().await;
+            Some((item, (streams, Some(heads), cmp)))
+        },
+    )
+}
+
+/// Step of a resharding run.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReshardPhase {
+    /// Copies rows to the shards of the new layout.
+    #[default]
+    Copy,
+    /// Deletes rows that were moved away from their old shard.
+    Cleanup,
+}
+
+/// Position reached in a table on one shard.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ReshardPosition {
+    /// Primary key of the last row processed, serialized as JSON.
+    pub last_key: Option<String>,
+    /// Number of rows copied or deleted so far.
+    pub rows: u64,
+    pub done: bool,
+}
+
+/// Path of the progress file of a database.
+pub fn progress_path(dir: &Path, db: &str) -> PathBuf {
+    dir.join(format!("reshard_{}.json", db))
+}
+
+/// Path of the log that applications append the keys of failed dual writes to.
+pub fn failure_log_path(progress_path: &Path) -> PathBuf {
+    progress_path.with_extension("failed")
+}
+
+/// Appends the keys of rows whose dual write failed to the failure log.
+/// Several processes can append to the log at the same time.
+pub fn record_failures(path: &Path, table: &str, keys: &[String]) -> Result<()> {
+    let mut buf = String::new();
+    for key in keys {
+        buf.push_str(table);
+        buf.push('\t');
+        buf.push_str(key);
+        buf.push('\n');
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(buf.as_bytes())?;
+    Ok(())
+}
+
+/// Progress of a resharding run.
+///
+/// The file is rewritten after every batch, so an interrupted run resumes
+/// from the last batch that completed.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ReshardProgress {
+    #[serde(skip)]
+    path: PathBuf,
+    phase: ReshardPhase,
+    shard_num: usize,
+    tables: BTreeMap<String, BTreeMap<ShardId, ReshardPosition>>,
+    /// Keys of rows whose dual write failed, by table, serialized as JSON.
+    #[serde(default)]
+    failures: BTreeMap<String, BTreeSet<String>>,
+    /// Length of the failure log already merged into `failures`.
+    #[serde(default)]
+    failure_log_len: u64,
+}
+
+impl ReshardProgress {
+    /// Loads the progress file. The progress starts over if the file records
+    /// another phase or another number of shards.
+    pub fn load(path: &Path, phase: ReshardPhase, shard_num: usize) -> Result<Self> {
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            let mut progress: Self = serde_json::from_str(&content)
+                .with_context(|| format!("Invalid progress file: {}", path.display()))?;
+            if progress.phase == phase && progress.shard_num == shard_num {
+                progress.path = path.to_path_buf();
+                return Ok(progress);
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            phase,
+            shard_num,
+            ..Default::default()
+        })
+    }
+
+    pub fn phase(&self) -> ReshardPhase {
+        self.phase
+    }
+
+    pub fn position(&self, table: &str, shard_id: ShardId) -> ReshardPosition {
+        self.tables
+            .get(table)
+            .and_then(|v| v.get(&shard_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Records the position and saves the file.
+    pub fn update(
+        &mut self,
+        table: &str,
+        shard_id: ShardId,
+        position: ReshardPosition,
+    ) -> Result<()> {
+        self.tables
+            .entry(table.to_string())
+            .or_default()
+            .insert(shard_id, position);
+        self.save()
+    }
+
+    /// Merges the keys appended to the failure log since the last merge and saves the file.
+    pub fn merge_failures(&mut self) -> Result<()> {
+        let path = failure_log_path(&self.path);
+        if !path.exists() {
+            return Ok(());
+        }
+        let mut file = fs::File::open(&path)?;
+        let mut buf = Vec::new();
+        file.seek(SeekFrom::Start(self.failure_log_len))?;
+        file.read_to_end(&mut buf)?;
+        // A line that is still being written is merged next time.
+        let Some(end) = buf.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        for line in String::from_utf8_lossy(&buf[..end]).lines() {
+            if let Some((table, key)) = line.split_once('\t') {
+                self.failures
+                    .entry(table.to_string())
+                    .or_default()
+                    .insert(key.to_string());
+            }
+        }
+        self.failure_log_len += end as u64 + 1;
+        self.save()
+    }
+
+    /// Keys of the table whose dual write failed.
+    pub fn failures(&self, table: &str) -> Vec<String> {
+        self.failures
+            .get(table)
+            .map(|v| v.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn failure_count(&self) -> usize {
+        self.failures.values().map(|v| v.len()).sum()
+    }
+
+    /// Removes the keys that have been copied again and saves the file.
+    pub fn resolve_failures(&mut self, table: &str, keys: &[String]) -> Result<()> {
+        if let Some(failures) = self.failures.get_mut(table) {
+            for key in keys {
+                failures.remove(key);
+            }
+            if failures.is_empty() {
+                self.failures.remove(table);
+            }
+        }
+        self.save()
+    }
+
+    /// Removes the failure log once the new layout has replaced the old one.
+    pub fn remove_failure_log(&mut self) -> Result<()> {
+        let path = failure_log_path(&self.path);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        self.failure_log_len = 0;
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Sets or removes a variable in the content of a .env file.
+/// A value with several lines, such as a shard list, is written in double quotes.
+pub fn replace_env_var(content: &str, key: &str, value: Option<&str>) -> String {
+    let prefix = format!("{}=", key);
+    let mut result = String::new();
+    let mut replaced = false;
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        let trimmed = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+        if let Some(old) = trimmed.strip_prefix(&prefix) {
+            // Skips the rest of a quoted value that spans several lines.
+            let old = old.trim();
+            if let Some(quote) = old.chars().next().filter(|c| *c == '"' || *c == '\'') {
+                let mut closed = old[1..].contains(quote);
+                while !closed {
+                    match lines.next() {
+                        Some(line) => closed = line.contains(quote),
+                        None => break,
+                    }
+                }
+            }
+            if !replaced && let Some(value) = value {
+                result.push_str(&format_env_var(key, value));
+            }
+            replaced = true;
+            continue;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    if !replaced && let Some(value) = value {
+        result.push_str(&format_env_var(key, value));
+    }
+    result
+}
+
+fn format_env_var(key: &str, value: &str) -> String {
+    if value.contains('\n') {
+        format!("{}=\"{}\"\n", key, value)
+    } else {
+        format!("{}={}\n", key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash() {
+        assert_eq!(shard_id_of(&1u64, &[], 1).unwrap(), 0);
+        for i in 0..100i64 {
+            let shard_id = shard_id_of(&i, &[], 4).unwrap();
+            assert!(shard_id < 4);
+            assert_eq!(shard_id_of(&(i as u32), &[], 4).unwrap(), shard_id);
+        }
+        assert_eq!(
+            shard_id_of("abc", &[], 4).unwrap(),
+            shard_id_of(&"abc".to_string(), &[], 4).unwrap()
+        );
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(fnv1a(b""), 14695981039346656037);
+        assert_eq!("a".shard_hash(), 12638187200555641996);
+        assert_eq!("abc".shard_hash(), 16654208175385433931);
+        assert_eq!(0i64.shard_hash(), 12161962213042174405);
+        assert_eq!(1u8.shard_hash(), 9929646806074584996);
+        assert_eq!((-1i32).shard_hash(), 10157053723145373757);
+        assert_eq!(shard_id_of(&1, &[], 4).unwrap(), 0);
+        assert_eq!(shard_id_of("abc", &[], 4).unwrap(), 3);
+    }
+
+    #[test]
+    fn range() {
+        let ranges = [1000, 2000];
+        assert_eq!(shard_id_of(&0, &ranges, 3).unwrap(), 0);
+        assert_eq!(shard_id_of(&999, &ranges, 3).unwrap(), 0);
+        assert_eq!(shard_id_of(&1000, &ranges, 3).unwrap(), 1);
+        assert_eq!(shard_id_of(&2000, &ranges, 3).unwrap(), 2);
+        assert!(shard_id_of(&2000, &ranges, 2).is_err());
+        assert!(shard_id_of("abc", &ranges, 3).is_err());
+    }
+
+    #[test]
+    fn nullable() {
+        assert_eq!(cmp_nullable(&None, &Some(1), true), Ordering::Less);
+        assert_eq!(cmp_nullable(&None, &Some(1), false), Ordering::Greater);
+        assert_eq!(cmp_nullable(&Some(2), &Some(1), false), Ordering::Greater);
+    }
+
+    #[test]
+    fn merge() {
+        let list = merge_sorted(
+            vec![vec![1, 4, 7], vec![2, 3, 8]],
+            cmp_value,
+            Some(1),
+            Some(3),
+        );
+        assert_eq!(list, vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn merge_streams() {
+        let streams = vec![
+            futures::stream::iter(vec![1, 4, 7]),
+            futures::stream::iter(vec![]),
+            futures::stream::iter(vec![2, 3, 8]),
+        ];
+        let list: Vec<i32> = merge_sorted_streams(streams, cmp_value).collect().await;
+        assert_eq!(list, vec![1, 2, 3, 4, 7, 8]);
+    }
+
+    #[test]
+    fn progress() {
+        let path =
+            std::env::temp_dir().join(format!("reshard_progress_{}.json", std::process::id()));
+        let mut progress = ReshardProgress::load(&path, ReshardPhase::Copy, 4).unwrap();
+        let position = ReshardPosition {
+            last_key: Some("[10]".to_string()),
+            rows: 3,
+            done: false,
+        };
+        progress.update("user", 1, position.clone()).unwrap();
+        let progress = ReshardProgress::load(&path, ReshardPhase::Copy, 4).unwrap();
+        assert_eq!(progress.position("user", 1), position);
+        assert_eq!(progress.position("user", 0), ReshardPosition::default());
+        let progress = ReshardProgress::load(&path, ReshardPhase::Cleanup, 4).unwrap();
+        assert_eq!(progress.position("user", 1), ReshardPosition::default());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failures() {
+        let path =
+            std::env::temp_dir().join(format!("reshard_failures_{}.json", std::process::id()));
+        let log = failure_log_path(&path);
+        let mut progress = ReshardProgress::load(&path, ReshardPhase::Copy, 4).unwrap();
+        progress.merge_failures().unwrap();
+        assert_eq!(progress.failure_count(), 0);
+        record_failures(&log, "user", &["[1]".to_string(), "[2]".to_string()]).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(b"user\t[3")
+            .unwrap();
+        progress.merge_failures().unwrap();
+        assert_eq!(progress.failures("user"), vec!["[1]", "[2]"]);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(b"]\n")
+            .unwrap();
+        record_failures(&log, "post", &["[1,2]".to_string()]).unwrap();
+        let mut progress = ReshardProgress::load(&path, ReshardPhase::Copy, 4).unwrap();
+        progress.merge_failures().unwrap();
+        assert_eq!(progress.failure_count(), 4);
+        progress
+            .resolve_failures(
+                "user",
+                &["[1]".to_string(), "[2]".to_string(), "[3]".to_string()],
+            )
+            .unwrap();
+        assert!(progress.failures("user").is_empty());
+        assert_eq!(progress.failures("post"), vec!["[1,2]"]);
+        progress.remove_failure_log().unwrap();
+        assert!(!log.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn env_var() {
+        let content = "A=1\nDB_URL=\"mysql://a\nmysql://b\"\nB=2\n";
+        assert_eq!(
+            replace_env_var(content, "DB_URL", Some("mysql://a\nmysql://b\nmysql://c")),
+            "A=1\nDB_URL=\"mysql://a\nmysql://b\nmysql://c\"\nB=2\n"
+        );
+        assert_eq!(replace_env_var(content, "DB_URL", None), "A=1\nB=2\n");
+        assert_eq!(replace_env_var("A=1", "C", Some("x")), "A=1\nC=x\n");
+    }
+}
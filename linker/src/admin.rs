@@ -0,0 +1,487 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+/// Counters updated by the hub, the listeners and the QUIC connections.
+pub struct Metrics {
+    pub packs_from_local: AtomicU64,
+    pub bytes_from_local: AtomicU64,
+    pub packs_from_outer: AtomicU64,
+    pub bytes_from_outer: AtomicU64,
+    pub packs_to_outer: AtomicU64,
+    pub inbound_connections: AtomicU64,
+    local_senders: Mutex<BTreeMap<u64, u64>>,
+    peers: Mutex<BTreeMap<(u64, String), PeerState>>,
+}
+
+pub static METRICS: Metrics = Metrics {
+    packs_from_local: AtomicU64::new(0),
+    bytes_from_local: AtomicU64::new(0),
+    packs_from_outer: AtomicU64::new(0),
+    bytes_from_outer: AtomicU64::new(0),
+    packs_to_outer: AtomicU64::new(0),
+    inbound_connections: AtomicU64::new(0),
+    local_senders: Mutex::new(BTreeMap::new()),
+    peers: Mutex::new(BTreeMap::new()),
+};
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum PeerState {
+    Connecting,
+    Connected,
+    Failed { error: String },
+}
+
+impl Metrics {
+    pub fn sender_connected(&self, stream_id: u64) {
+        *self
+            .local_senders
+            .lock()
+            .unwrap()
+            .entry(stream_id)
+            .or_default() += 1;
+    }
+
+    pub fn sender_disconnected(&self, stream_id: u64) {
+        let mut map = self.local_senders.lock().unwrap();
+        if let Some(count) = map.get_mut(&stream_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                map.remove(&stream_id);
+            }
+        }
+    }
+
+    /// Records the state of the QUIC connection to the peer. None removes it.
+    pub fn set_peer(&self, stream_id: u64, remote: &str, state: Option<PeerState>) {
+        let mut map = self.peers.lock().unwrap();
+        let key = (stream_id, remote.to_string());
+        match state {
+            Some(state) => {
+                map.insert(key, state);
+            }
+            None => {
+                map.remove(&key);
+            }
+        }
+    }
+}
+
+/// State of the hub collected on each request.
+#[derive(Serialize)]
+pub struct StreamStatus {
+    pub stream_id: u64,
+    pub local_receivers: usize,
+    pub outer_linkers: Vec<String>,
+    pub replay_epoch: u64,
+    pub replay_seq: u64,
+    pub replay_buffered: usize,
+    /// Seconds since the last local receiver disconnected
+    pub idle_secs: Option<u64>,
+}
+
+pub type StatusRequest = oneshot::Sender<Vec<StreamStatus>>;
+
+#[derive(Serialize)]
+struct Status {
+    uptime_secs: u64,
+    packs_from_local: u64,
+    bytes_from_local: u64,
+    packs_from_outer: u64,
+    bytes_from_outer: u64,
+    packs_to_outer: u64,
+    inbound_connections: u64,
+    streams: Vec<StreamStatusWithSenders>,
+    peers: Vec<Peer>,
+}
+
+#[derive(Serialize)]
+struct StreamStatusWithSenders {
+    #[serde(flatten)]
+    status: StreamStatus,
+    local_senders: u64,
+}
+
+#[derive(Serialize)]
+struct Peer {
+    stream_id: u64,
+    remote: String,
+    #[serde(flatten)]
+    state: PeerState,
+}
+
+/// Serves /metrics in the Prometheus text format and /status in JSON.
+pub fn run(addr: SocketAddr, tx_status: mpsc::Sender<StatusRequest>) -> Result<()> {
+    let started = SystemTime::now();
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+    info!("admin listening on {}", addr);
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    error!("admin: {}", e);
+                    continue;
+                }
+            };
+            let tx_status = tx_status.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, tx_status, started).await {
+                    warn!("admin: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    tx_status: mpsc::Sender<StatusRequest>,
+    started: SystemTime,
+) -> Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+    timeout(Duration::from_secs(10), stream.read_line(&mut request_line)).await??;
+    // Skip the headers
+    loop {
+        let mut line = String::new();
+        let n = timeout(Duration::from_secs(10), stream.read_line(&mut line)).await??;
+        if n == 0 || line.trim_end().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+    let (code, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => {
+            let status = status(&tx_status, started).await?;
+            (
+                "200 OK",
+                "text/plain; version=0.0.4",
+                render_metrics(&status)?,
+            )
+        }
+        ("GET", "/status") => {
+            let status = status(&tx_status, started).await?;
+            (
+                "200 OK",
+                "application/json",
+                serde_json::to_string_pretty(&status)?,
+            )
+        }
+        ("GET", _) => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method Not Allowed\n".to_string(),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        content_type,
+        body.len(),
+        body
+    );
+    let stream = stream.get_mut();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn status(tx_status: &mpsc::Sender<StatusRequest>, started: SystemTime) -> Result<Status> {
+    let (tx, rx) = oneshot::channel();
+    tx_status.send(tx).await?;
+    let streams = rx.await?;
+    let senders = METRICS.local_senders.lock().unwrap().clone();
+    let peers = METRICS
+        .peers
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((stream_id, remote), state)| Peer {
+            stream_id: *stream_id,
+            remote: remote.clone(),
+            state: state.clone(),
+        })
+        .collect();
+    Ok(Status {
+        uptime_secs: started.elapsed().map(|d| d.as_secs()).unwrap_or_default(),
+        packs_from_local: METRICS.packs_from_local.load(Ordering::Relaxed),
+        bytes_from_local: METRICS.bytes_from_local.load(Ordering::Relaxed),
+        packs_from_outer: METRICS.packs_from_outer.load(Ordering::Relaxed),
+        bytes_from_outer: METRICS.bytes_from_outer.load(Ordering::Relaxed),
+        packs_to_outer: METRICS.packs_to_outer.load(Ordering::Relaxed),
+        inbound_connections: METRICS.inbound_connections.load(Ordering::Relaxed),
+        streams: streams
+            .into_iter()
+            .map(|status| StreamStatusWithSenders {
+                local_senders: senders.get(&status.stream_id).copied().unwrap_or_default(),
+                status,
+            })
+            .collect(),
+        peers,
+    })
+}
+
+fn render_metrics(status: &Status) -> Result<String> {
+    let mut buf = String::new();
+    let counters = [
+        (
+            "packs_from_local_total",
+            "Packs received from local senders",
+            status.packs_from_local,
+        ),
+        (
+            "bytes_from_local_total",
+            "Bytes received from local senders",
+            status.bytes_from_local,
+        ),
+        (
+            "packs_from_outer_total",
+            "Packs received from other linkers",
+            status.packs_from_outer,
+        ),
+        (
+            "bytes_from_outer_total",
+            "Bytes received from other linkers",
+            status.bytes_from_outer,
+        ),
+        (
+            "packs_to_outer_total",
+            "Packs sent to other linkers",
+            status.packs_to_outer,
+        ),
+    ];
+    for (name, help, value) in counters {
+        writeln!(buf, "# HELP senax_linker_{name} {help}")?;
+        writeln!(buf, "# TYPE senax_linker_{name} counter")?;
+        writeln!(buf, "senax_linker_{name} {value}")?;
+    }
+    writeln!(
+        buf,
+        "# HELP senax_linker_inbound_connections Connections from other linkers"
+    )?;
+    writeln!(buf, "# TYPE senax_linker_inbound_connections gauge")?;
+    writeln!(
+        buf,
+        "senax_linker_inbound_connections {}",
+        status.inbound_connections
+    )?;
+    writeln!(
+        buf,
+        "# HELP senax_linker_uptime_seconds Seconds since the linker started"
+    )?;
+    writeln!(buf, "# TYPE senax_linker_uptime_seconds gauge")?;
+    writeln!(buf, "senax_linker_uptime_seconds {}", status.uptime_secs)?;
+
+    type Getter = fn(&StreamStatusWithSenders) -> u64;
+    let gauges: [(&str, &str, Getter); 5] = [
+        ("local_receivers", "Local receivers of the stream", |s| {
+            s.status.local_receivers as u64
+        }),
+        ("local_senders", "Local senders of the stream", |s| {
+            s.local_senders
+        }),
+        (
+            "outer_linkers",
+            "Other linkers the stream is sent to",
+            |s| s.status.outer_linkers.len() as u64,
+        ),
+        ("replay_seq", "Last sequence number of the stream", |s| {
+            s.status.replay_seq
+        }),
+        ("replay_buffered", "Packs kept in the replay buffer", |s| {
+            s.status.replay_buffered as u64
+        }),
+    ];
+    for (name, help, getter) in gauges {
+        writeln!(buf, "# HELP senax_linker_stream_{name} {help}")?;
+        writeln!(buf, "# TYPE senax_linker_stream_{name} gauge")?;
+        for stream in &status.streams {
+            writeln!(
+                buf,
+                "senax_linker_stream_{name}{{stream_id=\"{}\"}} {}",
+                stream.status.stream_id,
+                getter(stream)
+            )?;
+        }
+    }
+    writeln!(
+        buf,
+        "# HELP senax_linker_peer_up Whether the connection to the other linker is established"
+    )?;
+    writeln!(buf, "# TYPE senax_linker_peer_up gauge")?;
+    for peer in &status.peers {
+        let up = matches!(peer.state, PeerState::Connected) as u8;
+        writeln!(
+            buf,
+            "senax_linker_peer_up{{stream_id=\"{}\",peer=\"{}\"}} {}",
+            peer.stream_id,
+            peer.remote.replace('\\', "\\\\").replace('"', "\\\""),
+            up
+        )?;
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    fn stream_status(stream_id: u64) -> StreamStatus {
+        StreamStatus {
+            stream_id,
+            local_receivers: 2,
+            outer_linkers: vec!["10.0.0.2:25551".to_string()],
+            replay_epoch: 1,
+            replay_seq: 42,
+            replay_buffered: 3,
+            idle_secs: None,
+        }
+    }
+
+    #[test]
+    fn prometheus_output() {
+        let status = Status {
+            uptime_secs: 60,
+            packs_from_local: 10,
+            bytes_from_local: 1000,
+            packs_from_outer: 20,
+            bytes_from_outer: 2000,
+            packs_to_outer: 30,
+            inbound_connections: 4,
+            streams: vec![StreamStatusWithSenders {
+                status: stream_status(7),
+                local_senders: 5,
+            }],
+            peers: vec![
+                Peer {
+                    stream_id: 7,
+                    remote: "10.0.0.2:25551".to_string(),
+                    state: PeerState::Connected,
+                },
+                Peer {
+                    stream_id: 7,
+                    remote: "host\"x".to_string(),
+                    state: PeerState::Failed {
+                        error: "timed out".to_string(),
+                    },
+                },
+            ],
+        };
+        let metrics = render_metrics(&status).unwrap();
+        let lines: Vec<_> = metrics.lines().collect();
+        let position = |line: &str| {
+            lines
+                .iter()
+                .position(|v| *v == line)
+                .unwrap_or_else(|| panic!("{line} is not found in:\n{metrics}"))
+        };
+        for (name, kind, sample) in [
+            ("packs_from_local_total", "counter", "10"),
+            ("bytes_from_local_total", "counter", "1000"),
+            ("packs_from_outer_total", "counter", "20"),
+            ("bytes_from_outer_total", "counter", "2000"),
+            ("packs_to_outer_total", "counter", "30"),
+            ("inbound_connections", "gauge", "4"),
+            ("uptime_seconds", "gauge", "60"),
+        ] {
+            let help = position(&format!("# TYPE senax_linker_{name} {kind}")) - 1;
+            assert!(lines[help].starts_with(&format!("# HELP senax_linker_{name} ")));
+            assert_eq!(position(&format!("senax_linker_{name} {sample}")), help + 2);
+        }
+        for (name, sample) in [
+            ("local_receivers", "2"),
+            ("local_senders", "5"),
+            ("outer_linkers", "1"),
+            ("replay_seq", "42"),
+            ("replay_buffered", "3"),
+        ] {
+            let kind = position(&format!("# TYPE senax_linker_stream_{name} gauge"));
+            assert_eq!(
+                position(&format!(
+                    "senax_linker_stream_{name}{{stream_id=\"7\"}} {sample}"
+                )),
+                kind + 1
+            );
+        }
+        let kind = position("# TYPE senax_linker_peer_up gauge");
+        assert_eq!(
+            position("senax_linker_peer_up{stream_id=\"7\",peer=\"10.0.0.2:25551\"} 1"),
+            kind + 1
+        );
+        assert_eq!(
+            position("senax_linker_peer_up{stream_id=\"7\",peer=\"host\\\"x\"} 0"),
+            kind + 2
+        );
+        assert!(metrics.ends_with('\n'));
+    }
+
+    async fn request(raw: &str) -> (String, String) {
+        let (tx_status, mut rx_status) = mpsc::channel::<StatusRequest>(1);
+        tokio::spawn(async move {
+            while let Some(tx) = rx_status.recv().await {
+                let _ = tx.send(vec![stream_status(7)]);
+            }
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_connection(stream, tx_status, SystemTime::now()).await
+        });
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client.write_all(raw.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        server.await.unwrap().unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let content_length = head
+            .lines()
+            .find_map(|v| v.strip_prefix("Content-Length: "))
+            .unwrap();
+        assert_eq!(content_length.parse::<usize>().unwrap(), body.len());
+        (head.to_string(), body.to_string())
+    }
+
+    #[tokio::test]
+    async fn routing() {
+        let (head, body) = request("GET /metrics?x=1 HTTP/1.1\r\nHost: linker\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(body.contains("senax_linker_stream_local_receivers{stream_id=\"7\"} 2\n"));
+
+        let (head, body) = request("GET /status HTTP/1.1\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: application/json"));
+        let status: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status["streams"][0]["stream_id"], 7);
+        assert_eq!(status["streams"][0]["replay_seq"], 42);
+        assert!(status["peers"].is_array());
+
+        let (head, body) = request("GET /unknown HTTP/1.1\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert_eq!(body, "Not Found\n");
+
+        let (head, body) = request("POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert_eq!(body, "Method Not Allowed\n");
+    }
+}
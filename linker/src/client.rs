@@ -2,10 +2,18 @@ use anyhow::{Result, anyhow, bail};
 use bytes::{BufMut, BytesMut};
 use quinn::{Endpoint, TransportConfig, crypto::rustls::QuicClientConfig};
 use sha2::{Digest, Sha512};
-use std::{fs, net::ToSocketAddrs, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    fs,
+    net::ToSocketAddrs,
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use zstd::Encoder;
 
+use crate::admin::{METRICS, PeerState};
+
 use crate::common::{
     ALPN_QUIC_HTTP, LINKER_VER, LINKER_VER_ERROR, PASSWORD_ERROR, Pack, ZSTD_LEVEL,
 };
@@ -42,10 +50,24 @@ pub fn connect_client(
     let host = host.to_string();
     let (to_outer, from_local) = mpsc::unbounded_channel::<Pack>();
     tokio::spawn(async move {
-        if let Err(e) =
-            handle_connection(remote, host, endpoint, from_local, pw, stream_id, command).await
+        METRICS.set_peer(stream_id, &remote, Some(PeerState::Connecting));
+        match handle_connection(
+            remote.clone(),
+            host,
+            endpoint,
+            from_local,
+            pw,
+            stream_id,
+            command,
+        )
+        .await
         {
-            error!("{}", e);
+            Ok(_) => METRICS.set_peer(stream_id, &remote, None),
+            Err(e) => {
+                error!("{}", e);
+                let error = e.to_string();
+                METRICS.set_peer(stream_id, &remote, Some(PeerState::Failed { error }));
+            }
         }
     });
     Ok(to_outer)
@@ -60,20 +82,24 @@ async fn handle_connection(
     stream_id: u64,
     command: u16,
 ) -> Result<(), anyhow::Error> {
-    let remote = remote
+    let remote_addr = remote
         .to_socket_addrs()?
         .next()
         .ok_or_else(|| anyhow!("couldn't resolve to an address"))?;
     let conn = endpoint
-        .connect(remote, &host)?
+        .connect(remote_addr, &host)?
         .await
         .map_err(|e| anyhow!("failed to connect: {}", e))?;
     send_password(&conn, pw, stream_id, command).await?;
+    METRICS.set_peer(stream_id, &remote, Some(PeerState::Connected));
     while let Some(pack) = from_local.recv().await {
         let mut list1 = vec![pack];
         while let Ok(pack) = from_local.try_recv() {
             list1.push(pack);
         }
+        METRICS
+            .packs_to_outer
+            .fetch_add(list1.len() as u64, Ordering::Relaxed);
         let mut list2 = vec![];
         for pack in &list1 {
             list2.push(serde_bytes::Bytes::new(&pack.data[..]));
@@ -6,6 +6,9 @@ use tokio_uring::buf::{IoBuf, IoBufMut};
 
 pub const ALPN_QUIC_HTTP: &[&[u8]] = &[b"hq-29"];
 pub const LINKER_VER: u16 = 1;
+/// Version of the protocol with the local clients. Version 2 added the resume position to the
+/// receiver handshake, so the linker and senax-common must be upgraded together.
+pub const LOCAL_VER: u16 = 2;
 pub const CMD_RESET: u16 = 1;
 pub const SENDER: u16 = 0;
 pub const RECEIVER: u16 = 1;
@@ -21,6 +24,16 @@ pub struct Pack {
     pub data: Bytes, // including self length
     pub conn_no: u64,
     pub stream_id: u64,
+    pub epoch: u64,
+    pub seq: u64,
+}
+
+/// Position in the replay buffer requested by a reconnected receiver.
+/// The epoch is 0 on the first connection.
+#[derive(Clone, Copy)]
+pub struct Resume {
+    pub epoch: u64,
+    pub seq: u64,
 }
 
 pub struct IoBytesMut(pub BytesMut, usize, usize);
@@ -0,0 +1,30 @@
+use anyhow::Result;
+
+pub mod etcd;
+pub mod static_list;
+
+pub enum Event {
+    /// A peer linker joined. None means all streams.
+    Join(Option<u64>, String),
+    /// A peer linker left. None means all streams.
+    Leave(Option<u64>, String),
+    /// This linker has to stop.
+    Shutdown,
+}
+
+/// Discovers the peer linkers serving each stream.
+pub trait Discovery {
+    /// Returns the addresses of the peer linkers serving the stream.
+    async fn fetch_node_list(&mut self, stream_id: u64) -> Result<Vec<String>>;
+
+    /// Announces that this linker serves the stream.
+    async fn register_node(&mut self, stream_id: u64) -> Result<()>;
+
+    /// Announces that this linker no longer serves the stream.
+    async fn unregister_node(&mut self, stream_id: u64) -> Result<()>;
+
+    /// Waits for the next membership change. This must be cancel safe.
+    async fn next_event(&mut self) -> Result<Event>;
+
+    async fn close(&mut self);
+}
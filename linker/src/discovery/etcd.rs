@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use etcd_client::{
+    Client, ConnectOptions, EventType, GetOptions, PutOptions, TlsOptions, WatchOptions,
+    WatchStream, Watcher,
+};
+use regex::Regex;
+use std::{collections::VecDeque, env, fs, net::SocketAddr, time::Duration};
+use tokio::time::sleep;
+
+use super::{Discovery, Event};
+
+const WATCHDOG_TTL: i64 = 10;
+const WATCHDOG_KEEP_ALIVE: u64 = 1;
+
+/// Discovery with etcd leases and watches.
+pub struct EtcdDiscovery {
+    client: Client,
+    lease: i64,
+    link_port: SocketAddr,
+    watchdog_key: String,
+    _watcher: Watcher,
+    stream: WatchStream,
+    stream_re: Regex,
+    events: VecDeque<Event>,
+}
+
+impl EtcdDiscovery {
+    pub async fn connect(link_port: SocketAddr) -> Result<Self> {
+        let etcd_port = env::var("ETCD_PORT").unwrap_or_else(|_| "localhost:2379".to_string());
+        let etcd_user = env::var("ETCD_USER").ok();
+        let etcd_pw = env::var("ETCD_PW").ok();
+        let etcd_domain_name = env::var("ETCD_DOMAIN_NAME").ok();
+        let etcd_ca_pem_file = env::var("ETCD_CA_PEM_FILE").ok();
+        let etcd_cert_pem_file = env::var("ETCD_CERT_PEM_FILE").ok();
+        let etcd_key_pem_file = env::var("ETCD_KEY_PEM_FILE").ok();
+
+        let mut option = ConnectOptions::new();
+        if let (Some(etcd_user), Some(etcd_pw)) = (etcd_user, etcd_pw) {
+            option = option.with_user(etcd_user, etcd_pw);
+        }
+        if let Some(domain_name) = etcd_domain_name {
+            let mut tls = TlsOptions::new();
+            tls = tls.domain_name(domain_name);
+            if let Some(ca_path) = etcd_ca_pem_file {
+                let pem = fs::read(ca_path).context("failed to read ETCD_CA_PEM_FILE")?;
+                let ca = etcd_client::Certificate::from_pem(pem);
+                tls = tls.ca_certificate(ca);
+            }
+            if let (Some(cert_file), Some(key_file)) = (etcd_cert_pem_file, etcd_key_pem_file) {
+                let cert = fs::read(cert_file).context("failed to read ETCD_CERT_PEM_FILE")?;
+                let key = fs::read(key_file).context("failed to read ETCD_KEY_PEM_FILE")?;
+                let identity = etcd_client::Identity::from_pem(cert, key);
+                tls = tls.identity(identity);
+            }
+            option = option.with_tls(tls);
+        }
+        let etcd_port: Vec<_> = etcd_port.split(',').collect();
+        let mut client = Client::connect(etcd_port, Some(option)).await?;
+        let lease = client.lease_grant(WATCHDOG_TTL, None).await?.id();
+        let watchdog_key = set_watchdog(&mut client, link_port, lease).await?;
+
+        let opt = WatchOptions::new().with_prefix();
+        let key = "/senax_linker/";
+        let (watcher, stream) = client.watch(key, Some(opt)).await?;
+
+        Ok(Self {
+            client,
+            lease,
+            link_port,
+            watchdog_key,
+            _watcher: watcher,
+            stream,
+            stream_re: Regex::new(r"^/senax_linker/(\d+)/(.+)$").unwrap(),
+            events: VecDeque::new(),
+        })
+    }
+}
+
+impl Discovery for EtcdDiscovery {
+    async fn fetch_node_list(&mut self, stream_id: u64) -> Result<Vec<String>> {
+        let opt = GetOptions::new().with_prefix();
+        let key = format!("/senax_linker/{stream_id}/");
+        let resp = self.client.get(&*key, Some(opt)).await?;
+        let mut ports = Vec::new();
+        for kv in resp.kvs() {
+            ports.push(kv.key_str()?.trim_start_matches(&key).to_string());
+        }
+        Ok(ports)
+    }
+
+    async fn register_node(&mut self, stream_id: u64) -> Result<()> {
+        let opt = PutOptions::new().with_lease(self.lease);
+        let key = format!("/senax_linker/{stream_id}/{}", self.link_port);
+        self.client.put(&*key, "1", Some(opt)).await?;
+        Ok(())
+    }
+
+    async fn unregister_node(&mut self, stream_id: u64) -> Result<()> {
+        let key = format!("/senax_linker/{stream_id}/{}", self.link_port);
+        self.client.delete(&*key, None).await?;
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            let resp = self
+                .stream
+                .message()
+                .await?
+                .context("etcd watch disconnected")?;
+            for event in resp.events() {
+                if let Some(kv) = event.kv() {
+                    let key = kv.key_str()?;
+                    if EventType::Delete == event.event_type() && key == self.watchdog_key {
+                        self.events.push_back(Event::Shutdown);
+                    } else if let Some(caps) = self.stream_re.captures(key) {
+                        let stream_id: u64 = caps.get(1).unwrap().as_str().parse()?;
+                        let remote = caps.get(2).unwrap().as_str().to_string();
+                        if EventType::Put == event.event_type() {
+                            self.events.push_back(Event::Join(Some(stream_id), remote));
+                        } else if EventType::Delete == event.event_type() {
+                            self.events.push_back(Event::Leave(Some(stream_id), remote));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn close(&mut self) {
+        let _ = self.client.lease_revoke(self.lease).await;
+    }
+}
+
+async fn set_watchdog(
+    etcd_client: &mut Client,
+    link_port: SocketAddr,
+    lease: i64,
+) -> Result<String> {
+    let (mut keeper, mut _stream) = etcd_client.lease_keep_alive(lease).await?;
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_secs(WATCHDOG_KEEP_ALIVE)).await;
+            let _ = keeper.keep_alive().await;
+        }
+    });
+    let opt = PutOptions::new().with_lease(lease);
+    let key = format!("/senax_linker/watchdog/{link_port}");
+    etcd_client.put(&*key, "1", Some(opt)).await?;
+    Ok(key)
+}
@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::{collections::VecDeque, env, fs, path::PathBuf, time::Duration, time::SystemTime};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+use super::{Discovery, Event};
+
+const RELOAD_INTERVAL: u64 = 5;
+
+/// Discovery with a static peer list.
+/// Every peer is assumed to serve all streams.
+pub struct StaticDiscovery {
+    peers: Vec<String>,
+    file: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    interval: Interval,
+    events: VecDeque<Event>,
+}
+
+impl StaticDiscovery {
+    /// Reads PEERS_FILE or PEERS. Returns None when neither is set.
+    pub fn from_env() -> Result<Option<Self>> {
+        let mut interval = time::interval(Duration::from_secs(RELOAD_INTERVAL));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        if let Some(file) = env::var_os("PEERS_FILE") {
+            let file: PathBuf = file.into();
+            let modified = fs::metadata(&file)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("failed to read PEERS_FILE: {}", file.display()))?;
+            let peers = parse_peers(&fs::read_to_string(&file)?);
+            info!("static peers: {}", peers.join(","));
+            return Ok(Some(Self {
+                peers,
+                file: Some(file),
+                modified: Some(modified),
+                interval,
+                events: VecDeque::new(),
+            }));
+        }
+        if let Ok(peers) = env::var("PEERS") {
+            let peers = parse_peers(&peers);
+            info!("static peers: {}", peers.join(","));
+            return Ok(Some(Self {
+                peers,
+                file: None,
+                modified: None,
+                interval,
+                events: VecDeque::new(),
+            }));
+        }
+        Ok(None)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let modified = fs::metadata(file)?.modified()?;
+        if self.modified == Some(modified) {
+            return Ok(());
+        }
+        let peers = parse_peers(&fs::read_to_string(file)?);
+        self.modified = Some(modified);
+        for remote in &self.peers {
+            if !peers.contains(remote) {
+                self.events.push_back(Event::Leave(None, remote.clone()));
+            }
+        }
+        for remote in &peers {
+            if !self.peers.contains(remote) {
+                self.events.push_back(Event::Join(None, remote.clone()));
+            }
+        }
+        if !self.events.is_empty() {
+            info!("static peers reloaded: {}", peers.join(","));
+        }
+        self.peers = peers;
+        Ok(())
+    }
+}
+
+impl Discovery for StaticDiscovery {
+    async fn fetch_node_list(&mut self, _stream_id: u64) -> Result<Vec<String>> {
+        Ok(self.peers.clone())
+    }
+
+    async fn register_node(&mut self, _stream_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    async fn unregister_node(&mut self, _stream_id: u64) -> Result<()> {
+        Ok(())
+    }
+
+    async fn next_event(&mut self) -> Result<Event> {
+        if self.file.is_none() {
+            return std::future::pending().await;
+        }
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(event);
+            }
+            self.interval.tick().await;
+            if let Err(e) = self.reload() {
+                // Keep the current peers while the file is being replaced.
+                warn!("failed to reload PEERS_FILE: {}", e);
+            }
+        }
+    }
+
+    async fn close(&mut self) {}
+}
+
+/// Peers are separated by commas or newlines. Lines starting with # are ignored.
+fn parse_peers(list: &str) -> Vec<String> {
+    let mut peers = Vec::new();
+    for line in list.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        for peer in line.split(',') {
+            let peer = peer.trim();
+            if !peer.is_empty() && !peers.iter().any(|p| p == peer) {
+                peers.push(peer.to_string());
+            }
+        }
+    }
+    peers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let peers = parse_peers(
+            "# linkers\n10.0.0.1:25552, 10.0.0.2:25552\n\n  10.0.0.3:25552 ,10.0.0.1:25552,\n#10.0.0.4:25552\n",
+        );
+        assert_eq!(
+            peers,
+            vec!["10.0.0.1:25552", "10.0.0.2:25552", "10.0.0.3:25552"]
+        );
+        assert!(parse_peers("").is_empty());
+        assert!(parse_peers(" , \n# none").is_empty());
+    }
+
+    fn events(discovery: &mut StaticDiscovery) -> Vec<String> {
+        discovery
+            .events
+            .drain(..)
+            .map(|event| match event {
+                Event::Join(None, remote) => format!("join {remote}"),
+                Event::Leave(None, remote) => format!("leave {remote}"),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reload() {
+        let file = env::temp_dir().join(format!("senax-linker-peers-{}", std::process::id()));
+        fs::write(&file, "10.0.0.1:25552\n10.0.0.2:25552\n").unwrap();
+        let mut discovery = StaticDiscovery {
+            peers: parse_peers(&fs::read_to_string(&file).unwrap()),
+            file: Some(file.clone()),
+            modified: Some(fs::metadata(&file).unwrap().modified().unwrap()),
+            interval: time::interval(Duration::from_secs(RELOAD_INTERVAL)),
+            events: VecDeque::new(),
+        };
+
+        // Unchanged file
+        discovery.reload().unwrap();
+        assert!(events(&mut discovery).is_empty());
+
+        fs::write(&file, "10.0.0.2:25552,10.0.0.3:25552\n").unwrap();
+        // The modification time may not change within its resolution.
+        discovery.modified = None;
+        discovery.reload().unwrap();
+        assert_eq!(
+            events(&mut discovery),
+            vec!["leave 10.0.0.1:25552", "join 10.0.0.3:25552"]
+        );
+        assert_eq!(
+            discovery.fetch_node_list(1).await.unwrap(),
+            vec!["10.0.0.2:25552", "10.0.0.3:25552"]
+        );
+
+        // A missing file keeps the current peers.
+        fs::remove_file(&file).unwrap();
+        assert!(discovery.reload().is_err());
+        assert!(events(&mut discovery).is_empty());
+        assert_eq!(discovery.peers, vec!["10.0.0.2:25552", "10.0.0.3:25552"]);
+    }
+}
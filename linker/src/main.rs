@@ -5,39 +5,46 @@ use anyhow::{Context, Result, bail};
 use clap::Parser;
 use client::client_endpoint;
 use common::Pack;
+use discovery::{Discovery, Event, etcd::EtcdDiscovery, static_list::StaticDiscovery};
 use dotenvy::dotenv;
-use etcd_client::{
-    Client, ConnectOptions, EventType, GetOptions, PutOptions, TlsOptions, WatchOptions,
-};
 use mimalloc::MiMalloc;
+use quinn::Endpoint;
 use rcgen::generate_simple_self_signed;
-use regex::Regex;
 use std::collections::hash_map::Entry::Vacant;
+use std::sync::atomic::Ordering;
 use std::{
     collections::HashMap,
     env, fs,
-    net::{SocketAddr, ToSocketAddrs},
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
     path::PathBuf,
     str,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{sync::broadcast, time::sleep};
 use tokio::{
-    sync::mpsc::{self, UnboundedSender},
+    sync::mpsc::{self, Receiver, UnboundedReceiver, UnboundedSender},
     time,
 };
 
-use crate::{client::connect_client, common::CMD_RESET};
+use crate::{
+    admin::{METRICS, StatusRequest, StreamStatus},
+    client::connect_client,
+    common::{CMD_RESET, Resume},
+    replay::ReplayBuffer,
+};
 
+mod admin;
 mod client;
 pub mod common;
+mod discovery;
+mod replay;
 mod server;
 mod tcp_listener;
 mod unix_listener;
 
-const WATCHDOG_TTL: i64 = 10;
-const WATCHDOG_KEEP_ALIVE: u64 = 1;
 const HOST_NAME: &str = "senax_linker";
+const REPLAY_BUFFER_SIZE: usize = 10000;
+const REPLAY_RETENTION: u64 = 60;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -83,23 +90,36 @@ async fn main() -> Result<()> {
         .parse()?;
     let unix_port = env::var("UNIX_PORT").ok();
     let pw = env::var("PASSWORD").with_context(|| "PASSWORD required")?;
-    let etcd_port = env::var("ETCD_PORT").unwrap_or_else(|_| "localhost:2379".to_string());
-    let etcd_user = env::var("ETCD_USER").ok();
-    let etcd_pw = env::var("ETCD_PW").ok();
-    let etcd_domain_name = env::var("ETCD_DOMAIN_NAME").ok();
-    let etcd_ca_pem_file = env::var("ETCD_CA_PEM_FILE").ok();
-    let etcd_cert_pem_file = env::var("ETCD_CERT_PEM_FILE").ok();
-    let etcd_key_pem_file = env::var("ETCD_KEY_PEM_FILE").ok();
+    let replay = ReplayConfig {
+        capacity: env::var("REPLAY_BUFFER_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(REPLAY_BUFFER_SIZE),
+        retention: Duration::from_secs(
+            env::var("REPLAY_RETENTION")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(REPLAY_RETENTION),
+        ),
+    };
+
+    let admin_port: Option<SocketAddr> =
+        env::var("ADMIN_PORT").ok().map(|v| v.parse()).transpose()?;
 
     let link_port = link_port()?;
     let endpoint = client_endpoint(ca_path)?;
 
-    let (tx_end, mut rx_end) = broadcast::channel::<i32>(1);
-    let (to_all, mut from_local) = mpsc::channel::<Pack>(1);
-    let (tx_incoming_local, mut rx_incoming_local) =
-        mpsc::channel::<(u64, UnboundedSender<Pack>)>(1);
+    let (tx_end, rx_end) = broadcast::channel::<i32>(1);
+    let (to_all, from_local) = mpsc::channel::<Pack>(1);
+    let (tx_incoming_local, rx_incoming_local) =
+        mpsc::channel::<(u64, Resume, UnboundedSender<Pack>)>(1);
 
-    let (to_local, mut from_outer) = mpsc::unbounded_channel::<Pack>();
+    let (tx_status, rx_status) = mpsc::channel::<StatusRequest>(1);
+    if let Some(admin_port) = admin_port {
+        admin::run(admin_port, tx_status)?;
+    }
+
+    let (to_local, from_outer) = mpsc::unbounded_channel::<Pack>();
     tokio::spawn(server::run(
         key_path,
         cert_path,
@@ -125,65 +145,109 @@ async fn main() -> Result<()> {
         )?;
     }
 
-    let mut option = ConnectOptions::new();
-    if let (Some(etcd_user), Some(etcd_pw)) = (etcd_user, etcd_pw) {
-        option = option.with_user(etcd_user, etcd_pw);
-    }
-    if let Some(domain_name) = etcd_domain_name {
-        let mut tls = TlsOptions::new();
-        tls = tls.domain_name(domain_name);
-        if let Some(ca_path) = etcd_ca_pem_file {
-            let pem = fs::read(ca_path).context("failed to read ETCD_CA_PEM_FILE")?;
-            let ca = etcd_client::Certificate::from_pem(pem);
-            tls = tls.ca_certificate(ca);
-        }
-        if let (Some(cert_file), Some(key_file)) = (etcd_cert_pem_file, etcd_key_pem_file) {
-            let cert = fs::read(cert_file).context("failed to read ETCD_CERT_PEM_FILE")?;
-            let key = fs::read(key_file).context("failed to read ETCD_KEY_PEM_FILE")?;
-            let identity = etcd_client::Identity::from_pem(cert, key);
-            tls = tls.identity(identity);
-        }
-        option = option.with_tls(tls);
+    let channels = Channels {
+        tx_end,
+        rx_end,
+        rx_incoming_local,
+        from_local,
+        from_outer,
+        rx_status,
+    };
+    let exit_code = if let Some(discovery) = StaticDiscovery::from_env()? {
+        run(discovery, channels, replay, link_port, host, endpoint, pw).await?
+    } else {
+        let discovery = EtcdDiscovery::connect(link_port).await?;
+        run(discovery, channels, replay, link_port, host, endpoint, pw).await?
+    };
+    sleep(Duration::from_millis(100)).await;
+    if exit_code != 0 {
+        std::process::exit(exit_code);
     }
-    let etcd_port: Vec<_> = etcd_port.split(',').collect();
-    let mut etcd_client = Client::connect(etcd_port, Some(option)).await?;
-    let lease = etcd_client.lease_grant(WATCHDOG_TTL, None).await?.id();
-    let watchdog_key = set_watchdog(&mut etcd_client, link_port, lease).await?;
+    Ok(())
+}
 
-    let opt = WatchOptions::new().with_prefix();
-    let key = "/senax_linker/";
-    let (_watcher, mut etcd_stream) = etcd_client.watch(key, Some(opt)).await?;
+struct ReplayConfig {
+    capacity: usize,
+    /// How long a stream without local receivers keeps buffering
+    retention: Duration,
+}
 
-    let etcd_stream_re = Regex::new(r"^/senax_linker/(\d+)/(.+)$").unwrap();
+struct Channels {
+    tx_end: broadcast::Sender<i32>,
+    rx_end: broadcast::Receiver<i32>,
+    rx_incoming_local: Receiver<(u64, Resume, UnboundedSender<Pack>)>,
+    from_local: Receiver<Pack>,
+    from_outer: UnboundedReceiver<Pack>,
+    rx_status: Receiver<StatusRequest>,
+}
 
+async fn run<D: Discovery>(
+    mut discovery: D,
+    channels: Channels,
+    replay: ReplayConfig,
+    link_port: SocketAddr,
+    host: String,
+    endpoint: Endpoint,
+    pw: String,
+) -> Result<i32> {
+    let Channels {
+        tx_end,
+        mut rx_end,
+        mut rx_incoming_local,
+        mut from_local,
+        mut from_outer,
+        mut rx_status,
+    } = channels;
     let mut exit_code = 0;
     let mut to_locals: HashMap<u64, Vec<UnboundedSender<Pack>>> = HashMap::new();
     let mut to_outers: HashMap<u64, HashMap<String, UnboundedSender<Pack>>> = HashMap::new();
+    let mut replays: HashMap<u64, ReplayBuffer> = HashMap::new();
+    let mut idle_streams: HashMap<u64, Instant> = HashMap::new();
+    let mut self_check = SelfCheck::new(link_port);
     let mut interval = time::interval(Duration::from_secs(30));
     interval.tick().await;
     loop {
         tokio::select! {
-            Some((stream_id, sender)) = rx_incoming_local.recv() => {
+            Some((stream_id, resume, sender)) = rx_incoming_local.recv() => {
+                let buffer = replays
+                    .entry(stream_id)
+                    .or_insert_with(|| ReplayBuffer::new(stream_id, replay.capacity));
+                for pack in buffer.resume(resume) {
+                    let _ = sender.send(pack);
+                }
+                idle_streams.remove(&stream_id);
                 if let Some(vec) = to_locals.get_mut(&stream_id) {
                     vec.push(sender);
                 } else {
                     to_locals.insert(stream_id, vec![sender]);
 
                     let map = to_outers.entry(stream_id).or_default();
-                    for remote in fetch_node_list(&mut etcd_client, stream_id).await? {
+                    for remote in discovery.fetch_node_list(stream_id).await? {
+                        if self_check.is_self(&remote).await {
+                            continue;
+                        }
                         if let Vacant(e) = map.entry(remote.clone()) {
                             let to_outer = connect_client(remote, &host, endpoint.clone(), pw.clone(), stream_id, 0)?;
                             e.insert(to_outer);
                         }
                     }
-                    register_node(&mut etcd_client, stream_id, link_port, lease).await?;
+                    discovery.register_node(stream_id).await?;
                 }
             },
             Some(pack) = from_local.recv() => {
                 // from local to outer linker servers
+                METRICS.packs_from_local.fetch_add(1, Ordering::Relaxed);
+                METRICS.bytes_from_local.fetch_add(pack.data.len() as u64, Ordering::Relaxed);
                 let stream_id = pack.stream_id;
                 if let Some(vec) = to_locals.get_mut(&stream_id) {
+                    let pack = match replays.get_mut(&stream_id) {
+                        Some(buffer) => buffer.push(pack.clone()),
+                        None => pack.clone(),
+                    };
                     vec.retain(|sender| sender.send(pack.clone()).is_ok());
+                    if vec.is_empty() {
+                        idle_streams.entry(stream_id).or_insert_with(Instant::now);
+                    }
                 }
                 if let Some(map) = to_outers.get_mut(&stream_id) {
                     map.retain(|_, sender| sender.send(pack.clone()).is_ok());
@@ -191,56 +255,77 @@ async fn main() -> Result<()> {
             },
             Some(pack) = from_outer.recv() => {
                 // from outer linker server to local
+                METRICS.packs_from_outer.fetch_add(1, Ordering::Relaxed);
+                METRICS.bytes_from_outer.fetch_add(pack.data.len() as u64, Ordering::Relaxed);
                 let stream_id = pack.stream_id;
                 if let Some(vec) = to_locals.get_mut(&stream_id) {
+                    let pack = match replays.get_mut(&stream_id) {
+                        Some(buffer) => buffer.push(pack),
+                        None => pack,
+                    };
                     vec.retain(|sender| sender.send(pack.clone()).is_ok());
                     if vec.is_empty() {
-                        // when all local connections are disconnected
-                        log::warn!("stream removed {stream_id}");
-                        to_locals.remove(&stream_id);
-                        to_outers.remove(&stream_id);
-                        unregister_node(&mut etcd_client, stream_id, link_port).await?;
+                        // Keep buffering for receivers that reconnect.
+                        idle_streams.entry(stream_id).or_insert_with(Instant::now);
                     }
                 }
             },
-            result = etcd_stream.message() => {
-                let resp = match result {
-                    Ok(v) => match v {
-                        Some(resp) => resp,
-                        None => {
-                            error!("etcd watch disconnected");
-                            exit_code = 1;
-                            break;
-                        },
-                    },
+            Some(reply) = rx_status.recv() => {
+                let mut list: Vec<StreamStatus> = to_locals
+                    .iter()
+                    .map(|(&stream_id, vec)| {
+                        let replay = replays.get(&stream_id);
+                        StreamStatus {
+                            stream_id,
+                            local_receivers: vec.len(),
+                            outer_linkers: to_outers
+                                .get(&stream_id)
+                                .map(|map| map.keys().cloned().collect())
+                                .unwrap_or_default(),
+                            replay_epoch: replay.map(|v| v.epoch()).unwrap_or_default(),
+                            replay_seq: replay.map(|v| v.last_seq()).unwrap_or_default(),
+                            replay_buffered: replay.map(|v| v.len()).unwrap_or_default(),
+                            idle_secs: idle_streams.get(&stream_id).map(|v| v.elapsed().as_secs()),
+                        }
+                    })
+                    .collect();
+                list.sort_by_key(|v| v.stream_id);
+                let _ = reply.send(list);
+            },
+            result = discovery.next_event() => {
+                let event = match result {
+                    Ok(event) => event,
                     Err(e) => {
                         error!("{}", e);
                         exit_code = 1;
                         break;
                     },
                 };
-                for event in resp.events() {
-                    if let Some(kv) = event.kv() {
-                        let key = kv.key_str()?;
-                        if EventType::Delete == event.event_type() && key == watchdog_key {
-                            let _ = tx_end.send(1);
-                        } else if let Some(caps) = etcd_stream_re.captures(key) {
-                            let stream_id: u64 = caps.get(1).unwrap().as_str().parse()?;
-                            let remote = caps.get(2).unwrap().as_str().to_string();
-                            if let Some(map) = to_outers.get_mut(&stream_id) {
-                                if EventType::Put == event.event_type() {
-                                    if remote != link_port.to_string() && !map.contains_key(&remote) {
-                                        // connect with new linker server
-                                        let to_outer = connect_client(remote.clone(), &host, endpoint.clone(), pw.clone(), stream_id, 0)?;
-                                        map.insert(remote, to_outer);
-                                    }
-                                } else if EventType::Delete == event.event_type() {
-                                    // disconnect from linker server
-                                    map.remove(&remote);
-                                }
+                match event {
+                    Event::Shutdown => {
+                        let _ = tx_end.send(1);
+                    }
+                    Event::Join(target, remote) => {
+                        for (&stream_id, map) in to_outers.iter_mut() {
+                            if target.is_some_and(|v| v != stream_id) {
+                                continue;
+                            }
+                            if !map.contains_key(&remote) && !self_check.is_self(&remote).await {
+                                // connect with new linker server
+                                let to_outer = connect_client(remote.clone(), &host, endpoint.clone(), pw.clone(), stream_id, 0)?;
+                                map.insert(remote.clone(), to_outer);
                             }
                         }
                     }
+                    Event::Leave(target, remote) => {
+                        for (&stream_id, map) in to_outers.iter_mut() {
+                            if target.is_some_and(|v| v != stream_id) {
+                                continue;
+                            }
+                            // disconnect from linker server
+                            map.remove(&remote);
+                        }
+                    }
                 }
             },
             Ok(v) = rx_end.recv() => {
@@ -251,10 +336,24 @@ async fn main() -> Result<()> {
                 let _ = tx_end.send(0);
             }
             _ = interval.tick() => {
+                // remove streams whose local connections are all disconnected
+                let expired: Vec<u64> = idle_streams
+                    .iter()
+                    .filter(|(_, since)| since.elapsed() >= replay.retention)
+                    .map(|(&stream_id, _)| stream_id)
+                    .collect();
+                for stream_id in expired {
+                    log::warn!("stream removed {stream_id}");
+                    idle_streams.remove(&stream_id);
+                    to_locals.remove(&stream_id);
+                    to_outers.remove(&stream_id);
+                    replays.remove(&stream_id);
+                    discovery.unregister_node(stream_id).await?;
+                }
                 // check connections
                 for (&stream_id, map) in to_outers.iter_mut() {
-                    for remote in fetch_node_list(&mut etcd_client, stream_id).await? {
-                        if remote != link_port.to_string() && !map.contains_key(&remote) {
+                    for remote in discovery.fetch_node_list(stream_id).await? {
+                        if !map.contains_key(&remote) && !self_check.is_self(&remote).await {
                             log::warn!("send reset {remote}");
                             let to_outer = connect_client(remote.clone(), &host, endpoint.clone(), pw.clone(), stream_id, CMD_RESET)?;
                             map.insert(remote, to_outer);
@@ -266,12 +365,8 @@ async fn main() -> Result<()> {
         }
     }
     let _ = tx_end.send(0);
-    let _ = etcd_client.lease_revoke(lease).await;
-    sleep(Duration::from_millis(100)).await;
-    if exit_code != 0 {
-        std::process::exit(exit_code);
-    }
-    Ok(())
+    discovery.close().await;
+    Ok(exit_code)
 }
 
 fn link_port() -> Result<SocketAddr> {
@@ -296,53 +391,95 @@ fn link_port() -> Result<SocketAddr> {
     bail!("OUTER_PORT not found");
 }
 
-async fn set_watchdog(
-    etcd_client: &mut Client,
+/// Detects this linker in the peer lists.
+/// Peers are resolved and compared by socket address, so a host name and an IP address
+/// of this host are both recognized.
+struct SelfCheck {
     link_port: SocketAddr,
-    lease: i64,
-) -> Result<String> {
-    let (mut keeper, mut _stream) = etcd_client.lease_keep_alive(lease).await?;
-    tokio::spawn(async move {
-        loop {
-            sleep(Duration::from_secs(WATCHDOG_KEEP_ALIVE)).await;
-            let _ = keeper.keep_alive().await;
+    resolved: HashMap<String, bool>,
+}
+
+impl SelfCheck {
+    fn new(link_port: SocketAddr) -> Self {
+        Self {
+            link_port,
+            resolved: HashMap::new(),
+        }
+    }
+
+    async fn is_self(&mut self, remote: &str) -> bool {
+        if let Some(&result) = self.resolved.get(remote) {
+            return result;
+        }
+        match tokio::net::lookup_host(remote).await {
+            Ok(addrs) => {
+                let local_ips = local_ips();
+                let result = addrs
+                    .into_iter()
+                    .any(|addr| is_local_addr(addr, self.link_port, &local_ips));
+                self.resolved.insert(remote.to_string(), result);
+                result
+            }
+            Err(e) => {
+                // Resolved again next time.
+                warn!("failed to resolve {remote}: {}", e);
+                false
+            }
         }
-    });
-    let opt = PutOptions::new().with_lease(lease);
-    let key = format!("/senax_linker/watchdog/{link_port}");
-    etcd_client.put(&*key, "1", Some(opt)).await?;
-    Ok(key)
+    }
 }
 
-async fn register_node(
-    etcd_client: &mut Client,
-    stream_id: u64,
-    link_port: SocketAddr,
-    lease: i64,
-) -> Result<()> {
-    let opt = PutOptions::new().with_lease(lease);
-    let key = format!("/senax_linker/{stream_id}/{link_port}");
-    etcd_client.put(&*key, "1", Some(opt)).await?;
-    Ok(())
+fn local_ips() -> Vec<IpAddr> {
+    if_addrs::get_if_addrs()
+        .map(|v| v.iter().map(|iface| iface.ip()).collect())
+        .unwrap_or_default()
 }
 
-async fn unregister_node(
-    etcd_client: &mut Client,
-    stream_id: u64,
-    link_port: SocketAddr,
-) -> Result<()> {
-    let key = format!("/senax_linker/{stream_id}/{link_port}");
-    etcd_client.delete(&*key, None).await?;
-    Ok(())
+/// Whether the address is OUTER_PORT of this linker.
+/// When OUTER_PORT is bound to all interfaces, any address of this host matches.
+fn is_local_addr(addr: SocketAddr, link_port: SocketAddr, local_ips: &[IpAddr]) -> bool {
+    if addr.port() != link_port.port() {
+        return false;
+    }
+    if addr.ip() == link_port.ip() {
+        return true;
+    }
+    link_port.ip().is_unspecified() && (addr.ip().is_loopback() || local_ips.contains(&addr.ip()))
 }
 
-async fn fetch_node_list(etcd_client: &mut Client, stream_id: u64) -> Result<Vec<String>> {
-    let opt = GetOptions::new().with_prefix();
-    let key = format!("/senax_linker/{stream_id}/");
-    let resp = etcd_client.get(&*key, Some(opt)).await?;
-    let mut ports = Vec::new();
-    for kv in resp.kvs() {
-        ports.push(kv.key_str()?.trim_start_matches(&key).to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_addr() {
+        let local_ips: Vec<IpAddr> = vec!["192.168.0.10".parse().unwrap()];
+        let is_local = |addr: &str, link_port: &str| {
+            is_local_addr(
+                addr.parse().unwrap(),
+                link_port.parse().unwrap(),
+                &local_ips,
+            )
+        };
+        assert!(is_local("192.168.0.10:25552", "192.168.0.10:25552"));
+        assert!(!is_local("192.168.0.10:25553", "192.168.0.10:25552"));
+        assert!(!is_local("192.168.0.11:25552", "192.168.0.10:25552"));
+        assert!(!is_local("127.0.0.1:25552", "192.168.0.10:25552"));
+
+        assert!(is_local("192.168.0.10:25552", "0.0.0.0:25552"));
+        assert!(is_local("127.0.0.1:25552", "0.0.0.0:25552"));
+        assert!(!is_local("192.168.0.11:25552", "0.0.0.0:25552"));
+    }
+
+    #[tokio::test]
+    async fn self_check() {
+        let mut check = SelfCheck::new("127.0.0.1:25552".parse().unwrap());
+        assert!(check.is_self("127.0.0.1:25552").await);
+        assert!(check.is_self("localhost:25552").await);
+        assert!(!check.is_self("localhost:25553").await);
+        assert!(!check.is_self("127.0.0.2:25552").await);
+        // Not a socket address
+        assert!(!check.is_self("localhost").await);
+        assert!(!check.resolved.contains_key("localhost"));
     }
-    Ok(ports)
 }
@@ -0,0 +1,174 @@
+use bytes::Bytes;
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::common::{Pack, Resume};
+
+/// Recent packs of a stream kept for receivers that reconnect.
+pub struct ReplayBuffer {
+    stream_id: u64,
+    epoch: u64,
+    last_seq: u64,
+    capacity: usize,
+    packs: VecDeque<Pack>,
+}
+
+impl ReplayBuffer {
+    pub fn new(stream_id: u64, capacity: usize) -> Self {
+        // A new epoch tells receivers that the sequence numbers were reset.
+        let epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1)
+            .max(1);
+        Self {
+            stream_id,
+            epoch,
+            last_seq: 0,
+            capacity,
+            packs: VecDeque::with_capacity(capacity.min(1024)),
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    pub fn len(&self) -> usize {
+        self.packs.len()
+    }
+
+    /// Numbers the pack and keeps it.
+    pub fn push(&mut self, mut pack: Pack) -> Pack {
+        self.last_seq += 1;
+        pack.epoch = self.epoch;
+        pack.seq = self.last_seq;
+        if self.capacity > 0 {
+            if self.packs.len() >= self.capacity {
+                self.packs.pop_front();
+            }
+            self.packs.push_back(pack.clone());
+        }
+        pack
+    }
+
+    /// Returns the packs after the position.
+    /// When they are no longer kept, returns a pack that invalidates all caches instead.
+    pub fn resume(&self, resume: Resume) -> Vec<Pack> {
+        if resume.epoch == 0 {
+            return Vec::new();
+        }
+        let first_seq = self
+            .packs
+            .front()
+            .map(|p| p.seq)
+            .unwrap_or(self.last_seq + 1);
+        if resume.epoch == self.epoch && resume.seq < first_seq.saturating_sub(1) {
+            warn!(
+                "replay gap too large for stream {}: {} < {}",
+                self.stream_id, resume.seq, first_seq
+            );
+        } else if resume.epoch == self.epoch && resume.seq <= self.last_seq {
+            return self
+                .packs
+                .iter()
+                .filter(|p| p.seq > resume.seq)
+                .cloned()
+                .collect();
+        }
+        vec![Pack {
+            // An empty message
+            data: Bytes::from(0u64.to_le_bytes().to_vec()),
+            conn_no: 0,
+            stream_id: self.stream_id,
+            epoch: self.epoch,
+            seq: self.last_seq,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack(n: u8) -> Pack {
+        Pack {
+            data: Bytes::from(vec![n]),
+            conn_no: 1,
+            stream_id: 7,
+            epoch: 0,
+            seq: 0,
+        }
+    }
+
+    fn is_invalidation(packs: &[Pack], buf: &ReplayBuffer) -> bool {
+        packs.len() == 1
+            && packs[0].data.as_ref() == 0u64.to_le_bytes()
+            && packs[0].epoch == buf.epoch()
+            && packs[0].seq == buf.last_seq()
+    }
+
+    #[test]
+    fn resume() {
+        let mut buf = ReplayBuffer::new(7, 3);
+        for i in 1..=5 {
+            let pack = buf.push(pack(i));
+            assert_eq!(pack.seq, i as u64);
+            assert_eq!(pack.epoch, buf.epoch());
+        }
+        assert_eq!(buf.len(), 3);
+        let epoch = buf.epoch();
+
+        let packs = buf.resume(Resume { epoch, seq: 3 });
+        let seqs: Vec<_> = packs.iter().map(|p| p.seq).collect();
+        assert_eq!(seqs, vec![4, 5]);
+        assert_eq!(packs[0].data.as_ref(), [4]);
+        let packs = buf.resume(Resume { epoch, seq: 2 });
+        assert_eq!(packs.len(), 3);
+        assert!(buf.resume(Resume { epoch, seq: 5 }).is_empty());
+        assert!(buf.resume(Resume { epoch: 0, seq: 0 }).is_empty());
+    }
+
+    #[test]
+    fn gap() {
+        let mut buf = ReplayBuffer::new(7, 3);
+        for i in 1..=5 {
+            buf.push(pack(i));
+        }
+        let epoch = buf.epoch();
+        let packs = buf.resume(Resume { epoch, seq: 1 });
+        assert!(is_invalidation(&packs, &buf));
+        let packs = buf.resume(Resume { epoch, seq: 6 });
+        assert!(is_invalidation(&packs, &buf));
+    }
+
+    #[test]
+    fn epoch_mismatch() {
+        let mut buf = ReplayBuffer::new(7, 3);
+        for i in 1..=2 {
+            buf.push(pack(i));
+        }
+        let packs = buf.resume(Resume {
+            epoch: buf.epoch() + 1,
+            seq: 1,
+        });
+        assert!(is_invalidation(&packs, &buf));
+    }
+
+    #[test]
+    fn no_capacity() {
+        let mut buf = ReplayBuffer::new(7, 0);
+        for i in 1..=2 {
+            assert_eq!(buf.push(pack(i)).seq, i as u64);
+        }
+        assert_eq!(buf.len(), 0);
+        let epoch = buf.epoch();
+        assert!(buf.resume(Resume { epoch, seq: 2 }).is_empty());
+        let packs = buf.resume(Resume { epoch, seq: 1 });
+        assert!(is_invalidation(&packs, &buf));
+    }
+}
@@ -5,10 +5,16 @@ use log::{error, info};
 use quinn::{ServerConfig, crypto::rustls::QuicServerConfig};
 use rustls_pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
 use sha2::{Digest, Sha512};
-use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, atomic::Ordering},
+    time::Duration,
+};
 use tokio::sync::mpsc::UnboundedSender;
 use zstd::Decoder;
 
+use crate::admin::METRICS;
 use crate::common::{
     ALPN_QUIC_HTTP, CMD_RESET, CONNECTION_SUCCESS, LINKER_VER, LINKER_VER_ERROR, PASSWORD_ERROR,
     Pack,
@@ -59,6 +65,17 @@ async fn handle_connection(
 ) -> Result<()> {
     let connection = conn.await?;
     info!("connected from {}", connection.remote_address());
+    METRICS.inbound_connections.fetch_add(1, Ordering::Relaxed);
+    let result = handle_streams(connection, to_local, pw).await;
+    METRICS.inbound_connections.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+async fn handle_streams(
+    connection: quinn::Connection,
+    to_local: UnboundedSender<Pack>,
+    pw: String,
+) -> Result<()> {
     let (stream_id, cmd) = if let Ok((send, mut recv)) = connection.accept_bi().await {
         let mut v = [0; 2];
         recv.read_exact(&mut v)
@@ -100,6 +117,8 @@ async fn handle_connection(
             data: data.into(),
             conn_no: 0,
             stream_id,
+            epoch: 0,
+            seq: 0,
         });
         warn!("reset command received");
     }
@@ -149,6 +168,8 @@ async fn handle_request(
             data: buf.into_vec().into(),
             conn_no: 0,
             stream_id,
+            epoch: 0,
+            seq: 0,
         });
     }
     Ok(())
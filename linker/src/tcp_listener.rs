@@ -9,12 +9,13 @@ use tokio::sync::mpsc::{self, Sender, UnboundedReceiver, UnboundedSender};
 use tokio_uring::buf::IoBuf;
 use tokio_uring::net::{TcpListener, TcpStream};
 
-use crate::common::{CONN_NO, IoBytesMut, LINKER_VER, Pack, RECEIVER, SENDER};
+use crate::admin::METRICS;
+use crate::common::{CONN_NO, IoBytesMut, LOCAL_VER, Pack, RECEIVER, Resume, SENDER};
 
 pub fn run(
     tx_end: broadcast::Sender<i32>,
     to_all: Sender<Pack>,
-    tx_incoming_local: Sender<(u64, UnboundedSender<Pack>)>,
+    tx_incoming_local: Sender<(u64, Resume, UnboundedSender<Pack>)>,
     tcp_port: SocketAddr,
     pw: String,
 ) -> std::io::Result<JoinHandle<Result<()>>> {
@@ -29,21 +30,23 @@ pub fn run(
                     tokio::select! {
                         result = listener.accept() => {
                             let (stream, addr) = result?;
-                            let (stream_id, conn_no, mode) = check_stream(&stream, &pw).await?;
+                            let (stream_id, conn_no, mode, resume) = check_stream(&stream, &pw).await?;
                             let tx_end3 = tx_end2.clone();
                             if mode == SENDER {
                                 info!("sender connected from {}", addr);
                                 let to_all = to_all.clone();
+                                METRICS.sender_connected(stream_id);
                                 tokio_uring::spawn(async move {
                                     if let Err(e) = handle_sender_stream(conn_no, stream, stream_id, tx_end3, to_all).await {
                                         error!("tcp sender {}", &e);
                                     }
+                                    METRICS.sender_disconnected(stream_id);
                                     info!("sender disconnected: {}", addr);
                                 });
                             } else if mode == RECEIVER {
                                 info!("receiver connected from {}", addr);
                                 let (to_hub, from_hub) = mpsc::unbounded_channel::<Pack>();
-                                tx_incoming_local.send((stream_id, to_hub)).await?;
+                                tx_incoming_local.send((stream_id, resume, to_hub)).await?;
                                 tokio_uring::spawn(async move {
                                     if let Err(e) = handle_receiver_stream(conn_no, stream, tx_end3, from_hub).await {
                                         error!("tcp receiver {}", &e);
@@ -67,10 +70,10 @@ pub fn run(
         })
 }
 
-async fn check_stream(stream: &TcpStream, pw: &str) -> Result<(u64, u64, u16)> {
+async fn check_stream(stream: &TcpStream, pw: &str) -> Result<(u64, u64, u16, Resume)> {
     let buf = IoBytesMut::new(2);
     let version = read_all(buf, stream).await?.get_u16_le();
-    if version != LINKER_VER {
+    if version != LOCAL_VER {
         bail!("version error");
     }
 
@@ -88,6 +91,7 @@ async fn check_stream(stream: &TcpStream, pw: &str) -> Result<(u64, u64, u16)> {
     let buf = IoBytesMut::new(8);
     let stream_id = read_all(buf, stream).await?.get_u64_le();
 
+    let mut resume = Resume { epoch: 0, seq: 0 };
     let conn_no = if mode == SENDER {
         let conn_no = CONN_NO.fetch_add(1, Ordering::SeqCst);
         let mut buf = BytesMut::with_capacity(8);
@@ -95,10 +99,14 @@ async fn check_stream(stream: &TcpStream, pw: &str) -> Result<(u64, u64, u16)> {
         write_all(buf.freeze(), stream).await?;
         conn_no
     } else {
-        let buf = IoBytesMut::new(8);
-        read_all(buf, stream).await?.get_u64_le()
+        let buf = IoBytesMut::new(8 + 8 + 8);
+        let mut buf = read_all(buf, stream).await?;
+        let conn_no = buf.get_u64_le();
+        resume.epoch = buf.get_u64_le();
+        resume.seq = buf.get_u64_le();
+        conn_no
     };
-    Ok((stream_id, conn_no, mode))
+    Ok((stream_id, conn_no, mode, resume))
 }
 
 async fn handle_sender_stream(
@@ -117,7 +125,7 @@ async fn handle_sender_stream(
                 if n == 0 { break }
                 buf.advance(n);
                 let data =  read_msg(buf, &stream).await?.freeze();
-                let _ = to_all.send(Pack{data, conn_no, stream_id}).await;
+                let _ = to_all.send(Pack{data, conn_no, stream_id, epoch: 0, seq: 0}).await;
             },
             _stop = rx_end.recv() => break,
             else => break,
@@ -147,6 +155,10 @@ async fn handle_receiver_stream(
                     break;
                 };
                 if conn_no != recv.conn_no {
+                    let mut buf = BytesMut::with_capacity(8 + 8);
+                    buf.put_u64_le(recv.epoch);
+                    buf.put_u64_le(recv.seq);
+                    write_all(buf.freeze(), &stream).await?;
                     write_all(recv.data, &stream).await?;
                 }
             },
@@ -164,6 +164,7 @@ function EditModel() {
               <AutoField name="comment" {...formData} textarea />
               <AutoField name="table_name" {...formData} />
               <AutoField name="skip_ddl" {...formData} hidden={!detail} />
+              <AutoField name="allow_destructive_migration" {...formData} hidden={!detail} />
               <AutoField name="dummy_always_joinable" {...formData} hidden={!detail} />
               <AutoField name="upsert_conflict_target" {...formData} hidden={formData.additionalData.db_data.db == "mysql"}
                 autocomplete={formData.additionalData.selfModel.indexes?.filter((v: any) => v.type == "unique").map(
@@ -200,6 +201,16 @@ function EditModel() {
                 {...formData}
                 hidden={!detail}
               />
+              <AutoField name="warm_up" {...formData} hidden={!detail} />
+              <AutoField name="warm_up_limit" {...formData} hidden={!detail} />
+              <AutoField name="cache_ttl" {...formData} hidden={!detail} />
+              <AutoField name="cache_weight" {...formData} hidden={!detail} />
+              <AutoField name="cache_limit" {...formData} hidden={!detail} />
+              <AutoField
+                name="stale_while_revalidate"
+                {...formData}
+                hidden={!detail}
+              />
               <AutoField
                 name="clear_all_cache_on_update"
                 {...formData}
@@ -250,6 +261,8 @@ function EditModel() {
                 component={Inheritance}
               />
               <AutoField name="engine" {...formData} hidden={!detail || formData.additionalData.db_data.db !== "mysql"} />
+              <AutoField name="shard_key" {...formData} hidden={!detail} />
+              <AutoField name="shard_ranges" {...formData} hidden={!detail} />
               <AutoField
                 name="act_as"
                 {...formData}
@@ -203,6 +203,16 @@ pub fn generate(
     }
     fs_write(file_path, &*content)?;
 
+    #[derive(Template)]
+    #[template(path = "new_actix/src/metrics.rs", escape = "none")]
+    pub struct MetricsTemplate;
+
+    let file_path = src_path.join("metrics.rs");
+    if force || !file_path.exists() {
+        let tpl = MetricsTemplate;
+        fs_write(&file_path, tpl.render()?)?;
+    }
+
     #[derive(Template)]
     #[template(path = "new_actix/src/main.rs", escape = "none")]
     pub struct MainTemplate<'a> {
@@ -333,6 +343,14 @@ pub fn write_base_files(base_path: &Path, name: &str, db_list: &[&str], force: b
         fs::read_to_string(&file_path)?.replace("\r\n", "\n")
     };
     for db in db_list {
+        let chk = format!("_db_{}::cache_metrics()", &db.to_snake());
+        if !content.contains(&chk) {
+            let tpl = DbCacheMetricsTemplate { db };
+            content = content.replace(
+                "// Do not modify this line. (DbCacheMetrics)",
+                tpl.render()?.trim_start(),
+            );
+        }
         let chk = format!("_db_{}::clear_local_cache().await;", &db.to_snake());
         if !content.contains(&chk) {
             crate::schema::parse(db, false, false)?;
@@ -363,6 +381,16 @@ pub fn write_base_files(base_path: &Path, name: &str, db_list: &[&str], force: b
                     "// Do not modify this line. (RepoStatic)",
                     tpl.render()?.trim_start(),
                 );
+                let tpl = DbRepoWriterUntilTemplate { db };
+                content = content.replace(
+                    "// Do not modify this line. (RepoWriterUntil)",
+                    tpl.render()?.trim_start(),
+                );
+                let tpl = DbRepoSetWriterUntilTemplate { db };
+                content = content.replace(
+                    "// Do not modify this line. (RepoSetWriterUntil)",
+                    tpl.render()?.trim_start(),
+                );
                 let tpl = DbRepoImplTemplate { db };
                 content = content.replace(
                     "// Do not modify this line. (RepoImpl)",
@@ -578,6 +606,20 @@ pub struct DbClearCacheTemplate<'a> {
     pub db: &'a str,
 }
 
+#[derive(Template)]
+#[template(
+    source = r###"
+    if let Some(metrics) = _db_@{ db|snake }@::cache_metrics() {
+        list.push(("@{ db }@", metrics));
+    }
+    // Do not modify this line. (DbCacheMetrics)"###,
+    ext = "txt",
+    escape = "none"
+)]
+pub struct DbCacheMetricsTemplate<'a> {
+    pub db: &'a str,
+}
+
 #[derive(Template)]
 #[template(
     source = r###"
@@ -615,6 +657,30 @@ pub struct DbRepoStaticTemplate<'a> {
     pub db: &'a str,
 }
 
+#[derive(Template)]
+#[template(
+    source = r###"
+        until = until.max(self.@{ db|snake|ident }@.lock().await.writer_until());
+        // Do not modify this line. (RepoWriterUntil)"###,
+    ext = "txt",
+    escape = "none"
+)]
+pub struct DbRepoWriterUntilTemplate<'a> {
+    pub db: &'a str,
+}
+
+#[derive(Template)]
+#[template(
+    source = r###"
+        self.@{ db|snake|ident }@.lock().await.set_writer_until(until);
+        // Do not modify this line. (RepoSetWriterUntil)"###,
+    ext = "txt",
+    escape = "none"
+)]
+pub struct DbRepoSetWriterUntilTemplate<'a> {
+    pub db: &'a str,
+}
+
 #[derive(Template)]
 #[template(
     source = r###"
@@ -170,11 +170,27 @@ fn fix_env(content: &str, db: &str, db_type: DbType) -> Result<String> {
         content.to_owned()
     };
     let upper = db.to_upper_snake();
+    let re = Regex::new(&format!("^{upper}_DB_URL=")).unwrap();
+    if db_type == DbType::Sqlite {
+        if !re.is_match(&content) {
+            write!(
+                &mut content,
+                r#"
+{upper}_DB_URL=sqlite://{db}.db?mode=rwc
+{upper}_TEST_DB_URL=sqlite::memory:
+{upper}_DB_MAX_CONNECTIONS_FOR_WRITE=1
+{upper}_DB_MAX_CONNECTIONS_FOR_READ=10
+{upper}_DB_MAX_CONNECTIONS_FOR_CACHE=5
+"#
+            )?;
+        }
+        return Ok(content);
+    }
     let (user, pw) = match db_type {
         DbType::Mysql => ("root", "root"),
         DbType::Postgres => ("postgres", "postgres"),
+        DbType::Sqlite => unreachable!(),
     };
-    let re = Regex::new(&format!("^{upper}_DB_URL=")).unwrap();
     if !re.is_match(&content) {
         write!(
             &mut content,
@@ -255,6 +271,25 @@ fn fix_db_main(db: &str) -> Result<()> {
         tpl.render()?.trim_start(),
     );
 
+    #[derive(Template)]
+    #[template(
+        source = r###"
+    if db.is_none() || db == Some("@{ db }@") {
+        join_set.spawn_local(db_@{ db|snake }@::migrate_down(use_test, steps));
+    }
+    // Do not modify this line. (migrate_down)"###,
+        ext = "txt",
+        escape = "none"
+    )]
+    pub struct DbMigrateDownTemplate<'a> {
+        pub db: &'a str,
+    }
+    let tpl = DbMigrateDownTemplate { db };
+    content = content.replace(
+        "// Do not modify this line. (migrate_down)",
+        tpl.render()?.trim_start(),
+    );
+
     #[derive(Template)]
     #[template(
         source = r###"
@@ -308,6 +343,26 @@ fn fix_db_main(db: &str) -> Result<()> {
         "// Do not modify this line. (check)",
         tpl.render()?.trim_start(),
     );
+
+    #[derive(Template)]
+    #[template(
+        source = r###"
+    if db.is_none() || db == Some("@{ db }@") {
+        db_@{ db|snake }@::reshard(progress_dir, batch_size, interval, swap, cleanup).await?;
+    }
+    // Do not modify this line. (reshard)"###,
+        ext = "txt",
+        escape = "none"
+    )]
+    pub struct DbReshardTemplate<'a> {
+        pub db: &'a str,
+    }
+
+    let tpl = DbReshardTemplate { db };
+    content = content.replace(
+        "// Do not modify this line. (reshard)",
+        tpl.render()?.trim_start(),
+    );
     fs_write(file_path, &*content)?;
     Ok(())
 }
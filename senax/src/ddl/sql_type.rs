@@ -11,7 +11,7 @@ use senax_mysql_parser::common::TableKey as MysqlTableKey;
 
 use crate::common::escape_db_identifier;
 use crate::ddl::table::mysql_escape;
-use crate::schema::is_mysql_mode;
+use crate::schema::{is_mysql_mode, is_sqlite_mode};
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SqlType {
@@ -136,6 +136,71 @@ impl SqlType {
                 SqlType::Uuid => unimplemented!("SqlType::Uuid"),
                 SqlType::UnSupported => unimplemented!("SqlType::UnSupported"),
             }
+        } else if is_sqlite_mode() {
+            // The declared type names are chosen so that they keep the right
+            // column affinity and can be read back by ddl::sqlite.
+            let auto_inc = if constraint.auto_increment {
+                " PRIMARY KEY AUTOINCREMENT"
+            } else {
+                ""
+            };
+            let collation = if let Some(ref c) = constraint.collation {
+                format!(" COLLATE {}", c)
+            } else {
+                "".to_owned()
+            };
+            match *self {
+                SqlType::Int
+                | SqlType::UnsignedInt
+                | SqlType::Smallint
+                | SqlType::UnsignedSmallint
+                | SqlType::Bigint
+                | SqlType::UnsignedBigint
+                | SqlType::Tinyint
+                | SqlType::UnsignedTinyint
+                    if constraint.auto_increment =>
+                {
+                    write!(f, "INTEGER{auto_inc}")
+                }
+                SqlType::Bool => write!(f, "BOOLEAN"),
+                SqlType::Char(len) => write!(f, "CHAR({}){collation}", len),
+                SqlType::Varchar(len) => write!(f, "VARCHAR({}){collation}", len),
+                SqlType::Int => write!(f, "INT"),
+                SqlType::UnsignedInt => write!(f, "INT UNSIGNED"),
+                SqlType::Smallint => write!(f, "SMALLINT"),
+                SqlType::UnsignedSmallint => write!(f, "SMALLINT UNSIGNED"),
+                SqlType::Bigint => write!(f, "BIGINT"),
+                SqlType::UnsignedBigint => write!(f, "BIGINT UNSIGNED"),
+                SqlType::Tinyint => write!(f, "TINYINT"),
+                SqlType::UnsignedTinyint => write!(f, "TINYINT UNSIGNED"),
+                SqlType::Blob => write!(f, "BLOB"),
+                SqlType::Longblob => write!(f, "LONGBLOB"),
+                SqlType::Mediumblob => write!(f, "MEDIUMBLOB"),
+                SqlType::Tinyblob => write!(f, "TINYBLOB"),
+                SqlType::Double => write!(f, "DOUBLE"),
+                SqlType::Float => write!(f, "FLOAT"),
+                SqlType::Real => write!(f, "REAL"),
+                SqlType::Tinytext => write!(f, "TINYTEXT{collation}"),
+                SqlType::Mediumtext => write!(f, "MEDIUMTEXT{collation}"),
+                SqlType::Longtext => write!(f, "LONGTEXT{collation}"),
+                SqlType::Text => write!(f, "TEXT{collation}"),
+                SqlType::Date => write!(f, "DATE"),
+                SqlType::Time => write!(f, "TIME"),
+                SqlType::DateTime(_) => write!(f, "DATETIME"),
+                SqlType::Timestamp(_) => write!(f, "TIMESTAMP"),
+                SqlType::Binary(len) => write!(f, "BINARY({})", len),
+                SqlType::Varbinary(0) => write!(f, "VARBINARY"),
+                SqlType::Varbinary(len) => write!(f, "VARBINARY({})", len),
+                SqlType::Enum(ref _v) => unimplemented!("SqlType::Enum"),
+                SqlType::Set(ref _v) => unimplemented!("SqlType::Set"),
+                SqlType::Decimal(m, d) => write!(f, "DECIMAL({}, {})", m, d),
+                SqlType::Json => write!(f, "TEXT"),
+                SqlType::Jsonb => write!(f, "TEXT"),
+                SqlType::Point => unimplemented!("SqlType::Point"),
+                SqlType::Geometry => unimplemented!("SqlType::Geometry"),
+                SqlType::Uuid => write!(f, "UUID"),
+                SqlType::UnSupported => unimplemented!("SqlType::UnSupported"),
+            }
         } else {
             let collation = if let Some(ref c) = constraint.collation {
                 format!(" COLLATE \"{}\"", c)
@@ -0,0 +1,536 @@
+use anyhow::{Result, bail};
+use indexmap::IndexMap;
+use sqlx::pool::PoolConnection;
+use sqlx::{Row, Sqlite, SqlitePool};
+
+use crate::ddl::sql_type::{IndexColumn, Literal, ReferenceOption, SqlType, TableKey};
+use crate::ddl::table::{Column, Constraint, Table};
+
+pub async fn get_sqlite_table_def_map(database_url: &str) -> Result<IndexMap<String, Table>> {
+    let pool = SqlitePool::connect(database_url).await?;
+    let mut conn = pool.acquire().await?;
+    let rows = sqlx::query(
+        "SELECT name, sql FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )
+    .fetch_all(conn.as_mut())
+    .await?;
+
+    let mut result = IndexMap::new();
+    for row in rows {
+        let table_name: String = row.get(0);
+        let sql: Option<String> = row.get(1);
+        let def = get_table_def(&mut conn, &table_name, &sql.unwrap_or_default()).await?;
+        result.insert(table_name, def);
+    }
+    Ok(result)
+}
+
+async fn get_table_def(
+    conn: &mut PoolConnection<Sqlite>,
+    table_name: &str,
+    sql: &str,
+) -> Result<Table> {
+    let autoincrement = sql.to_ascii_uppercase().contains("AUTOINCREMENT");
+    let rows = sqlx::query(&format!("PRAGMA table_xinfo({})", quote(table_name)))
+        .fetch_all(conn.as_mut())
+        .await?;
+    let mut columns = IndexMap::new();
+    let mut primaries = Vec::new();
+    for row in rows {
+        let name: String = row.try_get("name")?;
+        let declared: String = row.try_get("type")?;
+        let notnull: i64 = row.try_get("notnull")?;
+        let dflt_value: Option<String> = row.try_get("dflt_value")?;
+        let pk: i64 = row.try_get("pk")?;
+        let hidden: i64 = row.try_get("hidden")?;
+        let (sql_type, alt_type) = convert_type(&declared)?;
+        let auto_increment = autoincrement && pk > 0 && declared.eq_ignore_ascii_case("INTEGER");
+        let query = if hidden == 2 || hidden == 3 {
+            generated_expr(sql, &name).map(|v| (v, hidden == 3))
+        } else {
+            None
+        };
+        if pk > 0 {
+            primaries.push((pk, name.clone()));
+        }
+        let constraint = Constraint {
+            // An INTEGER PRIMARY KEY column is an alias of the rowid and never null.
+            not_null: notnull != 0 || auto_increment,
+            collation: collation(sql, &name),
+            auto_increment,
+            srid: None,
+            query,
+        };
+        let column = Column {
+            old_name: None,
+            sql_type: sql_type.clone(),
+            alt_type: alt_type.unwrap_or(sql_type),
+            constraint,
+            default: dflt_value.and_then(|v| convert_default(&v)),
+            comment: None,
+        };
+        columns.insert(name, column);
+    }
+    primaries.sort();
+    let primary = if primaries.is_empty() {
+        None
+    } else {
+        let cols = primaries
+            .into_iter()
+            .map(|(_, name)| IndexColumn {
+                name,
+                ..Default::default()
+            })
+            .collect();
+        Some((String::new(), TableKey::PrimaryKey(cols)))
+    };
+
+    let mut indexes = IndexMap::new();
+    let rows = sqlx::query(&format!("PRAGMA index_list({})", quote(table_name)))
+        .fetch_all(conn.as_mut())
+        .await?;
+    for row in rows {
+        let index_name: String = row.try_get("name")?;
+        let unique: i64 = row.try_get("unique")?;
+        let origin: String = row.try_get("origin")?;
+        if origin == "pk" || index_name.starts_with("sqlite_autoindex_") {
+            continue;
+        }
+        let cols = sqlx::query(&format!("PRAGMA index_xinfo({})", quote(&index_name)))
+            .fetch_all(conn.as_mut())
+            .await?;
+        let mut index_cols = Vec::new();
+        for col in cols {
+            let key: i64 = col.try_get("key")?;
+            if key == 0 {
+                continue;
+            }
+            let name: Option<String> = col.try_get("name")?;
+            let desc: i64 = col.try_get("desc")?;
+            index_cols.push(IndexColumn {
+                name: name.unwrap_or_default(),
+                desc: desc != 0,
+                ..Default::default()
+            });
+        }
+        if unique != 0 {
+            indexes.insert(
+                index_name.clone(),
+                TableKey::UniqueKey(index_name, index_cols),
+            );
+        } else {
+            indexes.insert(index_name.clone(), TableKey::Key(index_name, index_cols));
+        }
+    }
+
+    let mut constraints: IndexMap<String, TableKey> = IndexMap::new();
+    let rows = sqlx::query(&format!("PRAGMA foreign_key_list({})", quote(table_name)))
+        .fetch_all(conn.as_mut())
+        .await?;
+    let mut fk_map: IndexMap<i64, ForeignKey> = IndexMap::new();
+    for row in rows {
+        let id: i64 = row.try_get("id")?;
+        let table: String = row.try_get("table")?;
+        let from: String = row.try_get("from")?;
+        let to: Option<String> = row.try_get("to")?;
+        let on_update: String = row.try_get("on_update")?;
+        let on_delete: String = row.try_get("on_delete")?;
+        let entry = fk_map.entry(id).or_insert_with(|| ForeignKey {
+            table,
+            from: Vec::new(),
+            to: Vec::new(),
+            on_delete,
+            on_update,
+        });
+        entry.from.push(from);
+        entry.to.push(to.unwrap_or_default());
+    }
+    let names = constraint_names(sql);
+    for (id, fk) in fk_map.into_iter().rev() {
+        let ForeignKey {
+            table,
+            from,
+            to,
+            on_delete,
+            on_update,
+        } = fk;
+        let name = names
+            .iter()
+            .find(|(_, cols)| *cols == from)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("FK_{}_{}", table_name, id));
+        let to_cols = if to.iter().all(|v| v.is_empty()) {
+            // The parent primary key is referenced implicitly.
+            Vec::new()
+        } else {
+            to.into_iter()
+                .map(|name| IndexColumn {
+                    name,
+                    ..Default::default()
+                })
+                .collect()
+        };
+        constraints.insert(
+            name.clone(),
+            TableKey::Constraint(
+                name,
+                from.into_iter()
+                    .map(|name| IndexColumn {
+                        name,
+                        ..Default::default()
+                    })
+                    .collect(),
+                table,
+                to_cols,
+                convert_action(&on_delete),
+                convert_action(&on_update),
+            ),
+        );
+    }
+
+    Ok(Table {
+        name: table_name.to_string(),
+        old_name: None,
+        old_soft_delete: None,
+        columns,
+        primary,
+        indexes,
+        constraints,
+        comment: None,
+        engine: None,
+        skip_ddl: false,
+    })
+}
+
+/// Rows of `PRAGMA foreign_key_list` grouped by constraint
+struct ForeignKey {
+    table: String,
+    from: Vec<String>,
+    to: Vec<String>,
+    on_delete: String,
+    on_update: String,
+}
+
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn convert_type(declared: &str) -> Result<(SqlType, Option<SqlType>)> {
+    let typ = declared.trim().to_ascii_uppercase();
+    let (base, args) = match typ.find('(') {
+        Some(pos) => {
+            let args: Vec<u32> = typ[pos + 1..]
+                .trim_end_matches(')')
+                .split(',')
+                .filter_map(|v| v.trim().parse().ok())
+                .collect();
+            (typ[..pos].trim().to_string(), args)
+        }
+        None => (typ, Vec::new()),
+    };
+    let arg = |i: usize| args.get(i).copied().unwrap_or_default();
+    let len = |i: usize| -> Result<u16> {
+        match u16::try_from(arg(i)) {
+            Ok(len) => Ok(len),
+            Err(_) => bail!("length out of range in SQLite type: {}", declared),
+        }
+    };
+    let (sql_type, alt_type) = match base.as_str() {
+        "BOOLEAN" | "BOOL" => (SqlType::Bool, None),
+        "CHAR" | "CHARACTER" => (SqlType::Char(arg(0)), None),
+        "VARCHAR" | "CHARACTER VARYING" => (SqlType::Varchar(arg(0)), None),
+        "INTEGER" => (SqlType::Bigint, Some(SqlType::Int)),
+        "INT" => (SqlType::Int, Some(SqlType::UnsignedInt)),
+        "INT UNSIGNED" => (SqlType::UnsignedInt, None),
+        "SMALLINT" => (SqlType::Smallint, Some(SqlType::UnsignedSmallint)),
+        "SMALLINT UNSIGNED" => (SqlType::UnsignedSmallint, None),
+        "BIGINT" => (SqlType::Bigint, Some(SqlType::UnsignedBigint)),
+        "BIGINT UNSIGNED" => (SqlType::UnsignedBigint, None),
+        "TINYINT" => (SqlType::Tinyint, Some(SqlType::UnsignedTinyint)),
+        "TINYINT UNSIGNED" => (SqlType::UnsignedTinyint, None),
+        "BLOB" | "" => (SqlType::Blob, Some(SqlType::Varbinary(0))),
+        "LONGBLOB" => (SqlType::Longblob, None),
+        "MEDIUMBLOB" => (SqlType::Mediumblob, None),
+        "TINYBLOB" => (SqlType::Tinyblob, None),
+        "DOUBLE" | "DOUBLE PRECISION" => (SqlType::Double, None),
+        "FLOAT" => (SqlType::Float, None),
+        "REAL" => (SqlType::Real, Some(SqlType::Double)),
+        "TINYTEXT" => (SqlType::Tinytext, None),
+        "MEDIUMTEXT" => (SqlType::Mediumtext, None),
+        "LONGTEXT" => (SqlType::Longtext, None),
+        "TEXT" | "CLOB" => (SqlType::Text, None),
+        "DATE" => (SqlType::Date, None),
+        "TIME" => (SqlType::Time, None),
+        "DATETIME" => (SqlType::DateTime(0), None),
+        "TIMESTAMP" => (SqlType::Timestamp(0), None),
+        "BINARY" => (SqlType::Binary(len(0)?), None),
+        "VARBINARY" => (SqlType::Varbinary(len(0)?), None),
+        "DECIMAL" | "NUMERIC" => (SqlType::Decimal(arg(0) as u16, arg(1) as u16), None),
+        "JSON" => (SqlType::Json, Some(SqlType::Text)),
+        "UUID" => (SqlType::Uuid, None),
+        _ => bail!("unsupported SQLite type: {}", declared),
+    };
+    Ok((sql_type, alt_type))
+}
+
+fn convert_default(value: &str) -> Option<Literal> {
+    let value = value.trim();
+    let upper = value.to_ascii_uppercase();
+    if upper == "NULL" {
+        Some(Literal::Null)
+    } else if upper == "CURRENT_TIMESTAMP" {
+        Some(Literal::CurrentTimestamp)
+    } else if upper == "CURRENT_DATE" {
+        Some(Literal::CurrentDate)
+    } else if upper == "CURRENT_TIME" {
+        Some(Literal::CurrentTime)
+    } else if upper == "TRUE" {
+        Some(Literal::Boolean(true))
+    } else if upper == "FALSE" {
+        Some(Literal::Boolean(false))
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        Some(Literal::String(
+            value[1..value.len() - 1].replace("''", "'"),
+        ))
+    } else if let Ok(v) = value.parse::<i64>() {
+        Some(Literal::Integer(v))
+    } else if value.parse::<f64>().is_ok() {
+        Some(Literal::String(value.to_string()))
+    } else {
+        None
+    }
+}
+
+fn convert_action(action: &str) -> Option<ReferenceOption> {
+    match action.to_ascii_uppercase().as_str() {
+        "RESTRICT" => Some(ReferenceOption::Restrict),
+        "CASCADE" => Some(ReferenceOption::Cascade),
+        "SET NULL" => Some(ReferenceOption::SetNull),
+        "SET DEFAULT" => Some(ReferenceOption::SetDefault),
+        _ => None,
+    }
+}
+
+/// Splits the CREATE TABLE statement into identifiers and symbols.
+fn tokenize(sql: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '"' || c == '`' || c == '[' || c == '\'' {
+            let end = if c == '[' { ']' } else { c };
+            let mut token = String::new();
+            if c == '\'' {
+                token.push(c);
+            }
+            while let Some(c) = chars.next() {
+                if c == end {
+                    if chars.peek() == Some(&end) && end != ']' {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                token.push(c);
+            }
+            if c == '\'' {
+                token.push(c);
+            }
+            tokens.push(token);
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut token = c.to_string();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    token.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(token);
+        } else {
+            tokens.push(c.to_string());
+        }
+    }
+    tokens
+}
+
+/// PRAGMA foreign_key_list does not return constraint names.
+fn constraint_names(sql: &str) -> Vec<(String, Vec<String>)> {
+    let tokens = tokenize(sql);
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i + 4 < tokens.len() {
+        if tokens[i].eq_ignore_ascii_case("CONSTRAINT")
+            && tokens[i + 2].eq_ignore_ascii_case("FOREIGN")
+            && tokens[i + 3].eq_ignore_ascii_case("KEY")
+            && tokens[i + 4] == "("
+        {
+            let name = tokens[i + 1].clone();
+            let mut cols = Vec::new();
+            let mut j = i + 5;
+            while j < tokens.len() && tokens[j] != ")" {
+                if tokens[j] != "," {
+                    cols.push(tokens[j].clone());
+                }
+                j += 1;
+            }
+            result.push((name, cols));
+            i = j;
+        }
+        i += 1;
+    }
+    result
+}
+
+/// Returns the definition of the column in the CREATE TABLE statement.
+fn column_definition<'a>(sql: &'a str, column: &str) -> Option<&'a str> {
+    let name = quote(column);
+    let mut offset = 0;
+    while let Some(pos) = sql[offset..].find(&name) {
+        let start = offset + pos;
+        offset = start + name.len();
+        let prev = sql[..start].trim_end().chars().last();
+        if prev != Some('(') && prev != Some(',') {
+            continue;
+        }
+        let mut depth = 0;
+        for (i, c) in sql[offset..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => return Some(&sql[offset..offset + i]),
+                ')' => depth -= 1,
+                ',' if depth == 0 => return Some(&sql[offset..offset + i]),
+                _ => {}
+            }
+        }
+        return Some(&sql[offset..]);
+    }
+    None
+}
+
+fn collation(sql: &str, column: &str) -> Option<String> {
+    let def = column_definition(sql, column)?;
+    let pos = def.to_ascii_uppercase().find(" COLLATE ")? + 9;
+    def[pos..]
+        .split_whitespace()
+        .next()
+        .map(|v| v.trim_matches('"').to_string())
+}
+
+/// Returns the expression of a generated column.
+fn generated_expr(sql: &str, column: &str) -> Option<String> {
+    let def = column_definition(sql, column)?;
+    let pos = def.to_ascii_uppercase().find("AS (")? + 4;
+    let mut depth = 1;
+    for (i, c) in def[pos..].char_indices() {
+        if c == '(' {
+            depth += 1;
+        } else if c == ')' {
+            depth -= 1;
+            if depth == 0 {
+                return Some(def[pos..pos + i].to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(
+            tokenize(r#"CREATE TABLE "a""b" ([c d] TEXT DEFAULT 'it''s', `e`)"#),
+            vec![
+                "CREATE", "TABLE", "a\"b", "(", "c d", "TEXT", "DEFAULT", "'it's'", ",", "e", ")"
+            ]
+        );
+    }
+
+    #[test]
+    fn foreign_key_names() {
+        let sql = r#"CREATE TABLE "post" (
+            "id" INTEGER PRIMARY KEY AUTOINCREMENT,
+            "user_id" INT NOT NULL,
+            "group_id" INT NOT NULL,
+            "name" TEXT DEFAULT 'CONSTRAINT x FOREIGN KEY (y)',
+            CONSTRAINT "fk_post_user" FOREIGN KEY ("user_id") REFERENCES "user" ("id"),
+            CONSTRAINT fk_post_member FOREIGN KEY ("group_id", "user_id") REFERENCES "member" ("group_id", "user_id") ON DELETE CASCADE
+        )"#;
+        assert_eq!(
+            constraint_names(sql),
+            vec![
+                ("fk_post_user".to_string(), vec!["user_id".to_string()]),
+                (
+                    "fk_post_member".to_string(),
+                    vec!["group_id".to_string(), "user_id".to_string()]
+                ),
+            ]
+        );
+        assert!(constraint_names(r#"CREATE TABLE "a" ("b" INT REFERENCES "c" ("d"))"#).is_empty());
+    }
+
+    #[test]
+    fn convert_types() {
+        assert_eq!(
+            convert_type("integer").unwrap(),
+            (SqlType::Bigint, Some(SqlType::Int))
+        );
+        assert_eq!(
+            convert_type("INT UNSIGNED").unwrap(),
+            (SqlType::UnsignedInt, None)
+        );
+        assert_eq!(
+            convert_type("VARCHAR(255)").unwrap(),
+            (SqlType::Varchar(255), None)
+        );
+        assert_eq!(
+            convert_type(" char ( 2 ) ").unwrap(),
+            (SqlType::Char(2), None)
+        );
+        assert_eq!(
+            convert_type("DECIMAL(10, 2)").unwrap(),
+            (SqlType::Decimal(10, 2), None)
+        );
+        assert_eq!(
+            convert_type("BINARY(16)").unwrap(),
+            (SqlType::Binary(16), None)
+        );
+        assert_eq!(
+            convert_type("").unwrap(),
+            (SqlType::Blob, Some(SqlType::Varbinary(0)))
+        );
+        assert_eq!(
+            convert_type("JSON").unwrap(),
+            (SqlType::Json, Some(SqlType::Text))
+        );
+        assert!(convert_type("VARBINARY(70000)").is_err());
+        assert!(convert_type("GEOMETRY").is_err());
+    }
+
+    #[test]
+    fn column_options() {
+        let sql = r#"CREATE TABLE "t" ("id" INT, "name" VARCHAR(10) COLLATE "NOCASE" NOT NULL, "len" INT AS (length("name")) VIRTUAL)"#;
+        assert_eq!(collation(sql, "name").as_deref(), Some("NOCASE"));
+        assert_eq!(collation(sql, "id"), None);
+        assert_eq!(
+            generated_expr(sql, "len").as_deref(),
+            Some(r#"length("name")"#)
+        );
+        assert_eq!(generated_expr(sql, "name"), None);
+        assert_eq!(
+            convert_default("'it''s'"),
+            Some(Literal::String("it's".to_string()))
+        );
+        assert_eq!(convert_default("-1"), Some(Literal::Integer(-1)));
+        assert_eq!(
+            convert_default("current_timestamp"),
+            Some(Literal::CurrentTimestamp)
+        );
+    }
+}
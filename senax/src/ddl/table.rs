@@ -7,12 +7,12 @@ use senax_mysql_parser::create_table_options::TableOption;
 use serde::{Deserialize, Serialize};
 use sqlx::migrate::MigrateDatabase;
 use sqlx::pool::PoolConnection;
-use sqlx::{MySql, MySqlPool, Postgres, Row};
+use sqlx::{MySql, MySqlPool, Postgres, Row, Sqlite};
 use std::fmt::{self, Debug};
 
 use crate::common::{escape_db_identifier, yaml_value_to_str};
 use crate::ddl::sql_type::{Literal, SqlType, TableKey};
-use crate::schema::{SoftDelete, is_mysql_mode, set_mysql_mode};
+use crate::schema::{SoftDelete, is_mysql_mode, is_sqlite_mode, set_mysql_mode, set_sqlite_mode};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct Table {
@@ -46,7 +46,22 @@ impl fmt::Display for Table {
                 .join(",\n    ")
         )?;
         if let Some(ref primary) = self.primary {
-            write!(f, ",\n    {}", primary.1)?;
+            // SQLite declares an auto-increment primary key on the column itself.
+            if !is_sqlite_mode() || !self.columns.values().any(|c| c.constraint.auto_increment) {
+                write!(f, ",\n    {}", primary.1)?;
+            }
+        }
+        if is_sqlite_mode() {
+            // SQLite cannot add foreign keys with ALTER TABLE.
+            write!(
+                f,
+                "{}",
+                self.constraints
+                    .values()
+                    .map(|constraint| format!(",\n    {}", constraint))
+                    .collect::<Vec<_>>()
+                    .join("")
+            )?;
         }
         if is_mysql_mode() {
             write!(
@@ -369,6 +384,7 @@ pub async fn get_mysql_table_def_map(
 pub async fn parse(database_url: &str) -> Result<IndexMap<String, Table>> {
     if database_url.starts_with("mysql:") {
         set_mysql_mode(true);
+        set_sqlite_mode(false);
         if !MySql::database_exists(database_url).await? {
             return Ok(Default::default());
         }
@@ -377,10 +393,18 @@ pub async fn parse(database_url: &str) -> Result<IndexMap<String, Table>> {
         get_mysql_table_def_map(&mut conn).await
     } else if database_url.starts_with("postgres:") {
         set_mysql_mode(false);
+        set_sqlite_mode(false);
         if !Postgres::database_exists(database_url).await? {
             return Ok(Default::default());
         }
         super::pgsql::get_pgsql_table_def_map(database_url).await
+    } else if database_url.starts_with("sqlite:") {
+        set_mysql_mode(false);
+        set_sqlite_mode(true);
+        if !Sqlite::database_exists(database_url).await? {
+            return Ok(Default::default());
+        }
+        super::sqlite::get_sqlite_table_def_map(database_url).await
     } else {
         bail!("unsupported database type");
     }
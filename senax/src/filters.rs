@@ -339,6 +339,7 @@ fn _fmt_join(f: &str, name: &&String, col: &&FieldDef, index: i32, foreign: &[St
         .replace("{clone}", col.clone_str())
         .replace("{clone_for_outer}", col.clone_for_outer_str())
         .replace("{placeholder}", &col.placeholder())
+        .replace("{xmax}", if_then_else!(is_sqlite_mode(), "0", "xmax"))
         .replace(
             "{label}",
             &label4(if_then_else!(SHOW_LABEL.relaxed_load(), &col.label, &None)).unwrap(),
@@ -34,6 +34,7 @@ mod db_document;
 pub(crate) mod ddl {
     mod pgsql;
     pub mod sql_type;
+    mod sqlite;
     pub mod table;
 }
 mod actix_generator;
@@ -48,6 +49,7 @@ mod migration_generator;
 mod model_generator;
 pub(crate) mod schema;
 mod schema_generator;
+mod schema_lint;
 mod schema_md;
 
 pub const SCHEMA_PATH: &str = "0_schema";
@@ -134,6 +136,11 @@ enum Commands {
         /// Specify the DB
         db: String,
     },
+    /// Check the schema for design smells
+    Lint {
+        /// Specify the DB
+        db: Option<String>,
+    },
     /// generate models
     Model {
         /// Specify the DB
@@ -164,6 +171,36 @@ enum Commands {
         skip_empty: bool,
         #[clap(long)]
         use_test_db: bool,
+        /// Diff against the saved schema snapshot instead of the database
+        #[clap(long)]
+        offline: bool,
+        /// Allow data-losing changes such as dropping columns
+        #[clap(long)]
+        allow_destructive: bool,
+        /// Generate online schema changes for MySQL
+        #[clap(long)]
+        online: bool,
+        /// Scaffold a Rust data migration step
+        #[clap(long, conflicts_with_all = ["empty", "online"])]
+        data: bool,
+    },
+    /// Report differences between the schema and the database
+    Drift {
+        /// Specify the DB
+        db: String,
+        #[clap(long)]
+        use_test_db: bool,
+        /// Output the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Replace the migrations before the timestamp with a baseline file
+    SquashMigrations {
+        /// Specify the DB
+        db: String,
+        /// Timestamp (YYYYMMDDhhmmss) of the first migration to keep
+        #[clap(long)]
+        before: String,
     },
     /// Reflect the name change in the schema after generating the migration.
     ReflectMigrationChanges,
@@ -220,6 +257,12 @@ enum Commands {
         ignore_timestampable: bool,
         #[clap(long)]
         interpret_tinyint_as_boolean: bool,
+        /// Infer relations from *_id columns without foreign keys
+        #[clap(long)]
+        infer_relations: bool,
+        /// Add groups for table name prefixes
+        #[clap(long)]
+        group_by_prefix: bool,
     },
     SenaxSchema {
         #[clap(short, long)]
@@ -385,6 +428,18 @@ async fn exec(cli: Cli) -> Result<()> {
             ensure!(db_re.is_match(db), "bad db name!");
             schema::parse(db, false, false)?;
         }
+        Commands::Lint { db } => {
+            let mut errors = 0;
+            if let Some(db) = db {
+                ensure!(db_re.is_match(db), "bad db name!");
+                errors += schema_lint::lint(db)?;
+            } else {
+                for db in crate::db_generator::db_list(false)? {
+                    errors += schema_lint::lint(&db)?;
+                }
+            }
+            ensure!(errors == 0, "{} lint errors found.", errors);
+        }
         Commands::Model {
             db,
             force,
@@ -419,24 +474,42 @@ async fn exec(cli: Cli) -> Result<()> {
             empty,
             skip_empty,
             use_test_db,
+            offline,
+            allow_destructive,
+            online,
+            data,
         } => {
+            let options = migration_generator::GenerateOptions {
+                empty: *empty,
+                skip_empty: *skip_empty,
+                use_test_db: *use_test_db,
+                offline: *offline,
+                allow_destructive: *allow_destructive,
+                online: *online,
+                data: *data,
+            };
             if let Some(db) = db {
                 ensure!(db_re.is_match(db), "bad db name!");
-                migration_generator::generate(db, description, *empty, *skip_empty, *use_test_db)
-                    .await?;
+                migration_generator::generate(db, description, options).await?;
             } else {
                 for db in crate::db_generator::db_list(false)? {
-                    migration_generator::generate(
-                        &db,
-                        description,
-                        *empty,
-                        *skip_empty,
-                        *use_test_db,
-                    )
-                    .await?;
+                    migration_generator::generate(&db, description, options).await?;
                 }
             }
         }
+        Commands::Drift {
+            db,
+            use_test_db,
+            json,
+        } => {
+            ensure!(db_re.is_match(db), "bad db name!");
+            let count = migration_generator::drift::check(db, *use_test_db, *json).await?;
+            ensure!(count == 0, "{} schema drifts found in {}.", count, db);
+        }
+        Commands::SquashMigrations { db, before } => {
+            ensure!(db_re.is_match(db), "bad db name!");
+            migration_generator::squash::squash(db, before)?;
+        }
         Commands::ReflectMigrationChanges => {
             for db in crate::db_generator::db_list(true)? {
                 common::reflect_migration_changes(&db)?;
@@ -474,6 +547,8 @@ async fn exec(cli: Cli) -> Result<()> {
             use_label_as_sql_comment,
             ignore_timestampable,
             interpret_tinyint_as_boolean,
+            infer_relations,
+            group_by_prefix,
         } => {
             ensure!(db_re.is_match(db), "bad db name!");
             schema_generator::generate(
@@ -482,6 +557,8 @@ async fn exec(cli: Cli) -> Result<()> {
                 *use_label_as_sql_comment,
                 *ignore_timestampable,
                 *interpret_tinyint_as_boolean,
+                *infer_relations,
+                *group_by_prefix,
             )
             .await?;
         }
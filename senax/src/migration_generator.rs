@@ -5,34 +5,79 @@ use indexmap::IndexMap;
 use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::fmt::Write;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::common::fs_write;
 use crate::common::{ToCase as _, escape_db_identifier};
+use crate::common::{fs_write, if_then_else};
 use crate::ddl::sql_type::{IndexColumn, Literal, ReferenceOption, SqlType, TableKey};
 use crate::ddl::table::{Column, Constraint, Table};
 use crate::schema::{
-    self, AutoGeneration, CONFIG, GROUPS, SoftDelete, SortDirection, is_mysql_mode,
+    self, AutoGeneration, CONFIG, GROUPS, SoftDelete, SortDirection, is_mysql_mode, is_sqlite_mode,
 };
 use crate::{DB_PATH, ddl};
+use safety::{Change, Risk};
+
+mod data;
+pub mod drift;
+mod online;
+mod safety;
+pub mod squash;
+
+const SNAPSHOT_FILE: &str = "schema_snapshot.json";
+/// Must match the generated db crate, which reverts a change set as a whole by this comment.
+const CHANGE_SET_CONTINUATION: &str = "-- Reverted by the first file of this change set.";
 
 pub const UTF8_BYTE_LEN: u32 = 4;
 pub const MYSQL_UUID_COLLATION: &str = "ascii_general_ci";
 
+/// Flags of `gen-migrate`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenerateOptions {
+    pub empty: bool,
+    pub skip_empty: bool,
+    pub use_test_db: bool,
+    pub offline: bool,
+    pub allow_destructive: bool,
+    pub online: bool,
+    pub data: bool,
+}
+
 pub async fn generate(
     db: &str,
     description: &Option<String>,
-    empty: bool,
-    skip_empty: bool,
-    use_test_db: bool,
+    options: GenerateOptions,
 ) -> Result<()> {
-    let (mut ddl, mut ddl_list) = if empty {
-        (String::new(), Vec::new())
+    let GenerateOptions {
+        empty,
+        skip_empty,
+        use_test_db,
+        offline,
+        allow_destructive,
+        online,
+        data,
+    } = options;
+    if data {
+        anyhow::ensure!(
+            description.is_some(),
+            "The description is required for a data migration."
+        );
+    }
+    let mut snapshot = None;
+    let (mut ddl, mut ddl_list, mut down) = if data {
+        let (up, down) = data::marker_sql(description.as_deref().unwrap_or_default());
+        (up, Vec::new(), down)
+    } else if empty {
+        (String::new(), Vec::new(), String::new())
     } else {
         schema::parse(db, false, false)?;
+        if online && !is_mysql_mode() {
+            anyhow::bail!("The online mode is only supported for MySQL.");
+        }
         let config = CONFIG.read().unwrap().as_ref().unwrap().clone();
         let mut new_tables = IndexMap::new();
+        let mut allowed_tables = HashSet::new();
         {
             let group_lock = GROUPS.read().unwrap();
             let groups = group_lock.as_ref().unwrap();
@@ -40,21 +85,41 @@ pub async fn generate(
                 for (_model_name, def) in defs {
                     if def.has_table() {
                         let (table_name, table, _) = make_table_def(def, &config)?;
+                        if def.allow_destructive_migration() {
+                            allowed_tables.insert(table_name.clone());
+                        }
                         new_tables.insert(table_name, table);
                     }
                 }
             }
         }
-        let url_name = if use_test_db {
-            format!("{}_TEST_DB_URL", db.to_upper_snake())
+        let old_tables = if offline {
+            load_snapshot(db)?
         } else {
-            format!("{}_DB_URL", db.to_upper_snake())
+            ddl::table::parse(&db_url(db, use_test_db)?).await?
         };
-        let db_url = env::var(&url_name)
-            .with_context(|| format!("{} is required in the .env file.", url_name))?;
-        let old_tables = ddl::table::parse(&db_url).await?;
+        snapshot = Some(normalize_tables(&new_tables));
         let cli_mode = description.is_none();
-        make_ddl(new_tables, old_tables, cli_mode)?
+        let down = if cli_mode {
+            String::new()
+        } else {
+            make_down_ddl(&new_tables, &old_tables)?
+        };
+        let (ddl, ddl_list, changes) = make_ddl(new_tables, old_tables, cli_mode, online)?;
+        safety::print_report(db, &changes);
+        let destructive: Vec<_> = changes
+            .iter()
+            .filter(|c| c.risk == Risk::DataLosing && !allowed_tables.contains(&c.table))
+            .collect();
+        if !cli_mode && !allow_destructive && !destructive.is_empty() {
+            let list: Vec<_> = destructive.iter().map(|c| c.to_string()).collect();
+            anyhow::bail!(
+                "Destructive changes detected in {}.\n{}\nUse --allow-destructive or set allow_destructive_migration on the model.",
+                db,
+                list.join("\n")
+            );
+        }
+        (ddl, ddl_list, down)
     };
     if skip_empty && ddl.is_empty() {
         return Ok(());
@@ -63,6 +128,16 @@ pub async fn generate(
         if ddl.is_empty() {
             ddl.push_str("-- TODO: Fix this file.\n");
         }
+        if down.is_empty() {
+            down.push_str("-- TODO: Fix this file.\n");
+        }
+        // The first file reverts the whole change set, so the down files of the
+        // following no-transaction files have nothing left to do.
+        // `migrate --down` recognizes them by the comment and reverts the change set as a whole.
+        let mut down_list = vec![down];
+        for _ in &ddl_list {
+            down_list.push(format!("{}\n", CHANGE_SET_CONTINUATION));
+        }
         ddl_list.insert(0, ddl);
         let description: String = description
             .chars()
@@ -79,6 +154,8 @@ pub async fn generate(
         let dt = Utc::now();
         let mut file_prefix: u64 = dt.format("%Y%m%d%H%M%S").to_string().parse().unwrap();
         ddl_list.reverse();
+        down_list.reverse();
+        let mut first_prefix = None;
         loop {
             let file_path = ddl_path.join(format!("{}_*.sql", file_prefix));
             if glob::glob(file_path.to_str().unwrap())?.count() > 0 {
@@ -90,8 +167,19 @@ pub async fn generate(
             } else {
                 break;
             };
-            let file_path = ddl_path.join(format!("{}_{}.sql", file_prefix, description));
+            let file_path = ddl_path.join(format!("{}_{}.up.sql", file_prefix, description));
             fs_write(file_path, &ddl)?;
+            first_prefix.get_or_insert(file_prefix);
+            if let Some(down) = down_list.pop() {
+                let file_path = ddl_path.join(format!("{}_{}.down.sql", file_prefix, description));
+                fs_write(file_path, &down)?;
+            }
+        }
+        if let Some(snapshot) = snapshot {
+            save_snapshot(db, &snapshot)?;
+        }
+        if data && let Some(version) = first_prefix {
+            data::scaffold(db, version, &description)?;
         }
     } else if !ddl.is_empty() || !ddl_list.is_empty() {
         println!("-- {}", &db);
@@ -238,6 +326,8 @@ pub fn make_table_def(
             SqlType::Varbinary(schema::BINARY_UUID_LENGTH)
         } else if is_mysql_mode() && col.data_type == schema::DataType::Jsonb {
             SqlType::Json
+        } else if is_sqlite_mode() && matches!(sql_type, SqlType::Json | SqlType::Jsonb) {
+            SqlType::Text
         } else {
             sql_type.clone()
         };
@@ -542,14 +632,132 @@ enum Type {
     DropForeign,
 }
 
+fn db_url(db: &str, use_test_db: bool) -> Result<String> {
+    let url_name = if use_test_db {
+        format!("{}_TEST_DB_URL", db.to_upper_snake())
+    } else {
+        format!("{}_DB_URL", db.to_upper_snake())
+    };
+    env::var(&url_name).with_context(|| format!("{} is required in the .env file.", url_name))
+}
+
+pub fn snapshot_path(db: &str) -> PathBuf {
+    Path::new(DB_PATH)
+        .join(format!("_{}", db.to_snake()))
+        .join(SNAPSHOT_FILE)
+}
+
+/// Loads the table definitions saved by the last generated migration.
+pub fn load_snapshot(db: &str) -> Result<IndexMap<String, Table>> {
+    let path = snapshot_path(db);
+    if !path.exists() {
+        let migrations = Path::new(DB_PATH)
+            .join(format!("_{}", db.to_snake()))
+            .join("migrations")
+            .join("*.sql");
+        if glob::glob(migrations.to_str().unwrap())?.count() > 0 {
+            anyhow::bail!(
+                "{} is not found. Run gen-migrate once against the database to create it.",
+                path.display()
+            );
+        }
+        return Ok(IndexMap::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}.", path.display()))
+}
+
+fn save_snapshot(db: &str, tables: &IndexMap<String, Table>) -> Result<()> {
+    let mut content = serde_json::to_string_pretty(tables)?;
+    content.push('\n');
+    fs_write(snapshot_path(db), content)
+}
+
+/// Table definitions as they are after the migration has been applied.
+fn normalize_tables(new_tables: &IndexMap<String, Table>) -> IndexMap<String, Table> {
+    let mut tables = IndexMap::new();
+    for (table_name, new_table) in new_tables {
+        if new_table.skip_ddl {
+            continue;
+        }
+        let mut table = new_table.clone();
+        table.old_name = None;
+        table.old_soft_delete = None;
+        for column in table.columns.values_mut() {
+            column.old_name = None;
+        }
+        tables.insert(table_name.clone(), table);
+    }
+    tables
+}
+
+/// Generates the DDL that reverts the schema from `new_tables` back to `old_tables`.
+fn make_down_ddl(
+    new_tables: &IndexMap<String, Table>,
+    old_tables: &IndexMap<String, Table>,
+) -> Result<String> {
+    let current = normalize_tables(new_tables);
+    let mut desired = IndexMap::new();
+    for (table_name, old_table) in old_tables {
+        let renamed = new_tables.iter().find(|(name, t)| {
+            t.old_name.as_ref() == Some(table_name)
+                && *name != table_name
+                && !old_tables.contains_key(*name)
+        });
+        let mut table = old_table.clone();
+        let new_table = if let Some((name, new_table)) = renamed {
+            table.old_name = Some(name.clone());
+            Some(new_table)
+        } else {
+            new_tables.get(table_name)
+        };
+        match new_table {
+            Some(new_table) if new_table.skip_ddl => continue,
+            Some(new_table) => {
+                for (name, new_field) in &new_table.columns {
+                    if let Some(old_name) = &new_field.old_name
+                        && name != old_name
+                        && !old_table.columns.contains_key(name)
+                        && let Some(column) = table.columns.get_mut(old_name)
+                    {
+                        column.old_name = Some(name.clone());
+                    }
+                }
+            }
+            None if table_name.starts_with('_') || table_name == "spatial_ref_sys" => continue,
+            None => {}
+        }
+        desired.insert(table_name.clone(), table);
+    }
+    let (mut ddl, ddl_list, _) = make_ddl(desired, current, false, false)?;
+    ddl = ddl.lines().filter(|line| !line.starts_with("-- [")).fold(
+        String::new(),
+        |mut acc, line| {
+            acc.push_str(line);
+            acc.push('\n');
+            acc
+        },
+    );
+    // Indexes are rebuilt inside the down script instead of concurrently.
+    for line in ddl_list {
+        let line = line.trim_start_matches("-- no-transaction\n");
+        ddl.push_str(&line.replace(" CONCURRENTLY ", " "));
+        ddl.push('\n');
+    }
+    Ok(ddl.replace(" CONCURRENTLY ", " "))
+}
+
 fn make_ddl(
     mut new_tables: IndexMap<String, Table>,
     mut old_tables: IndexMap<String, Table>,
     cli_mode: bool,
-) -> Result<(String, Vec<String>)> {
+    online: bool,
+) -> Result<(String, Vec<String>, Vec<Change>)> {
+    let online = online && is_mysql_mode();
     let mut result1 = String::new();
     let mut result2 = Vec::new();
     let mut history: BTreeMap<Type, IndexMap<String, Vec<String>>> = BTreeMap::new();
+    let mut rebuilt_tables = HashSet::new();
     while {
         let mut found = false;
         for (table_name, new_table) in new_tables.iter_mut() {
@@ -662,14 +870,28 @@ fn make_ddl(
             found
         } {}
     }
+    // Tables altered through a shadow table get their indexes and foreign keys in the shadow files.
+    let shadow_tables: HashSet<String> = new_tables
+        .iter()
+        .filter(|(table_name, new_table)| {
+            online
+                && !new_table.skip_ddl
+                && old_tables.get(*table_name).is_some_and(|old_table| {
+                    online::needs_copy(old_table, new_table)
+                        && online::can_copy_and_swap(table_name, new_table, old_table, &new_tables)
+                })
+        })
+        .map(|(table_name, _)| table_name.clone())
+        .collect();
     for (table_name, old_table) in &old_tables {
         if let Some(new_table) = new_tables.get(table_name) {
-            if new_table.skip_ddl {
+            if new_table.skip_ddl || shadow_tables.contains(table_name) {
                 continue;
             }
             for (name, constraint) in &new_table.constraints {
                 if let Some(old_constraint) = old_table.constraints.get(name)
                     && old_constraint != constraint
+                    && !is_sqlite_mode()
                 {
                     // fix foreign key constraints
                     if is_mysql_mode() {
@@ -698,9 +920,10 @@ fn make_ddl(
                     if is_mysql_mode() {
                         writeln!(
                             &mut result1,
-                            "ALTER TABLE {} DROP INDEX {};",
+                            "ALTER TABLE {} DROP INDEX {}{};",
                             &escape_db_identifier(table_name),
-                            &escape_db_identifier(name)
+                            &escape_db_identifier(name),
+                            if_then_else!(online, online::INPLACE, "")
                         )?;
                     } else {
                         writeln!(&mut result1, "DROP INDEX {};", &escape_db_identifier(name))?;
@@ -723,7 +946,9 @@ fn make_ddl(
                         .entry(table_name.clone())
                         .or_default()
                         .push(name.clone());
-                    if is_mysql_mode() {
+                    if is_sqlite_mode() || shadow_tables.contains(table_name) {
+                        // The table is rebuilt below.
+                    } else if is_mysql_mode() {
                         writeln!(
                             &mut result1,
                             "ALTER TABLE {} DROP FOREIGN KEY {};",
@@ -751,12 +976,15 @@ fn make_ddl(
                             .or_default()
                             .push(name.clone());
                     }
-                    if is_mysql_mode() {
+                    if shadow_tables.contains(table_name) {
+                        // Dropped in the shadow table
+                    } else if is_mysql_mode() {
                         writeln!(
                             &mut result1,
-                            "ALTER TABLE {} DROP INDEX {};",
+                            "ALTER TABLE {} DROP INDEX {}{};",
                             &escape_db_identifier(table_name),
-                            &escape_db_identifier(name)
+                            &escape_db_identifier(name),
+                            if_then_else!(online, online::INPLACE, "")
                         )?;
                     } else {
                         writeln!(&mut result1, "DROP INDEX {};", &escape_db_identifier(name))?;
@@ -792,8 +1020,16 @@ fn make_ddl(
             continue;
         }
         if let Some(old_table) = old_tables.get(table_name) {
+            if is_sqlite_mode() {
+                if sqlite_alter_table(&mut result1, &mut history, table_name, new_table, old_table)?
+                {
+                    rebuilt_tables.insert(table_name.clone());
+                }
+                continue;
+            }
             let mut alter_columns = Vec::new();
             let mut after_alter_table = Vec::new();
+            let needs_copy = is_mysql_mode() && online::needs_copy(old_table, new_table);
             if new_table.primary.as_ref().map(|v| &v.1) != old_table.primary.as_ref().map(|v| &v.1)
             {
                 // Fix primary keys
@@ -970,12 +1206,33 @@ fn make_ddl(
                 alter_columns.push(format!("ENGINE={}", engine));
             }
             if !alter_columns.is_empty() {
-                writeln!(
-                    &mut result1,
-                    "ALTER TABLE {} {};",
-                    &escape_db_identifier(table_name),
-                    &alter_columns.join(", ")
-                )?;
+                let shadow = if shadow_tables.contains(table_name) {
+                    alter_columns.extend(online::index_changes(old_table, new_table));
+                    online::copy_and_swap(table_name, new_table, old_table, &alter_columns)?
+                } else {
+                    None
+                };
+                if let Some(files) = shadow {
+                    result2.extend(files);
+                    if !after_alter_table.is_empty() {
+                        result2.push(after_alter_table.concat());
+                    }
+                    continue;
+                } else {
+                    if online && needs_copy {
+                        eprintln!(
+                            "The {} table cannot be altered online and is copied with a lock.",
+                            table_name
+                        );
+                    }
+                    writeln!(
+                        &mut result1,
+                        "ALTER TABLE {} {}{};",
+                        &escape_db_identifier(table_name),
+                        &alter_columns.join(", "),
+                        if_then_else!(online && !needs_copy, online::INPLACE, "")
+                    )?;
+                }
             }
             for line in after_alter_table {
                 result1.push_str(&line);
@@ -992,7 +1249,13 @@ fn make_ddl(
                 .entry(table_name.clone())
                 .or_default();
             writeln!(&mut result1, "{}", &new_table)?;
-            if !is_mysql_mode() {
+            if is_sqlite_mode() {
+                for index in new_table.indexes.values() {
+                    if let Some(ddl) = sqlite_create_index(table_name, index) {
+                        writeln!(&mut result1, "{}", ddl)?;
+                    }
+                }
+            } else if !is_mysql_mode() {
                 for (name, new_field) in &new_table.columns {
                     if let Some(comment) = &new_field.comment {
                         writeln!(
@@ -1052,14 +1315,24 @@ fn make_ddl(
             if new_table.skip_ddl {
                 continue;
             }
+            let shadow = shadow_tables.contains(table_name);
             for (name, index) in &new_table.indexes {
                 let mut add_index = || {
-                    if is_mysql_mode() {
+                    if shadow {
+                        // Added in the shadow table
+                    } else if is_sqlite_mode() {
+                        if !rebuilt_tables.contains(table_name)
+                            && let Some(ddl) = sqlite_create_index(table_name, index)
+                        {
+                            writeln!(&mut result1, "{}", ddl)?;
+                        }
+                    } else if is_mysql_mode() {
                         writeln!(
                             &mut result1,
-                            "ALTER TABLE {} ADD {};",
+                            "ALTER TABLE {} ADD {}{};",
                             &escape_db_identifier(table_name),
-                            index
+                            index,
+                            if_then_else!(online, online::index_clause(index), "")
                         )?;
                     } else {
                         if let TableKey::Key(index_name, cols) = index {
@@ -1134,12 +1407,14 @@ fn make_ddl(
                             .entry(table_name.clone())
                             .or_default()
                             .push(name.clone());
-                        writeln!(
-                            &mut result1,
-                            "ALTER TABLE {} ADD {};",
-                            &escape_db_identifier(table_name),
-                            constraint
-                        )?;
+                        if !is_sqlite_mode() && !shadow {
+                            writeln!(
+                                &mut result1,
+                                "ALTER TABLE {} ADD {};",
+                                &escape_db_identifier(table_name),
+                                constraint
+                            )?;
+                        }
                     }
                 } else {
                     // Add foreign key constraints
@@ -1149,18 +1424,20 @@ fn make_ddl(
                         .entry(table_name.clone())
                         .or_default()
                         .push(name.clone());
-                    writeln!(
-                        &mut result1,
-                        "ALTER TABLE {} ADD {};",
-                        &escape_db_identifier(table_name),
-                        constraint
-                    )?;
+                    if !is_sqlite_mode() && !shadow {
+                        writeln!(
+                            &mut result1,
+                            "ALTER TABLE {} ADD {};",
+                            &escape_db_identifier(table_name),
+                            constraint
+                        )?;
+                    }
                 }
             }
         }
     }
     for (table_name, new_table) in &new_tables {
-        if new_table.skip_ddl {
+        if new_table.skip_ddl || is_sqlite_mode() {
             continue;
         }
         if !old_tables.contains_key(table_name) {
@@ -1175,8 +1452,10 @@ fn make_ddl(
             }
         }
     }
+    let changes = safety::classify(&history, &new_tables, &old_tables, &rebuilt_tables);
     let mut buf = String::new();
     if !is_mysql_mode()
+        && !is_sqlite_mode()
         && result1.is_empty()
         && let Some(first) = result2.pop()
     {
@@ -1201,6 +1480,14 @@ fn make_ddl(
             buf.push_str("SET foreign_key_checks = 0;\n");
             buf.push_str(&result1);
             buf.push_str("SET foreign_key_checks = 1;\n");
+        } else if is_sqlite_mode() {
+            // PRAGMA foreign_keys has no effect inside a transaction.
+            if !cli_mode {
+                buf.insert_str(0, "-- no-transaction\n");
+            }
+            buf.push_str("PRAGMA foreign_keys = OFF;\nBEGIN;\n");
+            buf.push_str(&result1);
+            buf.push_str("COMMIT;\nPRAGMA foreign_keys = ON;\n");
         } else {
             if result1.contains("geography(") {
                 buf.push_str("CREATE EXTENSION IF NOT EXISTS postgis;\n");
@@ -1208,5 +1495,145 @@ fn make_ddl(
             buf.push_str(&result1);
         }
     }
-    Ok((buf, result2))
+    Ok((buf, result2, changes))
+}
+
+/// SQLite supports only a few forms of ALTER TABLE, so other changes rebuild the table.
+fn sqlite_alter_table(
+    result: &mut String,
+    history: &mut BTreeMap<Type, IndexMap<String, Vec<String>>>,
+    table_name: &str,
+    new_table: &Table,
+    old_table: &Table,
+) -> Result<bool> {
+    let primary_changed =
+        new_table.primary.as_ref().map(|v| &v.1) != old_table.primary.as_ref().map(|v| &v.1);
+    let mut rebuild = primary_changed || new_table.constraints != old_table.constraints;
+    let mut alter_columns = Vec::new();
+    for name in old_table.columns.keys() {
+        if !new_table.columns.contains_key(name) {
+            // Delete columns
+            history
+                .entry(Type::DropColumn)
+                .or_default()
+                .entry(table_name.to_string())
+                .or_default()
+                .push(name.clone());
+            alter_columns.push(format!("DROP COLUMN {}", &escape_db_identifier(name)));
+        }
+    }
+    for (name, new_field) in &new_table.columns {
+        if let Some(old_field) = old_table.columns.get(name) {
+            if new_field != old_field {
+                // fix columns
+                history
+                    .entry(Type::ChangeColumn)
+                    .or_default()
+                    .entry(table_name.to_string())
+                    .or_default()
+                    .push(name.clone());
+                rebuild = true;
+            }
+        } else {
+            // add columns
+            history
+                .entry(Type::AddColumn)
+                .or_default()
+                .entry(table_name.to_string())
+                .or_default()
+                .push(name.clone());
+            if new_field.constraint.auto_increment
+                || matches!(new_field.constraint.query, Some((_, true)))
+            {
+                rebuild = true;
+            }
+            alter_columns.push(format!(
+                "ADD COLUMN {} {}",
+                &escape_db_identifier(name),
+                new_field
+            ));
+        }
+    }
+    if primary_changed {
+        let typ = if new_table.primary.is_some() {
+            Type::ChangePrimary
+        } else {
+            Type::DropPrimary
+        };
+        history
+            .entry(typ)
+            .or_default()
+            .entry(table_name.to_string())
+            .or_default();
+    }
+    if !rebuild {
+        for alter in alter_columns {
+            writeln!(
+                result,
+                "ALTER TABLE {} {};",
+                &escape_db_identifier(table_name),
+                alter
+            )?;
+        }
+        return Ok(false);
+    }
+    let tmp_name = format!("_new_{}", table_name);
+    let mut tmp_table = new_table.clone();
+    tmp_table.name.clone_from(&tmp_name);
+    writeln!(result, "{}", &tmp_table)?;
+    let cols = new_table
+        .columns
+        .iter()
+        .filter(|(name, column)| {
+            !column.has_query()
+                && old_table
+                    .columns
+                    .get(*name)
+                    .map(|v| !v.has_query())
+                    .unwrap_or_default()
+        })
+        .map(|(name, _)| escape_db_identifier(name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if !cols.is_empty() {
+        writeln!(
+            result,
+            "INSERT INTO {} ({}) SELECT {} FROM {};",
+            &escape_db_identifier(&tmp_name),
+            &cols,
+            &cols,
+            &escape_db_identifier(table_name)
+        )?;
+    }
+    writeln!(result, "DROP TABLE {};", &escape_db_identifier(table_name))?;
+    writeln!(
+        result,
+        "ALTER TABLE {} RENAME TO {};",
+        &escape_db_identifier(&tmp_name),
+        &escape_db_identifier(table_name)
+    )?;
+    for index in new_table.indexes.values() {
+        if let Some(ddl) = sqlite_create_index(table_name, index) {
+            writeln!(result, "{}", ddl)?;
+        }
+    }
+    Ok(true)
+}
+
+fn sqlite_create_index(table_name: &str, index: &TableKey) -> Option<String> {
+    let (unique, index_name, cols) = match index {
+        TableKey::Key(index_name, cols) if !cols.is_empty() => ("", index_name, cols),
+        TableKey::UniqueKey(index_name, cols) => ("UNIQUE ", index_name, cols),
+        _ => return None,
+    };
+    Some(format!(
+        "CREATE {}INDEX {} ON {} ({});",
+        unique,
+        &escape_db_identifier(index_name),
+        &escape_db_identifier(table_name),
+        cols.iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    ))
 }
@@ -0,0 +1,87 @@
+use anyhow::{Result, ensure};
+use askama::Template;
+use std::fs;
+use std::path::Path;
+
+use crate::DB_PATH;
+use crate::common::{ToCase as _, fs_write};
+
+/// SQL file that records the data migration as applied in the tracking table.
+pub(super) fn marker_sql(description: &str) -> (String, String) {
+    (
+        format!("-- Data migration: {}\nSELECT 1;\n", description),
+        "-- The data migration is not reverted.\nSELECT 1;\n".to_string(),
+    )
+}
+
+/// Adds a data migration step to src/data_migrations of the db crate.
+pub(super) fn scaffold(db: &str, version: u64, description: &str) -> Result<()> {
+    let src_path = Path::new(DB_PATH)
+        .join(format!("_{}", db.to_snake()))
+        .join("src");
+    let list_path = src_path.join("data_migrations.rs");
+    ensure!(
+        list_path.exists(),
+        "File not found: {:?}\nRun senax model first.",
+        list_path
+    );
+    let mod_name: String = format!("m{}_{}", version, description.to_snake())
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    #[derive(Template)]
+    #[template(
+        source = r###"
+use anyhow::Result;
+
+#[allow(unused_imports)]
+use crate::repositories;
+use crate::DbConn;
+
+/// @{ description }@
+pub(super) async fn run(conn: &mut DbConn) -> Result<()> {
+    conn.begin().await?;
+    // TODO: Implement the data migration.
+    conn.commit().await?;
+    Ok(())
+}
+"###,
+        ext = "txt",
+        escape = "none"
+    )]
+    pub struct DataMigrationTemplate<'a> {
+        pub description: &'a str,
+    }
+
+    let file_path = src_path
+        .join("data_migrations")
+        .join(format!("{}.rs", mod_name));
+    let tpl = DataMigrationTemplate { description };
+    fs_write(file_path, tpl.render()?.trim_start())?;
+
+    let mut content = fs::read_to_string(&list_path)?.replace("\r\n", "\n");
+    content = content.replace(
+        "// Do not modify this line. (data_migration_mod)",
+        &format!(
+            "mod {};\n// Do not modify this line. (data_migration_mod)",
+            mod_name
+        ),
+    );
+    content = content.replace(
+        "// Do not modify this line. (data_migration_version)",
+        &format!(
+            "{},\n    // Do not modify this line. (data_migration_version)",
+            version
+        ),
+    );
+    content = content.replace(
+        "// Do not modify this line. (data_migration_run)",
+        &format!(
+            "{} => {}::run(conn).await,\n        // Do not modify this line. (data_migration_run)",
+            version, mod_name
+        ),
+    );
+    fs_write(list_path, &*content)?;
+    Ok(())
+}
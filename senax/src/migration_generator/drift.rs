@@ -0,0 +1,203 @@
+use anyhow::Result;
+use derive_more::Display;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::safety::Risk;
+use super::{db_url, make_ddl, make_table_def, normalize_tables};
+use crate::ddl;
+use crate::ddl::table::Table;
+use crate::schema::{self, CONFIG, GROUPS};
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Defined in the schema but not in the database
+    #[display("missing")]
+    Missing,
+    /// Exists in the database but not in the schema
+    #[display("extra")]
+    Extra,
+    #[display("different")]
+    Different,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Drift {
+    pub status: Status,
+    pub kind: String,
+    pub table: String,
+    pub target: String,
+    /// Risk of the migration that would apply the schema to the database
+    pub risk: Risk,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    db: &'a str,
+    drifts: &'a [Drift],
+}
+
+/// Compares the schema with the live database and prints the differences.
+/// Returns the number of the differences.
+pub async fn check(db: &str, use_test_db: bool, json: bool) -> Result<usize> {
+    schema::parse(db, false, false)?;
+    let config = CONFIG.read().unwrap().as_ref().unwrap().clone();
+    let mut new_tables = IndexMap::new();
+    {
+        let group_lock = GROUPS.read().unwrap();
+        let groups = group_lock.as_ref().unwrap();
+        for defs in groups.values() {
+            for def in defs.values() {
+                if def.has_table() {
+                    let (table_name, table, _) = make_table_def(def, &config)?;
+                    new_tables.insert(table_name, table);
+                }
+            }
+        }
+    }
+    let expected = expected_tables(&new_tables);
+    let actual = ddl::table::parse(&db_url(db, use_test_db)?).await?;
+    let (_, _, changes) = make_ddl(expected.clone(), actual.clone(), false, false)?;
+
+    let mut drifts = Vec::new();
+    for change in changes {
+        let status = match change.kind.as_str() {
+            "RebuildTable" => continue,
+            kind if kind.starts_with("Add") => Status::Missing,
+            kind if kind.starts_with("Drop") => Status::Extra,
+            _ => Status::Different,
+        };
+        let targets: Vec<String> = if change.target.is_empty() {
+            vec![String::new()]
+        } else {
+            change.target.split(", ").map(|v| v.to_string()).collect()
+        };
+        for target in targets {
+            drifts.push(Drift {
+                status,
+                kind: change.kind.clone(),
+                table: change.table.clone(),
+                risk: change.risk,
+                expected: describe(expected.get(&change.table), &change.kind, &target),
+                actual: describe(actual.get(&change.table), &change.kind, &target),
+                target,
+            });
+        }
+    }
+    drifts.sort_by(|a, b| (&a.table, &a.target).cmp(&(&b.table, &b.target)));
+
+    if json {
+        let report = Report {
+            db,
+            drifts: &drifts,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else if drifts.is_empty() {
+        println!("-- no schema drift: {}", db);
+    } else {
+        println!("-- schema drift: {}", db);
+        for drift in &drifts {
+            let target = if drift.target.is_empty() {
+                drift.table.clone()
+            } else {
+                format!("{}.{}", drift.table, drift.target)
+            };
+            println!(
+                "{:<9} {:<40} {} ({})",
+                drift.status, target, drift.kind, drift.risk
+            );
+            if let Some(expected) = &drift.expected {
+                println!("    schema:   {}", expected);
+            }
+            if let Some(actual) = &drift.actual {
+                println!("    database: {}", actual);
+            }
+        }
+    }
+    Ok(drifts.len())
+}
+
+/// Renames are not applied to the database yet, so they are compared by the current names.
+/// Tables with skip_ddl are kept so that they are not reported as extra.
+fn expected_tables(new_tables: &IndexMap<String, Table>) -> IndexMap<String, Table> {
+    let mut tables = normalize_tables(new_tables);
+    for (table_name, new_table) in new_tables {
+        if new_table.skip_ddl {
+            tables.insert(table_name.clone(), new_table.clone());
+        }
+    }
+    tables
+}
+
+fn describe(table: Option<&Table>, kind: &str, target: &str) -> Option<String> {
+    let table = table?;
+    if kind.ends_with("Column") {
+        table.columns.get(target).map(|c| c.to_string())
+    } else if kind.ends_with("Index") {
+        table.indexes.get(target).map(|i| i.to_string())
+    } else if kind.ends_with("Foreign") {
+        table.constraints.get(target).map(|c| c.to_string())
+    } else if kind.ends_with("Primary") {
+        table.primary.as_ref().map(|v| v.1.to_string())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ddl::sql_type::SqlType;
+    use crate::ddl::table::{Column, Constraint};
+
+    fn table(name: &str, skip_ddl: bool) -> Table {
+        let mut table = Table {
+            name: name.to_string(),
+            skip_ddl,
+            ..Default::default()
+        };
+        table.columns.insert(
+            "id".to_string(),
+            Column {
+                old_name: None,
+                sql_type: SqlType::Int,
+                alt_type: SqlType::Int,
+                constraint: Constraint::default(),
+                default: None,
+                comment: None,
+            },
+        );
+        table
+    }
+
+    #[test]
+    fn skip_ddl() -> Result<()> {
+        CONFIG
+            .write()
+            .unwrap()
+            .get_or_insert_with(schema::ConfigDef::default);
+        let mut new_tables = IndexMap::new();
+        new_tables.insert("users".to_string(), table("users", false));
+        new_tables.insert("legacy".to_string(), table("legacy", true));
+        let actual = expected_tables(&new_tables);
+        assert!(actual.contains_key("legacy"));
+        let (_, _, changes) = make_ddl(expected_tables(&new_tables), actual.clone(), false, false)?;
+        assert!(changes.is_empty());
+
+        let mut missing = actual.clone();
+        missing.shift_remove("legacy");
+        let (_, _, changes) = make_ddl(expected_tables(&new_tables), missing, false, false)?;
+        assert!(changes.is_empty());
+
+        let mut extra = actual;
+        extra.insert("old".to_string(), table("old", false));
+        let (_, _, changes) = make_ddl(expected_tables(&new_tables), extra, false, false)?;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, "DropTable");
+        assert_eq!(changes[0].table, "old");
+        Ok(())
+    }
+}
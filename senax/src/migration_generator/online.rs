@@ -0,0 +1,446 @@
+use anyhow::Result;
+use indexmap::IndexMap;
+use std::fmt::Write;
+
+use crate::common::escape_db_identifier;
+use crate::ddl::sql_type::{SqlType, TableKey};
+use crate::ddl::table::{Column, Table};
+
+use super::UTF8_BYTE_LEN;
+
+pub const INPLACE: &str = ", ALGORITHM=INPLACE, LOCK=NONE";
+const INPLACE_SHARED: &str = ", ALGORITHM=INPLACE, LOCK=SHARED";
+const CHUNK_SIZE: u32 = 10000;
+
+/// Lock clause for adding an index online.
+pub fn index_clause(index: &TableKey) -> &'static str {
+    match index {
+        TableKey::FulltextKey(..) | TableKey::GeometryKey(..) => INPLACE_SHARED,
+        _ => INPLACE,
+    }
+}
+
+/// Whether MySQL can add the column without copying the table.
+pub fn is_inplace_add(new: &Column) -> bool {
+    !new.constraint.auto_increment && !matches!(new.constraint.query, Some((_, true)))
+}
+
+/// Whether MySQL can change the column without copying the table.
+pub fn is_inplace_change(old: &Column, new: &Column) -> bool {
+    if old.constraint.auto_increment != new.constraint.auto_increment
+        || old.constraint.query.is_some()
+        || new.constraint.query.is_some()
+    {
+        return false;
+    }
+    if new.is_same_type(old) {
+        return true;
+    }
+    if old.constraint.collation != new.constraint.collation {
+        return false;
+    }
+    match (&old.sql_type, &new.sql_type) {
+        (SqlType::Varchar(old_len), SqlType::Varchar(new_len)) => {
+            // The length prefix must stay the same size.
+            new_len >= old_len && (old_len * UTF8_BYTE_LEN < 256) == (new_len * UTF8_BYTE_LEN < 256)
+        }
+        (SqlType::Enum(old_values), SqlType::Enum(new_values))
+        | (SqlType::Set(old_values), SqlType::Set(new_values)) => {
+            // Only appending members keeps the storage format.
+            new_values.len() >= old_values.len()
+                && new_values.iter().zip(old_values).all(|(a, b)| a == b)
+                && (old_values.len() < 256) == (new_values.len() < 256)
+        }
+        _ => false,
+    }
+}
+
+/// Whether MySQL has to copy the table to apply the changes.
+pub fn needs_copy(old_table: &Table, new_table: &Table) -> bool {
+    let columns = new_table
+        .columns
+        .iter()
+        .any(|(name, new)| match old_table.columns.get(name) {
+            Some(old) => new != old && !is_inplace_change(old, new),
+            None => !is_inplace_add(new),
+        });
+    let engine = new_table.engine.as_ref().is_some_and(|engine| {
+        old_table
+            .engine
+            .as_ref()
+            .map(|v| !v.eq_ignore_ascii_case(engine))
+            .unwrap_or(true)
+    });
+    columns || engine
+}
+
+/// Whether the table can be altered through a shadow table.
+/// Rows are identified by the primary key, so it must exist and stay the same.
+pub fn can_copy_and_swap(
+    table_name: &str,
+    new_table: &Table,
+    old_table: &Table,
+    tables: &IndexMap<String, Table>,
+) -> bool {
+    matches!(&old_table.primary, Some((_, TableKey::PrimaryKey(_))))
+        && new_table.primary.as_ref().map(|v| &v.1) == old_table.primary.as_ref().map(|v| &v.1)
+        && !is_referenced(table_name, tables)
+}
+
+/// Index changes applied to the shadow table before the backfill,
+/// so that they also cover the columns added in the same migration.
+pub fn index_changes(old_table: &Table, new_table: &Table) -> Vec<String> {
+    let is_empty_key = |index: &TableKey| matches!(index, TableKey::Key(_, x) if x.is_empty());
+    let mut list = Vec::new();
+    for (name, old_index) in &old_table.indexes {
+        match new_table.indexes.get(name) {
+            Some(index) if index == old_index || is_empty_key(index) => {}
+            _ => list.push(format!("DROP INDEX {}", escape_db_identifier(name))),
+        }
+    }
+    for (name, index) in &new_table.indexes {
+        if !is_empty_key(index) && old_table.indexes.get(name) != Some(index) {
+            list.push(format!("ADD {}", index));
+        }
+    }
+    list
+}
+
+/// Whether another table has a foreign key referencing the table.
+pub fn is_referenced(table_name: &str, tables: &IndexMap<String, Table>) -> bool {
+    tables.iter().any(|(name, table)| {
+        name != table_name
+            && table.constraints.values().any(
+                |c| matches!(c, TableKey::Constraint(_, _, ref_table, ..) if ref_table == table_name),
+            )
+    })
+}
+
+/// Shadow-table copy-and-swap migration split into three files:
+/// creating the shadow table with triggers, backfilling in chunks, and swapping.
+/// The swap adds all foreign keys of the new table.
+/// Returns None when the table has no primary key to identify rows.
+pub fn copy_and_swap(
+    table_name: &str,
+    new_table: &Table,
+    old_table: &Table,
+    alter_columns: &[String],
+) -> Result<Option<Vec<String>>> {
+    let Some((_, TableKey::PrimaryKey(old_primary))) = &old_table.primary else {
+        return Ok(None);
+    };
+    if new_table.primary.as_ref().map(|v| &v.1) != old_table.primary.as_ref().map(|v| &v.1) {
+        return Ok(None);
+    }
+    let table = escape_db_identifier(table_name);
+    let new = escape_db_identifier(&format!("_new_{}", table_name));
+    let old = escape_db_identifier(&format!("_old_{}", table_name));
+    let trigger = |suffix: &str| escape_db_identifier(&format!("_{}_{}", table_name, suffix));
+    let columns: Vec<_> = new_table
+        .columns
+        .iter()
+        .filter(|(name, column)| !column.has_query() && old_table.columns.contains_key(*name))
+        .map(|(name, _)| escape_db_identifier(name))
+        .collect();
+    let column_list = columns.join(", ");
+    let values = |prefix: &str| {
+        columns
+            .iter()
+            .map(|c| format!("{prefix}.{c}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let primary_cond = |prefix: &str| {
+        old_primary
+            .iter()
+            .map(|c| {
+                let c = escape_db_identifier(&c.name);
+                format!("{c} = {prefix}.{c}")
+            })
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    };
+
+    let mut create = String::new();
+    writeln!(&mut create, "CREATE TABLE {new} LIKE {table};")?;
+    if !alter_columns.is_empty() {
+        writeln!(
+            &mut create,
+            "ALTER TABLE {new} {};",
+            alter_columns.join(", ")
+        )?;
+    }
+    writeln!(
+        &mut create,
+        "CREATE TRIGGER {} AFTER INSERT ON {table} FOR EACH ROW REPLACE INTO {new} ({column_list}) VALUES ({});",
+        trigger("ins"),
+        values("NEW")
+    )?;
+    writeln!(
+        &mut create,
+        "CREATE TRIGGER {} AFTER UPDATE ON {table} FOR EACH ROW BEGIN DELETE FROM {new} WHERE {}; REPLACE INTO {new} ({column_list}) VALUES ({}); END;",
+        trigger("upd"),
+        primary_cond("OLD"),
+        values("NEW")
+    )?;
+    writeln!(
+        &mut create,
+        "CREATE TRIGGER {} AFTER DELETE ON {table} FOR EACH ROW DELETE FROM {new} WHERE {};",
+        trigger("del"),
+        primary_cond("OLD")
+    )?;
+
+    let mut backfill = String::new();
+    let int_key = match old_primary.as_slice() {
+        [key] => old_table.columns.get(&key.name).filter(|c| {
+            matches!(
+                c.sql_type,
+                SqlType::Int
+                    | SqlType::UnsignedInt
+                    | SqlType::Bigint
+                    | SqlType::UnsignedBigint
+                    | SqlType::Smallint
+                    | SqlType::UnsignedSmallint
+                    | SqlType::Tinyint
+                    | SqlType::UnsignedTinyint
+            )
+        }),
+        _ => None,
+    };
+    if int_key.is_some() {
+        let key = escape_db_identifier(&old_primary[0].name);
+        let procedure = escape_db_identifier(&format!("_backfill_{}", table_name));
+        writeln!(&mut backfill, "DROP PROCEDURE IF EXISTS {procedure};")?;
+        writeln!(&mut backfill, "CREATE PROCEDURE {procedure}() BEGIN")?;
+        writeln!(&mut backfill, "  DECLARE cur BIGINT DEFAULT 0;")?;
+        writeln!(&mut backfill, "  DECLARE max_id BIGINT DEFAULT 0;")?;
+        writeln!(
+            &mut backfill,
+            "  SELECT COALESCE(MIN({key}), 1) - 1, COALESCE(MAX({key}), 0) INTO cur, max_id FROM {table};"
+        )?;
+        writeln!(&mut backfill, "  WHILE cur < max_id DO")?;
+        writeln!(
+            &mut backfill,
+            "    INSERT IGNORE INTO {new} ({column_list}) SELECT {column_list} FROM {table} WHERE {key} > cur AND {key} <= cur + {CHUNK_SIZE};"
+        )?;
+        writeln!(&mut backfill, "    SET cur = cur + {CHUNK_SIZE};")?;
+        writeln!(&mut backfill, "  END WHILE;")?;
+        writeln!(&mut backfill, "END;")?;
+        writeln!(&mut backfill, "CALL {procedure}();")?;
+        writeln!(&mut backfill, "DROP PROCEDURE {procedure};")?;
+    } else {
+        // Without an integer key, the rows are copied in one statement.
+        writeln!(
+            &mut backfill,
+            "INSERT IGNORE INTO {new} ({column_list}) SELECT {column_list} FROM {table};"
+        )?;
+    }
+
+    let mut swap = String::new();
+    writeln!(
+        &mut swap,
+        "RENAME TABLE {table} TO {old}, {new} TO {table};"
+    )?;
+    for suffix in ["ins", "upd", "del"] {
+        writeln!(&mut swap, "DROP TRIGGER IF EXISTS {};", trigger(suffix))?;
+    }
+    writeln!(&mut swap, "DROP TABLE {old};")?;
+    if !new_table.constraints.is_empty() {
+        // CREATE TABLE ... LIKE does not copy foreign keys.
+        writeln!(&mut swap, "SET foreign_key_checks = 0;")?;
+        for constraint in new_table.constraints.values() {
+            writeln!(&mut swap, "ALTER TABLE {table} ADD {constraint};")?;
+        }
+        writeln!(&mut swap, "SET foreign_key_checks = 1;")?;
+    }
+    Ok(Some(vec![create, backfill, swap]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ddl::sql_type::IndexColumn;
+    use crate::ddl::table::Constraint;
+
+    fn column(sql_type: SqlType, query: Option<&str>) -> Column {
+        Column {
+            old_name: None,
+            sql_type: sql_type.clone(),
+            alt_type: sql_type,
+            constraint: Constraint {
+                not_null: true,
+                query: query.map(|v| (v.to_string(), true)),
+                ..Default::default()
+            },
+            default: None,
+            comment: None,
+        }
+    }
+
+    fn key(names: &[&str]) -> Vec<IndexColumn> {
+        names
+            .iter()
+            .map(|name| IndexColumn {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn table(primary: &[&str], columns: &[(&str, SqlType)]) -> Table {
+        let mut table = Table {
+            name: "post".to_string(),
+            primary: Some(("PRIMARY".to_string(), TableKey::PrimaryKey(key(primary)))),
+            ..Default::default()
+        };
+        for (name, sql_type) in columns {
+            table
+                .columns
+                .insert(name.to_string(), column(sql_type.clone(), None));
+        }
+        table
+    }
+
+    /// Returns the position of each line in the SQL, failing when a line is missing.
+    fn positions(sql: &str, lines: &[&str]) -> Vec<usize> {
+        lines
+            .iter()
+            .map(|line| {
+                sql.find(line)
+                    .unwrap_or_else(|| panic!("{line} is not found in:\n{sql}"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn copy_and_swap_int_key() {
+        let old_table = table(&["id"], &[("id", SqlType::Int), ("name", SqlType::Text)]);
+        let mut new_table = old_table.clone();
+        new_table
+            .columns
+            .insert("body".to_string(), column(SqlType::Text, None));
+        new_table.columns.insert(
+            "len".to_string(),
+            column(SqlType::Int, Some("length(`name`)")),
+        );
+        new_table.constraints.insert(
+            "fk_post_user".to_string(),
+            TableKey::Constraint(
+                "fk_post_user".to_string(),
+                key(&["id"]),
+                "user".to_string(),
+                key(&["id"]),
+                None,
+                None,
+            ),
+        );
+        let alter = vec!["ADD COLUMN `body` TEXT NOT NULL".to_string()];
+        let files = copy_and_swap("post", &new_table, &old_table, &alter)
+            .unwrap()
+            .unwrap();
+        assert_eq!(files.len(), 3);
+
+        let create = &files[0];
+        let pos = positions(
+            create,
+            &[
+                "CREATE TABLE `_new_post` LIKE `post`;",
+                "ALTER TABLE `_new_post` ADD COLUMN `body` TEXT NOT NULL;",
+                "CREATE TRIGGER `_post_ins` AFTER INSERT ON `post` FOR EACH ROW REPLACE INTO `_new_post` (`id`, `name`) VALUES (NEW.`id`, NEW.`name`);",
+                "CREATE TRIGGER `_post_upd` AFTER UPDATE ON `post` FOR EACH ROW BEGIN DELETE FROM `_new_post` WHERE `id` = OLD.`id`; REPLACE INTO `_new_post` (`id`, `name`) VALUES (NEW.`id`, NEW.`name`); END;",
+                "CREATE TRIGGER `_post_del` AFTER DELETE ON `post` FOR EACH ROW DELETE FROM `_new_post` WHERE `id` = OLD.`id`;",
+            ],
+        );
+        assert!(pos.is_sorted());
+
+        let backfill = &files[1];
+        let pos = positions(
+            backfill,
+            &[
+                "CREATE PROCEDURE `_backfill_post`() BEGIN",
+                "SELECT COALESCE(MIN(`id`), 1) - 1, COALESCE(MAX(`id`), 0) INTO cur, max_id FROM `post`;",
+                "INSERT IGNORE INTO `_new_post` (`id`, `name`) SELECT `id`, `name` FROM `post` WHERE `id` > cur AND `id` <= cur + 10000;",
+                "CALL `_backfill_post`();",
+                "DROP PROCEDURE `_backfill_post`;",
+            ],
+        );
+        assert!(pos.is_sorted());
+
+        let swap = &files[2];
+        let pos = positions(
+            swap,
+            &[
+                "RENAME TABLE `post` TO `_old_post`, `_new_post` TO `post`;",
+                "DROP TRIGGER IF EXISTS `_post_ins`;",
+                "DROP TRIGGER IF EXISTS `_post_upd`;",
+                "DROP TRIGGER IF EXISTS `_post_del`;",
+                "DROP TABLE `_old_post`;",
+                "SET foreign_key_checks = 0;",
+                "ALTER TABLE `post` ADD CONSTRAINT `fk_post_user` FOREIGN KEY",
+                "SET foreign_key_checks = 1;",
+            ],
+        );
+        assert!(pos.is_sorted());
+    }
+
+    #[test]
+    fn copy_and_swap_composite_key() {
+        let old_table = table(
+            &["user_id", "code"],
+            &[("user_id", SqlType::Int), ("code", SqlType::Varchar(10))],
+        );
+        let files = copy_and_swap("post", &old_table, &old_table, &[])
+            .unwrap()
+            .unwrap();
+        assert!(!files[0].contains("ALTER TABLE"));
+        assert!(files[0].contains("WHERE `user_id` = OLD.`user_id` AND `code` = OLD.`code`;"));
+        // The rows are copied in one statement without an integer key.
+        assert_eq!(
+            files[1],
+            "INSERT IGNORE INTO `_new_post` (`user_id`, `code`) SELECT `user_id`, `code` FROM `post`;\n"
+        );
+        assert!(!files[2].contains("foreign_key_checks"));
+    }
+
+    #[test]
+    fn copy_and_swap_needs_same_primary_key() {
+        let old_table = table(&["id"], &[("id", SqlType::Int), ("no", SqlType::Int)]);
+        let new_table = table(&["no"], &[("id", SqlType::Int), ("no", SqlType::Int)]);
+        assert!(
+            copy_and_swap("post", &new_table, &old_table, &[])
+                .unwrap()
+                .is_none()
+        );
+        let mut no_primary = old_table.clone();
+        no_primary.primary = None;
+        assert!(
+            copy_and_swap("post", &no_primary, &no_primary, &[])
+                .unwrap()
+                .is_none()
+        );
+
+        let mut tables = IndexMap::new();
+        tables.insert("post".to_string(), old_table.clone());
+        assert!(can_copy_and_swap("post", &old_table, &old_table, &tables));
+        let mut comment = table(&["id"], &[("id", SqlType::Int)]);
+        comment.constraints.insert(
+            "fk_comment_post".to_string(),
+            TableKey::Constraint(
+                "fk_comment_post".to_string(),
+                key(&["id"]),
+                "post".to_string(),
+                key(&["id"]),
+                None,
+                None,
+            ),
+        );
+        tables.insert("comment".to_string(), comment);
+        assert!(!can_copy_and_swap("post", &old_table, &old_table, &tables));
+        assert!(!can_copy_and_swap(
+            "post",
+            &new_table,
+            &old_table,
+            &IndexMap::new()
+        ));
+    }
+}
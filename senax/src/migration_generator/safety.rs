@@ -0,0 +1,445 @@
+use derive_more::Display;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use super::Type;
+use crate::ddl::sql_type::SqlType;
+use crate::ddl::table::{Column, Table};
+use crate::schema::{SoftDelete, is_mysql_mode, is_sqlite_mode};
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Risk {
+    #[display("safe")]
+    Safe,
+    #[display("locking")]
+    Locking,
+    #[display("data-losing")]
+    DataLosing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Change {
+    pub kind: String,
+    pub risk: Risk,
+    pub table: String,
+    pub target: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let target = if self.target.is_empty() {
+            self.table.clone()
+        } else {
+            format!("{}.{}", self.table, self.target)
+        };
+        write!(f, "{:<11} {:<40} {}", self.risk, target, self.reason)
+    }
+}
+
+/// Classifies the changes recorded by make_ddl.
+/// Both table maps must already have renames applied.
+pub(super) fn classify(
+    history: &BTreeMap<Type, IndexMap<String, Vec<String>>>,
+    new_tables: &IndexMap<String, Table>,
+    old_tables: &IndexMap<String, Table>,
+    rebuilt_tables: &HashSet<String>,
+) -> Vec<Change> {
+    let mut result = Vec::new();
+    let mut push = |kind: &str, risk: Risk, table: &str, target: &str, reason: &str| {
+        result.push(Change {
+            kind: kind.to_string(),
+            risk,
+            table: table.to_string(),
+            target: target.to_string(),
+            reason: reason.to_string(),
+        });
+    };
+    for (table_name, new_table) in new_tables {
+        if new_table.skip_ddl || !old_tables.contains_key(table_name) {
+            continue;
+        }
+        if let Some((name, typ)) = &new_table.old_soft_delete
+            && *typ != SoftDelete::None
+        {
+            push(
+                "DeleteSoftDeleted",
+                Risk::DataLosing,
+                table_name,
+                name,
+                "soft-deleted rows are deleted",
+            );
+        }
+    }
+    for (typ, tables) in history {
+        for (table_name, targets) in tables {
+            let new_table = new_tables.get(table_name);
+            let old_table = old_tables.get(table_name);
+            let kind = typ.to_string();
+            match typ {
+                Type::AddTable | Type::RenameTable | Type::RenameColumn | Type::DropIndex => {
+                    push(
+                        &kind,
+                        Risk::Safe,
+                        table_name,
+                        &targets.join(", "),
+                        &typ.to_string(),
+                    );
+                }
+                Type::DropTable => {
+                    push(&kind, Risk::DataLosing, table_name, "", "table is dropped");
+                }
+                Type::DropColumn => {
+                    for name in targets {
+                        push(
+                            &kind,
+                            Risk::DataLosing,
+                            table_name,
+                            name,
+                            "column is dropped",
+                        );
+                    }
+                }
+                Type::AddColumn => {
+                    for name in targets {
+                        let column = new_table.and_then(|t| t.columns.get(name));
+                        if column.is_some_and(|c| {
+                            c.constraint.not_null
+                                && c.default.is_none()
+                                && !c.constraint.auto_increment
+                                && !c.has_query()
+                        }) {
+                            push(
+                                &kind,
+                                Risk::Locking,
+                                table_name,
+                                name,
+                                "NOT NULL column without default is added",
+                            );
+                        } else {
+                            push(&kind, Risk::Safe, table_name, name, "column is added");
+                        }
+                    }
+                }
+                Type::ChangeColumn => {
+                    for name in targets {
+                        let new_column = new_table.and_then(|t| t.columns.get(name));
+                        let old_column = old_table.and_then(|t| t.columns.get(name));
+                        let (risk, reason) = match (old_column, new_column) {
+                            (Some(old), Some(new)) => classify_column(old, new),
+                            _ => (Risk::Locking, "column is changed"),
+                        };
+                        push(&kind, risk, table_name, name, reason);
+                    }
+                }
+                Type::ChangePrimary | Type::DropPrimary => {
+                    push(
+                        &kind,
+                        Risk::Locking,
+                        table_name,
+                        &targets.join(", "),
+                        "primary key is rebuilt",
+                    );
+                }
+                Type::AddIndex | Type::ChangeIndex => {
+                    for name in targets {
+                        if is_mysql_mode() || is_sqlite_mode() {
+                            push(&kind, Risk::Locking, table_name, name, "index is built");
+                        } else {
+                            push(
+                                &kind,
+                                Risk::Safe,
+                                table_name,
+                                name,
+                                "index is built concurrently",
+                            );
+                        }
+                    }
+                }
+                Type::AddForeign | Type::ChangeForeign => {
+                    for name in targets {
+                        push(
+                            &kind,
+                            Risk::Locking,
+                            table_name,
+                            name,
+                            "foreign key validates all rows",
+                        );
+                    }
+                }
+                Type::DropForeign => {
+                    for name in targets {
+                        push(
+                            &kind,
+                            Risk::Safe,
+                            table_name,
+                            name,
+                            "foreign key is dropped",
+                        );
+                    }
+                }
+            }
+        }
+    }
+    for table_name in rebuilt_tables {
+        push(
+            "RebuildTable",
+            Risk::Locking,
+            table_name,
+            "",
+            "table is rebuilt",
+        );
+    }
+    result.sort_by_key(|c| std::cmp::Reverse(c.risk));
+    result
+}
+
+fn classify_column(old: &Column, new: &Column) -> (Risk, &'static str) {
+    if !new.is_same_type(old) {
+        if old.constraint.collation != new.constraint.collation && old.sql_type == new.sql_type {
+            return (Risk::Locking, "collation is changed");
+        }
+        if is_narrowing(&old.sql_type, &new.sql_type) {
+            return (Risk::DataLosing, "type is narrowed");
+        }
+        return (Risk::Locking, "type is changed");
+    }
+    if !old.constraint.not_null && new.constraint.not_null {
+        return (Risk::Locking, "NOT NULL is added");
+    }
+    (Risk::Safe, "column attributes are changed")
+}
+
+enum Family {
+    Int(u8, bool),
+    Real(u8),
+    Decimal(u16, u16),
+    Str(u64),
+    Bin(u64),
+    Time(u8, u16),
+    Json,
+    Other,
+}
+
+fn family(typ: &SqlType) -> Family {
+    match typ {
+        SqlType::Bool => Family::Int(0, false),
+        SqlType::Tinyint => Family::Int(1, true),
+        SqlType::UnsignedTinyint => Family::Int(1, false),
+        SqlType::Smallint => Family::Int(2, true),
+        SqlType::UnsignedSmallint => Family::Int(2, false),
+        SqlType::Int => Family::Int(4, true),
+        SqlType::UnsignedInt => Family::Int(4, false),
+        SqlType::Bigint => Family::Int(8, true),
+        SqlType::UnsignedBigint => Family::Int(8, false),
+        SqlType::Float | SqlType::Real => Family::Real(4),
+        SqlType::Double => Family::Real(8),
+        SqlType::Decimal(p, s) => Family::Decimal(*p, *s),
+        SqlType::Char(len) | SqlType::Varchar(len) => Family::Str(*len as u64),
+        SqlType::Tinytext => Family::Str(u8::MAX as u64),
+        SqlType::Text => Family::Str(u16::MAX as u64),
+        SqlType::Mediumtext => Family::Str(16_777_215),
+        SqlType::Longtext => Family::Str(u32::MAX as u64),
+        SqlType::Binary(len) | SqlType::Varbinary(len) => Family::Bin(*len as u64),
+        SqlType::Tinyblob => Family::Bin(u8::MAX as u64),
+        SqlType::Blob => Family::Bin(u16::MAX as u64),
+        SqlType::Mediumblob => Family::Bin(16_777_215),
+        SqlType::Longblob => Family::Bin(u32::MAX as u64),
+        SqlType::Date => Family::Time(0, 0),
+        SqlType::Time => Family::Time(1, 0),
+        SqlType::Timestamp(p) => Family::Time(2, *p),
+        SqlType::DateTime(p) => Family::Time(3, *p),
+        SqlType::Json | SqlType::Jsonb => Family::Json,
+        _ => Family::Other,
+    }
+}
+
+/// Whether the new type cannot hold every value of the old type.
+fn is_narrowing(old: &SqlType, new: &SqlType) -> bool {
+    match (family(old), family(new)) {
+        (Family::Int(old_size, old_signed), Family::Int(new_size, new_signed)) => {
+            if old_signed && !new_signed && old_size > 0 {
+                return true;
+            }
+            if !old_signed && new_signed {
+                return new_size <= old_size;
+            }
+            new_size < old_size
+        }
+        (Family::Int(size, _), Family::Real(real)) => size > real / 2,
+        (Family::Int(size, signed), Family::Decimal(p, s)) => {
+            let digits = match size {
+                0 => 1,
+                1 => 3,
+                2 => 5,
+                4 => 10,
+                _ => 20 - signed as u16,
+            };
+            p.saturating_sub(s) < digits
+        }
+        (Family::Real(old_size), Family::Real(new_size)) => new_size < old_size,
+        (Family::Decimal(old_p, old_s), Family::Decimal(new_p, new_s)) => {
+            new_s < old_s || new_p.saturating_sub(new_s) < old_p.saturating_sub(old_s)
+        }
+        (Family::Decimal(_, _), Family::Real(_)) => true,
+        (Family::Str(old_len), Family::Str(new_len)) => new_len < old_len,
+        (Family::Bin(old_len), Family::Bin(new_len)) => new_len < old_len,
+        (Family::Time(old_kind, old_p), Family::Time(new_kind, new_p)) => {
+            match (old_kind, new_kind) {
+                (0, 2 | 3) => false,
+                (2, 3) => new_p < old_p,
+                (a, b) if a == b => new_p < old_p,
+                _ => true,
+            }
+        }
+        (Family::Json, Family::Json) => false,
+        (Family::Json, Family::Str(len)) => len < u32::MAX as u64,
+        (Family::Other, Family::Other) => match (old, new) {
+            (SqlType::Enum(old), SqlType::Enum(new)) | (SqlType::Set(old), SqlType::Set(new)) => {
+                old.iter().any(|v| !new.contains(v))
+            }
+            (SqlType::Uuid, SqlType::Uuid) => false,
+            _ => old != new,
+        },
+        (_, Family::Str(len)) => len < 20,
+        _ => true,
+    }
+}
+
+pub(super) fn print_report(db: &str, changes: &[Change]) {
+    if changes.iter().all(|c| c.risk == Risk::Safe) {
+        return;
+    }
+    eprintln!("-- migration safety report: {}", db);
+    for change in changes {
+        eprintln!("{}", change);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ddl::sql_type::Literal;
+    use crate::ddl::table::Constraint;
+
+    fn lit(v: &str) -> Literal {
+        Literal::String(v.to_string())
+    }
+
+    fn column(sql_type: SqlType) -> Column {
+        Column {
+            old_name: None,
+            sql_type: sql_type.clone(),
+            alt_type: sql_type,
+            constraint: Constraint::default(),
+            default: None,
+            comment: None,
+        }
+    }
+
+    fn table(columns: &[(&str, SqlType)]) -> Table {
+        let mut table = Table::default();
+        for (name, sql_type) in columns {
+            table
+                .columns
+                .insert(name.to_string(), column(sql_type.clone()));
+        }
+        table
+    }
+
+    #[test]
+    fn narrowing() {
+        assert!(is_narrowing(&SqlType::Int, &SqlType::Smallint));
+        assert!(is_narrowing(&SqlType::Int, &SqlType::UnsignedInt));
+        assert!(is_narrowing(&SqlType::UnsignedInt, &SqlType::Int));
+        assert!(!is_narrowing(&SqlType::UnsignedInt, &SqlType::Bigint));
+        assert!(!is_narrowing(&SqlType::Smallint, &SqlType::Int));
+        assert!(is_narrowing(&SqlType::Bigint, &SqlType::Double));
+        assert!(!is_narrowing(&SqlType::Int, &SqlType::Double));
+        assert!(is_narrowing(&SqlType::Int, &SqlType::Decimal(9, 0)));
+        assert!(!is_narrowing(&SqlType::Int, &SqlType::Decimal(10, 0)));
+        assert!(is_narrowing(
+            &SqlType::Decimal(10, 2),
+            &SqlType::Decimal(10, 1)
+        ));
+        assert!(!is_narrowing(
+            &SqlType::Decimal(10, 2),
+            &SqlType::Decimal(12, 2)
+        ));
+        assert!(is_narrowing(&SqlType::Varchar(255), &SqlType::Varchar(100)));
+        assert!(!is_narrowing(&SqlType::Varchar(100), &SqlType::Text));
+        assert!(is_narrowing(&SqlType::Blob, &SqlType::Varbinary(255)));
+        assert!(!is_narrowing(&SqlType::Date, &SqlType::DateTime(0)));
+        assert!(is_narrowing(&SqlType::DateTime(6), &SqlType::DateTime(0)));
+        assert!(is_narrowing(&SqlType::DateTime(0), &SqlType::Date));
+        assert!(!is_narrowing(&SqlType::Int, &SqlType::Varchar(20)));
+        assert!(is_narrowing(&SqlType::Int, &SqlType::Varchar(10)));
+        assert!(is_narrowing(&SqlType::Json, &SqlType::Text));
+        assert!(!is_narrowing(&SqlType::Json, &SqlType::Longtext));
+        assert!(is_narrowing(
+            &SqlType::Enum(vec![lit("a"), lit("b")]),
+            &SqlType::Enum(vec![lit("a")])
+        ));
+        assert!(!is_narrowing(
+            &SqlType::Enum(vec![lit("a")]),
+            &SqlType::Enum(vec![lit("a"), lit("b")])
+        ));
+    }
+
+    #[test]
+    fn classify_changes() {
+        let old_tables: IndexMap<String, Table> = [
+            (
+                "a".to_string(),
+                table(&[
+                    ("id", SqlType::Int),
+                    ("name", SqlType::Varchar(255)),
+                    ("memo", SqlType::Text),
+                ]),
+            ),
+            ("b".to_string(), table(&[("id", SqlType::Int)])),
+        ]
+        .into_iter()
+        .collect();
+        let new_tables: IndexMap<String, Table> = [
+            (
+                "a".to_string(),
+                table(&[("id", SqlType::Bigint), ("name", SqlType::Varchar(100))]),
+            ),
+            ("b".to_string(), table(&[("id", SqlType::Int)])),
+        ]
+        .into_iter()
+        .collect();
+        let mut history: BTreeMap<Type, IndexMap<String, Vec<String>>> = BTreeMap::new();
+        history
+            .entry(Type::ChangeColumn)
+            .or_default()
+            .insert("a".to_string(), vec!["id".to_string(), "name".to_string()]);
+        history
+            .entry(Type::DropColumn)
+            .or_default()
+            .insert("a".to_string(), vec!["memo".to_string()]);
+        let rebuilt_tables: HashSet<String> = ["b".to_string()].into_iter().collect();
+
+        let changes = classify(&history, &new_tables, &old_tables, &rebuilt_tables);
+        let result: Vec<_> = changes
+            .iter()
+            .map(|c| (c.kind.as_str(), c.risk, c.table.as_str(), c.target.as_str()))
+            .collect();
+        assert_eq!(
+            result,
+            vec![
+                ("ChangeColumn", Risk::DataLosing, "a", "name"),
+                ("DropColumn", Risk::DataLosing, "a", "memo"),
+                ("ChangeColumn", Risk::Locking, "a", "id"),
+                ("RebuildTable", Risk::Locking, "b", ""),
+            ]
+        );
+        assert_eq!(changes[0].reason, "type is narrowed");
+        assert_eq!(changes[1].reason, "column is dropped");
+        assert_eq!(changes[2].reason, "type is changed");
+        assert_eq!(changes[3].reason, "table is rebuilt");
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context as _, Result, ensure};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::DB_PATH;
+use crate::common::{ToCase as _, fs_write};
+use crate::schema::{self, is_sqlite_mode};
+
+/// The generated db crate recognizes the baseline by this description
+/// and replaces the records of the squashed migrations with it.
+pub const BASELINE_DESCRIPTION: &str = "squashed_baseline";
+
+struct Migration {
+    up: PathBuf,
+    down: Option<PathBuf>,
+}
+
+/// Replaces the migrations before the timestamp with a single baseline file.
+/// The baseline takes over the version of the last squashed migration.
+pub fn squash(db: &str, before: &str) -> Result<()> {
+    ensure!(
+        !before.is_empty() && before.len() <= 14 && before.chars().all(|c| c.is_ascii_digit()),
+        "The timestamp must be in the form of YYYYMMDDhhmmss."
+    );
+    let before = format!("{:0<14}", before);
+    schema::parse(db, false, true)?;
+    let ddl_path = Path::new(DB_PATH)
+        .join(format!("_{}", db.to_snake()))
+        .join("migrations");
+    let re = Regex::new(r"^(\d{14})_(.+?)(\.up|\.down)?\.sql$").unwrap();
+    let mut migrations: BTreeMap<String, Migration> = BTreeMap::new();
+    let mut downs = BTreeMap::new();
+    for entry in ddl_path
+        .read_dir()
+        .with_context(|| format!("Cannot read directory: {:?}", ddl_path))?
+        .flatten()
+    {
+        let path = entry.path();
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some(caps) = re.captures(&file_name) else {
+            continue;
+        };
+        let version = caps.get(1).unwrap().as_str().to_string();
+        if version >= before {
+            continue;
+        }
+        if caps.get(3).map(|v| v.as_str()) == Some(".down") {
+            downs.insert(version, path);
+        } else {
+            migrations.insert(
+                version,
+                Migration {
+                    up: path,
+                    down: None,
+                },
+            );
+        }
+    }
+    for (version, path) in downs {
+        if let Some(migration) = migrations.get_mut(&version) {
+            migration.down = Some(path);
+        }
+    }
+    ensure!(
+        migrations.len() > 1,
+        "There are no migrations to squash before {}.",
+        before
+    );
+    let first = migrations.keys().next().unwrap().clone();
+    let last = migrations.keys().next_back().unwrap().clone();
+
+    let mut up = String::new();
+    if is_sqlite_mode() {
+        // PRAGMA foreign_keys has no effect inside a transaction.
+        up.push_str("-- no-transaction\n");
+    }
+    writeln!(
+        &mut up,
+        "/* Squashed migrations from {} to {} */",
+        first, last
+    )?;
+    let mut body = String::new();
+    for (version, migration) in &migrations {
+        writeln!(&mut body, "\n/* {} */", version)?;
+        // The baseline runs in a transaction on an empty database.
+        let content = read_sql(&migration.up)?;
+        let content = content
+            .strip_prefix("-- no-transaction\n")
+            .unwrap_or(&content)
+            .replace(" CONCURRENTLY ", " ");
+        body.push_str(&content);
+    }
+    up.push_str(&wrap(body));
+
+    let mut down = String::new();
+    if migrations.values().all(|m| m.down.is_some()) {
+        let mut body = String::new();
+        for (version, migration) in migrations.iter().rev() {
+            writeln!(&mut body, "/* {} */", version)?;
+            let content = read_sql(migration.down.as_ref().unwrap())?;
+            let content = content
+                .strip_prefix("-- no-transaction\n")
+                .unwrap_or(&content)
+                .replace(" CONCURRENTLY ", " ");
+            body.push_str(&content);
+            body.push('\n');
+        }
+        if is_sqlite_mode() {
+            down.push_str("-- no-transaction\n");
+        }
+        down.push_str(&wrap(body));
+    } else {
+        down.push_str("-- TODO: Fix this file.\n");
+    }
+
+    for migration in migrations.values() {
+        fs::remove_file(&migration.up)?;
+        if let Some(path) = &migration.down {
+            fs::remove_file(path)?;
+        }
+    }
+    fs_write(
+        ddl_path.join(format!("{}_{}.up.sql", last, BASELINE_DESCRIPTION)),
+        &up,
+    )?;
+    fs_write(
+        ddl_path.join(format!("{}_{}.down.sql", last, BASELINE_DESCRIPTION)),
+        &down,
+    )?;
+    println!(
+        "{} migrations of {} are squashed into {}_{}.up.sql.",
+        migrations.len(),
+        db,
+        last,
+        BASELINE_DESCRIPTION
+    );
+    let retired = retired_data_migrations(db, &last)?;
+    if !retired.is_empty() {
+        println!(
+            "The data migrations {} are retired by the baseline and no longer run.",
+            retired.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Versions of the data migrations at or below the baseline, which the generated db crate skips
+fn retired_data_migrations(db: &str, last: &str) -> Result<Vec<String>> {
+    let list_path = Path::new(DB_PATH)
+        .join(format!("_{}", db.to_snake()))
+        .join("src")
+        .join("data_migrations.rs");
+    if !list_path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&list_path)?;
+    let re = Regex::new(r"(?m)^\s*(\d{14}),\s*$").unwrap();
+    Ok(re
+        .captures_iter(&content)
+        .map(|caps| caps.get(1).unwrap().as_str().to_string())
+        .filter(|version| version.as_str() <= last)
+        .collect())
+}
+
+/// Each SQLite migration has its own transaction, which cannot be nested.
+/// Replaces them with a single transaction over the whole body.
+fn wrap(body: String) -> String {
+    if !is_sqlite_mode() {
+        return body;
+    }
+    let mut result = String::from("PRAGMA foreign_keys = OFF;\nBEGIN;\n");
+    for line in body.lines() {
+        if !matches!(
+            line,
+            "PRAGMA foreign_keys = OFF;" | "BEGIN;" | "COMMIT;" | "PRAGMA foreign_keys = ON;"
+        ) {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result.push_str("COMMIT;\nPRAGMA foreign_keys = ON;\n");
+    result
+}
+
+fn read_sql(path: &PathBuf) -> Result<String> {
+    let mut content = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read file: {:?}", path))?
+        .replace("\r\n", "\n");
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    Ok(content)
+}
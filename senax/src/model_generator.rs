@@ -200,6 +200,12 @@ pub fn generate(
         fs::read_to_string(&file_path)?.replace("\r\n", "\n")
     };
 
+    if !content.contains("mod data_migrations;") {
+        content = content.replace(
+            "pub mod seeder;\n",
+            "pub mod seeder;\npub mod data_migrations;\n",
+        );
+    }
     let reg = Regex::new(r"(?m)^[ \t]*pub use _repo_\w+::repositories::[#\w]+;\n")?;
     content = reg.replace_all(&content, "").into_owned();
     let reg = Regex::new(
@@ -316,6 +322,15 @@ pub fn generate(
     };
     fs_write(file_path, tpl.render()?)?;
 
+    let file_path = model_src_dir.join("data_migrations.rs");
+    if !file_path.exists() {
+        #[derive(Template)]
+        #[template(path = "db/src/data_migrations.rs", escape = "none")]
+        struct DataMigrationsTemplate {}
+
+        fs_write(file_path, DataMigrationsTemplate {}.render()?)?;
+    }
+
     let path = model_dir.join("migrations");
     if !path.exists() {
         let file_path = path.join(".gitkeep");
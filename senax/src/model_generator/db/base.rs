@@ -102,6 +102,17 @@ pub fn write_files(
     };
     fs_write(file_path, tpl.render()?)?;
 
+    #[derive(Template)]
+    #[template(path = "db/base/src/lock.rs", escape = "none")]
+    struct LockTemplate<'a> {
+        pub db: &'a str,
+        pub config: &'a ConfigDef,
+    }
+
+    let file_path = src_dir.join("lock.rs");
+    let tpl = LockTemplate { db, config };
+    fs_write(file_path, tpl.render()?)?;
+
     let file_path = src_dir.join("models.rs");
 
     #[derive(Template)]
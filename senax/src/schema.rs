@@ -38,6 +38,7 @@ static DELETED: RwLock<CompactString> = RwLock::new(CompactString::const_new("")
 static AGGREGATION_TYPE: RwLock<CompactString> = RwLock::new(CompactString::const_new(""));
 static VERSION: RwLock<CompactString> = RwLock::new(CompactString::const_new(""));
 static MYSQL_MODE: AtomicBool = AtomicBool::new(true);
+static SQLITE_MODE: AtomicBool = AtomicBool::new(false);
 
 pub type GroupsDef = IndexMap<String, IndexMap<String, Arc<ModelDef>>>;
 
@@ -81,6 +82,7 @@ pub fn parse(db: &str, outer_crate: bool, config_only: bool) -> Result<(), anyho
     }
     config.fix_static_vars();
     set_mysql_mode(config.is_mysql());
+    set_sqlite_mode(config.is_sqlite());
 
     for (name, def) in config.groups.iter_mut() {
         if let Some(def) = def
@@ -630,6 +632,20 @@ pub fn parse(db: &str, outer_crate: bool, config_only: bool) -> Result<(), anyho
             }
         }
     }
+    if config.is_sqlite() {
+        for defs in groups.values() {
+            for def in defs.values() {
+                let model = def.borrow();
+                if let Some((name, _)) = model.all_fields_only_geo().first() {
+                    error_exit!(
+                        "The {} field of the {} model has a spatial type, which SQLite does not support.",
+                        name,
+                        model.name
+                    );
+                }
+            }
+        }
+    }
     for (cur_group_name, defs) in groups.iter() {
         for (cur_model_name, def) in defs.iter() {
             let model = def.borrow();
@@ -914,3 +930,11 @@ pub fn set_mysql_mode(is_mysql: bool) {
 pub fn is_mysql_mode() -> bool {
     MYSQL_MODE.load(std::sync::atomic::Ordering::Relaxed)
 }
+
+pub fn set_sqlite_mode(is_sqlite: bool) {
+    SQLITE_MODE.store(is_sqlite, std::sync::atomic::Ordering::SeqCst);
+}
+
+pub fn is_sqlite_mode() -> bool {
+    SQLITE_MODE.load(std::sync::atomic::Ordering::Relaxed)
+}
@@ -12,7 +12,7 @@ use crate::{
     api_generator::schema::ApiFieldDef,
     common::{if_then_else, yaml_value_to_str},
     migration_generator::UTF8_BYTE_LEN,
-    schema::is_mysql_mode,
+    schema::{is_mysql_mode, is_sqlite_mode},
 };
 use crate::{common::ToCase as _, schema::to_id_name_wo_changing_case};
 
@@ -1051,6 +1051,31 @@ impl FieldDef {
                 .or(CONFIG.read().unwrap().as_ref().unwrap().output_time_zone);
         tz == Some(TimeZone::Utc)
     }
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self.data_type,
+            DataType::Char | DataType::IdVarchar | DataType::TextVarchar | DataType::Text
+        )
+    }
+    /// Whether the collation orders text by code point.
+    /// Without a collation, MySQL and PostgreSQL use the database default, which is not assumed to be binary.
+    pub fn is_binary_collation(&self) -> bool {
+        let collation = self.collation.clone().or_else(|| {
+            let config = CONFIG.read().unwrap();
+            let config = config.as_ref().unwrap();
+            if matches!(self.data_type, DataType::Char | DataType::IdVarchar) {
+                config.id_collation.clone()
+            } else {
+                config.text_collation.clone()
+            }
+        });
+        match collation {
+            Some(collation) if is_mysql_mode() => collation.ends_with("_bin"),
+            Some(collation) if is_sqlite_mode() => collation.eq_ignore_ascii_case("BINARY"),
+            Some(collation) => matches!(collation.as_str(), "C" | "POSIX" | "ucs_basic"),
+            None => is_sqlite_mode(),
+        }
+    }
     pub fn is_integer(&self) -> bool {
         self.data_type == DataType::TinyInt
             || self.data_type == DataType::SmallInt
@@ -175,6 +175,10 @@ pub struct ConfigDef {
     /// 明示的にexistsとanyを区別するのではなく、MySQLに任せる場合に enable_semijoin をtureにする。
     #[serde(default, skip_serializing_if = "super::is_false")]
     pub enable_semijoin: bool,
+    /// ### lintルールの重要度
+    /// 未設定のルールはデフォルトの重要度で検査する
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub lint: IndexMap<LintRule, LintLevel>,
     /// ### モデルグループ
     pub groups: IndexMap<String, Option<GroupDef>>,
 }
@@ -344,6 +348,10 @@ pub struct ConfigJson {
     /// 明示的にexistsとanyを区別するのではなく、MySQLに任せる場合に enable_semijoin をtureにする。
     #[serde(default, skip_serializing_if = "super::is_false")]
     pub enable_semijoin: bool,
+    /// ### lintルールの重要度
+    /// 未設定のルールはデフォルトの重要度で検査する
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub lint: IndexMap<LintRule, LintLevel>,
     /// ### モデルグループ
     pub groups: Vec<GroupJson>,
 }
@@ -404,6 +412,7 @@ impl From<ConfigDef> for ConfigJson {
             rename_version: value.rename_version,
             label_of_version: value.label_of_version,
             enable_semijoin: value.enable_semijoin,
+            lint: value.lint,
             groups: value
                 .groups
                 .into_iter()
@@ -474,6 +483,7 @@ impl From<ConfigJson> for ConfigDef {
             rename_version: value.rename_version,
             label_of_version: value.label_of_version,
             enable_semijoin: value.enable_semijoin,
+            lint: value.lint,
             groups: value
                 .groups
                 .into_iter()
@@ -492,6 +502,13 @@ impl From<ConfigJson> for ConfigDef {
 }
 
 impl ConfigDef {
+    pub fn lint_level(&self, rule: LintRule) -> LintLevel {
+        self.lint
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_level())
+    }
+
     pub fn fix_static_vars(&self) {
         let mut v = CREATED_AT.write().unwrap();
         v.clear();
@@ -530,6 +547,7 @@ impl ConfigDef {
         match self.db {
             DbType::Mysql => "MySql",
             DbType::Postgres => "Pg",
+            DbType::Sqlite => "Sqlite",
         }
     }
 
@@ -537,13 +555,14 @@ impl ConfigDef {
         match self.db {
             DbType::Mysql => "MySql",
             DbType::Postgres => "Postgres",
+            DbType::Sqlite => "Sqlite",
         }
     }
 
     pub fn db_type_switch(&self, mysql: &'static str, pgsql: &'static str) -> &'static str {
         match self.db {
             DbType::Mysql => mysql,
-            DbType::Postgres => pgsql,
+            DbType::Postgres | DbType::Sqlite => pgsql,
         }
     }
 
@@ -551,10 +570,19 @@ impl ConfigDef {
         self.db == DbType::Mysql
     }
 
+    pub fn is_sqlite(&self) -> bool {
+        self.db == DbType::Sqlite
+    }
+
+    /// The first column of RETURNING used to distinguish inserts from updates
+    pub fn xmax(&self) -> &'static str {
+        if self.is_sqlite() { "0" } else { "xmax" }
+    }
+
     pub fn signed_only(&self) -> bool {
         match self.db {
             DbType::Mysql => false,
-            DbType::Postgres => true,
+            DbType::Postgres | DbType::Sqlite => true,
         }
     }
 
@@ -590,6 +618,7 @@ impl ConfigDef {
         match self.db {
             DbType::Mysql => 4 * 1024 * 1024 * 1024 - 1,
             DbType::Postgres => 1024 * 1024 * 1024 - 1,
+            DbType::Sqlite => 1_000_000_000,
         }
     }
 
@@ -720,12 +749,18 @@ pub enum DbType {
     Mysql,
     #[display("postgres")]
     Postgres,
+    #[display("sqlite")]
+    Sqlite,
 }
 
 impl DbType {
     pub fn is_mysql(&self) -> bool {
         *self == DbType::Mysql
     }
+
+    pub fn is_sqlite(&self) -> bool {
+        *self == DbType::Sqlite
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Copy, Clone, JsonSchema)]
@@ -769,6 +804,49 @@ pub enum SoftDelete {
     UnixTime,
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Copy, Clone, JsonSchema, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+/// ### lintルール
+pub enum LintRule {
+    /// ### belongs_toのローカルIDにインデックスがない
+    BelongsToWithoutIndex,
+    /// ### has_manyの外部キーにインデックスがない
+    HasManyWithoutIndex,
+    /// ### ユニークインデックスにtext型のカラムが含まれる
+    TextInUniqueIndex,
+    /// ### 更新可能なモデルで全行キャッシュが有効
+    AllRowsCacheOnUpdatableModel,
+    /// ### 論理名が設定されていない
+    MissingLabel,
+    /// ### 名前がスネークケースではない
+    InconsistentNaming,
+}
+
+impl LintRule {
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            LintRule::MissingLabel => LintLevel::Allow,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+#[derive(
+    Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Copy, Clone, JsonSchema, AsRefStr,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+/// ### lint重要度
+pub enum LintLevel {
+    /// ### 検査しない
+    Allow,
+    /// ### 警告
+    Warn,
+    /// ### エラー
+    Error,
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Copy, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 /// ### トランザクション分離レベル
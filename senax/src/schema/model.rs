@@ -204,6 +204,9 @@ pub struct ModelDef {
     /// ### DDL定義を出力しない
     #[serde(skip_serializing_if = "Option::is_none")]
     pub skip_ddl: Option<bool>,
+    /// ### マイグレーションでのカラム削除などデータが失われる変更を許可する
+    #[serde(default, skip_serializing_if = "super::is_false")]
+    pub allow_destructive_migration: bool,
     /// ### 主キー以外で結合する多対多リレーション用ダミーテーブル
     #[serde(default, skip_serializing_if = "super::is_false")]
     pub dummy_always_joinable: bool,
@@ -241,6 +244,29 @@ pub struct ModelDef {
     /// ### 条件付き全行キャッシュを有効化する
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub enable_filtered_rows_cache: Option<bool>,
+    /// ### 起動時にキャッシュをウォームアップする
+    /// サーバがリクエストを受け付ける前に全行をキャッシュに読み込む
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_up: Option<bool>,
+    /// ### ウォームアップで読み込む最大行数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_up_limit: Option<u64>,
+    /// ### キャッシュ保持時間(秒)
+    /// 指定しない場合は {DB名}_CACHE_TTL と {DB名}_LONG_CACHE_TIME が使用される
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+    /// ### キャッシュ容量計算時の重み(%)
+    /// 大きくすると他のモデルより先に追い出される。デフォルトは100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_weight: Option<u32>,
+    /// ### キャッシュの最大メモリ使用量(MiB)
+    /// ロングキャッシュのシャードごとの上限。超えた場合は期限切れで空くまで新たにキャッシュせず、更新されたデータは古い値を削除する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_limit: Option<u64>,
+    /// ### キャッシュ保持時間を過ぎた後も古い値を返す時間(秒)
+    /// その間にバックグラウンドでキャッシュを更新する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_while_revalidate: Option<u64>,
     /// ### 更新時に常にすべてのキャッシュをクリアする
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub clear_all_cache_on_update: Option<bool>,
@@ -283,6 +309,14 @@ pub struct ModelDef {
     /// ### ストレージエンジン(MySQLのみ)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub engine: Option<String>,
+    /// ### シャードキー
+    /// シャードを決定するフィールド。プライマリキーに含まれる整数または文字列のフィールドを指定する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_key: Option<String>,
+    /// ### シャードの範囲
+    /// シャード1以降の開始値を昇順に指定する。指定しない場合はハッシュで振り分ける
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_ranges: Option<Vec<i64>>,
     /// ### 機能追加
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub act_as: Option<ActAs>,
@@ -355,6 +389,9 @@ pub struct ModelJson {
     /// ### DDL定義を出力しない
     #[serde(skip_serializing_if = "Option::is_none")]
     pub skip_ddl: Option<bool>,
+    /// ### マイグレーションでのカラム削除などデータが失われる変更を許可する
+    #[serde(default, skip_serializing_if = "super::is_false")]
+    pub allow_destructive_migration: bool,
     /// ### 主キー以外で結合する多対多リレーション用ダミーテーブル
     #[serde(default, skip_serializing_if = "super::is_false")]
     pub dummy_always_joinable: bool,
@@ -392,6 +429,29 @@ pub struct ModelJson {
     /// ### 条件付き全行キャッシュを有効化する
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub enable_filtered_rows_cache: Option<bool>,
+    /// ### 起動時にキャッシュをウォームアップする
+    /// サーバがリクエストを受け付ける前に全行をキャッシュに読み込む
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_up: Option<bool>,
+    /// ### ウォームアップで読み込む最大行数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_up_limit: Option<u64>,
+    /// ### キャッシュ保持時間(秒)
+    /// 指定しない場合は {DB名}_CACHE_TTL と {DB名}_LONG_CACHE_TIME が使用される
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl: Option<u64>,
+    /// ### キャッシュ容量計算時の重み(%)
+    /// 大きくすると他のモデルより先に追い出される。デフォルトは100
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_weight: Option<u32>,
+    /// ### キャッシュの最大メモリ使用量(MiB)
+    /// ロングキャッシュのシャードごとの上限。超えた場合は期限切れで空くまで新たにキャッシュせず、更新されたデータは古い値を削除する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_limit: Option<u64>,
+    /// ### キャッシュ保持時間を過ぎた後も古い値を返す時間(秒)
+    /// その間にバックグラウンドでキャッシュを更新する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_while_revalidate: Option<u64>,
     /// ### 更新時に常にすべてのキャッシュをクリアする
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub clear_all_cache_on_update: Option<bool>,
@@ -434,6 +494,14 @@ pub struct ModelJson {
     /// ### ストレージエンジン(MySQLのみ)
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub engine: Option<String>,
+    /// ### シャードキー
+    /// シャードを決定するフィールド。プライマリキーに含まれる整数または文字列のフィールドを指定する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_key: Option<String>,
+    /// ### シャードの範囲
+    /// シャード1以降の開始値を昇順に指定する。指定しない場合はハッシュで振り分ける
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_ranges: Option<Vec<i64>>,
     /// ### 機能追加
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub act_as: Option<ActAsJson>,
@@ -486,6 +554,7 @@ impl From<ModelDef> for ModelJson {
             comment: value.comment,
             table_name: value.table_name,
             skip_ddl: value.skip_ddl,
+            allow_destructive_migration: value.allow_destructive_migration,
             dummy_always_joinable: value.dummy_always_joinable,
             upsert_conflict_target: value.upsert_conflict_target,
             ignore_foreign_key: value.ignore_foreign_key,
@@ -498,6 +567,12 @@ impl From<ModelDef> for ModelJson {
             use_cache: value.use_cache,
             enable_all_rows_cache: value.enable_all_rows_cache,
             enable_filtered_rows_cache: value.enable_filtered_rows_cache,
+            warm_up: value.warm_up,
+            warm_up_limit: value.warm_up_limit,
+            cache_ttl: value.cache_ttl,
+            cache_weight: value.cache_weight,
+            cache_limit: value.cache_limit,
+            stale_while_revalidate: value.stale_while_revalidate,
             clear_all_cache_on_update: value.clear_all_cache_on_update,
             overwrite_on_relation_save: value.overwrite_on_relation_save,
             enable_update_notice: value.enable_update_notice,
@@ -511,6 +586,8 @@ impl From<ModelDef> for ModelJson {
             abstract_mode: value.abstract_mode,
             inheritance: value.inheritance.map(|v| v.into()),
             engine: value.engine,
+            shard_key: value.shard_key,
+            shard_ranges: value.shard_ranges,
             act_as: value.act_as.map(|v| v.into()),
             hide_er_relations: value.hide_er_relations,
             model_id: value.model_id,
@@ -604,6 +681,7 @@ impl TryFrom<ModelJson> for ModelDef {
             comment: value.comment,
             table_name: value.table_name,
             skip_ddl: value.skip_ddl,
+            allow_destructive_migration: value.allow_destructive_migration,
             dummy_always_joinable: value.dummy_always_joinable,
             upsert_conflict_target: value.upsert_conflict_target,
             ignore_foreign_key: value.ignore_foreign_key,
@@ -616,6 +694,12 @@ impl TryFrom<ModelJson> for ModelDef {
             use_cache: value.use_cache,
             enable_all_rows_cache: value.enable_all_rows_cache,
             enable_filtered_rows_cache: value.enable_filtered_rows_cache,
+            warm_up: value.warm_up,
+            warm_up_limit: value.warm_up_limit,
+            cache_ttl: value.cache_ttl,
+            cache_weight: value.cache_weight,
+            cache_limit: value.cache_limit,
+            stale_while_revalidate: value.stale_while_revalidate,
             clear_all_cache_on_update: value.clear_all_cache_on_update,
             overwrite_on_relation_save: value.overwrite_on_relation_save,
             enable_update_notice: value.enable_update_notice,
@@ -633,6 +717,8 @@ impl TryFrom<ModelJson> for ModelDef {
                 .transpose()?
                 .flatten(),
             engine: value.engine,
+            shard_key: value.shard_key,
+            shard_ranges: value.shard_ranges,
             act_as: value.act_as.map(|v| v.try_into()).transpose()?.flatten(),
             hide_er_relations: value.hide_er_relations,
             model_id: value.model_id,
@@ -756,6 +842,10 @@ impl ModelDef {
         format!("{}::{}", &self.group_name, &self.name)
     }
 
+    pub fn allow_destructive_migration(&self) -> bool {
+        self.allow_destructive_migration
+    }
+
     pub fn dummy_always_joinable(&self) -> bool {
         self.dummy_always_joinable
     }
@@ -884,6 +974,17 @@ impl ModelDef {
         self.enable_filtered_rows_cache.unwrap_or(false)
     }
 
+    pub fn warm_up(&self) -> bool {
+        self.use_cache() && self.warm_up.unwrap_or(false)
+    }
+
+    pub fn has_cache_policy(&self) -> bool {
+        self.cache_ttl.is_some()
+            || self.cache_weight.is_some()
+            || self.cache_limit.is_some()
+            || self.stale_while_revalidate.is_some()
+    }
+
     pub fn clear_all_cache_on_update(&self) -> bool {
         self.clear_all_cache_on_update.unwrap_or(
             CONFIG
@@ -1118,9 +1219,62 @@ impl ModelDef {
             .get_inner_type(true, true)
     }
 
+    /// Position of the shard key in the primary key
+    pub fn shard_key_index(&self) -> Option<usize> {
+        let name = self.shard_key.as_ref()?;
+        let (index, col) = self
+            .primaries()
+            .into_iter()
+            .enumerate()
+            .find(|(_, (k, _))| *k == name)
+            .map(|(i, (_, col))| (i, col))
+            .unwrap_or_else(|| {
+                error_exit!(
+                    "The shard_key {} of the {} model must be a primary key field.",
+                    name,
+                    &self.name
+                )
+            });
+        if col.auto == Some(AutoGeneration::AutoIncrement) {
+            error_exit!(
+                "The shard_key {} of the {} model cannot be auto increment.",
+                name,
+                &self.name
+            )
+        }
+        let is_string = matches!(
+            col.data_type,
+            DataType::Char | DataType::IdVarchar | DataType::TextVarchar | DataType::Text
+        );
+        if !col.is_integer() && (!is_string || !self.shard_ranges().is_empty()) {
+            error_exit!(
+                "The shard_key {} of the {} model has an unsupported type.",
+                name,
+                &self.name
+            )
+        }
+        if self.shard_ranges().windows(2).any(|w| w[0] >= w[1]) {
+            error_exit!(
+                "The shard_ranges of the {} model must be in ascending order.",
+                &self.name
+            )
+        }
+        Some(index)
+    }
+
+    pub fn shard_ranges(&self) -> Vec<i64> {
+        self.shard_ranges.clone().unwrap_or_default()
+    }
+
     pub fn all_fields(&self) -> Vec<(&String, &FieldDef)> {
         self.merged_fields.iter().collect()
     }
+    pub fn all_fields_except_read_only(&self) -> Vec<(&String, &FieldDef)> {
+        self.merged_fields
+            .iter()
+            .filter(|v| v.1.query.is_none())
+            .collect()
+    }
     pub fn all_fields_except_read_only_and_auto_inc(&self) -> Vec<(&String, &FieldDef)> {
         self.merged_fields
             .iter()
@@ -1149,6 +1303,39 @@ impl ModelDef {
             .filter(|(_k, v)| !v.not_null)
             .collect()
     }
+    /// Fields that can be compared in memory to merge ordered results.
+    /// Text is compared by code point, so only binary collations are included.
+    pub fn orderable(&self, not_null: bool) -> Vec<(&String, &FieldDef)> {
+        self.merged_fields
+            .iter()
+            .filter(|(_k, v)| v.not_null == not_null && v.enum_class.is_none())
+            .filter(|(_k, v)| !v.is_text() || v.is_binary_collation())
+            .filter(|(_k, v)| {
+                matches!(
+                    v.data_type,
+                    DataType::Char
+                        | DataType::IdVarchar
+                        | DataType::TextVarchar
+                        | DataType::Text
+                        | DataType::Uuid
+                        | DataType::BinaryUuid
+                        | DataType::TinyInt
+                        | DataType::SmallInt
+                        | DataType::Int
+                        | DataType::BigInt
+                        | DataType::Float
+                        | DataType::Double
+                        | DataType::Decimal
+                        | DataType::Date
+                        | DataType::Time
+                        | DataType::NaiveDateTime
+                        | DataType::UtcDateTime
+                        | DataType::TimestampWithTimeZone
+                        | DataType::Boolean
+                )
+            })
+            .collect()
+    }
     pub fn text(&self) -> Vec<(&String, &FieldDef)> {
         self.merged_fields
             .iter()
@@ -2234,6 +2421,33 @@ impl ModelDef {
         }
     }
 
+    /// Fingerprint of the packed cache layout, including the models embedded by cached relations
+    pub fn cache_fingerprint(&self) -> u64 {
+        let mut visited = HashSet::new();
+        let mut buf = format!("{} ", env!("CARGO_PKG_VERSION"));
+        self.write_cache_schema(&mut visited, &mut buf);
+        hash(&buf)
+    }
+
+    fn write_cache_schema(&self, visited: &mut HashSet<String>, buf: &mut String) {
+        if !visited.insert(format!("{}::{}", self.group_name, self.name)) {
+            return;
+        }
+        buf.push_str(&self.group_name);
+        buf.push_str("::");
+        buf.push_str(&self.name);
+        buf.push_str(
+            &serde_json::to_string(&(&self.merged_fields, &self.merged_relations)).unwrap(),
+        );
+        for (_, _, rel) in self
+            .relations_one_cache(Joinable::Join, false)
+            .into_iter()
+            .chain(self.relations_many_cache(Joinable::Join, false))
+        {
+            rel.get_foreign_model().write_cache_schema(visited, buf);
+        }
+    }
+
     pub fn get_type_id(&self, target: &str) -> u64 {
         static TYPE_IDS: Lazy<std::sync::Mutex<HashSet<u64>>> =
             Lazy::new(|| std::sync::Mutex::new(HashSet::new()));
@@ -5,13 +5,14 @@ use regex::Regex;
 use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::env;
+use std::fs;
 use std::path::Path;
 
 use crate::SCHEMA_PATH;
 use crate::common::ToCase as _;
 use crate::common::{fs_write, simplify_yml, to_plural, to_singular};
 use crate::ddl::sql_type::{IndexColumn, ReferenceOption, SqlType, TableKey};
-use crate::ddl::table::parse;
+use crate::ddl::table::{Table, parse};
 use crate::migration_generator::MYSQL_UUID_COLLATION;
 use crate::schema::{
     self, BelongsToDef, CONFIG, ConfigDef, DataType, EnumValue, FieldDef, FieldDefOrSubsetType,
@@ -26,10 +27,12 @@ pub async fn generate(
     use_label_as_sql_comment: bool,
     ignore_timestampable: bool,
     interpret_tinyint_as_boolean: bool,
+    infer_relations: bool,
+    group_by_prefix: bool,
 ) -> Result<()> {
     let non_snake_case = crate::common::check_non_snake_case()?;
     schema::parse(db, false, true)?;
-    let config = CONFIG.read().unwrap().as_ref().unwrap().clone();
+    let mut config = CONFIG.read().unwrap().as_ref().unwrap().clone();
     ensure!(!config.groups.is_empty(), "The groups are empty.");
     let url_name = format!("{}_DB_URL", db.to_upper_snake());
     let db_url = if let Some(db_url) = db_url {
@@ -39,10 +42,52 @@ pub async fn generate(
             .with_context(|| format!("{} is required in the .env file.", url_name))?
     };
     let tables = parse(&db_url).await?;
+    let new_groups = if group_by_prefix {
+        add_prefix_groups(&mut config, &tables, non_snake_case)
+    } else {
+        Vec::new()
+    };
+    let groups = make_groups(
+        &config,
+        &tables,
+        use_label_as_sql_comment,
+        ignore_timestampable,
+        interpret_tinyint_as_boolean,
+        infer_relations,
+        non_snake_case,
+    )?;
+    if !new_groups.is_empty() {
+        let path = Path::new(SCHEMA_PATH).join(format!("{db}.yml"));
+        let content = fs::read_to_string(&path)?.replace("\r\n", "\n");
+        fs_write(path, add_groups_to_yml(&content, &new_groups)?)?;
+    }
+    let path = Path::new(SCHEMA_PATH).join(db);
+    for (group, defs) in groups {
+        let file_path = path.join(format!("{}.yml", group));
+        let mut buf =
+            "# yaml-language-server: $schema=../../senax-schema.json#properties/model\n\n"
+                .to_string();
+        buf.push_str(&simplify_yml(serde_yaml::to_string(&defs)?)?);
+        fs_write(file_path, &buf)?;
+    }
+    Ok(())
+}
+
+/// Converts the tables into model definitions for each group.
+#[allow(clippy::collapsible_if)]
+fn make_groups(
+    config: &ConfigDef,
+    tables: &IndexMap<String, Table>,
+    use_label_as_sql_comment: bool,
+    ignore_timestampable: bool,
+    interpret_tinyint_as_boolean: bool,
+    infer_relations: bool,
+    non_snake_case: bool,
+) -> Result<IndexMap<String, IndexMap<String, ModelDef>>> {
     let mut defs: IndexMap<String, ModelDef> = IndexMap::new();
     let mut has_many: HashMap<String, Vec<HasManyDef>> = HashMap::new();
     let mut has_one: HashMap<String, Vec<HasOneDef>> = HashMap::new();
-    for (table_name, table) in &tables {
+    for (table_name, table) in tables {
         if table_name.starts_with('_') {
             continue;
         }
@@ -53,7 +98,7 @@ pub async fn generate(
         let mut pk = Vec::new();
         let mut indexes = IndexMap::new();
         let mut belongs_to: IndexMap<String, Vec<BelongsToDef>> = IndexMap::new();
-        let singular_name = to_singular_name(&config, table_name, non_snake_case);
+        let singular_name = to_singular_name(config, table_name, non_snake_case);
         model.table_name = Some(table_name.clone());
         if use_label_as_sql_comment || config.use_label_as_sql_comment {
             if let Some(comment) = &table.comment {
@@ -83,27 +128,40 @@ pub async fn generate(
         let mut updated_at = false;
         let mut soft_delete = SoftDelete::None;
         for (name, column) in &table.columns {
-            if name == ConfigDef::created_at().as_str() {
+            // Only columns with the shapes senax generates are treated as special columns.
+            let is_time = matches!(
+                column.sql_type,
+                SqlType::DateTime(_) | SqlType::Timestamp(_)
+            );
+            let is_int = matches!(
+                column.sql_type,
+                SqlType::Int | SqlType::UnsignedInt | SqlType::Bigint | SqlType::UnsignedBigint
+            );
+            if name == ConfigDef::created_at().as_str() && is_time {
                 created_at = true;
                 continue;
             }
-            if name == ConfigDef::updated_at().as_str() {
+            if name == ConfigDef::updated_at().as_str() && is_time {
                 updated_at = true;
                 continue;
             }
-            if name == ConfigDef::deleted_at().as_str() {
+            if name == ConfigDef::deleted_at().as_str() && is_time && !column.constraint.not_null {
                 soft_delete = SoftDelete::Time;
                 continue;
             }
-            if name == ConfigDef::deleted().as_str() {
-                if column.sql_type == SqlType::Bool || column.sql_type == SqlType::Tinyint {
+            if name == ConfigDef::deleted().as_str() && column.constraint.not_null {
+                if matches!(
+                    column.sql_type,
+                    SqlType::Bool | SqlType::Tinyint | SqlType::UnsignedTinyint
+                ) {
                     soft_delete = SoftDelete::Flag;
-                } else {
+                    continue;
+                } else if is_int {
                     soft_delete = SoftDelete::UnixTime;
+                    continue;
                 }
-                continue;
             }
-            if name == ConfigDef::version().as_str() {
+            if name == ConfigDef::version().as_str() && is_int && column.constraint.not_null {
                 model.versioned = true;
                 continue;
             }
@@ -423,10 +481,10 @@ pub async fn generate(
                     on_delete,
                     on_update,
                 ) => {
-                    let parent_singular_name = to_singular_name(&config, table, non_snake_case);
+                    let parent_singular_name = to_singular_name(config, table, non_snake_case);
                     let mut def = BelongsToDef {
-                        group: to_rel_group(&config, &parent_singular_name, &singular_name),
-                        model: to_rel_model(&config, &parent_singular_name, &singular_name),
+                        group: to_rel_group(config, &parent_singular_name, &singular_name),
+                        model: to_rel_model(config, &parent_singular_name, &singular_name),
                         ..Default::default()
                     };
                     let local = if columns.len() == 1 {
@@ -476,8 +534,8 @@ pub async fn generate(
                             Some(local.clone())
                         };
                         let def = HasOneDef {
-                            group: to_rel_group(&config, &singular_name, &parent_singular_name),
-                            model: to_rel_model(&config, &singular_name, &parent_singular_name),
+                            group: to_rel_group(config, &singular_name, &parent_singular_name),
+                            model: to_rel_model(config, &singular_name, &parent_singular_name),
                             foreign,
                             ..Default::default()
                         };
@@ -498,8 +556,8 @@ pub async fn generate(
                             Some(local.clone())
                         };
                         let def = HasManyDef {
-                            group: to_rel_group(&config, &singular_name, &parent_singular_name),
-                            model: to_rel_model(&config, &singular_name, &parent_singular_name),
+                            group: to_rel_group(config, &singular_name, &parent_singular_name),
+                            model: to_rel_model(config, &singular_name, &parent_singular_name),
                             foreign,
                             ..Default::default()
                         };
@@ -509,9 +567,9 @@ pub async fn generate(
                 _ => unimplemented!(),
             }
         }
-        if config.ignore_foreign_key {
+        if config.ignore_foreign_key || infer_relations {
             for (name, _col) in &table.columns {
-                if name.ends_with("_id") {
+                if name.ends_with("_id") && !constraint_idx.contains(name) {
                     let parent_singular_name = name.trim_end_matches("_id");
                     let parent = if config.plural_table_name {
                         to_plural(parent_singular_name)
@@ -521,16 +579,21 @@ pub async fn generate(
                     if parent == *table_name {
                         continue;
                     }
-                    let mut table_exists = tables.get(&parent).is_some();
-                    for (group, _) in &config.groups {
-                        let n = format!("{}_{}", group, &parent);
-                        table_exists =
-                            table_exists || (n != *table_name && tables.get(&n).is_some());
-                    }
-                    if table_exists {
+                    let parent_table = if tables.contains_key(&parent) {
+                        Some(parent.clone())
+                    } else {
+                        config
+                            .groups
+                            .keys()
+                            .map(|group| format!("{}_{}", group, &parent))
+                            .find(|n| n != table_name && tables.contains_key(n))
+                    };
+                    if let Some(parent_table) = parent_table {
+                        // The singular name of the parent table resolves its group and model.
+                        let parent_name = to_singular_name(config, &parent_table, non_snake_case);
                         let mut def = BelongsToDef {
-                            group: to_rel_group(&config, parent_singular_name, &singular_name),
-                            model: to_rel_model(&config, parent_singular_name, &singular_name),
+                            group: to_rel_group(config, &parent_name, &singular_name),
+                            model: to_rel_model(config, &parent_name, &singular_name),
                             ..Default::default()
                         };
                         // if parent_singular_name == def.model.as_ref().unwrap() {
@@ -547,7 +610,9 @@ pub async fn generate(
                         if soft_delete == SoftDelete::Flag || soft_delete == SoftDelete::UnixTime {
                             constraint_idx.insert(format!("{},{}", name, ConfigDef::deleted()));
                         }
-                        def.on_delete = Some(schema::ReferenceOption::Cascade);
+                        if config.ignore_foreign_key {
+                            def.on_delete = Some(schema::ReferenceOption::Cascade);
+                        }
                         belongs_to
                             .entry(parent_singular_name.to_string())
                             .or_default()
@@ -555,24 +620,18 @@ pub async fn generate(
 
                         if pk == local {
                             let def = HasOneDef {
-                                group: to_rel_group(&config, &singular_name, parent_singular_name),
-                                model: to_rel_model(&config, &singular_name, parent_singular_name),
+                                group: to_rel_group(config, &singular_name, &parent_name),
+                                model: to_rel_model(config, &singular_name, &parent_name),
                                 ..Default::default()
                             };
-                            has_one
-                                .entry(parent_singular_name.to_string())
-                                .or_default()
-                                .push(def);
+                            has_one.entry(parent_name.clone()).or_default().push(def);
                         } else {
                             let def = HasManyDef {
-                                group: to_rel_group(&config, &singular_name, parent_singular_name),
-                                model: to_rel_model(&config, &singular_name, parent_singular_name),
+                                group: to_rel_group(config, &singular_name, &parent_name),
+                                model: to_rel_model(config, &singular_name, &parent_name),
                                 ..Default::default()
                             };
-                            has_many
-                                .entry(parent_singular_name.to_string())
-                                .or_default()
-                                .push(def);
+                            has_many.entry(parent_name.clone()).or_default().push(def);
                         }
                     }
                 }
@@ -719,7 +778,7 @@ pub async fn generate(
                 _ => unimplemented!(),
             }
         }
-        let (group_name, model_name) = to_group_and_model_name(&config, &singular_name);
+        let (group_name, model_name) = to_group_and_model_name(config, &singular_name);
         for (org_name, mut defs) in belongs_to {
             if defs.len() == 1 {
                 model.belongs_to.insert(org_name, Some(defs.pop().unwrap()));
@@ -835,16 +894,99 @@ pub async fn generate(
             .or_default()
             .insert(model.name.clone(), model);
     }
-    let path = Path::new(SCHEMA_PATH).join(db);
-    for (group, defs) in groups {
-        let file_path = path.join(format!("{}.yml", group));
-        let mut buf =
-            "# yaml-language-server: $schema=../../senax-schema.json#properties/model\n\n"
-                .to_string();
-        buf.push_str(&simplify_yml(serde_yaml::to_string(&defs)?)?);
-        fs_write(file_path, &buf)?;
+    Ok(groups)
+}
+
+/// Adds groups for table name prefixes shared by two or more tables
+/// that do not belong to the configured groups.
+fn add_prefix_groups(
+    config: &mut ConfigDef,
+    tables: &IndexMap<String, Table>,
+    non_snake_case: bool,
+) -> Vec<String> {
+    let mut counts: IndexMap<String, usize> = IndexMap::new();
+    for table_name in tables.keys() {
+        if table_name.starts_with('_') || table_name.eq("spatial_ref_sys") {
+            continue;
+        }
+        let singular_name = to_singular_name(config, table_name, non_snake_case);
+        if config
+            .groups
+            .keys()
+            .any(|group| singular_name.starts_with(&format!("{}_", group)))
+        {
+            continue;
+        }
+        if let Some((prefix, _)) = singular_name.split_once('_')
+            && !prefix.is_empty()
+        {
+            *counts.entry(prefix.to_string()).or_default() += 1;
+        }
     }
-    Ok(())
+    let mut new_groups = Vec::new();
+    for (prefix, count) in counts {
+        if count > 1 {
+            config.groups.insert(prefix.clone(), None);
+            new_groups.push(prefix);
+        }
+    }
+    new_groups
+}
+
+/// Adds the groups to the `groups` mapping of the db config
+/// without rewriting the other lines, so that comments and formatting are kept.
+fn add_groups_to_yml(content: &str, groups: &[String]) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut buf = String::new();
+    let Some(start) = lines.iter().position(|v| v.starts_with("groups:")) else {
+        buf.push_str(content.trim_end());
+        buf.push_str("\ngroups:\n");
+        for group in groups {
+            buf.push_str(&format!("  {}:\n", group));
+        }
+        return Ok(buf);
+    };
+    let value = lines[start]["groups:".len()..]
+        .split('#')
+        .next()
+        .unwrap()
+        .trim();
+    ensure!(
+        value.is_empty() || value == "{}",
+        "The groups of the db config must be a block mapping."
+    );
+    let mut indent = None;
+    let mut end = start + 1;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed.len() == line.len() {
+            break;
+        }
+        indent.get_or_insert(&line[..line.len() - trimmed.len()]);
+        end = i + 1;
+    }
+    let indent = indent.unwrap_or("  ");
+    for line in &lines[..start] {
+        buf.push_str(line);
+        buf.push('\n');
+    }
+    buf.push_str(lines[start].replacen("{}", "", 1).trim_end());
+    buf.push('\n');
+    for line in &lines[start + 1..end] {
+        buf.push_str(line);
+        buf.push('\n');
+    }
+    for group in groups {
+        buf.push_str(&format!("{}{}:\n", indent, group));
+    }
+    for line in &lines[end..] {
+        buf.push_str(line);
+        buf.push('\n');
+    }
+    Ok(buf)
 }
 
 fn make_name(col: &IndexColumn) -> String {
@@ -920,3 +1062,253 @@ fn to_singular_name(config: &ConfigDef, table_name: &str, non_snake_case: bool)
     }
     singular_name
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ddl::sql_type::Literal;
+    use crate::ddl::table::{Column, Constraint};
+
+    fn config(groups: &[&str]) -> ConfigDef {
+        CONFIG
+            .write()
+            .unwrap()
+            .get_or_insert_with(ConfigDef::default);
+        let config = ConfigDef {
+            groups: groups.iter().map(|v| (v.to_string(), None)).collect(),
+            ..Default::default()
+        };
+        config.fix_static_vars();
+        config
+    }
+
+    fn key(names: &[&str]) -> Vec<IndexColumn> {
+        names
+            .iter()
+            .map(|name| IndexColumn {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn table(name: &str, columns: &[(&str, SqlType, bool)]) -> Table {
+        let mut table = Table {
+            name: name.to_string(),
+            primary: Some(("PRIMARY".to_string(), TableKey::PrimaryKey(key(&["id"])))),
+            engine: Some("InnoDB".to_string()),
+            ..Default::default()
+        };
+        for (name, sql_type, not_null) in columns {
+            table.columns.insert(
+                name.to_string(),
+                Column {
+                    old_name: None,
+                    sql_type: sql_type.clone(),
+                    alt_type: sql_type.clone(),
+                    constraint: Constraint {
+                        not_null: *not_null,
+                        ..Default::default()
+                    },
+                    default: None,
+                    comment: None,
+                },
+            );
+        }
+        table
+    }
+
+    fn blog_tables() -> IndexMap<String, Table> {
+        let user = table("blog_user", &[("id", SqlType::UnsignedInt, true)]);
+        let mut post = table(
+            "blog_post",
+            &[
+                ("id", SqlType::UnsignedInt, true),
+                ("user_id", SqlType::UnsignedInt, true),
+            ],
+        );
+        post.indexes.insert(
+            "user_id".to_string(),
+            TableKey::Key("user_id".to_string(), key(&["user_id"])),
+        );
+        IndexMap::from([
+            ("blog_user".to_string(), user),
+            ("blog_post".to_string(), post),
+        ])
+    }
+
+    fn make(
+        config: &ConfigDef,
+        tables: &IndexMap<String, Table>,
+        infer_relations: bool,
+    ) -> IndexMap<String, IndexMap<String, ModelDef>> {
+        make_groups(config, tables, false, false, false, infer_relations, false).unwrap()
+    }
+
+    fn assert_blog_relations(groups: &IndexMap<String, IndexMap<String, ModelDef>>) {
+        let post = &groups["blog"]["post"];
+        let belongs_to = post.belongs_to["user"].as_ref().unwrap();
+        assert_eq!(belongs_to.group, None);
+        assert_eq!(belongs_to.model.as_deref(), Some("user"));
+        assert_eq!(
+            belongs_to.local,
+            Some(StringOrArray::One("user_id".to_string()))
+        );
+        assert!(post.indexes.is_empty());
+        let user = &groups["blog"]["user"];
+        let has_many = user.has_many["posts"].as_ref().unwrap();
+        assert_eq!(has_many.model.as_deref(), Some("post"));
+        assert_eq!(has_many.foreign, None);
+    }
+
+    #[test]
+    fn relations_from_foreign_keys() {
+        let config = config(&["blog"]);
+        let mut tables = blog_tables();
+        tables["blog_post"].constraints.insert(
+            "fk_user".to_string(),
+            TableKey::Constraint(
+                "fk_user".to_string(),
+                key(&["user_id"]),
+                "blog_user".to_string(),
+                key(&["id"]),
+                Some(ReferenceOption::Cascade),
+                None,
+            ),
+        );
+        let groups = make(&config, &tables, false);
+        assert_blog_relations(&groups);
+        assert_eq!(
+            groups["blog"]["post"].belongs_to["user"]
+                .as_ref()
+                .unwrap()
+                .on_delete,
+            Some(schema::ReferenceOption::Cascade)
+        );
+    }
+
+    #[test]
+    fn relations_from_naming_convention() {
+        let config = config(&["blog"]);
+        let tables = blog_tables();
+        let groups = make(&config, &tables, false);
+        assert!(groups["blog"]["post"].belongs_to.is_empty());
+        assert!(groups["blog"]["user"].has_many.is_empty());
+
+        let groups = make(&config, &tables, true);
+        assert_blog_relations(&groups);
+    }
+
+    #[test]
+    fn column_conventions() {
+        let config = config(&["blog"]);
+        let mut tables = blog_tables();
+        let mut post = table(
+            "blog_post",
+            &[
+                ("id", SqlType::UnsignedInt, true),
+                (
+                    "status",
+                    SqlType::Enum(vec![
+                        Literal::String("draft".to_string()),
+                        Literal::String("published".to_string()),
+                    ]),
+                    true,
+                ),
+                ("created_at", SqlType::DateTime(0), true),
+                ("updated_at", SqlType::DateTime(0), true),
+                ("deleted_at", SqlType::DateTime(0), false),
+                ("_version", SqlType::UnsignedInt, true),
+            ],
+        );
+        post.indexes.insert(
+            "status".to_string(),
+            TableKey::Key("status".to_string(), key(&["status", "created_at"])),
+        );
+        tables.insert("blog_post".to_string(), post);
+        let groups = make(&config, &tables, false);
+        let post = &groups["blog"]["post"];
+        assert_eq!(post.fields.keys().collect::<Vec<_>>(), vec!["id", "status"]);
+        let status = post.fields["status"].exact();
+        assert_eq!(status.data_type, DataType::DbEnum);
+        assert_eq!(
+            status
+                .enum_values
+                .unwrap()
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["draft", "published"]
+        );
+        assert_eq!(post.timestampable, Some(schema::Timestampable::FixedTime));
+        assert!(!post.disable_created_at && !post.disable_updated_at);
+        assert_eq!(post.soft_delete, Some(SoftDelete::Time));
+        assert!(post.versioned);
+        let index = post.indexes["status"].as_ref().unwrap();
+        assert_eq!(
+            index.fields.keys().collect::<Vec<_>>(),
+            vec!["status", "created_at"]
+        );
+
+        let user = &groups["blog"]["user"];
+        assert_eq!(user.timestampable, None);
+        assert_eq!(user.soft_delete, None);
+        assert!(!user.versioned);
+    }
+
+    #[test]
+    fn prefix_groups() {
+        let mut config = config(&["blog"]);
+        let mut tables = blog_tables();
+        for name in ["shop_item", "shop_order", "audit_log"] {
+            tables.insert(
+                name.to_string(),
+                table(name, &[("id", SqlType::UnsignedInt, true)]),
+            );
+        }
+        let new_groups = add_prefix_groups(&mut config, &tables, false);
+        assert_eq!(new_groups, vec!["shop"]);
+        assert_eq!(
+            config.groups.keys().collect::<Vec<_>>(),
+            vec!["blog", "shop"]
+        );
+        let groups = make(&config, &tables, false);
+        assert_eq!(
+            groups["shop"].keys().collect::<Vec<_>>(),
+            vec!["item", "order"]
+        );
+        assert!(groups["blog"].contains_key("audit_log"));
+        assert_eq!(
+            groups["blog"]["audit_log"].table_name.as_deref(),
+            Some("audit_log")
+        );
+    }
+
+    #[test]
+    fn groups_added_in_place() {
+        let groups = vec!["shop".to_string(), "audit".to_string()];
+        let content = "# yaml-language-server: $schema=../senax-schema.json#definitions/ConfigDef\n\ndb: mysql # main db\ngroups:\n    # shared tables\n    common:\n        exclude_group_from_table_name: true\n    blog:\n\n# trailing comment\nuse_cache: true\n";
+        let result = add_groups_to_yml(content, &groups).unwrap();
+        assert_eq!(
+            result,
+            "# yaml-language-server: $schema=../senax-schema.json#definitions/ConfigDef\n\ndb: mysql # main db\ngroups:\n    # shared tables\n    common:\n        exclude_group_from_table_name: true\n    blog:\n    shop:\n    audit:\n\n# trailing comment\nuse_cache: true\n"
+        );
+        let config: ConfigDef = serde_yaml::from_str(&result).unwrap();
+        assert_eq!(
+            config.groups.keys().collect::<Vec<_>>(),
+            vec!["common", "blog", "shop", "audit"]
+        );
+        assert!(
+            config.groups["common"]
+                .as_ref()
+                .unwrap()
+                .exclude_group_from_table_name
+        );
+
+        let result = add_groups_to_yml("db: mysql\ngroups: {}\n", &groups).unwrap();
+        assert_eq!(result, "db: mysql\ngroups:\n  shop:\n  audit:\n");
+        let result = add_groups_to_yml("db: mysql\n", &groups).unwrap();
+        assert_eq!(result, "db: mysql\ngroups:\n  shop:\n  audit:\n");
+        assert!(add_groups_to_yml("db: mysql\ngroups: {blog: ~}\n", &groups).is_err());
+    }
+}
@@ -0,0 +1,494 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::common::ToCase as _;
+use crate::ddl::sql_type::{IndexColumn, SqlType, TableKey};
+use crate::ddl::table::Table;
+use crate::migration_generator::make_table_def;
+use crate::schema::{self, CONFIG, ConfigDef, GROUPS, LintLevel, LintRule, ModelDef};
+
+pub struct Issue {
+    pub level: LintLevel,
+    pub rule: LintRule,
+    pub model: String,
+    pub message: String,
+}
+
+/// Checks the schema of the db and prints the issues.
+/// Returns the number of error-level issues.
+pub fn lint(db: &str) -> Result<usize> {
+    schema::parse(db, false, false)?;
+    let config = CONFIG.read().unwrap().as_ref().unwrap().clone();
+    let mut models = Vec::new();
+    {
+        let group_lock = GROUPS.read().unwrap();
+        let groups = group_lock.as_ref().unwrap();
+        for defs in groups.values() {
+            for def in defs.values() {
+                models.push(def.clone());
+            }
+        }
+    }
+    let mut tables = HashMap::new();
+    for def in &models {
+        if def.has_table() {
+            let (_, table, _) = make_table_def(def, &config)?;
+            tables.insert(def.full_name(), table);
+        }
+    }
+    let mut linter = Linter {
+        config: &config,
+        issues: Vec::new(),
+    };
+    for def in &models {
+        linter.check_model(def, &tables);
+    }
+    let mut errors = 0;
+    for issue in &linter.issues {
+        if issue.level == LintLevel::Error {
+            errors += 1;
+        }
+        println!(
+            "{}[{}] {}::{}: {}",
+            issue.level.as_ref(),
+            issue.rule.as_ref(),
+            db,
+            issue.model,
+            issue.message
+        );
+    }
+    Ok(errors)
+}
+
+struct Linter<'a> {
+    config: &'a ConfigDef,
+    issues: Vec<Issue>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, rule: LintRule, def: &ModelDef, message: String) {
+        let level = self.config.lint_level(rule);
+        if level == LintLevel::Allow {
+            return;
+        }
+        self.issues.push(Issue {
+            level,
+            rule,
+            model: def.full_name(),
+            message,
+        });
+    }
+
+    fn check_model(&mut self, def: &Arc<ModelDef>, tables: &HashMap<String, Table>) {
+        self.check_naming(def);
+        self.check_label(def);
+        if def.enable_all_rows_cache() && !def.disable_update() {
+            self.report(
+                LintRule::AllRowsCacheOnUpdatableModel,
+                def,
+                "all rows cache is enabled on an updatable model".to_string(),
+            );
+        }
+        let Some(table) = tables.get(&def.full_name()) else {
+            return;
+        };
+        for (name, rel) in &def.merged_relations {
+            if rel.is_type_of_belongs_to() {
+                let cols: Vec<_> = rel
+                    .get_local_cols(name, def)
+                    .into_iter()
+                    .map(|(n, c)| c.get_col_name(n).to_string())
+                    .collect();
+                if !cols.is_empty() && !is_indexed(table, &cols) {
+                    self.report(
+                        LintRule::BelongsToWithoutIndex,
+                        def,
+                        format!("belongs_to {} has no index on ({})", name, cols.join(", ")),
+                    );
+                }
+            } else if rel.is_type_of_has_many() {
+                let foreign = rel.get_foreign_model();
+                let Some(foreign_table) = tables.get(&foreign.full_name()) else {
+                    continue;
+                };
+                let cols: Vec<_> = rel
+                    .get_foreign_cols(def)
+                    .iter()
+                    .map(|(n, c)| c.get_col_name(n).to_string())
+                    .collect();
+                if !cols.is_empty() && !is_indexed(foreign_table, &cols) {
+                    self.report(
+                        LintRule::HasManyWithoutIndex,
+                        def,
+                        format!(
+                            "has_many {} has no index on {} ({})",
+                            name,
+                            foreign_table.name,
+                            cols.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+        for index in table.indexes.values() {
+            if let TableKey::UniqueKey(index_name, cols) = index {
+                for col in cols {
+                    let is_text = table.columns.get(&col.name).is_some_and(|c| {
+                        matches!(
+                            c.sql_type,
+                            SqlType::Tinytext
+                                | SqlType::Text
+                                | SqlType::Mediumtext
+                                | SqlType::Longtext
+                                | SqlType::Tinyblob
+                                | SqlType::Blob
+                                | SqlType::Mediumblob
+                                | SqlType::Longblob
+                        )
+                    });
+                    if is_text {
+                        self.report(
+                            LintRule::TextInUniqueIndex,
+                            def,
+                            format!(
+                                "unique index {} contains the text column {}",
+                                index_name, col.name
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_label(&mut self, def: &ModelDef) {
+        if def.label.is_none() {
+            self.report(
+                LintRule::MissingLabel,
+                def,
+                "model has no label".to_string(),
+            );
+        }
+        for name in def.fields.keys() {
+            if let Some(field) = def.merged_fields.get(name)
+                && field.label.is_none()
+            {
+                self.report(
+                    LintRule::MissingLabel,
+                    def,
+                    format!("field {} has no label", name),
+                );
+            }
+        }
+    }
+
+    fn check_naming(&mut self, def: &ModelDef) {
+        check_name(self, def, "model", &def.name);
+        if let Some(table_name) = &def.table_name {
+            check_name(self, def, "table", table_name);
+        }
+        for name in def.fields.keys() {
+            check_name(self, def, "field", name);
+        }
+        for name in def.merged_relations.keys() {
+            check_name(self, def, "relation", name);
+        }
+        for name in def.merged_indexes.keys() {
+            check_name(self, def, "index", name);
+        }
+
+        fn check_name(linter: &mut Linter, def: &ModelDef, kind: &str, name: &str) {
+            if name.to_snake() != name {
+                linter.report(
+                    LintRule::InconsistentNaming,
+                    def,
+                    format!("{} name {} is not snake_case", kind, name),
+                );
+            }
+        }
+    }
+}
+
+/// Whether an index or the primary key starts with the columns.
+fn is_indexed(table: &Table, cols: &[String]) -> bool {
+    let starts_with = |index_cols: &[IndexColumn]| {
+        index_cols.len() >= cols.len()
+            && index_cols
+                .iter()
+                .zip(cols)
+                .all(|(a, b)| a.query.is_none() && &a.name == b)
+    };
+    if let Some((_, TableKey::PrimaryKey(index_cols))) = &table.primary
+        && starts_with(index_cols)
+    {
+        return true;
+    }
+    table.indexes.values().any(|index| match index {
+        TableKey::UniqueKey(_, index_cols) | TableKey::Key(_, index_cols) => {
+            starts_with(index_cols)
+        }
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ddl::table::{Column, Constraint};
+    use crate::schema::{FieldDef, FieldDefOrSubsetType, RelDef, RelationsType};
+
+    fn model(name: &str, fields: &[&str]) -> ModelDef {
+        CONFIG
+            .write()
+            .unwrap()
+            .get_or_insert_with(ConfigDef::default);
+        let mut def = ModelDef {
+            group_name: "lint_test".to_string(),
+            name: name.to_string(),
+            label: Some(name.to_string()),
+            ..Default::default()
+        };
+        for field in fields {
+            def.merged_fields.insert(
+                field.to_string(),
+                FieldDef {
+                    label: Some(field.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+        def
+    }
+
+    fn key(names: &[&str]) -> Vec<IndexColumn> {
+        names
+            .iter()
+            .map(|name| IndexColumn {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn table(name: &str, columns: &[(&str, SqlType)]) -> Table {
+        let mut table = Table {
+            name: name.to_string(),
+            primary: Some(("PRIMARY".to_string(), TableKey::PrimaryKey(key(&["id"])))),
+            ..Default::default()
+        };
+        for (name, sql_type) in columns {
+            table.columns.insert(
+                name.to_string(),
+                Column {
+                    old_name: None,
+                    sql_type: sql_type.clone(),
+                    alt_type: sql_type.clone(),
+                    constraint: Constraint::default(),
+                    default: None,
+                    comment: None,
+                },
+            );
+        }
+        table
+    }
+
+    fn rules(config: &ConfigDef, def: &ModelDef, tables: &HashMap<String, Table>) -> Vec<LintRule> {
+        let mut linter = Linter {
+            config,
+            issues: Vec::new(),
+        };
+        linter.check_model(&Arc::new(def.clone()), tables);
+        linter.issues.iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn belongs_to_without_index() {
+        let config = ConfigDef::default();
+        let mut def = model("post", &["id", "user_id"]);
+        def.merged_relations.insert(
+            "user".to_string(),
+            RelDef {
+                model: "lint_test::user".to_string(),
+                rel_type: Some(RelationsType::BelongsTo),
+                ..Default::default()
+            },
+        );
+        let mut post = table("post", &[("id", SqlType::Int), ("user_id", SqlType::Int)]);
+        let mut tables = HashMap::from([(def.full_name(), post.clone())]);
+        assert_eq!(
+            rules(&config, &def, &tables),
+            vec![LintRule::BelongsToWithoutIndex]
+        );
+
+        post.indexes.insert(
+            "user_id".to_string(),
+            TableKey::Key("user_id".to_string(), key(&["user_id", "id"])),
+        );
+        tables.insert(def.full_name(), post.clone());
+        assert!(rules(&config, &def, &tables).is_empty());
+
+        post.indexes.clear();
+        post.primary = Some((
+            "PRIMARY".to_string(),
+            TableKey::PrimaryKey(key(&["user_id", "id"])),
+        ));
+        tables.insert(def.full_name(), post);
+        assert!(rules(&config, &def, &tables).is_empty());
+    }
+
+    #[test]
+    fn has_many_without_index() {
+        let config = ConfigDef::default();
+        let comment = model("comment", &["id", "post_id"]);
+        GROUPS
+            .write()
+            .unwrap()
+            .get_or_insert_with(Default::default)
+            .entry("lint_test".to_string())
+            .or_default()
+            .insert("comment".to_string(), Arc::new(comment.clone()));
+        let mut def = model("post", &["id"]);
+        def.merged_relations.insert(
+            "comments".to_string(),
+            RelDef {
+                model: "lint_test::comment".to_string(),
+                rel_type: Some(RelationsType::HasMany),
+                ..Default::default()
+            },
+        );
+        let post = table("post", &[("id", SqlType::Int)]);
+        let mut comments = table(
+            "comment",
+            &[("id", SqlType::Int), ("post_id", SqlType::Int)],
+        );
+        let mut tables = HashMap::from([
+            (def.full_name(), post.clone()),
+            (comment.full_name(), comments.clone()),
+        ]);
+        assert_eq!(
+            rules(&config, &def, &tables),
+            vec![LintRule::HasManyWithoutIndex]
+        );
+
+        comments.indexes.insert(
+            "post_id".to_string(),
+            TableKey::Key("post_id".to_string(), key(&["post_id"])),
+        );
+        tables.insert(comment.full_name(), comments);
+        assert!(rules(&config, &def, &tables).is_empty());
+    }
+
+    #[test]
+    fn text_in_unique_index() {
+        let config = ConfigDef::default();
+        let def = model("user", &["id", "email", "code"]);
+        let mut user = table(
+            "user",
+            &[
+                ("id", SqlType::Int),
+                ("email", SqlType::Text),
+                ("code", SqlType::Varchar(32)),
+            ],
+        );
+        user.indexes.insert(
+            "code".to_string(),
+            TableKey::UniqueKey("code".to_string(), key(&["code"])),
+        );
+        let mut tables = HashMap::from([(def.full_name(), user.clone())]);
+        assert!(rules(&config, &def, &tables).is_empty());
+
+        user.indexes.insert(
+            "email".to_string(),
+            TableKey::UniqueKey("email".to_string(), key(&["email"])),
+        );
+        tables.insert(def.full_name(), user);
+        assert_eq!(
+            rules(&config, &def, &tables),
+            vec![LintRule::TextInUniqueIndex]
+        );
+    }
+
+    #[test]
+    fn all_rows_cache_on_updatable_model() {
+        let config = ConfigDef::default();
+        let mut def = model("setting", &["id"]);
+        def.use_cache = Some(true);
+        def.enable_all_rows_cache = Some(true);
+        def.disable_update = Some(false);
+        assert_eq!(
+            rules(&config, &def, &HashMap::new()),
+            vec![LintRule::AllRowsCacheOnUpdatableModel]
+        );
+
+        def.disable_update = Some(true);
+        assert!(rules(&config, &def, &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn label_and_naming() {
+        let mut config = ConfigDef::default();
+        let mut def = model("userProfile", &[]);
+        def.label = None;
+        def.fields.insert(
+            "nick_name".to_string(),
+            FieldDefOrSubsetType::Exact(FieldDef::default()),
+        );
+        def.merged_fields
+            .insert("nick_name".to_string(), FieldDef::default());
+        assert_eq!(
+            rules(&config, &def, &HashMap::new()),
+            vec![LintRule::InconsistentNaming]
+        );
+
+        config.lint.insert(LintRule::MissingLabel, LintLevel::Warn);
+        assert_eq!(
+            rules(&config, &def, &HashMap::new()),
+            vec![
+                LintRule::InconsistentNaming,
+                LintRule::MissingLabel,
+                LintRule::MissingLabel
+            ]
+        );
+    }
+
+    #[test]
+    fn severity_config() {
+        let mut config = ConfigDef::default();
+        assert_eq!(config.lint_level(LintRule::MissingLabel), LintLevel::Allow);
+        assert_eq!(
+            config.lint_level(LintRule::TextInUniqueIndex),
+            LintLevel::Warn
+        );
+
+        config
+            .lint
+            .insert(LintRule::InconsistentNaming, LintLevel::Error);
+        config.lint.insert(LintRule::MissingLabel, LintLevel::Allow);
+        let mut def = model("UserProfile", &[]);
+        def.label = None;
+        let mut linter = Linter {
+            config: &config,
+            issues: Vec::new(),
+        };
+        linter.check_model(&Arc::new(def), &HashMap::new());
+        assert_eq!(linter.issues.len(), 1);
+        assert_eq!(linter.issues[0].rule, LintRule::InconsistentNaming);
+        assert_eq!(linter.issues[0].level, LintLevel::Error);
+        assert_eq!(linter.issues[0].model, "lint_test::UserProfile");
+
+        let config: ConfigDef = serde_yaml::from_str(
+            "db: mysql\ngroups: {}\nlint:\n  missing_label: error\n  has_many_without_index: allow\n",
+        )
+        .unwrap();
+        assert_eq!(config.lint_level(LintRule::MissingLabel), LintLevel::Error);
+        assert_eq!(
+            config.lint_level(LintRule::HasManyWithoutIndex),
+            LintLevel::Allow
+        );
+        assert_eq!(
+            config.lint_level(LintRule::BelongsToWithoutIndex),
+            LintLevel::Warn
+        );
+    }
+}
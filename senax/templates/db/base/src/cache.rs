@@ -5,7 +5,8 @@ use futures::future::join_all;
 use fxhash::FxHashMap;
 use once_cell::sync::OnceCell;
 use senax_common::{
-    cache::db_cache::{CacheVal, DbCache, HashVal},
+    cache::db_cache::{CacheMetrics, CacheVal, DbCache, HashVal},
+    cache::single_flight::FlightGuard,
     ShardId,
 };
 use std::{path::Path, sync::Arc};
@@ -39,6 +40,42 @@ impl Cache {
         }
     }
 
+    pub fn dump() -> Result<usize> {
+        if let Some(c) = CACHE.get() {
+            c.dump()
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Waits for the other loads of the hashes and returns the guard of the hashes this caller loads.
+    pub async fn begin_flight(hashes: &[u128]) -> Option<FlightGuard<'static>> {
+        match CACHE.get() {
+            Some(c) => Some(c.single_flight().begin(hashes).await),
+            None => None,
+        }
+    }
+
+    /// Returns the guard of the hashes not being loaded by others without waiting.
+    pub fn try_begin_flight(hashes: &[u128]) -> Option<FlightGuard<'static>> {
+        CACHE.get().map(|c| c.single_flight().try_begin(hashes))
+    }
+
+    pub fn is_stale<T>(value: &T) -> bool
+    where
+        T: CacheVal,
+    {
+        CACHE.get().map(|c| c.is_stale(value)).unwrap_or(false)
+    }
+
+    pub async fn load_dump(is_hot_deploy: bool) -> Result<usize> {
+        if let Some(c) = CACHE.get() {
+            c.load_dump(is_hot_deploy).await
+        } else {
+            Ok(0)
+        }
+    }
+
     pub async fn insert_short(id: &dyn HashVal, value: Arc<dyn CacheVal>) {
         if let Some(cache) = CACHE.get() {
             cache.insert_short(id, value).await;
@@ -177,6 +214,10 @@ impl Cache {
         }
     }
 
+    pub fn metrics() -> Option<CacheMetrics> {
+        CACHE.get().map(|c| c.metrics())
+    }
+
     pub fn long_cache_hit() -> Option<u64> {
         CACHE.get().map(|c| c.long_cache_hit())
     }
@@ -186,6 +227,9 @@ impl Cache {
     pub fn storage_cache_hit() -> Option<u64> {
         CACHE.get().map(|c| c.storage_cache_hit())
     }
+    pub fn remote_cache_hit() -> Option<u64> {
+        CACHE.get().map(|c| c.remote_cache_hit())
+    }
     pub fn cache_request_count() -> Option<u64> {
         CACHE.get().map(|c| c.cache_request_count())
     }
@@ -33,13 +33,20 @@ pub type DbType = sqlx::@{ config.db_type_long() }@;
 pub type DbPool = @{ config.db_type_short() }@Pool;
 pub type DbConnection = @{ config.db_type_short() }@Connection;
 pub type DbConnectOptions = @{ config.db_type_short() }@ConnectOptions;
-pub type DbArguments = sqlx::@{ config.db }@::@{ config.db_type_short() }@Arguments;
+@%- if config.is_sqlite() %@
+pub type DbArguments<'q> = sqlx::@{ config.db }@::@{ config.db_type_short() }@Arguments<'q>;
+@%- else %@
+pub type DbArguments<'q> = sqlx::@{ config.db }@::@{ config.db_type_short() }@Arguments;
+@%- endif %@
 pub type DbRow = sqlx::@{ config.db }@::@{ config.db_type_short() }@Row;
 pub const TX_ISOLATION: Option<&'static str> = @{ tx_isolation|disp_opt }@;
 pub const READ_TX_ISOLATION: Option<&'static str> = @{ read_tx_isolation|disp_opt }@;
 @%- if config.is_mysql() %@
 const CHECK_SQL: &str = "select @@innodb_read_only OR @@read_only OR @@super_read_only";
 const CHECK_SQL_WRITABLE_RESULT: i8 = 0;
+@%- else if config.is_sqlite() %@
+const CHECK_SQL: &str = "PRAGMA query_only";
+const CHECK_SQL_WRITABLE_RESULT: i8 = 0;
 @%- else %@
 const CHECK_SQL: &str = "SHOW transaction_read_only";
 const CHECK_SQL_WRITABLE_RESULT: &str = "off";
@@ -64,12 +71,18 @@ static MAX_CONNECTIONS_FOR_WRITE: AtomicU32 = AtomicU32::new(0);
 static MAX_CONNECTIONS_FOR_READ: AtomicU32 = AtomicU32::new(0);
 static MAX_CONNECTIONS_FOR_CACHE: AtomicU32 = AtomicU32::new(0);
 static SEQUENCE_FETCH_NUM: AtomicU32 = AtomicU32::new(0);
+static REPLICA_MAX_LAG: AtomicU32 = AtomicU32::new(0);
+static REPLICA_LAG_CHECK_INTERVAL: AtomicU32 = AtomicU32::new(0);
+static READ_YOUR_WRITES_TIME: AtomicU32 = AtomicU32::new(0);
 static ENABLE_FAST_FAILOVER: AtomicBool = AtomicBool::new(false);
 static USE_IPV4_ONLY: AtomicBool = AtomicBool::new(false);
 static USE_IPV6_ONLY: AtomicBool = AtomicBool::new(false);
 
 static WRITER: RwLock<Vec<Option<(SocketAddr, DbPool)>>> = RwLock::const_new(Vec::new());
 static SHARD_NUM: AtomicUsize = AtomicUsize::new(0);
+static RESHARD_DB_URL: RwLock<String> = RwLock::const_new(String::new());
+static RESHARD_NUM: AtomicUsize = AtomicUsize::new(0);
+static RESHARD_WRITER: Mutex<Vec<Option<DbPool>>> = Mutex::const_new(Vec::new());
 type AddrPoolMap = FxHashMap<SocketAddr, (Arc<PoolInfo>, Option<bool>)>;
 static READER: OnceCell<Vec<Arc<RwLock<AddrPoolMap>>>> = OnceCell::new();
 static CACHE: OnceCell<Vec<Arc<RwLock<AddrPoolMap>>>> = OnceCell::new();
@@ -90,6 +103,8 @@ struct PoolInfo {
     addr: SocketAddr,
     target: Target,
     inner: DbPool,
+    /// Replication lag in seconds
+    lag: AtomicU64,
 }
 
 impl Drop for PoolInfo {
@@ -134,6 +149,29 @@ async fn config(etcd: &FxHashMap<String, String>, test_mode: bool) -> Result<()>
         let s_url = env_urls(etcd, "@{ db|upper_snake }@_DB_URL")?
             .with_context(|| "@{ db|upper_snake }@_DB_URL is required in the .env file.")?;
         let r_url = env_urls(etcd, "@{ db|upper_snake }@_REPLICA_DB_URL")?.unwrap_or_else(|| s_url.clone());
+        let shard_num = SHARD_NUM.load(Ordering::SeqCst);
+        ensure!(
+            shard_num == 0 || shard_num == split_shard!(s_url).count(),
+            "Changing the number of shards requires a restart."
+        );
+        let reshard_url = env_urls(etcd, "@{ db|upper_snake }@_RESHARD_DB_URL")?.unwrap_or_default();
+        let reshard_num = split_shard!(reshard_url).count();
+        if reshard_num > 0 {
+            ensure!(
+                reshard_num > split_shard!(s_url).count()
+                    && split_shard!(s_url).zip(split_shard!(reshard_url)).all(|(s, r)| s == r),
+                "@{ db|upper_snake }@_RESHARD_DB_URL must list the current shards first, followed by the new shards."
+            );
+            ensure!(
+                reshard_num <= ShardId::MAX as usize + 1,
+                "Number of shards exceeds limit."
+            );
+        }
+        if *RESHARD_DB_URL.read().await != reshard_url {
+            *RESHARD_DB_URL.write().await = reshard_url;
+            RESHARD_WRITER.lock().await.clear();
+        }
+        RESHARD_NUM.store(reshard_num, Ordering::SeqCst);
         *DB_URL.write().await = s_url;
         *REPLICA_DB_URL.write().await = r_url;
         let s_user = env_opt_str(etcd, "@{ db|upper_snake }@_DB_USER");
@@ -157,6 +195,9 @@ async fn config(etcd: &FxHashMap<String, String>, test_mode: bool) -> Result<()>
             let url = url::Url::parse(source)?;
 @%- if config.is_mysql() %@
             v.push(format!("`{}`.", url.path().trim_matches('/')));
+@%- else if config.is_sqlite() %@
+            let _ = url;
+            v.push("\"main\".".to_string());
 @%- else %@
             v.push(format!("\"{}\".", url.path().trim_matches('/')));
 @%- endif %@
@@ -207,6 +248,12 @@ async fn config(etcd: &FxHashMap<String, String>, test_mode: bool) -> Result<()>
     MAX_CONNECTIONS_FOR_CACHE.store(v, Ordering::SeqCst);
     let v = env_u32(etcd, "@{ db|upper_snake }@_SEQUENCE_FETCH_NUM", DEFAULT_SEQUENCE_FETCH_NUM)?;
     SEQUENCE_FETCH_NUM.store(cmp::max(1, v), Ordering::SeqCst);
+    let v = env_u32(etcd, "@{ db|upper_snake }@_REPLICA_MAX_LAG", DEFAULT_REPLICA_MAX_LAG)?;
+    REPLICA_MAX_LAG.store(v, Ordering::SeqCst);
+    let v = env_u32(etcd, "@{ db|upper_snake }@_REPLICA_LAG_CHECK_INTERVAL", DEFAULT_REPLICA_LAG_CHECK_INTERVAL)?;
+    REPLICA_LAG_CHECK_INTERVAL.store(cmp::max(1, v), Ordering::SeqCst);
+    let v = env_u32(etcd, "@{ db|upper_snake }@_READ_YOUR_WRITES_TIME", DEFAULT_READ_YOUR_WRITES_TIME)?;
+    READ_YOUR_WRITES_TIME.store(v, Ordering::SeqCst);
     if let Some(v) = env_opt_str(etcd, "@{ db|upper_snake }@_ENABLE_FAST_FAILOVER") {
         ENABLE_FAST_FAILOVER.store(v.parse()?, Ordering::SeqCst);
     }
@@ -267,6 +314,16 @@ pub async fn init() -> Result<()> {
         }
     });
 
+    tokio::spawn(async {
+        loop {
+            if REPLICA_MAX_LAG.load(Ordering::Relaxed) > 0 {
+                check_replica_lag().await;
+            }
+            let interval = REPLICA_LAG_CHECK_INTERVAL.load(Ordering::Relaxed);
+            tokio::time::sleep(Duration::from_secs(interval as u64)).await;
+        }
+    });
+
     #[cfg(feature = "etcd")]
     tokio::spawn(async {
         loop {
@@ -337,6 +394,56 @@ pub async fn reconnect() {
     }
 }
 
+/// Replication lag of a newly created pool.
+/// A replica is excluded until its lag is measured for the first time.
+fn initial_lag(writable: Option<bool>) -> u64 {
+    if writable != Some(true) && REPLICA_MAX_LAG.load(Ordering::Relaxed) > 0 {
+        u64::MAX
+    } else {
+        0
+    }
+}
+
+/// Measures the replication lag of the read-only connections for reading and cache.
+async fn check_replica_lag() {
+    let max_lag = REPLICA_MAX_LAG.load(Ordering::Relaxed) as u64;
+    let mut pools = Vec::new();
+    for collection in [&READER, &CACHE] {
+        if let Some(list) = collection.get() {
+            for map in list {
+                pools.extend(
+                    map.read()
+                        .await
+                        .values()
+                        .filter(|(_, writable)| *writable != Some(true))
+                        .map(|(pool, _)| pool.clone()),
+                );
+            }
+        }
+    }
+    let mut join_set = JoinSet::new();
+    for pool in pools {
+        join_set.spawn(async move {
+            let lag = match pool.inner.acquire().await {
+                Ok(mut conn) => fetch_replica_lag(conn.as_mut()).await,
+                Err(e) => Err(e.into()),
+            };
+            // A replica whose lag cannot be confirmed is treated as lagging.
+            let lag = lag.unwrap_or_else(|e| {
+                log::warn!(target: "db_@{ db|snake }@::connection", "{}: {}", e, pool.addr);
+                u64::MAX
+            });
+            let old = pool.lag.swap(lag, Ordering::Relaxed);
+            if old <= max_lag && lag > max_lag {
+                log::warn!(target: "db_@{ db|snake }@::connection", "exclude lagging replica for {}: {} ({}s)", pool.target, pool.addr, lag);
+            } else if old > max_lag && lag <= max_lag {
+                log::info!(target: "db_@{ db|snake }@::connection", "restore replica for {}: {}", pool.target, pool.addr);
+            }
+        });
+    }
+    while join_set.join_next().await.is_some() {}
+}
+
 pub async fn connect(
     update_writer: bool,
     update_reader: bool,
@@ -553,8 +660,13 @@ pub async fn reset_database(is_test: bool, clean: bool) -> Result<()> {
             url.set_password(pw.as_deref()).expect("DB_URL ERROR");
         }
         let url = url.as_str();
+@%- if config.is_sqlite() %@
+        if url.contains(":memory:") || url.contains("mode=memory") {
+            continue;
+        }
+@%- endif %@
         if clean {
-@%- if config.is_mysql() %@
+@%- if config.is_mysql() || config.is_sqlite() %@
             DbType::drop_database(url).await?;
 @%- else %@
             DbType::force_drop_database(url).await?;
@@ -591,6 +703,8 @@ pub struct DbConn {
     wo_tx: usize,
     has_read_tx: usize,
     lock_list: Vec<DbLock>,
+    read_your_writes_time: Duration,
+    writer_until: Option<SystemTime>,
     @%- for db in config.outer_db() %@
     pub _@{ db|snake }@_db: ::db_@{ db|snake }@::connection::DbConn,
     @%- endfor %@
@@ -598,7 +712,10 @@ pub struct DbConn {
 
 impl Clone for DbConn {
     fn clone(&self) -> Self {
-        DbConn::__new(self.ctx_no, self.time, self.shard_id)
+        let mut conn = DbConn::__new(self.ctx_no, self.time, self.shard_id);
+        conn.read_your_writes_time = self.read_your_writes_time;
+        conn.writer_until = self.writer_until;
+        conn
     }
 }
 
@@ -639,6 +756,8 @@ impl DbConn {
             wo_tx: 0,
             has_read_tx: 0,
             lock_list: Vec::new(),
+            read_your_writes_time: Duration::from_secs(READ_YOUR_WRITES_TIME.load(Ordering::Relaxed) as u64),
+            writer_until: None,
             @%- for db in config.outer_db() %@
             _@{ db|snake }@_db: ::db_@{ db|snake }@::connection::DbConn::__new(ctx_no, time, shard_id),
             @%- endfor %@
@@ -659,7 +778,7 @@ impl DbConn {
             .nth(shard_id as usize)
             .ok_or_else(|| anyhow::anyhow!("shard_id is out of range."))?;
         let url = url::Url::parse(url)?;
-        Ok(url.host_str().unwrap().to_string())
+        Ok(url.host_str().unwrap_or_default().to_string())
     }
     @%- for db in config.outer_db() %@
 
@@ -684,13 +803,108 @@ impl DbConn {
         0..=(Self::shard_num() - 1) as ShardId
     }
 
+    /// Number of shards in the layout of `@{ db|upper_snake }@_RESHARD_DB_URL`, or 0 if no resharding is in progress.
+    pub fn reshard_num() -> usize {
+        RESHARD_NUM.load(Ordering::Relaxed)
+    }
+
+    /// Log of the rows whose dual write failed. The reshard command copies them again.
+    /// `@{ db|upper_snake }@_RESHARD_PROGRESS_DIR` must be the directory given to `--progress-dir`.
+    pub fn reshard_failure_log() -> std::path::PathBuf {
+        let dir = env::var("@{ db|upper_snake }@_RESHARD_PROGRESS_DIR").unwrap_or_else(|_| ".".to_string());
+        senax_common::shard::failure_log_path(&senax_common::shard::progress_path(std::path::Path::new(&dir), "@{ db|snake }@"))
+    }
+
+    /// Acquires a writer for a shard in the layout of `@{ db|upper_snake }@_RESHARD_DB_URL`.
+    pub async fn _acquire_reshard_writer(shard_id: ShardId) -> Result<PoolConnection<DbType>> {
+        let mut pools = RESHARD_WRITER.lock().await;
+        if pools.len() != Self::reshard_num() {
+            *pools = vec![None; Self::reshard_num()];
+        }
+        let pool = pools
+            .get_mut(shard_id as usize)
+            .context("shard_id is out of range of the new shard layout.")?;
+        if pool.is_none() {
+            let url = RESHARD_DB_URL.read().await.to_owned();
+            let url = split_shard!(url)
+                .nth(shard_id as usize)
+                .context("shard_id is out of range of the new shard layout.")?;
+            let user = DB_USER.read().await.to_owned();
+            let pw = DB_PASSWORD.read().await.to_owned();
+            let (_, options, _) = check_connection(
+                url,
+                &user,
+                &pw,
+                shard_id as usize,
+                None,
+                None,
+                None,
+                Target::Write,
+            )
+            .await?
+            .into_iter()
+            .find(|(_, _, writable)| *writable == Some(true))
+            .with_context(|| format!("There is no writable database for new shard {}.", shard_id))?;
+            *pool = Some(writer_connect_with(db_options_for_write(), options).await?);
+        }
+        let pool = pool.clone().unwrap();
+        drop(pools);
+        Ok(pool.acquire().await?)
+    }
+
     pub fn shard_id(&self) -> ShardId {
         self.shard_id
     }
 
+    /// Sets how long reads go to the writer after a commit. Zero disables it.
+    pub fn set_read_your_writes_time(&mut self, time: Duration) {
+        self.read_your_writes_time = time;
+    }
+
+    /// Time until which reads go to the writer. Carry it through the session to read your own writes in later requests.
+    pub fn writer_until(&self) -> Option<SystemTime> {
+        self.writer_until
+    }
+
+    pub fn set_writer_until(&mut self, until: Option<SystemTime>) {
+        if until != self.writer_until {
+            self.writer_until = until;
+            self.conn.clear();
+        }
+    }
+
+    /// Whether reads currently go to the writer.
+    pub fn reads_from_writer(&self) -> bool {
+        self.writer_until
+            .map(|until| until > SystemTime::now())
+            .unwrap_or_default()
+    }
+
+    fn stick_to_writer(&mut self) {
+        if !self.read_your_writes_time.is_zero() {
+            self.writer_until = Some(SystemTime::now() + self.read_your_writes_time);
+            self.conn.clear();
+        }
+    }
+
     pub fn set_shard_id(&mut self, shard_id: usize) {
         self.shard_id = shard_id as ShardId;
     }
+
+    /// Switches to the shard resolved from a shard key and returns the previous shard.
+    /// Fails if the transaction has already written to another shard.
+    pub fn route_shard(&mut self, shard_id: ShardId) -> Result<ShardId> {
+        let prev_shard_id = self.shard_id;
+        if self.shard_id != shard_id {
+            ensure!(
+                self.tx.keys().all(|s| *s == shard_id),
+                "The transaction has already written to another shard, so shard {} cannot be used in it.",
+                shard_id
+            );
+            self.shard_id = shard_id;
+        }
+        Ok(prev_shard_id)
+    }
     @%- if !config.force_disable_cache %@
 
     /// Cache transaction synchronization
@@ -717,7 +931,7 @@ impl DbConn {
             .clone()
             .context("There are no writable database connections.(code:2)")?;
         let mut conn = pool.acquire().await?;
-@%- if config.is_mysql() %@
+@%- if config.is_mysql() || config.is_sqlite() %@
         let row: (i8,) = sqlx::query_as(CHECK_SQL).fetch_one(conn.as_mut()).await?;
         ensure!(
             row.0 == CHECK_SQL_WRITABLE_RESULT,
@@ -772,17 +986,25 @@ impl DbConn {
             Self::shard_num_range().contains(&shard_id),
             "shard_id is out of range."
         );
-        let pools: Vec<_> = pool_collection.get().unwrap()[shard_id as usize]
+        let max_lag = REPLICA_MAX_LAG.load(Ordering::Relaxed) as u64;
+        let mut pools: Vec<_> = pool_collection.get().unwrap()[shard_id as usize]
             .read()
             .await
             .values()
             .cloned()
             .collect();
-        let len = pools.len();
         ensure!(
-            len != 0,
+            !pools.is_empty(),
             "There are no readable database connections.(code:11)"
         );
+        if max_lag > 0 {
+            pools.retain(|(pool, _)| pool.lag.load(Ordering::Relaxed) <= max_lag);
+            if pools.is_empty() {
+                // All replicas are lagging, so read from the writer.
+                return Self::_acquire_writer(shard_id).await;
+            }
+        }
+        let len = pools.len();
         let mut indexes = Vec::with_capacity(len);
         for i in 0..len {
             indexes.push(i);
@@ -829,6 +1051,9 @@ impl DbConn {
     }
 
     pub async fn acquire_reader(&self) -> Result<PoolConnection<DbType>> {
+        if self.reads_from_writer() {
+            return Self::_acquire_writer(self.shard_id).await;
+        }
         Self::_acquire_reader(self.shard_id).await
     }
 
@@ -900,6 +1125,9 @@ impl DbConn {
     pub async fn end_without_transaction(&mut self) -> Result<()> {
         ensure!(self.wo_tx > 0, "No without transaction is active.");
         self.wo_tx -= 1;
+        if self.wo_tx == 0 {
+            self.stick_to_writer();
+        }
         @%- for db in config.outer_db() %@
         self._@{ db|snake }@_db.end_without_transaction().await?;
         @%- endfor %@
@@ -1023,6 +1251,9 @@ impl DbConn {
             return Ok(());
         }
         self.has_tx = false;
+        if !self.tx.is_empty() {
+            self.stick_to_writer();
+        }
         let mut cache_internal_op_list: FxHashMap<ShardId, Vec<CacheOp>> = FxHashMap::default();
         for (s, v) in self.cache_internal_op_list.drain(..) {
             cache_internal_op_list.entry(s).or_default().push(v);
@@ -1120,7 +1351,11 @@ impl DbConn {
         match self.read_tx.entry(self.shard_id) {
             Entry::Occupied(tx) => Ok(tx.into_mut()),
             Entry::Vacant(v) => {
-                let mut tx = Self::acquire_reader_tx(self.shard_id).await?;
+                let mut tx = if self.reads_from_writer() {
+                    Self::_acquire_writer_tx(self.shard_id).await?
+                } else {
+                    Self::acquire_reader_tx(self.shard_id).await?
+                };
                 set_read_tx_isolation(&mut tx).await?;
                 Ok(v.insert(tx))
             }
@@ -1226,7 +1461,7 @@ impl DbConn {
         sync_map
     }
 
-    pub async fn execute(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments>) -> Result<(u64, u64)> {
+    pub async fn execute(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments<'_>>) -> Result<(u64, u64)> {
         let result = if self.wo_tx() {
             query.execute(self.acquire_writer().await?.as_mut()).await?
         } else {
@@ -1236,7 +1471,7 @@ impl DbConn {
     }
     @%- if config.is_mysql() %@
 
-    pub async fn execute_with_last_insert_id(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments>) -> Result<(u64, u64)> {
+    pub async fn execute_with_last_insert_id(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments<'_>>) -> Result<(u64, u64)> {
         let result = if self.wo_tx() {
             query.execute(self.acquire_writer().await?.as_mut()).await?
         } else {
@@ -1246,7 +1481,7 @@ impl DbConn {
     }
     @%- else %@
 
-    pub async fn execute_with_last_insert_id(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments>) -> Result<(u64, u64)> {
+    pub async fn execute_with_last_insert_id(&mut self, query: sqlx::query::Query<'_, DbType, DbArguments<'_>>) -> Result<(u64, u64)> {
         let result = if self.wo_tx() {
             query.fetch_all(self.acquire_writer().await?.as_mut()).await?
         } else {
@@ -1408,6 +1643,20 @@ impl DbConn {
         } else {
             Err(senax_common::err::LockFailed::new(key.to_string()).into())
         }
+@%- else if config.is_sqlite() %@
+        // SQLite has no advisory locks, so the lock only excludes the tasks of this process.
+        // Other processes that open the same database file are not excluded.
+        let semaphore = if timeout_secs >= 0 {
+            tokio::time::timeout(Duration::from_secs(timeout_secs as u64), lock).await?
+                .with_context(|| senax_common::err::LockFailed::new(key.to_string()))?
+        } else {
+            lock.await?
+        };
+        self.lock_list.push(DbLock {
+            conn: None,
+            _semaphore: semaphore,
+        });
+        Ok(())
 @%- else %@
         let start = std::time::Instant::now();
         let semaphore = if timeout_secs >= 0 {
@@ -1474,7 +1723,9 @@ pub struct DbLock {
 }
 impl Drop for DbLock {
     fn drop(&mut self) {
-        let mut conn = self.conn.take().unwrap();
+        let Some(mut conn) = self.conn.take() else {
+            return;
+        };
         tokio::spawn(async move {
 @%- if config.is_mysql() %@
             if let Err(e) = sqlx::query("DO RELEASE_ALL_LOCKS()")
@@ -1500,6 +1751,21 @@ impl Drop for DbConn {
     }
 }
 
+@%- if config.is_sqlite() %@
+
+/// Parsed options are reused so that all pools share the same in-memory database.
+async fn sqlite_options(url: &str) -> Result<DbConnectOptions> {
+    static OPTIONS: Mutex<BTreeMap<String, DbConnectOptions>> = Mutex::const_new(BTreeMap::new());
+    let mut map = OPTIONS.lock().await;
+    if let Some(options) = map.get(url) {
+        return Ok(options.clone());
+    }
+    let options: DbConnectOptions = url.parse()?;
+    map.insert(url.to_string(), options.clone());
+    Ok(options)
+}
+@%- else %@
+
 #[allow(clippy::explicit_counter_loop)]
 async fn lookup(url: &url::Url) -> Result<Vec<SocketAddr>> {
     use std::net::{IpAddr, Ipv4Addr};
@@ -1535,6 +1801,7 @@ async fn lookup(url: &url::Url) -> Result<Vec<SocketAddr>> {
         Ok(vec![SocketAddr::new(IpAddr::V4(ip), port)])
     }
 }
+@%- endif %@
 
 async fn check_if_writable(options: &DbConnectOptions) -> Result<Option<bool>> {
     use sqlx::{Acquire, Connection};
@@ -1550,7 +1817,7 @@ async fn check_if_writable(options: &DbConnectOptions) -> Result<Option<bool>> {
         Ok(conn) => conn,
     };
     let conn = conn.acquire().await?;
-@%- if config.is_mysql() %@
+@%- if config.is_mysql() || config.is_sqlite() %@
     let row: (i8,) = sqlx::query_as(CHECK_SQL).fetch_one(conn).await?;
     Ok(Some(row.0 == CHECK_SQL_WRITABLE_RESULT))
 @%- else %@
@@ -1559,6 +1826,39 @@ async fn check_if_writable(options: &DbConnectOptions) -> Result<Option<bool>> {
 @%- endif %@
 }
 
+/// Returns the replication lag in seconds. A stopped replication is treated as the maximum lag.
+async fn fetch_replica_lag(conn: &mut DbConnection) -> Result<u64> {
+@%- if config.is_mysql() %@
+    let rows = match sqlx::query("SHOW REPLICA STATUS").fetch_all(&mut *conn).await {
+        Ok(rows) => rows,
+        // MySQL before 8.0.22 and MariaDB
+        Err(_) => sqlx::query("SHOW SLAVE STATUS").fetch_all(&mut *conn).await?,
+    };
+    let Some(row) = rows.first() else {
+        // Not a replica, e.g. an Aurora reader
+        return Ok(0);
+    };
+    let name = if row.try_column("Seconds_Behind_Source").is_ok() {
+        "Seconds_Behind_Source"
+    } else {
+        "Seconds_Behind_Master"
+    };
+    let lag = match row.try_get::<Option<u64>, _>(name) {
+        Ok(lag) => lag,
+        Err(_) => row.try_get::<Option<i64>, _>(name)?.map(|v| v.max(0) as u64),
+    };
+    Ok(lag.unwrap_or(u64::MAX))
+@%- else if config.is_sqlite() %@
+    let _ = conn;
+    Ok(0)
+@%- else %@
+    let sql = "SELECT CASE WHEN NOT pg_is_in_recovery() OR pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+        ELSE COALESCE(EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()), 0) END::BIGINT";
+    let row: (i64,) = sqlx::query_as(sql).fetch_one(conn).await?;
+    Ok(row.0.max(0) as u64)
+@%- endif %@
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn check_connection(
     urls: &str,
@@ -1572,6 +1872,17 @@ async fn check_connection(
 ) -> Result<Vec<(SocketAddr, DbConnectOptions, Option<bool>)>> {
     let re = regex::Regex::new(r"[ \t]+").unwrap();
     let mut join_set: JoinSet<Result<Vec<(SocketAddr, DbConnectOptions)>>> = JoinSet::new();
+@%- if config.is_sqlite() %@
+    let _ = (user, pw);
+    for url in re.split(urls) {
+        let options = sqlite_options(url).await?;
+        join_set.spawn(async move {
+            // A database file has no network address.
+            let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+            Ok(vec![(addr, crate::db_options(options))])
+        });
+    }
+@%- else %@
     for url in re.split(urls) {
         let mut url = url::Url::parse(url)?;
         let user = user.clone();
@@ -1601,6 +1912,7 @@ async fn check_connection(
             Ok(list)
         });
     }
+@%- endif %@
     let mut list = Vec::new();
     let mut check_set = FxHashSet::default();
     while let Some(r) = join_set.join_next().await {
@@ -1699,6 +2011,7 @@ async fn check_connection(
                                     addr,
                                     target,
                                     inner: pool,
+                                    lag: AtomicU64::new(initial_lag(writable)),
                                 });
                                 pools.insert(addr, (pool, writable));
                             }
@@ -1902,6 +2215,16 @@ async fn writer_connect_with(
         .connect_with(options)
         .await?)
 }
+@%- else if config.is_sqlite() %@
+
+async fn writer_connect_with(
+    pool_option: sqlx::pool::PoolOptions<connection::DbType>,
+    options: DbConnectOptions,
+) -> Result<DbPool> {
+    Ok(pool_option
+        .connect_with(options)
+        .await?)
+}
 @%- else %@
 
 async fn writer_connect_with(
@@ -2031,6 +2354,7 @@ async fn reset_reader_pool(
                                     addr,
                                     target,
                                     inner: pool,
+                                    lag: AtomicU64::new(initial_lag(writable)),
                                 });
                                 pools.insert(addr, (pool, writable));
                             }
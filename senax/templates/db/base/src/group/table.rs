@@ -53,6 +53,30 @@ static VERSION_TYPE_ID: u64 = @{ def.get_type_id("VERSION_TYPE_ID") }@;
 static CACHE_SYNC_TYPE_ID: u64 = @{ def.get_type_id("CACHE_SYNC_TYPE_ID") }@;
 static CACHE_TYPE_ID: u64 = @{ def.get_type_id("CACHE_TYPE_ID") }@;
 pub static COL_KEY_TYPE_ID: u64 = @{ def.get_type_id("COL_KEY_TYPE_ID") }@;
+@%- if !config.force_disable_cache %@
+
+#[doc(hidden)]
+pub fn _register_cache_types() {
+    use senax_common::cache::db_cache::{register_decoder, register_type_name};
+    register_type_name(CACHE_TYPE_ID, "@{ group_name }@.@{ model_name }@");
+    register_type_name(PRIMARY_TYPE_ID, "@{ group_name }@.@{ model_name }@:unique");
+    register_type_name(VERSION_TYPE_ID, "@{ group_name }@.@{ model_name }@:version");
+    register_type_name(CACHE_SYNC_TYPE_ID, "@{ group_name }@.@{ model_name }@:sync");
+    register_decoder(CACHE_TYPE_ID, @{ def.cache_fingerprint() }@, |v| Ok(Arc::new(CacheWrapper::_decode(v)?) as Arc<dyn CacheVal>));
+    register_decoder(PRIMARY_TYPE_ID, @{ def.cache_fingerprint() }@, |v| Ok(Arc::new(PrimaryWrapper::_decode(v)?) as Arc<dyn CacheVal>));
+    @%- if def.has_cache_policy() %@
+    use senax_common::cache::db_cache::{register_type_policy, CachePolicy};
+    let policy = CachePolicy {
+        ttl: @{ "{:?}"|format(def.cache_ttl) }@,
+        weight: @{ "{:?}"|format(def.cache_weight) }@,
+        limit: @{ "{:?}"|format(def.cache_limit) }@.map(|v: u64| v * 1024 * 1024),
+        stale: @{ "{:?}"|format(def.stale_while_revalidate) }@,
+    };
+    register_type_policy(CACHE_TYPE_ID, policy);
+    register_type_policy(PRIMARY_TYPE_ID, policy);
+    @%- endif %@
+}
+@%- endif %@
 
 #[derive(Decode, Encode, Pack, Unpack, Clone, Debug)]
 #[cfg_attr(all(debug_assertions, not(feature = "production_mode")), senax(disable_pack, disable_encode))]
@@ -260,7 +284,7 @@ impl Primary {
     }
 }
 #[derive(
-    Hash, PartialEq, Eq, Serialize, Decode, Encode, Pack, Unpack, Clone, Debug, PartialOrd, Ord,
+    Hash, PartialEq, Eq, Serialize, Deserialize, Decode, Encode, Pack, Unpack, Clone, Debug, PartialOrd, Ord,
 )]
 #[cfg_attr(all(debug_assertions, not(feature = "production_mode")), senax(disable_pack, disable_encode))]
 pub struct InnerPrimary(@{ def.primaries()|fmt_join("pub {inner}", ", ") }@);
@@ -36,6 +36,8 @@ pub mod connection;
 pub mod impl_domain;
 @%- endif %@
 #[rustfmt::skip]
+pub mod lock;
+#[rustfmt::skip]
 pub mod misc;
 #[rustfmt::skip]
 #[allow(clippy::module_inception)]
@@ -72,6 +74,9 @@ pub const DEFAULT_DB_MAX_CONNECTIONS_FOR_WRITE: &str = "50";
 pub const DEFAULT_DB_MAX_CONNECTIONS_FOR_READ: &str = "100";
 pub const DEFAULT_DB_MAX_CONNECTIONS_FOR_CACHE: &str = "50";
 pub const DEFAULT_SEQUENCE_FETCH_NUM: &str = "1000";
+pub const DEFAULT_REPLICA_MAX_LAG: &str = "0";
+pub const DEFAULT_REPLICA_LAG_CHECK_INTERVAL: &str = "5";
+pub const DEFAULT_READ_YOUR_WRITES_TIME: &str = "0";
 pub const CONNECT_CHECK_INTERVAL: u64 = 10;
 pub const CHECK_CONNECTION_TIMEOUT: u64 = 8;
 pub const ACQUIRE_CONNECTION_WAIT_TIME: u64 = 10;
@@ -184,10 +189,36 @@ pub async fn _start_test() -> Result<MutexGuard<'static, u8>> {
 pub fn stop() {
     SYS_STOP.store(true, Ordering::SeqCst);
     @%- if !config.force_disable_cache %@
+    match Cache::dump() {
+        Ok(0) => {}
+        Ok(n) => log::info!(target: "db_@{ db|snake }@", "{} cache entries dumped", n),
+        Err(e) => warn!(target: "db_@{ db|snake }@", "cache dump failed: {}", e),
+    }
     Cache::stop();
     @%- endif %@
 }
 
+/// Restores the cache dumped by the previous server.
+/// When hot deploying, the old server writes it after this server starts, so it is loaded in the background.
+/// On a cold start the dump is only discarded.
+#[allow(unused_variables)]
+pub async fn _load_cache_dump(is_hot_deploy: bool) {
+    @%- if !config.force_disable_cache %@
+    let load = async move {
+        match Cache::load_dump(is_hot_deploy).await {
+            Ok(0) => {}
+            Ok(n) => log::info!(target: "db_@{ db|snake }@", "{} cache entries loaded", n),
+            Err(e) => warn!(target: "db_@{ db|snake }@", "cache dump load failed: {}", e),
+        }
+    };
+    if is_hot_deploy {
+        tokio::spawn(load);
+    } else {
+        load.await;
+    }
+    @%- endif %@
+}
+
 pub fn is_stopped() -> bool {
     SYS_STOP.load(Ordering::SeqCst)
 }
@@ -216,6 +247,14 @@ pub async fn clear_local_cache() {
     models::_clear_cache(&sync_map, false).await;
 }
 
+pub fn cache_metrics() -> Option<senax_common::cache::db_cache::CacheMetrics> {
+    @%- if !config.force_disable_cache %@
+    Cache::metrics()
+    @%- else %@
+    None
+    @%- endif %@
+}
+
 pub async fn clear_all_cache() {
     CacheMsg(vec![CacheOp::_AllClear], DbConn::inc_all_cache_sync().await)
         .do_send()
@@ -230,6 +269,10 @@ pub(crate) fn db_options(options: connection::DbConnectOptions) -> connection::D
         .pipes_as_concat(false)
         .no_engine_substitution(false)
         .timezone(None)", "") }@
+        @%- if config.is_sqlite() %@
+        .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+        .foreign_keys(true)
+        @%- endif %@
         .statement_cache_capacity(5)
 }
 
@@ -237,6 +280,10 @@ pub(crate) fn db_options_for_write() -> sqlx::pool::PoolOptions<connection::DbTy
     sqlx::pool::PoolOptions::new()
         .acquire_timeout(Duration::from_secs(5))
         .max_connections(DbConn::max_connections_for_write())
+        @%- if config.is_sqlite() %@
+        // An in-memory database is removed when its last connection is closed.
+        .min_connections(1)
+        @%- endif %@
 }
 
 pub(crate) fn db_options_for_read() -> sqlx::pool::PoolOptions<connection::DbType> {
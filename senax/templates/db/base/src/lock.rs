@@ -0,0 +1,317 @@
+// This code is automatically generated by Senax and is always overwritten.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use fxhash::FxHashMap;
+use senax_common::ShardId;
+use senax_common::err::{LockFailed, LockLost};
+use senax_common::lock::{is_timed_out, new_holder, Lease, LockProvider, RETRY_INTERVAL};
+use sqlx::pool::PoolConnection;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+
+use crate::connection::{DbConn, DbConnection, DbType};
+
+@%- if config.is_mysql() %@
+const INSERT_SQL: &str = "INSERT IGNORE INTO _lock (name, holder, token, expires_at) VALUES (?, '', 0, 0)";
+const ACQUIRE_SQL: &str = "UPDATE _lock SET holder = ?, token = token + 1, expires_at = CAST(UNIX_TIMESTAMP(NOW(3)) * 1000 AS SIGNED) + ? WHERE name = ? AND (holder = '' OR expires_at < CAST(UNIX_TIMESTAMP(NOW(3)) * 1000 AS SIGNED))";
+const RENEW_SQL: &str = "UPDATE _lock SET expires_at = CAST(UNIX_TIMESTAMP(NOW(3)) * 1000 AS SIGNED) + ? WHERE name = ? AND holder = ? AND token = ?";
+const RELEASE_SQL: &str = "UPDATE _lock SET holder = '', expires_at = 0 WHERE name = ? AND holder = ? AND token = ?";
+const INC_TOKEN_SQL: &str = "UPDATE _lock SET token = token + 1 WHERE name = ?";
+const TOKEN_SQL: &str = "SELECT token FROM _lock WHERE name = ?";
+@%- else if config.is_sqlite() %@
+const INSERT_SQL: &str = r#"INSERT INTO "_lock" ("name", "holder", "token", "expires_at") VALUES ($1, '', 0, 0) ON CONFLICT DO NOTHING"#;
+const ACQUIRE_SQL: &str = r#"UPDATE "_lock" SET "holder" = $1, "token" = "token" + 1, "expires_at" = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) + $2 WHERE "name" = $3 AND ("holder" = '' OR "expires_at" < CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER))"#;
+const RENEW_SQL: &str = r#"UPDATE "_lock" SET "expires_at" = CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) + $1 WHERE "name" = $2 AND "holder" = $3 AND "token" = $4"#;
+const RELEASE_SQL: &str = r#"UPDATE "_lock" SET "holder" = '', "expires_at" = 0 WHERE "name" = $1 AND "holder" = $2 AND "token" = $3"#;
+const INC_TOKEN_SQL: &str = r#"UPDATE "_lock" SET "token" = "token" + 1 WHERE "name" = $1"#;
+const TOKEN_SQL: &str = r#"SELECT "token" FROM "_lock" WHERE "name" = $1"#;
+@%- else %@
+const INSERT_SQL: &str = r#"INSERT INTO "_lock" ("name", "holder", "token", "expires_at") VALUES ($1, '', 0, 0) ON CONFLICT DO NOTHING"#;
+const ACQUIRE_SQL: &str = r#"UPDATE "_lock" SET "holder" = $1, "token" = "token" + 1, "expires_at" = CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT) + $2 WHERE "name" = $3 AND ("holder" = '' OR "expires_at" < CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT))"#;
+const RENEW_SQL: &str = r#"UPDATE "_lock" SET "expires_at" = CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT) + $1 WHERE "name" = $2 AND "holder" = $3 AND "token" = $4"#;
+const RELEASE_SQL: &str = r#"UPDATE "_lock" SET "holder" = '', "expires_at" = 0 WHERE "name" = $1 AND "holder" = $2 AND "token" = $3"#;
+const INC_TOKEN_SQL: &str = r#"UPDATE "_lock" SET "token" = "token" + 1 WHERE "name" = $1"#;
+const TOKEN_SQL: &str = r#"SELECT "token" FROM "_lock" WHERE "name" = $1"#;
+@%- endif %@
+
+type Token = @{ config.db_type_switch("u64", "i64") }@;
+
+/// Increments the fencing token of the key in the `_lock` table.
+async fn next_token(conn: &mut DbConnection, key: &str) -> Result<u64> {
+    sqlx::query(INSERT_SQL).bind(key).execute(&mut *conn).await?;
+    sqlx::query(INC_TOKEN_SQL).bind(key).execute(&mut *conn).await?;
+    let row: (Token,) = sqlx::query_as(TOKEN_SQL).bind(key).fetch_one(conn).await?;
+    Ok(row.0 as u64)
+}
+
+/// Lock provider using the `_lock` table.
+/// The lease expires by the database clock unless it is renewed, so a crashed holder does not block others.
+#[derive(Clone, Copy, Default)]
+pub struct LeaseLockProvider {
+    shard_id: ShardId,
+}
+
+impl LeaseLockProvider {
+    pub fn new(shard_id: ShardId) -> LeaseLockProvider {
+        LeaseLockProvider { shard_id }
+    }
+}
+
+#[async_trait]
+impl LockProvider for LeaseLockProvider {
+    async fn acquire(&self, key: &str, ttl: Duration, timeout: Option<Duration>) -> Result<Lease> {
+        let start = Instant::now();
+        let holder = new_holder();
+        let mut conn = DbConn::_new(self.shard_id).acquire_writer().await?;
+        sqlx::query(INSERT_SQL).bind(key).execute(conn.as_mut()).await?;
+        loop {
+            let now = Instant::now();
+            let result = sqlx::query(ACQUIRE_SQL)
+                .bind(&holder)
+                .bind(ttl.as_millis() as i64)
+                .bind(key)
+                .execute(conn.as_mut())
+                .await?;
+            if result.rows_affected() == 1 {
+                let row: (Token,) = sqlx::query_as(TOKEN_SQL)
+                    .bind(key)
+                    .fetch_one(conn.as_mut())
+                    .await?;
+                return Ok(Lease::new(key, row.0 as u64, ttl, holder, now));
+            }
+            if is_timed_out(start, timeout) {
+                return Err(LockFailed::new(key.to_string()).into());
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    async fn renew(&self, lease: &mut Lease) -> Result<()> {
+        let now = Instant::now();
+        let mut conn = DbConn::_new(self.shard_id).acquire_writer().await?;
+        let result = sqlx::query(RENEW_SQL)
+            .bind(lease.ttl.as_millis() as i64)
+            .bind(&lease.key)
+            .bind(&lease.holder)
+            .bind(lease.token as Token)
+            .execute(conn.as_mut())
+            .await?;
+        if result.rows_affected() != 1 {
+            return Err(LockLost::new(lease.key.clone()).into());
+        }
+        lease.expires_at = now + lease.ttl;
+        Ok(())
+    }
+
+    async fn release(&self, lease: Lease) -> Result<()> {
+        let mut conn = DbConn::_new(self.shard_id).acquire_writer().await?;
+        sqlx::query(RELEASE_SQL)
+            .bind(&lease.key)
+            .bind(&lease.holder)
+            .bind(lease.token as Token)
+            .execute(conn.as_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+type LocalKey = (ShardId, String);
+
+static LOCAL_LOCKS: std::sync::Mutex<BTreeMap<LocalKey, Arc<Semaphore>>> =
+    std::sync::Mutex::new(BTreeMap::new());
+
+/// Serializes the acquisitions of a key within the process.
+/// The entry of the key is removed when the last permit is released.
+struct LocalPermit {
+    key: LocalKey,
+    semaphore: Arc<Semaphore>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl LocalPermit {
+    async fn acquire(
+        shard_id: ShardId,
+        key: &str,
+        timeout: Option<Duration>,
+    ) -> Result<LocalPermit> {
+        let key = (shard_id, key.to_string());
+        let semaphore = LOCAL_LOCKS
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(Semaphore::new(1)))
+            .clone();
+        let mut local = LocalPermit {
+            key,
+            semaphore,
+            permit: None,
+        };
+        let lock = local.semaphore.clone().acquire_owned();
+        let permit = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, lock).await.ok(),
+            None => Some(lock.await),
+        };
+        let Some(permit) = permit else {
+            return Err(LockFailed::new(local.key.1.clone()).into());
+        };
+        local.permit = Some(permit?);
+        Ok(local)
+    }
+}
+
+impl Drop for LocalPermit {
+    fn drop(&mut self) {
+        self.permit.take();
+        let mut map = LOCAL_LOCKS.lock().unwrap();
+        // Only the map and this permit refer to the semaphore when nobody is waiting.
+        if Arc::strong_count(&self.semaphore) == 2 && self.semaphore.available_permits() == 1 {
+            map.remove(&self.key);
+        }
+    }
+}
+
+struct AdvisoryLock {
+    conn: Option<Arc<Mutex<PoolConnection<DbType>>>>,
+    _permit: LocalPermit,
+}
+
+/// Lock provider using the advisory locks of the database.
+/// The lock is held by a writer connection while it is alive, so the TTL only decides how often the connection is checked.
+/// The fencing token is counted in the `_lock` table.
+@%- if config.is_sqlite() %@
+/// SQLite has no advisory locks, so the lock only excludes the tasks of this process.
+/// Use `LeaseLockProvider` when other processes open the same database file.
+@%- endif %@
+#[derive(Clone, Default)]
+pub struct AdvisoryLockProvider {
+    shard_id: ShardId,
+    locks: Arc<Mutex<FxHashMap<String, AdvisoryLock>>>,
+}
+
+impl AdvisoryLockProvider {
+    pub fn new(shard_id: ShardId) -> AdvisoryLockProvider {
+        AdvisoryLockProvider {
+            shard_id,
+            locks: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl LockProvider for AdvisoryLockProvider {
+    async fn acquire(&self, key: &str, ttl: Duration, timeout: Option<Duration>) -> Result<Lease> {
+        let start = Instant::now();
+        let permit = LocalPermit::acquire(self.shard_id, key, timeout).await?;
+        let mut conn = DbConn::_new(self.shard_id).acquire_writer().await?;
+@%- if config.is_mysql() %@
+        let wait = timeout
+            .map(|t| t.saturating_sub(start.elapsed()).as_secs() as i64)
+            .unwrap_or(-1);
+        let result: (Option<i64>,) = sqlx::query_as("SELECT GET_LOCK(?, ?)")
+            .bind(key)
+            .bind(wait)
+            .fetch_one(conn.as_mut())
+            .await?;
+        if result.0 != Some(1) {
+            return Err(LockFailed::new(key.to_string()).into());
+        }
+@%- else if config.is_sqlite() %@
+        let _ = start;
+@%- else %@
+        let hash = fxhash::hash64(key) as i64;
+        if timeout.is_none() {
+            sqlx::query("SELECT pg_advisory_lock($1)")
+                .bind(hash)
+                .fetch_all(conn.as_mut())
+                .await?;
+        } else {
+            loop {
+                let result: (bool,) = sqlx::query_as("SELECT pg_try_advisory_lock($1)")
+                    .bind(hash)
+                    .fetch_one(conn.as_mut())
+                    .await?;
+                if result.0 {
+                    break;
+                }
+                if is_timed_out(start, timeout) {
+                    return Err(LockFailed::new(key.to_string()).into());
+                }
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+@%- endif %@
+        let now = Instant::now();
+        let token = match next_token(conn.as_mut(), key).await {
+            Ok(token) => token,
+            Err(e) => {
+                // Closed so that the lock is not left in the pool
+                conn.close_on_drop();
+                return Err(e);
+            }
+        };
+        let holder = new_holder();
+        self.locks.lock().await.insert(
+            holder.clone(),
+            AdvisoryLock {
+@%- if config.is_sqlite() %@
+                conn: None,
+@%- else %@
+                conn: Some(Arc::new(Mutex::new(conn))),
+@%- endif %@
+                _permit: permit,
+            },
+        );
+        Ok(Lease::new(key, token, ttl, holder, now))
+    }
+
+    async fn renew(&self, lease: &mut Lease) -> Result<()> {
+        let now = Instant::now();
+        let conn = match self.locks.lock().await.get(&lease.holder) {
+            Some(lock) => lock.conn.clone(),
+            None => return Err(LockLost::new(lease.key.clone()).into()),
+        };
+        if let Some(conn) = conn {
+            let result = sqlx::query("SELECT 1").execute(conn.lock().await.as_mut()).await;
+            if let Err(e) = result {
+                // The lock has been released along with the session.
+                log::warn!(target: "db_@{ db|snake }@::lock", "{}", e);
+                self.locks.lock().await.remove(&lease.holder);
+                conn.lock().await.close_on_drop();
+                return Err(LockLost::new(lease.key.clone()).into());
+            }
+        }
+        lease.expires_at = now + lease.ttl;
+        Ok(())
+    }
+
+    async fn release(&self, lease: Lease) -> Result<()> {
+        let Some(lock) = self.locks.lock().await.remove(&lease.holder) else {
+            return Ok(());
+        };
+        if let Some(conn) = lock.conn.as_ref() {
+            let mut conn = conn.lock().await;
+@%- if config.is_mysql() %@
+            let result = sqlx::query("DO RELEASE_LOCK(?)")
+                .bind(&lease.key)
+                .execute(conn.as_mut())
+                .await;
+@%- else if config.is_sqlite() %@
+            let result: Result<(), sqlx::Error> = Ok(());
+@%- else %@
+            let result = sqlx::query("SELECT pg_advisory_unlock($1)")
+                .bind(fxhash::hash64(&lease.key) as i64)
+                .execute(conn.as_mut())
+                .await;
+@%- endif %@
+            if let Err(e) = result {
+                conn.close_on_drop();
+                return Err(e.into());
+            }
+        }
+        Ok(())
+    }
+}
+@{-"\n"}@
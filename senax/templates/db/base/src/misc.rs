@@ -108,7 +108,7 @@ pub enum BindValue {
 }
 
 impl BindValue {
-    pub fn bind(self, query: Query<DbType, DbArguments>) -> Query<DbType, DbArguments> {
+    pub fn bind<'q>(self, query: Query<'q, DbType, DbArguments<'q>>) -> Query<'q, DbType, DbArguments<'q>> {
         log::debug!(target: "db_@{ db|snake }@::misc", "bind: {:?}", &self);
         match self {
             BindValue::Bool(v) => query.bind(v),
@@ -655,10 +655,10 @@ impl BindTr for ColOne_ {
             _ => "?",
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{ def.all_fields_except_json()|fmt_join("            ColOne_::{ident}(v) => query.bind(v{bind_as_for_filter}),", "\n") }@
@@ -700,10 +700,10 @@ impl BindTr for ColKey_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
             @{- def.unique_key()|fmt_index_col("
@@ -786,10 +786,10 @@ impl BindTr for ColMany_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        mut query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        mut query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{ def.all_fields_except_json()|fmt_join("            ColMany_::{ident}(v) => {for v in v { query = query.bind(v{bind_as_for_filter}); } query},", "\n") }@
@@ -831,10 +831,10 @@ impl BindTr for ColJson_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{- def.all_fields_only_json()|fmt_join("
@@ -874,10 +874,10 @@ impl BindTr for ColJsonArray_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{- def.all_fields_only_json()|fmt_join("
@@ -887,10 +887,10 @@ impl BindTr for ColJsonArray_ {
     }
 }
 impl BindArrayTr for ColJsonArray_ {
-    fn query_each_bind(
+    fn query_each_bind<'q>(
         self,
-        mut query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        mut query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{- def.all_fields_only_json()|fmt_join("
@@ -930,10 +930,10 @@ impl BindTr for ColGeo_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @{- def.all_fields_only_geo()|fmt_join("
@@ -973,10 +973,10 @@ impl BindTr for ColGeoDistance_ {
             _ => unreachable!(),
         }
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "bind: {:?}", &self);
         match self {
 @%- if !config.is_mysql() %@
@@ -1077,10 +1077,10 @@ impl ColRelTr for ColRel_ {
         };
 @%- endif %@
     }
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments> {
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>> {
 @%- if def.relations_one_and_belonging(Joinable::Filter, false).len() + def.relations_many(Joinable::Filter, false).len() + def.relations_belonging_outer_db(Joinable::Filter, false).len() > 0 %@
         match self {
 @{- def.relations_one_and_belonging(Joinable::Filter, false)|fmt_rel_join("
@@ -1182,7 +1182,7 @@ pub fn write_having_rel(buf: &mut String, filter: &Option<Box<Filter_>>, cols1:
     }
 }
 
-pub fn bind_for_rel_query(filter: Option<Box<Filter_>>, query: Query<'_, DbType, DbArguments>) -> Query<'_, DbType, DbArguments> {
+pub fn bind_for_rel_query<'q>(filter: Option<Box<Filter_>>, query: Query<'q, DbType, DbArguments<'q>>) -> Query<'q, DbType, DbArguments<'q>> {
     if let Some(filter) = filter {
         filter.bind_to_query(query)
     } else {
@@ -28,31 +28,31 @@ pub trait BindTr {
     fn len(&self) -> usize {
         1
     }
-    fn bind_to_query(self, query: Query<'_, DbType, DbArguments>)
-        -> Query<'_, DbType, DbArguments>;
+    fn bind_to_query<'q>(self, query: Query<'q, DbType, DbArguments<'q>>)
+        -> Query<'q, DbType, DbArguments<'q>>;
 }
 #[allow(dead_code)]
 pub trait BindArrayTr {
-    fn query_each_bind(
+    fn query_each_bind<'q>(
         self,
-        query: Query<'_, DbType, DbArguments>,
-    ) -> Query<'_, DbType, DbArguments>;
+        query: Query<'q, DbType, DbArguments<'q>>,
+    ) -> Query<'q, DbType, DbArguments<'q>>;
 }
 pub trait ColRelTr {
     fn write_rel(&self, buf: &mut String, idx: usize, without_key: bool, shard_id: ShardId, is_outer: bool);
     fn write_key(&self, buf: &mut String);
-    fn bind_to_query(self, query: Query<'_, DbType, DbArguments>)
-        -> Query<'_, DbType, DbArguments>;
+    fn bind_to_query<'q>(self, query: Query<'q, DbType, DbArguments<'q>>)
+        -> Query<'q, DbType, DbArguments<'q>>;
 }
 pub trait FilterTr
 where
     Self: Sized,
 {
     fn write(&self, buf: &mut String, idx: usize, trash_mode: &mut TrashMode, shard_id: ShardId, is_outer: bool);
-    fn bind_to_query(
+    fn bind_to_query<'q>(
         self,
-        query: sqlx::query::Query<'_, DbType, DbArguments>,
-    ) -> sqlx::query::Query<'_, DbType, DbArguments>;
+        query: sqlx::query::Query<'q, DbType, DbArguments<'q>>,
+    ) -> sqlx::query::Query<'q, DbType, DbArguments<'q>>;
     fn write_where(
         filter: &Option<Self>,
         trash_mode: TrashMode,
@@ -255,6 +255,68 @@ macro_rules! filter {
                     buf.push_str(c.name());
                     buf.push_str(", ST_Buffer(ST_GeomFromGeoJSON(?, 1, ?), ? * 1.1)) AND ");
                 }
+@%- else if config.is_sqlite() %@
+                Filter_::Contains(c, p) => {
+                    buf.push_str("NOT EXISTS (WITH _t(v) AS (SELECT value FROM json_each(");
+                    buf.push_str(c.name());
+                    if p.is_some() {
+                        buf.push_str(", ?");
+                    }
+                    buf.push_str(")) SELECT 1 FROM json_each(?) AS _c WHERE _c.value NOT IN (SELECT v FROM _t)) AND ");
+                }
+                Filter_::JsonIn(c, p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) IN (SELECT value FROM json_each(?)) AND ");
+                }
+                Filter_::JsonContainsPath(c, _p) => {
+                    buf.push_str("json_type(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) IS NOT NULL AND ");
+                }
+                Filter_::JsonEq(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) = json_extract(?, '$') AND ");
+                }
+                Filter_::JsonIsNull(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) IS NULL AND ");
+                }
+                Filter_::JsonIsNotNull(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) IS NOT NULL AND ");
+                }
+                Filter_::JsonLt(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) < json_extract(?, '$') AND ");
+                }
+                Filter_::JsonLte(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) <= json_extract(?, '$') AND ");
+                }
+                Filter_::JsonGt(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) > json_extract(?, '$') AND ");
+                }
+                Filter_::JsonGte(c, _p) => {
+                    buf.push_str("json_extract(");
+                    buf.push_str(c.name());
+                    buf.push_str(", ?) >= json_extract(?, '$') AND ");
+                }
+                Filter_::GeoEquals(_)
+                | Filter_::Within(_)
+                | Filter_::Intersects(_)
+                | Filter_::Crosses(_)
+                | Filter_::DWithin(_) => {
+                    // Spatial columns are rejected for SQLite when the schema is parsed.
+                    unreachable!("Spatial filters are not supported on SQLite.");
+                }
 @%- else %@
                 Filter_::Contains(c, p) => {
                     if p.is_some() {
@@ -408,10 +470,10 @@ macro_rules! filter {
                 }
             };
         }
-        fn bind_to_query(
+        fn bind_to_query<'q>(
             self,
-            mut query: sqlx::query::Query<'_, DbType, DbArguments>,
-        ) -> sqlx::query::Query<'_, DbType, DbArguments> {
+            mut query: sqlx::query::Query<'q, DbType, DbArguments<'q>>,
+        ) -> sqlx::query::Query<'q, DbType, DbArguments<'q>> {
             match self {
                 Filter_::WithTrashed => query,
                 Filter_::OnlyTrashed => query,
@@ -9,6 +9,7 @@
 use ::anyhow::Result;
 use ::fxhash::FxHashMap;
 use ::senax_common::ShardId;
+use ::senax_common::shard::ReshardProgress;
 use ::std::path::Path;
 use ::std::sync::Arc;
 use ::std::time::Duration;
@@ -69,6 +70,26 @@ pub async fn check(shard_id: ShardId) -> Vec<Result<()>> {
     ]
 }
 
+#[rustfmt::skip]
+pub async fn warm_up(shard_id: ShardId) -> Result<()> {
+@%- for (name, def) in models %@
+@%- if def.warm_up() %@
+    _base::_@{ def.mod_name() }@::warm_up(shard_id).await?;
+@%- endif %@
+@%- endfor %@
+    Ok(())
+}
+
+#[rustfmt::skip]
+pub async fn reshard(progress: &mut ReshardProgress, batch_size: usize, interval: Duration) -> Result<()> {
+@%- for (name, def) in models %@
+@%- if def.shard_key_index().is_some() %@
+    _base::_@{ def.mod_name() }@::reshard(progress, batch_size, interval).await?;
+@%- endif %@
+@%- endfor %@
+    Ok(())
+}
+
 #[rustfmt::skip]
 impl super::GroupCacheOpTr for CacheOp {
     #[allow(unreachable_patterns)]
@@ -38,7 +38,7 @@ use ::zstd::{decode_all, encode_all};
 use ::db::cache::Cache;
 @%- else %@
 @% endif %@
-use ::db::connection::{DbArguments, DbConn, DbRow, DbType};
+use ::db::connection::{DbArguments, DbConn, DbConnection, DbRow, DbType};
 use crate::misc::{ToBindableJson as _};
 use crate::repositories::CacheOpTr;
 use ::db::misc::{BindValue, Count, Exists, Updater, Size, TrashMode, UpdaterForInner as _};
@@ -97,6 +97,9 @@ type __Updater__ = _@{pascal_name}@Updater;
 
 #[allow(clippy::needless_if)]
 pub(crate) async fn init() -> Result<()> {
+    @%- if !config.force_disable_cache %@
+    _register_cache_types();
+    @%- endif %@
     if ALL_ROWS_CACHE.get().is_none() {
         ALL_ROWS_CACHE.set(DbConn::shard_num_range().map(|_| ArcSwapOption::const_empty()).collect()).unwrap();
         CACHE_RESET_SYNC.set(DbConn::shard_num_range().map(|_| RwLock::new(0)).collect()).unwrap();
@@ -132,6 +135,21 @@ pub(crate) async fn check(shard_id: ShardId) -> Result<()> {
     _repo_::query().limit(0).select(&mut conn).await.with_context(|| format!("Schema mismatch detected in the {TABLE_NAME} model on shard {shard_id}."))?;
     Ok(())
 }
+@%- if def.warm_up() %@
+
+pub(crate) async fn warm_up(shard_id: ShardId) -> Result<()> {
+    let mut conn = DbConn::_new(shard_id);
+    @%- if def.warm_up_limit.is_some() %@
+    _repo_::query().limit(@{ def.warm_up_limit.unwrap() }@).select_from_cache(&mut conn).await?;
+    @%- else %@
+    _repo_::query().select_from_cache(&mut conn).await?;
+    @%- endif %@
+    @%- if def.enable_all_rows_cache() && !def.enable_filtered_rows_cache() %@
+    _repo_::find_all_from_cache(&conn, None).await?;
+    @%- endif %@
+    Ok(())
+}
+@%- endif %@
 
 pub(crate) async fn init_db(path: &Path) -> Result<()> {
     @%- if def.enable_delayed_insert() %@
@@ -900,6 +918,33 @@ async fn handle_delayed_msg_insert_from_memory() {
     if vec.is_empty() {
         return;
     }
+    @%- if def.shard_key_index().is_some() %@
+    let mut shard_map: FxHashMap<ShardId, Vec<ForInsert>> = FxHashMap::default();
+    for data in vec {
+        match _shard_id_of([InnerPrimary::from(&data._data)]) {
+            Ok(shard_id) => shard_map.entry(shard_id.unwrap_or_default()).or_default().push(data),
+            Err(err) => {
+                error!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", table = TABLE_NAME; "INSERT DELAYED FAILED:{}", err);
+            }
+        }
+    }
+    let mut handles = Vec::new();
+    for (shard_id, vec) in shard_map {
+        let mut conn = DbConn::_new(shard_id);
+        match conn.begin_immediately().await {
+            Ok(_) => {
+                handles.push(_handle_delayed_msg_insert_from_memory(conn, vec));
+            }
+            Err(err) => {
+                error!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", table = TABLE_NAME; "INSERT DELAYED ERROR:{}", err);
+                if let Err(err) = push_delayed_db(&vec) {
+                    error!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "push_delayed_db:{}", err);
+                }
+            }
+        }
+    }
+    future::join_all(handles).await;
+    @%- else %@
     let mut conn_list = Vec::new();
     for shard_id in DbConn::shard_num_range() {
         let mut conn = DbConn::_new(shard_id);
@@ -927,6 +972,7 @@ async fn handle_delayed_msg_insert_from_memory() {
         }
     }
     future::join_all(handles).await;
+    @%- endif %@
 }
 
 async fn _handle_delayed_msg_insert_from_memory(mut conn: DbConn, vec: Vec<ForInsert>) {
@@ -1002,7 +1048,23 @@ async fn handle_delayed_msg_insert_from_disk(shard_id: ShardId) -> Result<()> {
     if vec.is_empty() {
         return Ok(());
     }
+    @%- if def.shard_key_index().is_some() %@
+    // Rows of other shards are routed through the memory queue.
+    let (vec, others): (Vec<_>, Vec<_>) = vec
+        .into_iter()
+        .partition(|data| _shard_id_of([InnerPrimary::from(&data._data)]).ok().flatten() == Some(shard_id));
+    if !others.is_empty() {
+        for data in others {
+            DELAYED_INSERT_QUEUE.push(data);
+        }
+        DelayedActor::handle(DelayedMsg::InsertFromMemory);
+    }
+    if !vec.is_empty() {
+        _handle_delayed_msg_insert_from_memory(conn, vec).await;
+    }
+    @%- else %@
     _handle_delayed_msg_insert_from_memory(conn, vec).await;
+    @%- endif %@
     if db.is_empty() {
         info!("Insert delayed successfully recovered.");
     } else if !db::is_stopped() {
@@ -2042,6 +2104,7 @@ pub struct QueryBuilder {
     raw_query: String,
     bind: Vec<BindValue>,
     joiner: Option<Box<Joiner_>>,
+    all_shards: bool,
 }
 
 impl QueryBuilder {
@@ -2132,6 +2195,7 @@ impl QueryBuilder {
         Ok(result?)
     }
     async fn __select(self, sql_cols: &str, conn: &mut DbConn) -> Result<Vec<(DbRow, BTreeMap<&'static str, bool>)>> {
+        self._check_shard(conn)?;
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "filter digest:{}", filter_digest);
         let filter_flag_names: Vec<_> = self.filter_flag.keys().cloned().collect();
@@ -2160,6 +2224,41 @@ impl QueryBuilder {
             .collect())
     }
 
+    /// Fails when the filter selects a shard key of another shard than the connection,
+    /// because such rows are never found on this shard.
+    fn _check_shard(&self, conn: &DbConn) -> Result<()> {
+        @%- if let Some(shard_key) = def.shard_key.as_ref() %@
+        fn shard_id_of(filter: &Filter_) -> Result<Option<ShardId>> {
+            match filter {
+                Filter_::Eq(ColOne_::@{ shard_key|ident }@(v)) => Ok(Some(_repo_::shard_id_of(v)?)),
+                Filter_::And(filters) => {
+                    for filter in filters {
+                        if let Some(shard_id) = shard_id_of(filter)? {
+                            return Ok(Some(shard_id));
+                        }
+                    }
+                    Ok(None)
+                }
+                _ => Ok(None),
+            }
+        }
+        if !self.all_shards
+            && let Some(filter) = &self.filter
+            && let Some(shard_id) = shard_id_of(filter)?
+        {
+            ensure!(
+                shard_id == conn.shard_id(),
+                "The {TABLE_NAME} query selects the shard key of shard {}, but the connection is on shard {}.",
+                shard_id,
+                conn.shard_id()
+            );
+        }
+        @%- else %@
+        let _ = conn;
+        @%- endif %@
+        Ok(())
+    }
+
     #[allow(clippy::if_same_then_else)]
     fn _sql(&self, sql_cols: &str, for_update: bool, shard_id: ShardId, filter_digest: &str) -> String {
         let force_indexes = make_force_indexes(filter_digest);
@@ -2187,8 +2286,17 @@ impl QueryBuilder {
             write!(sql, " limit {}", limit).unwrap();
         }
         if let Some(offset) = self.offset {
+            @%- if config.is_sqlite() %@
+            if self.limit.is_none() {
+                write!(sql, " limit -1").unwrap();
+            }
+            @%- endif %@
             write!(sql, " offset {}", offset).unwrap();
         }
+        @%- if config.is_sqlite() %@
+        // SQLite serializes all writers, so row locks are not needed.
+        let _ = for_update;
+        @%- else %@
         if for_update {
             if self.skip_locked {
                 write!(sql, " FOR UPDATE SKIP LOCKED").unwrap();
@@ -2196,10 +2304,11 @@ impl QueryBuilder {
                 write!(sql, " FOR UPDATE").unwrap();
             }
         }
+        @%- endif %@
         sql
     }
 
-    fn _bind(self, mut query: Query<DbType, DbArguments>, filter_flag: bool) -> Query<DbType, DbArguments> {
+    fn _bind<'q>(self, mut query: Query<'q, DbType, DbArguments<'q>>, filter_flag: bool) -> Query<'q, DbType, DbArguments<'q>> {
         if filter_flag {
             for (_name, filter) in self.filter_flag {
                 query = filter.bind_to_query(query);
@@ -2216,6 +2325,7 @@ impl QueryBuilder {
     }
 
     async fn _select_stream(self, sql_cols: &str, conn: &mut DbConn) -> Result<mpsc::Receiver<(DbRow, BTreeMap<&'static str, bool>)>> {
+        self._check_shard(conn)?;
         let ctx_no = conn.ctx_no();
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         let filter_flag_names: Vec<_> = self.filter_flag.keys().cloned().collect();
@@ -2256,6 +2366,7 @@ impl QueryBuilder {
 
     #[allow(clippy::if_same_then_else)]
     async fn _select_from_cache(mut self, conn: &mut DbConn) -> Result<Vec<_@{ pascal_name }@Cache>> {
+        self._check_shard(conn)?;
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "filter digest:{}", filter_digest);
         let force_indexes = make_force_indexes(&filter_digest);
@@ -2316,6 +2427,7 @@ impl QueryBuilder {
     @%- if !def.disable_update() %@
 
     pub async fn select_for_update(mut self, conn: &mut DbConn) -> Result<Vec<_@{ pascal_name }@Updater>> {
+        self._check_shard(conn)?;
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "filter digest:{}", filter_digest);
         let filter_flag_names: Vec<_> = self.filter_flag.keys().cloned().collect();
@@ -2439,12 +2551,125 @@ impl QueryBuilder {
             }
         })
     }
+
+    /// Runs the query on all shards concurrently and merges the results by order_by.
+    /// limit and offset are applied to the merged results.
+    /// Rows left on their old shard by resharding are excluded,
+    /// and each shard is read further until it returns limit + offset owned rows.
+    pub async fn select_all_shards(mut self, conn: &mut DbConn) -> Result<Vec<_@{ pascal_name }@>> {
+        ensure!(self.raw_order.is_none(), "raw_order_by cannot be merged across shards.");
+        _check_order(&self.order)?;
+        self.all_shards = true;
+        let (offset, limit) = (self.offset.take(), self.limit);
+        self.limit = limit.map(|limit| limit + offset.unwrap_or_default());
+        let ctx_no = conn.ctx_no();
+        let lists = future::try_join_all(DbConn::shard_num_range().map(|shard_id| {
+            let mut query = self.clone();
+            async move {
+                let mut conn = DbConn::_new_with_ctx(ctx_no, shard_id);
+                let mut list = Vec::new();
+                loop {
+                    let page = query.clone().select(&mut conn).await?;
+                    let fetched = page.len();
+                    list.extend(page.into_iter().filter(|v| _is_owned(shard_id, &v._inner)));
+                    match query.limit {
+                        Some(need) if fetched == need && list.len() < need => {
+                            query.offset = Some(query.offset.unwrap_or_default() + fetched);
+                        }
+                        _ => break,
+                    }
+                }
+                Ok::<_, anyhow::Error>(list)
+            }
+        }))
+        .await?;
+        let order = self.order.unwrap_or_default();
+        Ok(::senax_common::shard::merge_sorted(lists, |a, b| _cmp_order(&order, &a._inner, &b._inner), offset, limit))
+    }
+
+    /// Counts the rows of all shards concurrently.
+    /// While resharding, rows that have been copied to another shard are counted twice until the cleanup.
+    pub async fn count_all_shards(mut self, conn: &mut DbConn) -> Result<i64> {
+        self.all_shards = true;
+        let ctx_no = conn.ctx_no();
+        let counts = future::try_join_all(DbConn::shard_num_range().map(|shard_id| {
+            let query = self.clone();
+            async move {
+                let mut conn = DbConn::_new_with_ctx(ctx_no, shard_id);
+                query.count(&mut conn).await
+            }
+        }))
+        .await?;
+        Ok(counts.into_iter().sum())
+    }
+
+    /// Streams the rows of all shards merged by order_by.
+    /// limit and offset are applied to the merged stream.
+    /// Rows left on their old shard by resharding are excluded,
+    /// so the shards are read without a limit until the merged stream has enough rows.
+    pub async fn select_stream_all_shards(mut self, conn: &mut DbConn) -> Result<impl Stream<Item = _@{ pascal_name }@>> {
+        ensure!(self.raw_order.is_none(), "raw_order_by cannot be merged across shards.");
+        _check_order(&self.order)?;
+        self.all_shards = true;
+        let (offset, limit) = (self.offset.take().unwrap_or_default(), self.limit.take());
+        let ctx_no = conn.ctx_no();
+        let streams = future::try_join_all(DbConn::shard_num_range().map(|shard_id| {
+            let query = self.clone();
+            async move {
+                let mut conn = DbConn::_new_with_ctx(ctx_no, shard_id);
+                query
+                    .select_stream(&mut conn)
+                    .await
+                    .map(|stream| stream.filter(move |v| future::ready(_is_owned(shard_id, &v._inner))).boxed())
+            }
+        }))
+        .await?;
+        let order = self.order.unwrap_or_default();
+        Ok(::senax_common::shard::merge_sorted_streams(streams, move |a: &_@{ pascal_name }@, b: &_@{ pascal_name }@| _cmp_order(&order, &a._inner, &b._inner))
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX)))
+    }
     @#- @%- if !def.disable_update() || def.soft_delete().is_some() %@ #@
+    @%- if !def.disable_update() || !def.disable_delete() %@
+
+    /// While resharding, locks the rows that update or force_delete will change
+    /// and returns their primary keys for the dual write.
+    async fn _reshard_ids(&self, conn: &mut DbConn) -> Result<Vec<InnerPrimary>> {
+        @%- if def.shard_key_index().is_some() %@
+        if DbConn::reshard_num() == 0 {
+            return Ok(Vec::new());
+        }
+        let mut query_builder = self.clone();
+        query_builder.filter_flag = BTreeMap::new();
+        query_builder.offset = None;
+        query_builder.skip_locked = false;
+        let filter_digest = query_builder.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
+        let sql = query_builder._sql(r#"@{ def.primaries()|fmt_join("{col_query}", ", ") }@"#, !conn.wo_tx(), conn.shard_id(), &filter_digest);
+        @%- if !config.is_mysql() %@
+        let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+        @%- endif %@
+        let mut query = sqlx::query(&sql);
+        debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
+        query = query_builder._bind(query, false);
+        let result = if conn.wo_tx() {
+            query.fetch_all(conn.acquire_writer().await?.as_mut()).await?
+        } else {
+            query.fetch_all(conn.get_tx().await?.as_mut()).await?
+        };
+        Ok(result.iter().map(InnerPrimary::from_row).collect::<sqlx::Result<_>>()?)
+        @%- else %@
+        let _ = conn;
+        Ok(Vec::new())
+        @%- endif %@
+    }
+    @%- endif %@
     @%- if !def.disable_update() %@
 
     #[allow(unused_mut)]
     #[allow(clippy::if_same_then_else)]
     pub async fn update(self, conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<u64> {
+        self._check_shard(conn)?;
+        let reshard_ids = self._reshard_ids(conn).await?;
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "filter digest:{}", filter_digest);
         let force_indexes = make_force_indexes(&filter_digest);
@@ -2505,6 +2730,7 @@ impl QueryBuilder {
         if now.elapsed() > std::time::Duration::from_secs(1) {
             warn!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "[SLOW QUERY] time={}s digest={:?}", now.elapsed().as_millis() as f64 / 1000.0, filter_digest);
         }
+        _dual_write(conn, &reshard_ids).await;
         @%- if !config.force_disable_cache %@
         if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
             conn.push_cache_op(CacheOp::InvalidateAll.wrap()).await;
@@ -2534,6 +2760,8 @@ impl QueryBuilder {
     #[allow(unused_mut)]
     #[allow(clippy::if_same_then_else)]
     pub async fn force_delete(self, conn: &mut DbConn) -> Result<u64> {
+        self._check_shard(conn)?;
+        let reshard_ids = self._reshard_ids(conn).await?;
         let filter_digest = self.filter.as_ref().map(|f| f.to_string()).unwrap_or_default();
         debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "filter digest:{}", filter_digest);
         let force_indexes = make_force_indexes(&filter_digest);
@@ -2579,6 +2807,7 @@ impl QueryBuilder {
         if now.elapsed() > std::time::Duration::from_secs(1) {
             warn!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(); "[SLOW QUERY] time={}s digest={:?}", now.elapsed().as_millis() as f64 / 1000.0, filter_digest);
         }
+        _dual_write(conn, &reshard_ids).await;
         @%- if !config.force_disable_cache %@
         if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
             conn.push_cache_op(CacheOp::InvalidateAll.wrap()).await;
@@ -2730,6 +2959,380 @@ fn primaries_to_str(s: &[InnerPrimary]) -> String {
     });
     format!("@{ def.primaries()|fmt_join_with_paren("{col}", ", ") }@={}", v.join(","))
 }
+@%- if let Some(shard_key_index) = def.shard_key_index() %@
+
+fn _shard_id_of<I>(ids: I) -> Result<Option<ShardId>>
+where
+    I: IntoIterator,
+    I::Item: Borrow<InnerPrimary>,
+{
+    let mut shard_id = None;
+    for id in ids {
+        let s = _repo_::shard_id_of(&id.borrow().@{ shard_key_index }@)?;
+        ensure!(shard_id.is_none_or(|v| v == s), "The {TABLE_NAME} rows span multiple shards.");
+        shard_id = Some(s);
+    }
+    Ok(shard_id)
+}
+
+/// Routes the connection to the shard of the given rows and returns the previous shard.
+/// The caller restores it when the routed call finishes.
+fn _route_shard<I>(conn: &mut DbConn, ids: I) -> Result<ShardId>
+where
+    I: IntoIterator,
+    I::Item: Borrow<InnerPrimary>,
+{
+    match _shard_id_of(ids)? {
+        Some(shard_id) => conn.route_shard(shard_id),
+        None => Ok(conn.shard_id()),
+    }
+}
+
+/// Whether the row belongs to the shard in the current layout.
+/// Rows moved by resharding are also left on their old shard until the cleanup.
+fn _is_owned(shard_id: ShardId, data: &Data) -> bool {
+    _repo_::shard_id_of(&InnerPrimary::from(data).@{ shard_key_index }@).is_ok_and(|v| v == shard_id)
+}
+
+/// Shard of the row in a layout with `shard_num` shards.
+fn _reshard_id_of(id: &InnerPrimary, shard_num: usize) -> Result<ShardId> {
+    ::senax_common::shard::shard_id_of(&id.@{ shard_key_index }@, &@{ "{:?}"|format(def.shard_ranges()) }@, shard_num)
+}
+
+/// While resharding, copies the written rows that move to another shard in the new layout
+/// once the transaction is committed.
+async fn _dual_write<I>(conn: &mut DbConn, ids: I)
+where
+    I: IntoIterator,
+    I::Item: Borrow<InnerPrimary>,
+{
+    let reshard_num = DbConn::reshard_num();
+    if reshard_num == 0 {
+        return;
+    }
+    let shard_id = conn.shard_id();
+    let ids: Vec<InnerPrimary> = ids
+        .into_iter()
+        .map(|id| id.borrow().clone())
+        .filter(|id| _reshard_id_of(id, reshard_num).is_ok_and(|v| v != shard_id))
+        .collect();
+    if ids.is_empty() {
+        return;
+    }
+    conn.push_callback(Box::new(move || {
+        async move {
+            if let Err(e) = _reshard_rows(shard_id, &ids).await {
+                error!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ids = primaries_to_str(&ids); "dual write failed: {}", e);
+                // The reshard command copies the rows again and refuses the swap until then.
+                let keys: Vec<String> = ids.iter().filter_map(|id| serde_json::to_string(id).ok()).collect();
+                if let Err(e) = ::senax_common::shard::record_failures(&DbConn::reshard_failure_log(), TABLE_NAME, &keys) {
+                    error!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ids = primaries_to_str(&ids); "failed to record the dual write failure: {}", e);
+                }
+            }
+        }
+        .boxed()
+    }))
+    .await;
+}
+
+/// Replaces the rows on the new shards with the current rows on the source shard.
+/// Rows that no longer exist on the source shard are deleted.
+async fn _reshard_rows(shard_id: ShardId, ids: &[InnerPrimary]) -> Result<u64> {
+    let reshard_num = DbConn::reshard_num();
+    let mut map: BTreeMap<ShardId, Vec<InnerPrimary>> = BTreeMap::new();
+    for id in ids {
+        let target = _reshard_id_of(id, reshard_num)?;
+        if target != shard_id {
+            map.entry(target).or_default().push(id.clone());
+        }
+    }
+    let mut copied = 0;
+    let mut source = DbConn::_new(shard_id).acquire_writer().await?;
+    for (target, ids) in map {
+        let mut conn = DbConn::_acquire_reshard_writer(target).await?;
+        let mut tx = ::sqlx::Connection::begin(conn.as_mut()).await?;
+        // Deleting first locks the rows on the new shard, so the last writer reads the latest rows.
+        _reshard_delete(&mut tx, &ids).await?;
+        let rows = _reshard_fetch(source.as_mut(), &ids).await?;
+        // A concurrent writer may insert the same rows from the latest source after the delete.
+        _reshard_insert(&mut tx, &rows, true).await?;
+        tx.commit().await?;
+        copied += rows.len() as u64;
+    }
+    Ok(copied)
+}
+
+async fn _reshard_fetch(conn: &mut DbConnection, ids: &[InnerPrimary]) -> Result<Vec<Data>> {
+    let mut list = Vec::with_capacity(ids.len());
+    for ids in ids.chunks(IN_CONDITION_LIMIT) {
+        let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
+        let sql = format!(
+            r#"SELECT {} FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
+            Data::_sql_cols(@{ is_mysql_str }@),
+            &q[0..q.len() - 1]
+        );
+        @%- if !config.is_mysql() %@
+        let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+        @%- endif %@
+        let mut query = sqlx::query(&sql);
+        for id in ids {
+            @{- def.primaries()|fmt_join("
+            query = query.bind(id.{index}{bind_as});", "") }@
+        }
+        for row in query.fetch_all(&mut *conn).await.context(err::ErrorTable(TABLE_NAME))? {
+            list.push(Data::from_row(&row)?);
+        }
+    }
+    Ok(list)
+}
+
+async fn _reshard_delete(conn: &mut DbConnection, ids: &[InnerPrimary]) -> Result<u64> {
+    let mut rows_affected = 0;
+    for ids in ids.chunks(IN_CONDITION_LIMIT) {
+        let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
+        let sql = format!(
+            r#"DELETE FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
+            &q[0..q.len() - 1]
+        );
+        @%- if !config.is_mysql() %@
+        let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+        @%- endif %@
+        let mut query = sqlx::query(&sql);
+        for id in ids {
+            @{- def.primaries()|fmt_join("
+            query = query.bind(id.{index}{bind_as});", "") }@
+        }
+        rows_affected += query.execute(&mut *conn).await.context(err::ErrorTable(TABLE_NAME))?.rows_affected();
+    }
+    Ok(rows_affected)
+}
+
+/// Inserts rows with all stored columns, including auto increment and version columns.
+async fn _reshard_insert(conn: &mut DbConnection, rows: &[Data], ignore: bool) -> Result<()> {
+    @%- if config.is_mysql() %@
+    const SQL1: &str = r#"INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only()|fmt_join("{col_esc}", ",") }@) VALUES "#;
+    @%- else %@
+    const SQL1: &str = r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only()|fmt_join("{col_esc}", ",") }@) VALUES "#;
+    @%- endif %@
+    const SQL2: &str = r#"(@{ def.all_fields_except_read_only()|fmt_join("{placeholder}", ",") }@)"#;
+    let max_size = BULK_INSERT_MAX_SIZE.get().copied().unwrap_or(1024 * 1024);
+    for chunk in crate::misc::split_by_weight(rows, max_size, @{ 15000 / def.all_fields_except_read_only().len() }@, |v| v._size()) {
+        if chunk.is_empty() {
+            continue;
+        }
+        let mut sql = String::with_capacity(SQL1.len() + (SQL2.len() + 1) * chunk.len() + 30);
+        @%- if config.is_mysql() %@
+        sql.push_str(if ignore { "INSERT IGNORE " } else { "INSERT " });
+        @%- endif %@
+        sql.push_str(SQL1);
+        sql.push_str(SQL2);
+        for _i in 0..chunk.len() - 1 {
+            sql.push(',');
+            sql.push_str(SQL2);
+        }
+        @%- if !config.is_mysql() %@
+        if ignore {
+            sql.push_str(" ON CONFLICT DO NOTHING");
+        }
+        let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+        @%- endif %@
+        let mut query = sqlx::query(&sql);
+        for data in chunk {
+            @{- def.all_fields_except_read_only()|fmt_join("
+            query = query.bind(data.{ident}{bind_as});", "") }@
+        }
+        query.execute(&mut *conn).await.context(err::ErrorTable(TABLE_NAME))?;
+    }
+    Ok(())
+}
+
+/// Reads rows in primary key order, starting after `after`.
+/// Soft deleted rows are included.
+async fn _reshard_scan(conn: &mut DbConnection, after: Option<&InnerPrimary>, limit: usize) -> Result<Vec<Data>> {
+    let sql = format!(
+        r#"SELECT {} FROM @{ table_name|db_esc }@ {} ORDER BY @{ def.primaries()|fmt_join("{col_esc}", ",") }@ LIMIT {};"#,
+        Data::_sql_cols(@{ is_mysql_str }@),
+        if after.is_some() {
+            r#"WHERE @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ > @{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@"#
+        } else {
+            ""
+        },
+        limit
+    );
+    @%- if !config.is_mysql() %@
+    let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+    @%- endif %@
+    let mut query = sqlx::query(&sql);
+    if let Some(id) = after {
+        @{- def.primaries()|fmt_join("
+        query = query.bind(id.{index}{bind_as});", "") }@
+    }
+    let mut list = Vec::new();
+    for row in query.fetch_all(conn).await.context(err::ErrorTable(TABLE_NAME))? {
+        list.push(Data::from_row(&row)?);
+    }
+    Ok(list)
+}
+
+/// Copies a batch of rows that move to another shard in the new layout.
+/// The rows are read again from the source shard inside the transaction on the new shard,
+/// so rows deleted after the scan are not copied.
+async fn _reshard_copy(shard_id: ShardId, after: Option<&InnerPrimary>, limit: usize) -> Result<Option<(InnerPrimary, u64)>> {
+    let rows = _reshard_scan(DbConn::_new(shard_id).acquire_writer().await?.as_mut(), after, limit).await?;
+    let Some(last) = rows.last().map(InnerPrimary::from) else {
+        return Ok(None);
+    };
+    let ids: Vec<InnerPrimary> = rows.iter().map(InnerPrimary::from).collect();
+    let copied = _reshard_rows(shard_id, &ids).await?;
+    Ok(Some((last, copied)))
+}
+
+/// Deletes a batch of rows that were moved to another shard by the swap.
+async fn _reshard_cleanup(shard_id: ShardId, after: Option<&InnerPrimary>, limit: usize) -> Result<Option<(InnerPrimary, u64)>> {
+    let mut conn = DbConn::_new(shard_id).acquire_writer().await?;
+    let rows = _reshard_scan(conn.as_mut(), after, limit).await?;
+    let Some(last) = rows.last().map(InnerPrimary::from) else {
+        return Ok(None);
+    };
+    let mut ids = Vec::new();
+    for data in rows {
+        let id = InnerPrimary::from(&data);
+        if _repo_::shard_id_of(&id.@{ shard_key_index }@)? != shard_id {
+            ids.push(id);
+        }
+    }
+    let deleted = _reshard_delete(conn.as_mut(), &ids).await?;
+    Ok(Some((last, deleted)))
+}
+
+/// Copies the rows to the new shard layout, or deletes the moved rows after the swap,
+/// saving the progress after each batch.
+pub(crate) async fn reshard(progress: &mut ::senax_common::shard::ReshardProgress, batch_size: usize, interval: Duration) -> Result<()> {
+    use ::senax_common::shard::ReshardPhase;
+    if progress.phase() == ReshardPhase::Copy {
+        // Copies the rows whose dual write failed again.
+        let keys = progress.failures(TABLE_NAME);
+        let mut map: BTreeMap<ShardId, Vec<InnerPrimary>> = BTreeMap::new();
+        for key in &keys {
+            let id: InnerPrimary = serde_json::from_str(key)?;
+            map.entry(_repo_::shard_id_of(&id.@{ shard_key_index }@)?).or_default().push(id);
+        }
+        for (shard_id, ids) in map {
+            _reshard_rows(shard_id, &ids).await?;
+        }
+        if !keys.is_empty() {
+            progress.resolve_failures(TABLE_NAME, &keys)?;
+        }
+    }
+    for shard_id in DbConn::shard_num_range() {
+        let mut position = progress.position(TABLE_NAME, shard_id);
+        let mut after: Option<InnerPrimary> = position.last_key.as_deref().map(serde_json::from_str).transpose()?;
+        while !position.done {
+            let result = match progress.phase() {
+                ReshardPhase::Copy => _reshard_copy(shard_id, after.as_ref(), batch_size).await?,
+                ReshardPhase::Cleanup => _reshard_cleanup(shard_id, after.as_ref(), batch_size).await?,
+            };
+            if let Some((last, rows)) = result {
+                position.last_key = Some(serde_json::to_string(&last)?);
+                position.rows += rows;
+                after = Some(last);
+            } else {
+                position.done = true;
+            }
+            progress.update(TABLE_NAME, shard_id, position.clone())?;
+            if !position.done && !interval.is_zero() {
+                sleep(interval).await;
+            }
+        }
+        info!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "{}: {} rows on shard {}", TABLE_NAME, position.rows, shard_id);
+    }
+    Ok(())
+}
+@%- else %@
+
+#[inline]
+fn _is_owned(_shard_id: ShardId, _data: &Data) -> bool {
+    true
+}
+
+#[inline]
+fn _route_shard<I>(conn: &mut DbConn, _ids: I) -> Result<ShardId>
+where
+    I: IntoIterator,
+    I::Item: Borrow<InnerPrimary>,
+{
+    Ok(conn.shard_id())
+}
+
+#[inline]
+async fn _dual_write<I>(_conn: &mut DbConn, _ids: I)
+where
+    I: IntoIterator,
+    I::Item: Borrow<InnerPrimary>,
+{
+}
+@%- endif %@
+
+#[allow(dead_code)]
+const NULLS_FIRST: bool = @{ config.is_mysql() || config.is_sqlite() }@;
+
+#[allow(unreachable_patterns)]
+fn _cmp_col(col: &Col_, a: &Data, b: &Data) -> std::cmp::Ordering {
+    match col {
+        @{- def.orderable(true)|fmt_join("
+        Col_::{ident} => ::senax_common::shard::cmp_value(&a.{ident}, &b.{ident}),", "") }@
+        @{- def.orderable(false)|fmt_join("
+        Col_::{ident} => ::senax_common::shard::cmp_nullable(&a.{ident}, &b.{ident}, NULLS_FIRST),", "") }@
+        _ => std::cmp::Ordering::Equal,
+    }
+}
+
+/// Whether _cmp_col can compare the column in the same order as the database.
+#[allow(unreachable_patterns, clippy::match_like_matches_macro)]
+fn _is_orderable(col: &Col_) -> bool {
+    match col {
+        @{- def.orderable(true)|fmt_join("
+        Col_::{ident} => true,", "") }@
+        @{- def.orderable(false)|fmt_join("
+        Col_::{ident} => true,", "") }@
+        _ => false,
+    }
+}
+
+/// Fails if the merged results of shards would not follow the order.
+fn _check_order(order: &Option<Vec<Order_>>) -> Result<()> {
+    for o in order.iter().flatten() {
+        if let Order_::Asc(c) | Order_::Desc(c) = o {
+            ensure!(_is_orderable(c), "{:?} cannot be used to merge the ordered results of shards.", c);
+        }
+    }
+    Ok(())
+}
+
+#[allow(unreachable_patterns, clippy::match_single_binding)]
+fn _is_null(col: &Col_, data: &Data) -> bool {
+    match col {
+        @{- def.nullable()|fmt_join("
+        Col_::{ident} => data.{ident}.is_none(),", "") }@
+        _ => false,
+    }
+}
+
+/// Compares rows in the same way as the ORDER BY clause to merge the results of shards.
+fn _cmp_order(order: &[Order_], a: &Data, b: &Data) -> std::cmp::Ordering {
+    for o in order {
+        let ordering = match o {
+            Order_::Asc(c) => _cmp_col(c, a, b),
+            Order_::Desc(c) => _cmp_col(c, a, b).reverse(),
+            Order_::IsNullAsc(c) => _is_null(c, a).cmp(&_is_null(c, b)),
+            Order_::IsNullDesc(c) => _is_null(c, b).cmp(&_is_null(c, a)),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    std::cmp::Ordering::Equal
+}
 
 @%- if !config.force_disable_cache %@
 
@@ -2752,10 +3355,15 @@ impl _@{ pascal_name }@Cache_ {
                 .await;
             @%- else %@
             let id: InnerPrimary = (&id.into()).into();
-            let sync = DbConn::inc_cache_sync(conn.shard_id()).await;
+            @%- if def.shard_key_index().is_some() %@
+            let shard_id = _shard_id_of([&id])?.unwrap_or(conn.shard_id());
+            @%- else %@
+            let shard_id = conn.shard_id();
+            @%- endif %@
+            let sync = DbConn::inc_cache_sync(shard_id).await;
             let mut sync_map = FxHashMap::default();
-            sync_map.insert(conn.shard_id(), sync);
-            CacheMsg(vec![CacheOp::Invalidate{id, shard_id: conn.shard_id()}.wrap()], sync_map)
+            sync_map.insert(shard_id, sync);
+            CacheMsg(vec![CacheOp::Invalidate{id, shard_id}.wrap()], sync_map)
                 .do_send()
                 .await;
             @%- endif %@
@@ -2981,6 +3589,13 @@ pub mod _repo_ {
     pub fn query() -> QueryBuilder {
         QueryBuilder::default()
     }
+    @%- if def.shard_key_index().is_some() %@
+
+    /// Returns the shard that stores the rows with the given shard key.
+    pub fn shard_id_of<K: ::senax_common::shard::ShardKey + ?Sized>(key: &K) -> Result<ShardId> {
+        ::senax_common::shard::shard_id_of(key, &@{ "{:?}"|format(def.shard_ranges()) }@, DbConn::shard_num())
+    }
+    @%- endif %@
     @%- if !config.force_disable_cache %@
 
     pub async fn clear_cache() -> Result<()> {
@@ -3028,12 +3643,17 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        __find_optional_from_cache(conn, id.clone(), joiner)
-            .await?
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            __find_optional_from_cache(conn, id.clone(), joiner)
+                .await?
 @{- def.soft_delete_tpl("","
-            .filter(|data| data._wrapper._inner.deleted_at.is_none())","
-            .filter(|data| data._wrapper._inner.deleted == 0)")}@
-            .with_context(|| err::RowNotFound::new(TABLE_NAME, id_to_string(&id)))
+                .filter(|data| data._wrapper._inner.deleted_at.is_none())","
+                .filter(|data| data._wrapper._inner.deleted == 0)")}@
+                .with_context(|| err::RowNotFound::new(TABLE_NAME, id_to_string(&id)))
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if def.is_soft_delete() %@
 
@@ -3042,9 +3662,14 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        __find_optional_from_cache(conn, id.clone(), joiner)
-            .await?
-            .with_context(|| err::RowNotFound::new(TABLE_NAME, id_to_string(&id)))
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            __find_optional_from_cache(conn, id.clone(), joiner)
+                .await?
+                .with_context(|| err::RowNotFound::new(TABLE_NAME, id_to_string(&id)))
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- endif %@
@@ -3081,9 +3706,14 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        let mut list = __find_many(conn, &ids, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.into_iter().map(|v| v.into()).collect();
-        _@{ pascal_name }@Joiner::join(&mut list, conn, joiner).await?;
-        Ok(list)
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            let mut list = __find_many(conn, &ids, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.into_iter().map(|v| v.into()).collect();
+            _@{ pascal_name }@Joiner::join(&mut list, conn, joiner).await?;
+            Ok(list)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if def.is_soft_delete() %@
 
@@ -3093,9 +3723,14 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        let mut list = __find_many(conn, &ids, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.into_iter().map(|v| v.into()).collect();
-        _@{ pascal_name }@Joiner::join(&mut list, conn, joiner).await?;
-        Ok(list)
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            let mut list = __find_many(conn, &ids, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.into_iter().map(|v| v.into()).collect();
+            _@{ pascal_name }@Joiner::join(&mut list, conn, joiner).await?;
+            Ok(list)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if def.use_cache() %@
@@ -3106,7 +3741,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        Ok(__find_many_from_cache(conn, ids, joiner).await?.into_iter()@{ def.soft_delete_tpl("",".filter(|data| data._wrapper._inner.deleted_at.is_none())",".filter(|data| data._wrapper._inner.deleted == 0)")}@.collect())
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            Ok(__find_many_from_cache(conn, ids, joiner).await?.into_iter()@{ def.soft_delete_tpl("",".filter(|data| data._wrapper._inner.deleted_at.is_none())",".filter(|data| data._wrapper._inner.deleted == 0)")}@.collect())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if def.is_soft_delete() %@
 
@@ -3116,7 +3756,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        __find_many_from_cache(conn, ids, joiner).await
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            __find_many_from_cache(conn, ids, joiner).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- endif %@
@@ -3126,20 +3771,25 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        @%- if def.dummy_always_joinable() %@
-        let data: Option<(Data, _)> = Some((Data {
-            @{- def.primaries()|fmt_join("
-            {ident}: id.{index}{raw_to_inner},", "") }@
-            ..Default::default()
-        }, Default::default()));
-        @%- else %@
-        let data: Option<_> = __find_optional(conn, Data::_sql_cols(@{ is_mysql_str }@), id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.map(|(v, f)| Data::from_row(&v).map(|v| (v, f))).transpose()?;
-        @%- endif %@
-        let mut obj = data.map(_@{ pascal_name }@::from);
-        if let Some(obj) = obj.as_mut() {
-            _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
-        }
-        Ok(obj)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            @%- if def.dummy_always_joinable() %@
+            let data: Option<(Data, _)> = Some((Data {
+                @{- def.primaries()|fmt_join("
+                {ident}: id.{index}{raw_to_inner},", "") }@
+                ..Default::default()
+            }, Default::default()));
+            @%- else %@
+            let data: Option<_> = __find_optional(conn, Data::_sql_cols(@{ is_mysql_str }@), id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.map(|(v, f)| Data::from_row(&v).map(|v| (v, f))).transpose()?;
+            @%- endif %@
+            let mut obj = data.map(_@{ pascal_name }@::from);
+            if let Some(obj) = obj.as_mut() {
+                _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
+            }
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 
     pub async fn exists<T>(conn: &mut DbConn, id: T, filter: Option<Filter_>, filter_flag: Option<BTreeMap<&'static str, Filter_>>) -> Result<Option<BTreeMap<&'static str, bool>>>
@@ -3147,12 +3797,17 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        @%- if def.dummy_always_joinable() %@
-        Ok(Default::default())
-        @%- else %@
-        let data: Option<_> = __find_optional(conn, Exists::_sql_cols(@{ is_mysql_str }@), id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.map(|(_, f)| f);
-        Ok(data)
-        @%- endif %@
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            @%- if def.dummy_always_joinable() %@
+            Ok(Default::default())
+            @%- else %@
+            let data: Option<_> = __find_optional(conn, Exists::_sql_cols(@{ is_mysql_str }@), id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?.map(|(_, f)| f);
+            Ok(data)
+            @%- endif %@
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if !def.disable_update() %@
 
@@ -3161,12 +3816,17 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        let result = __find_for_update(conn, &id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?;
-        let mut obj = result.map(__Updater__::from);
-        if let Some(obj) = obj.as_mut() {
-            _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
-        }
-        Ok(obj)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            let result = __find_for_update(conn, &id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?;
+            let mut obj = result.map(__Updater__::from);
+            if let Some(obj) = obj.as_mut() {
+                _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
+            }
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if def.is_soft_delete() %@
@@ -3176,20 +3836,25 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        @%- if def.dummy_always_joinable() %@
-        let data: Option<Data> = Some(Data {
-            @{- def.primaries()|fmt_join("
-            {ident}: id.{index}{raw_to_inner},", "") }@
-            ..Default::default()
-        });
-        @%- else %@
-        let data: Option<_> = __find_optional(conn, Data::_sql_cols(@{ is_mysql_str }@), id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.map(|(v, f)| Data::from_row(&v).map(|v| (v, f))).transpose()?;
-        @%- endif %@
-        let mut obj = data.map(_@{ pascal_name }@::from);
-        if let Some(obj) = obj.as_mut() {
-            _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
-        }
-        Ok(obj)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            @%- if def.dummy_always_joinable() %@
+            let data: Option<Data> = Some(Data {
+                @{- def.primaries()|fmt_join("
+                {ident}: id.{index}{raw_to_inner},", "") }@
+                ..Default::default()
+            });
+            @%- else %@
+            let data: Option<_> = __find_optional(conn, Data::_sql_cols(@{ is_mysql_str }@), id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.map(|(v, f)| Data::from_row(&v).map(|v| (v, f))).transpose()?;
+            @%- endif %@
+            let mut obj = data.map(_@{ pascal_name }@::from);
+            if let Some(obj) = obj.as_mut() {
+                _@{ pascal_name }@Joiner::join(obj, conn, joiner).await?;
+            }
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 
     pub async fn exists_with_trashed<T>(conn: &mut DbConn, id: T, filter: Option<Filter_>, filter_flag: Option<BTreeMap<&'static str, Filter_>>) -> Result<Option<BTreeMap<&'static str, bool>>>
@@ -3197,12 +3862,17 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        @%- if def.dummy_always_joinable() %@
-        Ok(Default::default())
-        @%- else %@
-        let data: Option<_> = __find_optional(conn, Exists::_sql_cols(@{ is_mysql_str }@), id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.map(|(_, f)| f);
-        Ok(data)
-        @%- endif %@
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            @%- if def.dummy_always_joinable() %@
+            Ok(Default::default())
+            @%- else %@
+            let data: Option<_> = __find_optional(conn, Exists::_sql_cols(@{ is_mysql_str }@), id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?.map(|(_, f)| f);
+            Ok(data)
+            @%- endif %@
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if def.use_cache() %@
@@ -3213,7 +3883,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        Ok(__find_optional_from_cache(conn, id, joiner).await?@{- def.soft_delete_tpl("",".filter(|data| data._wrapper._inner.deleted_at.is_none())",".filter(|data| data._wrapper._inner.deleted == 0)")}@)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            Ok(__find_optional_from_cache(conn, id, joiner).await?@{- def.soft_delete_tpl("",".filter(|data| data._wrapper._inner.deleted_at.is_none())",".filter(|data| data._wrapper._inner.deleted == 0)")}@)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if def.is_soft_delete() %@
 
@@ -3222,7 +3897,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        __find_optional_from_cache(conn, id, joiner).await
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            __find_optional_from_cache(conn, id, joiner).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- endif %@
@@ -3233,11 +3913,16 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        let result = __find_for_update(conn, &id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?@{ def.soft_delete_tpl("",".filter(|(data, _)| data.deleted_at.is_none())",".filter(|(data, _)| data.deleted == 0)")}@;
-        let data = result.with_context(|| err::RowNotFound::new(TABLE_NAME, id.to_string()))?;
-        let mut obj = __Updater__::from(data);
-        _@{ pascal_name }@Joiner::join(&mut obj, conn, joiner).await?;
-        Ok(obj)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            let result = __find_for_update(conn, &id, TrashMode::Not, filter, filter_flag.unwrap_or_default()).await?@{ def.soft_delete_tpl("",".filter(|(data, _)| data.deleted_at.is_none())",".filter(|(data, _)| data.deleted == 0)")}@;
+            let data = result.with_context(|| err::RowNotFound::new(TABLE_NAME, id.to_string()))?;
+            let mut obj = __Updater__::from(data);
+            _@{ pascal_name }@Joiner::join(&mut obj, conn, joiner).await?;
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if !def.disable_update() && def.is_soft_delete() %@
@@ -3247,11 +3932,16 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let id: InnerPrimary = (&id.into()).into();
-        let result = __find_for_update(conn, &id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?;
-        let data = result.with_context(|| err::RowNotFound::new(TABLE_NAME, id.to_string()))?;
-        let mut obj = __Updater__::from(data);
-        _@{ pascal_name }@Joiner::join(&mut obj, conn, joiner).await?;
-        Ok(obj)
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            let result = __find_for_update(conn, &id, TrashMode::With, filter, filter_flag.unwrap_or_default()).await?;
+            let data = result.with_context(|| err::RowNotFound::new(TABLE_NAME, id.to_string()))?;
+            let mut obj = __Updater__::from(data);
+            _@{ pascal_name }@Joiner::join(&mut obj, conn, joiner).await?;
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
 @%- if !def.disable_update() %@
@@ -3262,7 +3952,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        __find_many_for_update(conn, &ids, TrashMode::Not, joiner, filter, filter_flag.unwrap_or_default()).await
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            __find_many_for_update(conn, &ids, TrashMode::Not, joiner, filter, filter_flag.unwrap_or_default()).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 
     pub async fn find_many_for_update_with_trashed<I, T>(conn: &mut DbConn, ids: I, joiner: Option<Box<Joiner_>>, filter: Option<Filter_>, filter_flag: Option<BTreeMap<&'static str, Filter_>>) -> Result<Vec<_@{ pascal_name }@Updater>>
@@ -3271,7 +3966,12 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        __find_many_for_update(conn, &ids, TrashMode::With, joiner, filter, filter_flag.unwrap_or_default()).await
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            __find_many_for_update(conn, &ids, TrashMode::With, joiner, filter, filter_flag.unwrap_or_default()).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 @%- endif %@
 @%- for (index_name, index) in def.unique_index() %@
@@ -3335,15 +4035,29 @@ pub mod _repo_ {
 
     pub async fn save(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<Option<_@{ pascal_name }@>> {
         obj.__validate()?;
-        obj.__set_default_value(conn).await?;
-        __save(conn, obj, 0).await
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj.__set_default_value(conn).await?;
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, [InnerPrimary::from(&obj)])?;
+            __save(conn, obj, 0).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 
     pub async fn overwrite(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<Option<_@{ pascal_name }@>> {
         obj.__validate()?;
         ensure!(obj.is_new(), "The obj is not new.");
-        obj.__set_default_value(conn).await?;
-        __save(conn, obj, 1).await
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj.__set_default_value(conn).await?;
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, [InnerPrimary::from(&obj)])?;
+            __save(conn, obj, 1).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if !def.disable_update() %@
 
@@ -3354,12 +4068,17 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        @%- if def.updated_at_conf().is_some() %@
-        if updater._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
-            updater.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
-        }
-        @%- endif %@
-        __update_many(conn, ids, updater).await
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            @%- if def.updated_at_conf().is_some() %@
+            if updater._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
+                updater.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
+            }
+            @%- endif %@
+            __update_many(conn, ids, updater).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
 
@@ -3369,48 +4088,56 @@ pub mod _repo_ {
     pub async fn insert_ignore(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<Option<_@{ pascal_name }@Updater>> {
         obj.__validate()?;
         ensure!(obj.is_new(), "The obj is not new.");
-        obj.__set_default_value(conn).await?;
-        @%- if config.is_mysql() %@
-        let sql = r#"INSERT IGNORE INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) 
-            VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@)"#;
-        @%- else %@
-        let sql = r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) 
-            VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) ON CONFLICT DO NOTHING@{ def.auto_inc()|fmt_join(" RETURNING xmax,{col_esc}", "") }@;"#;
-        let sql = &senax_common::convert_mysql_placeholders_to_postgresql(sql);
-        @%- endif %@
-        let query = bind_to_query(sqlx::query(sql), &obj._data);
-        debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
-        let (rows_affected, _last_insert_id) = conn.execute@{ def.auto_inc()|fmt_join("_with_last_insert_id", "") }@(query).await.context(err::ErrorTable(TABLE_NAME))?;
-        if rows_affected == 0 {
-            return Ok(None);
-        }
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj.__set_default_value(conn).await?;
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, [InnerPrimary::from(&obj)])?;
+            @%- if config.is_mysql() %@
+            let sql = r#"INSERT IGNORE INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) 
+                VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@)"#;
+            @%- else %@
+            let sql = r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) 
+                VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) ON CONFLICT DO NOTHING@{ def.auto_inc()|fmt_join(" RETURNING {xmax},{col_esc}", "") }@;"#;
+            let sql = &senax_common::convert_mysql_placeholders_to_postgresql(sql);
+            @%- endif %@
+            let query = bind_to_query(sqlx::query(sql), &obj._data);
+            debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
+            let (rows_affected, _last_insert_id) = conn.execute@{ def.auto_inc()|fmt_join("_with_last_insert_id", "") }@(query).await.context(err::ErrorTable(TABLE_NAME))?;
+            if rows_affected == 0 {
+                return Ok(None);
+            }
 @{- def.auto_inc()|fmt_join("
-        if obj._data.{ident} == 0 {
-            obj._data.{ident} = _last_insert_id as {inner};
-        }", "") }@
-        debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "insert_ignore", ctx = conn.ctx_no(); "{}", &obj);
-        debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "{:?}", &obj);
-        obj._is_new = false;
-        obj._op = OpData::default();
-        @%- if !config.force_disable_cache %@
-        @%- if def.act_as_job_queue() %@
-        conn.push_cache_op(CacheOp::Queued.wrap()).await;
-        @%- else if def.clear_all_cache_on_update() %@
-        conn.clear_all_cache = true;
-        @%- else %@
-        if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-            let cache_msg = CacheOp::Insert {
-                overwrite: false,
-                shard_id: conn.shard_id(),
-                data: obj._data.clone(),
+            if obj._data.{ident} == 0 {
+                obj._data.{ident} = _last_insert_id as {inner};
+            }", "") }@
+            _dual_write(conn, [InnerPrimary::from(&obj._data)]).await;
+            debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "insert_ignore", ctx = conn.ctx_no(); "{}", &obj);
+            debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "{:?}", &obj);
+            obj._is_new = false;
+            obj._op = OpData::default();
+            @%- if !config.force_disable_cache %@
+            @%- if def.act_as_job_queue() %@
+            conn.push_cache_op(CacheOp::Queued.wrap()).await;
+            @%- else if def.clear_all_cache_on_update() %@
+            conn.clear_all_cache = true;
+            @%- else %@
+            if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+                let cache_msg = CacheOp::Insert {
+                    overwrite: false,
+                    shard_id: conn.shard_id(),
+                    data: obj._data.clone(),
 @{- def.relations_one_cache(Joinable::Join, false)|fmt_rel_join("\n                _{rel_name}: None,", "") }@
 @{- def.relations_many_cache(Joinable::Join, false)|fmt_rel_join("\n                _{rel_name}: None,", "") }@
-            };
-            conn.push_cache_op(cache_msg.wrap()).await;
-        }
-        @%- endif %@
-        @%- endif %@
-        Ok(Some(obj))
+                };
+                conn.push_cache_op(cache_msg.wrap()).await;
+            }
+            @%- endif %@
+            @%- endif %@
+            Ok(Some(obj))
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- if def.enable_delayed_insert() %@
 
@@ -3440,25 +4167,32 @@ pub mod _repo_ {
     // delayed_save does not support relational tables.
     pub async fn delayed_save(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<()> {
         obj.__validate()?;
-        obj.__set_default_value(conn).await?;
-        if obj.will_be_deleted() {
-            obj._op = OpData::default();
-        }
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj.__set_default_value(conn).await?;
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, [InnerPrimary::from(&obj)])?;
+            if obj.will_be_deleted() {
+                obj._op = OpData::default();
+            }
 @{- def.relations_one(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_many(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_belonging(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
-        let shard_id = conn.shard_id() as usize;
-        conn.push_callback(Box::new(move || {
-            async move {
-                DELAYED_SAVE_QUEUE.get().unwrap()[shard_id].push(obj);
-                if !db::is_test_mode() {
-                    DelayedActor::handle(DelayedMsg::Save);
-                } else {
-                    handle_delayed_msg_save().await;
-                }
-            }.boxed()
-        })).await;
-        Ok(())
+            let shard_id = conn.shard_id() as usize;
+            conn.push_callback(Box::new(move || {
+                async move {
+                    DELAYED_SAVE_QUEUE.get().unwrap()[shard_id].push(obj);
+                    if !db::is_test_mode() {
+                        DelayedActor::handle(DelayedMsg::Save);
+                    } else {
+                        handle_delayed_msg_save().await;
+                    }
+                }.boxed()
+            })).await;
+            Ok(())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if def.enable_delayed_update() %@
@@ -3475,21 +4209,26 @@ pub mod _repo_ {
         }
         @%- endif %@
         obj.__validate()?;
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
 @{- def.relations_one(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_many(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_belonging(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
-        let shard_id = conn.shard_id() as usize;
-        conn.push_callback(Box::new(move || {
-            async move {
-                DELAYED_UPDATE_QUEUE.get().unwrap()[shard_id].push(obj);
-                if !db::is_test_mode() {
-                    DelayedActor::handle(DelayedMsg::Update);
-                } else {
-                    handle_delayed_msg_update().await;
-                }
-            }.boxed()
-        })).await;
-        Ok(())
+            let shard_id = conn.shard_id() as usize;
+            conn.push_callback(Box::new(move || {
+                async move {
+                    DELAYED_UPDATE_QUEUE.get().unwrap()[shard_id].push(obj);
+                    if !db::is_test_mode() {
+                        DelayedActor::handle(DelayedMsg::Update);
+                    } else {
+                        handle_delayed_msg_update().await;
+                    }
+                }.boxed()
+            })).await;
+            Ok(())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
     @%- if def.enable_delayed_upsert() %@
@@ -3503,22 +4242,29 @@ pub mod _repo_ {
             panic!("DELETE is not supported.");
         }
         @%- endif %@
-        obj.__set_default_value(conn).await?;
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj.__set_default_value(conn).await?;
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, [InnerPrimary::from(&obj)])?;
 @{- def.relations_one(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_many(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
 @{- def.relations_belonging(Joinable::Join, false)|fmt_rel_join("\n        obj.{rel_name} = None;", "") }@
-        let shard_id = conn.shard_id() as usize;
-        conn.push_callback(Box::new(move || {
-            async move {
-                DELAYED_UPSERT_QUEUE.get().unwrap()[shard_id].push(obj);
-                if !db::is_test_mode() {
-                    DelayedActor::handle(DelayedMsg::Upsert);
-                } else {
-                    handle_delayed_msg_upsert().await;
-                }
-            }.boxed()
-        })).await;
-        Ok(())
+            let shard_id = conn.shard_id() as usize;
+            conn.push_callback(Box::new(move || {
+                async move {
+                    DELAYED_UPSERT_QUEUE.get().unwrap()[shard_id].push(obj);
+                    if !db::is_test_mode() {
+                        DelayedActor::handle(DelayedMsg::Upsert);
+                    } else {
+                        handle_delayed_msg_upsert().await;
+                    }
+                }.boxed()
+            })).await;
+            Ok(())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
 
@@ -3533,29 +4279,34 @@ pub mod _repo_ {
     }
 
     pub async fn bulk_overwrite(conn: &mut DbConn, mut list: Vec<_@{ pascal_name }@Updater>) -> Result<()> {
-        let mut vec: Vec<ForInsert> = Vec::with_capacity(list.len());
-        @%- if def.soft_delete().is_none() && !def.disable_delete() %@
-        let mut remove_ids: Vec<Primary> = Vec::with_capacity(list.len());
-        @%- endif %@
-        for mut obj in list.into_iter() {
-            obj.__validate()?;
-            obj.__set_default_value(conn).await?;
+        let prev_shard_id = _route_shard(conn, list.iter().map(InnerPrimary::from))?;
+        let result: Result<_> = async {
+            let mut vec: Vec<ForInsert> = Vec::with_capacity(list.len());
             @%- if def.soft_delete().is_none() && !def.disable_delete() %@
-            if obj.will_be_deleted() {
-                remove_ids.push((&obj).into());
-                continue;
-            } else {
+            let mut remove_ids: Vec<Primary> = Vec::with_capacity(list.len());
+            @%- endif %@
+            for mut obj in list.into_iter() {
+                obj.__validate()?;
+                obj.__set_default_value(conn).await?;
+                @%- if def.soft_delete().is_none() && !def.disable_delete() %@
+                if obj.will_be_deleted() {
+                    remove_ids.push((&obj).into());
+                    continue;
+                } else {
+                    obj.__set_overwrite_extra_value(conn);
+                }
+                @%- else %@
                 obj.__set_overwrite_extra_value(conn);
+                @%- endif %@
+                vec.push(obj.into());
             }
-            @%- else %@
-            obj.__set_overwrite_extra_value(conn);
+            @%- if def.soft_delete().is_none() && !def.disable_delete() %@
+            delete_by_ids(conn, remove_ids).await?;
             @%- endif %@
-            vec.push(obj.into());
-        }
-        @%- if def.soft_delete().is_none() && !def.disable_delete() %@
-        delete_by_ids(conn, remove_ids).await?;
-        @%- endif %@
-        __bulk_insert(conn, &vec, false, false, true).await
+            __bulk_insert(conn, &vec, false, false, true).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 @%- endif %@
     @%- if !def.disable_update() %@
@@ -3564,19 +4315,26 @@ pub mod _repo_ {
         if list.is_empty() {
             return Ok(());
         }
-        let mut vec = Vec::with_capacity(list.len());
-        for mut obj in list.into_iter() {
-            ensure!(obj.is_new(), "bulk_upsert supports only new objects.");
-            obj.__validate()?;
-            obj.__set_default_value(conn).await?;
-            vec.push(obj._data);
-        }
-        @%- if def.updated_at_conf().is_some() %@
-        if updater._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
-            updater.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
-        }
-        @%- endif %@
-        __bulk_upsert(conn, &vec, &updater).await
+        let prev_shard_id = _route_shard(conn, list.iter().map(InnerPrimary::from))?;
+        let result: Result<_> = async {
+            let mut vec = Vec::with_capacity(list.len());
+            for mut obj in list.into_iter() {
+                ensure!(obj.is_new(), "bulk_upsert supports only new objects.");
+                obj.__validate()?;
+                obj.__set_default_value(conn).await?;
+                vec.push(obj._data);
+            }
+            @%- if def.updated_at_conf().is_some() %@
+            if updater._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
+                updater.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
+            }
+            @%- endif %@
+            // A shard key generated by the default values routes the connection again.
+            _route_shard(conn, vec.iter().map(InnerPrimary::from))?;
+            __bulk_upsert(conn, &vec, &updater).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
     @%- endif %@
 @%- if !def.disable_delete() %@
@@ -3589,82 +4347,88 @@ pub mod _repo_ {
     {
 @%- if def.soft_delete().is_some() %@
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        async fn inner(conn: &mut DbConn, ids: Vec<InnerPrimary>) -> Result<u64> {
-            if ids.is_empty() {
-                return Ok(0);
-            }
-            let mut rows_affected = 0u64;
-            let id_chunks = ids.chunks(IN_CONDITION_LIMIT);
-            @{- def.soft_delete_tpl2("","
-            let deleted_at: {filter_type} = {val}.into();","","
-            let deleted = cmp::max(1, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as {u32});")}@
-            @%- if def.updated_at_conf().is_some() %@
-            @%- let updated_at = def.get_updated_at() %@
-            let updated_at: @{ updated_at.get_filter_type(false) }@ = @{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into();
-            @%- endif %@
-            for ids in id_chunks {
-                let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
-                let sql = format!(
-                    r#"UPDATE @{ table_name|db_esc }@ SET @{ def.soft_delete_tpl2("","deleted_at=?","deleted=1","deleted=?")}@@% if def.updated_at_conf().is_some() %@, updated_at=?@%- endif %@ WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
-                    &q[0..q.len() - 1]
-                );
-                @%- if !config.is_mysql() %@
-                let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
-                @%- endif %@
-                let mut query = sqlx::query(&sql);
-                debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
-    @{- def.soft_delete_tpl2("","
-                query = query.bind(deleted_at);","","
-                query = query.bind(deleted);")}@
-    @%- if def.updated_at_conf().is_some() %@
-                query = query.bind(updated_at);
-    @%- endif %@
-                for id in ids {
-                    @{- def.primaries()|fmt_join("
-                    query = query.bind(id.{index}{bind_as});", "") }@
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            async fn inner(conn: &mut DbConn, ids: Vec<InnerPrimary>) -> Result<u64> {
+                if ids.is_empty() {
+                    return Ok(0);
                 }
-                let result = if conn.wo_tx() {
-                    query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
-                } else {
-                    query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
-                };
-                rows_affected += result.rows_affected();
-            }
-            debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "delete_by_ids", ctx = conn.ctx_no(), ids = primaries_to_str(&ids); "");
-            @%- if !config.force_disable_cache %@
-            @%- if def.act_as_job_queue() %@
-            @%- else if def.clear_all_cache_on_update() %@
-            conn.clear_all_cache = true;
-            @%- else %@
-            if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-                let mut updater = _repo_::updater();
+                let mut rows_affected = 0u64;
+                let id_chunks = ids.chunks(IN_CONDITION_LIMIT);
                 @{- def.soft_delete_tpl2("","
-                updater.mut_deleted_at().set(Some(deleted_at));","
-                updater.mut_deleted().set(true);","
-                let deleted = cmp::max(1, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as {u32});
-                updater.mut_deleted().set(deleted);")}@
-    @%- if def.updated_at_conf().is_some() %@
-                updater.mut_@{ ConfigDef::updated_at() }@().set(updated_at);
-    @%- endif %@
-                @{- def.non_primaries()|fmt_join_cache_or_not("", "
-                updater._op.{ident} = Op::None;
-                updater._update.{ident} = Default::default();", "") }@
-                let cache_msg = CacheOp::UpdateMany {
-                    ids,
-                    shard_id: conn.shard_id(),
-                    update: updater._update,
-                    data_list: Vec::new(),
-                    op: updater._op,
-                };
-                conn.push_cache_op(cache_msg.wrap()).await;
-                @{- def.cache_owners|fmt_cache_owners("
-                conn.push_cache_op(db::models::{mod}::CacheOp::InvalidateAll.wrap()).await;") }@
+                let deleted_at: {filter_type} = {val}.into();","","
+                let deleted = cmp::max(1, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as {u32});")}@
+                @%- if def.updated_at_conf().is_some() %@
+                @%- let updated_at = def.get_updated_at() %@
+                let updated_at: @{ updated_at.get_filter_type(false) }@ = @{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into();
+                @%- endif %@
+                for ids in id_chunks {
+                    let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
+                    let sql = format!(
+                        r#"UPDATE @{ table_name|db_esc }@ SET @{ def.soft_delete_tpl2("","deleted_at=?","deleted=1","deleted=?")}@@% if def.updated_at_conf().is_some() %@, updated_at=?@%- endif %@ WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
+                        &q[0..q.len() - 1]
+                    );
+                    @%- if !config.is_mysql() %@
+                    let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+                    @%- endif %@
+                    let mut query = sqlx::query(&sql);
+                    debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
+        @{- def.soft_delete_tpl2("","
+                    query = query.bind(deleted_at);","","
+                    query = query.bind(deleted);")}@
+        @%- if def.updated_at_conf().is_some() %@
+                    query = query.bind(updated_at);
+        @%- endif %@
+                    for id in ids {
+                        @{- def.primaries()|fmt_join("
+                        query = query.bind(id.{index}{bind_as});", "") }@
+                    }
+                    let result = if conn.wo_tx() {
+                        query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
+                    } else {
+                        query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
+                    };
+                    rows_affected += result.rows_affected();
+                }
+                _dual_write(conn, &ids).await;
+                debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "delete_by_ids", ctx = conn.ctx_no(), ids = primaries_to_str(&ids); "");
+                @%- if !config.force_disable_cache %@
+                @%- if def.act_as_job_queue() %@
+                @%- else if def.clear_all_cache_on_update() %@
+                conn.clear_all_cache = true;
+                @%- else %@
+                if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+                    let mut updater = _repo_::updater();
+                    @{- def.soft_delete_tpl2("","
+                    updater.mut_deleted_at().set(Some(deleted_at));","
+                    updater.mut_deleted().set(true);","
+                    let deleted = cmp::max(1, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as {u32});
+                    updater.mut_deleted().set(deleted);")}@
+        @%- if def.updated_at_conf().is_some() %@
+                    updater.mut_@{ ConfigDef::updated_at() }@().set(updated_at);
+        @%- endif %@
+                    @{- def.non_primaries()|fmt_join_cache_or_not("", "
+                    updater._op.{ident} = Op::None;
+                    updater._update.{ident} = Default::default();", "") }@
+                    let cache_msg = CacheOp::UpdateMany {
+                        ids,
+                        shard_id: conn.shard_id(),
+                        update: updater._update,
+                        data_list: Vec::new(),
+                        op: updater._op,
+                    };
+                    conn.push_cache_op(cache_msg.wrap()).await;
+                    @{- def.cache_owners|fmt_cache_owners("
+                    conn.push_cache_op(db::models::{mod}::CacheOp::InvalidateAll.wrap()).await;") }@
+                }
+                @%- endif %@
+                @%- endif %@
+                Ok(rows_affected)
             }
-            @%- endif %@
-            @%- endif %@
-            Ok(rows_affected)
-        }
-        inner(conn, ids).await
+            inner(conn, ids).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
 @%- else %@
         force_delete_by_ids(conn, ids).await
 @%- endif %@
@@ -3677,128 +4441,144 @@ pub mod _repo_ {
         T: Into<Primary>,
     {
         let ids: Vec<InnerPrimary> = ids.into_iter().map(|id| (&id.into()).into()).collect();
-        async fn inner(conn: &mut DbConn, ids: Vec<InnerPrimary>) -> Result<u64> {
-            if ids.is_empty() {
-                return Ok(0);
-            }
-            let mut rows_affected = 0u64;
-            let id_chunks = ids.chunks(IN_CONDITION_LIMIT);
-            for ids in id_chunks {
-                let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
-                let sql = format!(
-                    r#"DELETE FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
-                    &q[0..q.len() - 1]
-                );
-                @%- if !config.is_mysql() %@
-                let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
-                @%- endif %@
-                let mut query = sqlx::query(&sql);
-                debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
-                for id in ids {
-                    @{- def.primaries()|fmt_join("
-                    query = query.bind(id.{index}{bind_as});", "") }@
+        let prev_shard_id = _route_shard(conn, &ids)?;
+        let result: Result<_> = async {
+            async fn inner(conn: &mut DbConn, ids: Vec<InnerPrimary>) -> Result<u64> {
+                if ids.is_empty() {
+                    return Ok(0);
                 }
-                let result = if conn.wo_tx() {
-                    query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
-                } else {
-                    query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
-                };
-                rows_affected += result.rows_affected();
+                let mut rows_affected = 0u64;
+                let id_chunks = ids.chunks(IN_CONDITION_LIMIT);
+                for ids in id_chunks {
+                    let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
+                    let sql = format!(
+                        r#"DELETE FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({});"#,
+                        &q[0..q.len() - 1]
+                    );
+                    @%- if !config.is_mysql() %@
+                    let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
+                    @%- endif %@
+                    let mut query = sqlx::query(&sql);
+                    debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
+                    for id in ids {
+                        @{- def.primaries()|fmt_join("
+                        query = query.bind(id.{index}{bind_as});", "") }@
+                    }
+                    let result = if conn.wo_tx() {
+                        query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
+                    } else {
+                        query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?
+                    };
+                    rows_affected += result.rows_affected();
+                }
+                _dual_write(conn, &ids).await;
+                debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "force_delete_by_ids", ctx = conn.ctx_no(), ids = primaries_to_str(&ids); "");
+                @%- if !config.force_disable_cache %@
+                @%- if def.act_as_job_queue() %@
+                @%- else if def.clear_all_cache_on_update() %@
+                conn.clear_all_cache = true;
+                @%- else %@
+                if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+                    let shard_id = conn.shard_id();
+                    conn.push_cache_op(CacheOp::DeleteMany { ids, shard_id }.wrap()).await;
+                    @{- def.cache_owners|fmt_cache_owners("
+                    conn.push_cache_op(db::models::{mod}::CacheOp::InvalidateAll.wrap()).await;") }@
+                }
+                @%- endif %@
+                @%- endif %@
+                Ok(rows_affected)
+            }
+            inner(conn, ids).await
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
+    }
+@%- endif %@
+@%- if !def.disable_delete() %@
+
+    #[allow(unused_mut)]
+    pub async fn delete(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<()> {
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            @%- if def.updated_at_conf().is_some() %@
+            if obj._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
+                obj.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
+            }
+            @%- endif %@
+            @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
+            if !conn.clear_all_cache {
+                @{- def.cache_owners|fmt_cache_owners("
+                if let Some(v) = _base_filter_::repositories::{base_mod}::RelFk{rel_name_pascal}::get_fk(&obj._data) {
+                    if _base_filter_::repositories::{base_mod}::USE_CACHE || _base_filter_::repositories::{base_mod}::ENABLE_ALL_ROWS_CACHE || _base_filter_::repositories::{base_mod}::ENABLE_UPDATE_NOTICE {
+                        let id: _base_filter_::repositories::{base_mod}::Primary = v.into();
+                        let id: _base_filter_::repositories::{base_mod}::InnerPrimary = (&id).into();
+                        conn.push_cache_op(_base_filter_::repositories::{base_mod}::CacheOp::Invalidate{id, shard_id: conn.shard_id()}.wrap()).await;
+                    }
+                }") }@
             }
-            debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "force_delete_by_ids", ctx = conn.ctx_no(), ids = primaries_to_str(&ids); "");
+            @%- endif %@
+            let cache_msg = __delete(conn, obj).await?;
             @%- if !config.force_disable_cache %@
             @%- if def.act_as_job_queue() %@
             @%- else if def.clear_all_cache_on_update() %@
             conn.clear_all_cache = true;
             @%- else %@
             if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-                let shard_id = conn.shard_id();
-                conn.push_cache_op(CacheOp::DeleteMany { ids, shard_id }.wrap()).await;
-                @{- def.cache_owners|fmt_cache_owners("
-                conn.push_cache_op(db::models::{mod}::CacheOp::InvalidateAll.wrap()).await;") }@
+                if let Some(cache_msg) = cache_msg {
+                    conn.push_cache_op(cache_msg.wrap()).await;
+                }
             }
             @%- endif %@
             @%- endif %@
-            Ok(rows_affected)
-        }
-        inner(conn, ids).await
-    }
-@%- endif %@
-@%- if !def.disable_delete() %@
-
-    #[allow(unused_mut)]
-    pub async fn delete(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<()> {
-        @%- if def.updated_at_conf().is_some() %@
-        if obj._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
-            obj.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
-        }
-        @%- endif %@
-        @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
-        if !conn.clear_all_cache {
-            @{- def.cache_owners|fmt_cache_owners("
-            if let Some(v) = _base_filter_::repositories::{base_mod}::RelFk{rel_name_pascal}::get_fk(&obj._data) {
-                if _base_filter_::repositories::{base_mod}::USE_CACHE || _base_filter_::repositories::{base_mod}::ENABLE_ALL_ROWS_CACHE || _base_filter_::repositories::{base_mod}::ENABLE_UPDATE_NOTICE {
-                    let id: _base_filter_::repositories::{base_mod}::Primary = v.into();
-                    let id: _base_filter_::repositories::{base_mod}::InnerPrimary = (&id).into();
-                    conn.push_cache_op(_base_filter_::repositories::{base_mod}::CacheOp::Invalidate{id, shard_id: conn.shard_id()}.wrap()).await;
-                }
-            }") }@
-        }
-        @%- endif %@
-        let cache_msg = __delete(conn, obj).await?;
-        @%- if !config.force_disable_cache %@
-        @%- if def.act_as_job_queue() %@
-        @%- else if def.clear_all_cache_on_update() %@
-        conn.clear_all_cache = true;
-        @%- else %@
-        if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-            if let Some(cache_msg) = cache_msg {
-                conn.push_cache_op(cache_msg.wrap()).await;
-            }
-        }
-        @%- endif %@
-        @%- endif %@
-        Ok(())
+            Ok(())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 @%- endif %@
 @%- if !def.disable_update() && def.soft_delete().is_some() %@
 
     #[allow(unused_mut)]
     pub async fn restore(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) -> Result<_@{ pascal_name }@> {
-        obj._do_delete = false;
-        if !obj.has_been_deleted() {
-            return Ok(obj.into());
-        }
-        @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
-        if !conn.clear_all_cache {
-            @{- def.cache_owners|fmt_cache_owners("
-            _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
-        }
-        @%- endif %@
-        @%- if def.updated_at_conf().is_some() %@
-        if obj._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
-            obj.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
-        }
-        @%- endif %@
+        let prev_shard_id = _route_shard(conn, [InnerPrimary::from(&obj)])?;
+        let result: Result<_> = async {
+            obj._do_delete = false;
+            if !obj.has_been_deleted() {
+                return Ok(obj.into());
+            }
+            @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
+            if !conn.clear_all_cache {
+                @{- def.cache_owners|fmt_cache_owners("
+                _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
+            }
+            @%- endif %@
+            @%- if def.updated_at_conf().is_some() %@
+            if obj._op.@{ ConfigDef::updated_at()|ident }@ == Op::None {
+                obj.mut_@{ ConfigDef::updated_at() }@().set(@{(def.updated_at_conf().unwrap() == Timestampable::RealTime)|if_then_else_ref("SystemTime::now()","conn.time()")}@.into());
+            }
+            @%- endif %@
 @{- def.soft_delete_tpl2("","
-        obj.mut_deleted_at().set(None);","
-        obj.mut_deleted().set(false);","
-        obj.mut_deleted().set(0);")}@
-        let (obj, cache_msg) = __save_update(conn, obj).await?;
-        @%- if !config.force_disable_cache %@
-        @%- if def.act_as_job_queue() %@
-        conn.push_cache_op(CacheOp::Queued.wrap()).await;
-        @%- else if def.clear_all_cache_on_update() %@
-        conn.clear_all_cache = true;
-        @%- else %@
-        if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-            if let Some(cache_msg) = cache_msg {
-                conn.push_cache_op(cache_msg.wrap()).await;
+            obj.mut_deleted_at().set(None);","
+            obj.mut_deleted().set(false);","
+            obj.mut_deleted().set(0);")}@
+            let (obj, cache_msg) = __save_update(conn, obj).await?;
+            @%- if !config.force_disable_cache %@
+            @%- if def.act_as_job_queue() %@
+            conn.push_cache_op(CacheOp::Queued.wrap()).await;
+            @%- else if def.clear_all_cache_on_update() %@
+            conn.clear_all_cache = true;
+            @%- else %@
+            if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+                if let Some(cache_msg) = cache_msg {
+                    conn.push_cache_op(cache_msg.wrap()).await;
+                }
             }
-        }
-        @%- endif %@
-        @%- endif %@
-        Ok(obj)
+            @%- endif %@
+            @%- endif %@
+            Ok(obj)
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 @%- endif %@
 @%- if !def.disable_delete() %@
@@ -3806,38 +4586,44 @@ pub mod _repo_ {
     #[allow(clippy::needless_borrow)]
     pub async fn force_delete(conn: &mut DbConn, obj: _@{ pascal_name }@Updater) -> Result<()> {
         let id: InnerPrimary = (&obj).into();
-        let sql = r#"DELETE FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@"#;
-        @%- if !config.is_mysql() %@
-        let sql = senax_common::convert_mysql_placeholders_to_postgresql(sql);
-        @%- endif %@
-        let mut query = sqlx::query(&sql);
-        debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
-        @{- def.primaries()|fmt_join("
-        query = query.bind(id.{index}{bind_as});", "") }@
-        if conn.wo_tx() {
-            query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?;
-        } else {
-            query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?;
-        }
-        debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "force_delete", ctx = conn.ctx_no(), id = id.to_string(); "{}", &obj);
+        let prev_shard_id = _route_shard(conn, [&id])?;
+        let result: Result<_> = async {
+            let sql = r#"DELETE FROM @{ table_name|db_esc }@ WHERE @{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@"#;
+            @%- if !config.is_mysql() %@
+            let sql = senax_common::convert_mysql_placeholders_to_postgresql(sql);
+            @%- endif %@
+            let mut query = sqlx::query(&sql);
+            debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", ctx = conn.ctx_no(), sql = &query.sql(); "query");
+            @{- def.primaries()|fmt_join("
+            query = query.bind(id.{index}{bind_as});", "") }@
+            if conn.wo_tx() {
+                query.execute(conn.acquire_writer().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?;
+            } else {
+                query.execute(conn.get_tx().await?.as_mut()).await.context(err::ErrorTable(TABLE_NAME))?;
+            }
+            _dual_write(conn, [&id]).await;
+            debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "force_delete", ctx = conn.ctx_no(), id = id.to_string(); "{}", &obj);
 @%- if !config.force_disable_cache %@
-        @%- if def.act_as_job_queue() %@
-        @%- else if def.clear_all_cache_on_update() %@
-        conn.clear_all_cache = true;
-        @%- else %@
-        if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-            let shard_id = conn.shard_id();
-            conn.push_cache_op(CacheOp::Delete { id, shard_id }.wrap()).await;
-        }
-        @%- endif %@
-        @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
-        if !conn.clear_all_cache {
-            @{- def.cache_owners|fmt_cache_owners("
-            _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
-        }
-        @%- endif %@
+            @%- if def.act_as_job_queue() %@
+            @%- else if def.clear_all_cache_on_update() %@
+            conn.clear_all_cache = true;
+            @%- else %@
+            if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+                let shard_id = conn.shard_id();
+                conn.push_cache_op(CacheOp::Delete { id, shard_id }.wrap()).await;
+            }
+            @%- endif %@
+            @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
+            if !conn.clear_all_cache {
+                @{- def.cache_owners|fmt_cache_owners("
+                _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
+            }
+            @%- endif %@
 @%- endif %@
-        Ok(())
+            Ok(())
+        }.await;
+        conn.set_shard_id(prev_shard_id as usize);
+        result
     }
 @%- endif %@
 
@@ -4047,8 +4833,16 @@ async fn ___find_many_from_cache(conn: &mut DbConn, ids: Vec<PrimaryHasher>, joi
     let ctx_no = conn.ctx_no();
     let hash_and_id: Vec<_> = ids.into_iter().map(|id| (id.hash_val(shard_id), id)).collect();
     let cache_map = Cache::get_many::<CacheWrapper>(&hash_and_id.iter().map(|(hash, id)| *hash).collect(), shard_id, USE_FAST_CACHE).await;
+    @%- if def.stale_while_revalidate.is_some() %@
+    let mut stale_ids = Vec::new();
+    @%- endif %@
     for (hash, id) in hash_and_id {
         if let Some(obj) = cache_map.get(&hash).filter(|o| InnerPrimary::from(*o) == id.0) {
+            @%- if def.stale_while_revalidate.is_some() %@
+            if Cache::is_stale(obj.as_ref()) {
+                stale_ids.push(id.clone());
+            }
+            @%- endif %@
             if obj._has_join(&joiner) {
                 list.push((obj.clone(), Default::default()).into());
             } else {
@@ -4058,7 +4852,14 @@ async fn ___find_many_from_cache(conn: &mut DbConn, ids: Vec<PrimaryHasher>, joi
             rest_ids.push(id);
         }
     }
+    @%- if def.stale_while_revalidate.is_some() %@
+    if !stale_ids.is_empty() {
+        tokio::spawn(___revalidate_cache(shard_id, stale_ids, joiner.clone()));
+    }
+    @%- endif %@
     if !rest_ids.is_empty() {
+        // Only one caller loads the same rows and the others read them from the cache after waiting.
+        let _flight = Cache::begin_flight(&rest_ids.iter().map(|id| id.hash_val(shard_id)).collect::<Vec<_>>()).await;
         if rest_ids.len() <= 10 {
             for id in rest_ids.iter() {
                 BULK_FETCH_QUEUE.get().unwrap()[shard_id as usize].push(id.0.clone());
@@ -4109,50 +4910,7 @@ async fn ___find_many_from_cache(conn: &mut DbConn, ids: Vec<PrimaryHasher>, joi
             for v in result.into_iter() {
                 let arc = Arc::new(v);
                 let id = PrimaryHasher(InnerPrimary::from(&arc), shard_id);
-                let sync = CACHE_RESET_SYNC.get().unwrap()[shard_id as usize].read().await;
-                if *sync <= conn.cache_sync() {
-                    @%- if def.versioned %@
-                    let vw = VersionWrapper {
-                        id: id.0.clone(),
-                        shard_id,
-                        time: MSec::default(),
-                        version: 0,
-                    };
-                    if let Some(ver) = Cache::get_version::<VersionWrapper>(&vw, shard_id).await.filter(|o| o.id == id.0) {
-                        if arc._inner.@{ version_col }@.greater_equal(ver.version) {
-                            Cache::insert_long(&id, arc.clone(), USE_FAST_CACHE).await;
-                        }
-                    } else {
-                        let cs = CacheSyncWrapper {
-                            id: id.0.clone(),
-                            shard_id,
-                            time: MSec::default(),
-                            sync: 0,
-                        };
-                        if let Some(cs) = Cache::get_version::<CacheSyncWrapper>(&cs, shard_id).await.filter(|o| o.id == id.0) {
-                            if cs.sync <= conn.cache_sync() {
-                                Cache::insert_long(&id, arc.clone(), USE_FAST_CACHE).await;
-                            }
-                        } else {
-                            Cache::insert_long(&id, arc.clone(), USE_FAST_CACHE).await;
-                        }
-                    }
-                    @%- else %@
-                    let cs = CacheSyncWrapper {
-                        id: id.0.clone(),
-                        shard_id,
-                        time: MSec::default(),
-                        sync: 0,
-                    };
-                    if let Some(cs) = Cache::get_version::<CacheSyncWrapper>(&cs, shard_id).await.filter(|o| o.id == id.0) {
-                        if cs.sync <= conn.cache_sync() {
-                            Cache::insert_long(&id, arc.clone(), USE_FAST_CACHE).await;
-                        }
-                    } else {
-                        Cache::insert_long(&id, arc.clone(), USE_FAST_CACHE).await;
-                    }
-                    @%- endif %@
-                }
+                ___insert_to_cache(&conn, &id, &arc).await;
                 if rest_ids2.contains(&id) {
                     list.push((arc, Default::default()).into());
                 }
@@ -4163,6 +4921,91 @@ async fn ___find_many_from_cache(conn: &mut DbConn, ids: Vec<PrimaryHasher>, joi
     _@{ pascal_name }@Joiner::join(&mut list, conn, joiner).await?;
     Ok(list)
 }
+
+async fn ___insert_to_cache(conn: &DbConn, id: &PrimaryHasher, arc: &Arc<CacheWrapper>) {
+    let shard_id = id.1;
+    let sync = CACHE_RESET_SYNC.get().unwrap()[shard_id as usize].read().await;
+    if *sync <= conn.cache_sync() {
+        @%- if def.versioned %@
+        let vw = VersionWrapper {
+            id: id.0.clone(),
+            shard_id,
+            time: MSec::default(),
+            version: 0,
+        };
+        if let Some(ver) = Cache::get_version::<VersionWrapper>(&vw, shard_id).await.filter(|o| o.id == id.0) {
+            if arc._inner.@{ version_col }@.greater_equal(ver.version) {
+                Cache::insert_long(id, arc.clone(), USE_FAST_CACHE).await;
+            }
+        } else {
+            let cs = CacheSyncWrapper {
+                id: id.0.clone(),
+                shard_id,
+                time: MSec::default(),
+                sync: 0,
+            };
+            if let Some(cs) = Cache::get_version::<CacheSyncWrapper>(&cs, shard_id).await.filter(|o| o.id == id.0) {
+                if cs.sync <= conn.cache_sync() {
+                    Cache::insert_long(id, arc.clone(), USE_FAST_CACHE).await;
+                }
+            } else {
+                Cache::insert_long(id, arc.clone(), USE_FAST_CACHE).await;
+            }
+        }
+        @%- else %@
+        let cs = CacheSyncWrapper {
+            id: id.0.clone(),
+            shard_id,
+            time: MSec::default(),
+            sync: 0,
+        };
+        if let Some(cs) = Cache::get_version::<CacheSyncWrapper>(&cs, shard_id).await.filter(|o| o.id == id.0) {
+            if cs.sync <= conn.cache_sync() {
+                Cache::insert_long(id, arc.clone(), USE_FAST_CACHE).await;
+            }
+        } else {
+            Cache::insert_long(id, arc.clone(), USE_FAST_CACHE).await;
+        }
+        @%- endif %@
+    }
+}
+@%- if def.stale_while_revalidate.is_some() %@
+
+async fn ___revalidate_cache(shard_id: ShardId, ids: Vec<PrimaryHasher>, joiner: Option<Box<Joiner_>>) {
+    let hashes: Vec<_> = ids.iter().map(|id| id.hash_val(shard_id)).collect();
+    let Some(flight) = Cache::try_begin_flight(&hashes) else {
+        return;
+    };
+    let ids: Vec<InnerPrimary> = ids.into_iter().filter(|id| flight.contains(id.hash_val(shard_id))).map(|id| id.0).collect();
+    if ids.is_empty() {
+        return;
+    }
+    let result: Result<()> = async {
+        let mut conn = DbConn::_new(shard_id);
+        conn.begin_cache_tx().await?;
+        #[allow(unused_mut)]
+        let mut result = ___find_many_for_cache(&mut conn, &ids).await?;
+        if let Some(joiner) = &joiner {
+@{- def.relations_in_cache(Joinable::Join)|fmt_rel_join("
+            if joiner.{rel_name}.is_some() {
+                CacheWrapper::fetch_{raw_rel_name}_for_vec(&mut result, &mut conn).await?;
+            }", "") }@
+        }
+        let _lock = db::models::CACHE_UPDATE_LOCK.read().await;
+        for v in result.into_iter() {
+            let arc = Arc::new(v);
+            let id = PrimaryHasher(InnerPrimary::from(&arc), shard_id);
+            ___insert_to_cache(&conn, &id, &arc).await;
+        }
+        conn.release_cache_tx();
+        Ok(())
+    }
+    .await;
+    if let Err(e) = result {
+        warn!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "revalidate: {}", e);
+    }
+}
+@%- endif %@
 @%- endif %@
 
 #[allow(clippy::needless_borrow)]
@@ -4223,7 +5066,7 @@ async fn __find_for_update(conn: &mut DbConn, id: &InnerPrimary, trash_mode: Tra
     } else {
         filter_str.push_str(" AND ");
     }
-    let sql = format!(r#"SELECT {} FROM @{ table_name|db_esc }@ as _t1 {filter_str} @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@ FOR UPDATE"#, sql_cols);
+    let sql = format!(r#"SELECT {} FROM @{ table_name|db_esc }@ as _t1 {filter_str} @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@@% if !config.is_sqlite() %@ FOR UPDATE@% endif %@"#, sql_cols);
     @%- if !config.is_mysql() %@
     let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
     @%- endif %@
@@ -4271,7 +5114,7 @@ async fn __find_many_for_update(conn: &mut DbConn, ids: &[InnerPrimary], trash_m
     for ids in id_chunks {
         let q = "@{ def.primaries()|fmt_join_with_paren("{placeholder}", ",") }@,".repeat(ids.len());
         let sql = format!(
-            r#"SELECT {} FROM @{ table_name|db_esc }@ {filter_str} @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({}) FOR UPDATE;"#,
+            r#"SELECT {} FROM @{ table_name|db_esc }@ {filter_str} @{ def.primaries()|fmt_join_with_paren("{col_esc}", ",") }@ in ({})@% if !config.is_sqlite() %@ FOR UPDATE@% endif %@;"#,
             sql_cols,
             &q[0..q.len() - 1],
         );
@@ -4404,10 +5247,10 @@ async fn __save_insert(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater, ov
         @%- if config.is_mysql() %@
         r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) AS new ON DUPLICATE KEY UPDATE @{ def.non_primaries_except_created_at()|fmt_join("{col_esc}=new.{col_esc}", ",") }@@{ def.auto_inc()|fmt_join(",{col_esc}=LAST_INSERT_ID({col_esc})", "") }@;"#
         @%- else %@
-        r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) ON CONFLICT @{ def.upsert_conflict_target() }@ DO UPDATE SET @{ def.non_primaries_except_created_at()|fmt_join("{col_esc}=excluded.{col_esc}", ",") }@@{ def.auto_inc()|fmt_join(" RETURNING xmax,{col_esc}", "") }@;"#
+        r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) ON CONFLICT @{ def.upsert_conflict_target() }@ DO UPDATE SET @{ def.non_primaries_except_created_at()|fmt_join("{col_esc}=excluded.{col_esc}", ",") }@@{ def.auto_inc()|fmt_join(" RETURNING {xmax},{col_esc}", "") }@;"#
         @%- endif %@
     } else {
-        r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@)@% if !config.is_mysql() %@@{ def.auto_inc()|fmt_join(" RETURNING xmax,{col_esc}", "") }@@% endif %@;"#
+        r#"INSERT INTO @{ table_name|db_esc }@ (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@)@% if !config.is_mysql() %@@{ def.auto_inc()|fmt_join(" RETURNING {xmax},{col_esc}", "") }@@% endif %@;"#
     };
     @%- if !config.is_mysql() %@
     let sql = &senax_common::convert_mysql_placeholders_to_postgresql(sql);
@@ -4421,6 +5264,7 @@ async fn __save_insert(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater, ov
     }", "") }@
     debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "insert", ctx = conn.ctx_no(); "{}", &obj);
     debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "{:?}", &obj);
+    _dual_write(conn, [InnerPrimary::from(&obj._data)]).await;
     let mut obj2: _@{ pascal_name }@ = (obj._data.clone(), BTreeMap::default()).into();
     let mut update_cache = true;
 
@@ -4475,9 +5319,9 @@ async fn __save_update(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) ->
         @%- endif %@
         @%- endif %@
         @%- if def.versioned %@
-        let sql = format!(r#"UPDATE @{ table_name|db_esc }@ SET {} WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@ AND @{ version_col|db_esc }@=?@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING xmax,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING xmax,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
+        let sql = format!(r#"UPDATE @{ table_name|db_esc }@ SET {} WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@ AND @{ version_col|db_esc }@=?@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING @{ config.xmax() }@,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING @{ config.xmax() }@,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
         @%- else %@
-        let sql = format!(r#"UPDATE @{ table_name|db_esc }@ SET {} WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING xmax,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING xmax,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
+        let sql = format!(r#"UPDATE @{ table_name|db_esc }@ SET {} WHERE @{ def.inheritance_cond(" AND ") }@@{ def.primaries()|fmt_join("{col_esc}={placeholder}", " AND ") }@@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING @{ config.xmax() }@,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING @{ config.xmax() }@,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
         @%- endif %@
         @%- if !config.is_mysql() %@
         let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
@@ -4498,6 +5342,7 @@ async fn __save_update(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) ->
         if rows_affected == 0 {
             anyhow::bail!(err::RowNotFound::new(TABLE_NAME, id.to_string()));
         }
+        _dual_write(conn, [&id]).await;
         @%- if def.versioned %@
         obj._data.@{ version_col }@ = _last_insert_id as @{ config.u32() }@;
         obj._update.@{ version_col }@ = _last_insert_id as @{ config.u32() }@;
@@ -4547,7 +5392,7 @@ fn assign_non_primaries(obj: &_@{ pascal_name }@Updater) -> (Vec<String>, bool)
     (vec, update_cache)
 }
 
-fn bind_non_primaries<'a>(obj: &'a _@{ pascal_name }@Updater, mut query: Query<'a, DbType, DbArguments>, _sql: &'a str) -> Query<'a, DbType, DbArguments> {
+fn bind_non_primaries<'a>(obj: &'a _@{ pascal_name }@Updater, mut query: Query<'a, DbType, DbArguments<'a>>, _sql: &'a str) -> Query<'a, DbType, DbArguments<'a>> {
     @{- def.non_primaries_except_read_only()|fmt_join("
     for _n in 0..obj._op.{ident}.get_bind_num({may_null}) {
         query = query.bind(obj._update.{ident}{bind_as});
@@ -4573,7 +5418,7 @@ async fn __save_upsert(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) ->
     @%- endif %@
     let sql = format!(r#"INSERT INTO @{ table_name|db_esc }@ 
         (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{col_esc}", ",") }@) 
-        VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) @{ config.db_type_switch("ON DUPLICATE KEY UPDATE", "ON CONFLICT ") }@@{ def.upsert_conflict_target() }@@{ config.db_type_switch("", " DO UPDATE SET") }@ {}@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING xmax,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING xmax,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
+        VALUES (@{ def.all_fields_except_read_only_and_auto_inc()|fmt_join("{placeholder}", ",") }@) @{ config.db_type_switch("ON DUPLICATE KEY UPDATE", "ON CONFLICT ") }@@{ def.upsert_conflict_target() }@@{ config.db_type_switch("", " DO UPDATE SET") }@ {}@% if !config.is_mysql() %@@% if def.versioned %@ RETURNING @{ config.xmax() }@,@{ version_col|db_esc }@@% endif %@@% if def.counter_field.is_some() %@ RETURNING @{ config.xmax() }@,@{ def.get_counter_field_col()|db_esc }@@% endif %@@% endif %@;"#, &vec.join(","));
     @%- if !config.is_mysql() %@
     let sql = senax_common::convert_mysql_placeholders_to_postgresql(&sql);
     @%- endif %@
@@ -4588,6 +5433,7 @@ async fn __save_upsert(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) ->
         if obj._data.{ident} == 0 {
             obj._data.{ident} = _last_insert_id as {inner};
         }", "") }@
+        _dual_write(conn, [InnerPrimary::from(&obj._data)]).await;
         let mut obj2: _@{ pascal_name }@ = (obj._data.clone(), BTreeMap::default()).into();
         @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && !def.act_as_job_queue() %@
         let cache_msg = Some(CacheOp::Insert {
@@ -4614,6 +5460,7 @@ async fn __save_upsert(conn: &mut DbConn, mut obj: _@{ pascal_name }@Updater) ->
         }
         @%- endif %@
         let id = InnerPrimary::from(&obj);
+        _dual_write(conn, [&id]).await;
         let mut obj2: _@{ pascal_name }@ = (obj._data, BTreeMap::default()).into();
         @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && !def.act_as_job_queue() %@
         let mut cache_msg = Some(CacheOp::Update {
@@ -4648,6 +5495,7 @@ async fn __update_many(conn: &mut DbConn, ids: Vec<InnerPrimary>, mut obj: __Upd
     for ids in ids.chunks(IN_CONDITION_LIMIT) {
         rows_affected += ___update_many(conn, ids, &obj).await?;
     }
+    _dual_write(conn, &ids).await;
     debug!(target: "_db_update::@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", op = "update_many", ctx = conn.ctx_no(), ids = primaries_to_str(&ids); "{}", &obj);
     debug!(target: "db_@{ db|snake }@::@{ group_name|snake }@::@{ mod_name }@", "{:?}", &obj);
     @%- if !config.force_disable_cache %@
@@ -4733,49 +5581,59 @@ async fn ___update_many(conn: &mut DbConn, ids: &[InnerPrimary], obj: &__Updater
 @%- endif %@
 
 async fn _bulk_insert(conn: &mut DbConn, mut list: Vec<_@{ pascal_name }@Updater>, ignore: bool, replace: bool) -> Result<()> {
-    let mut vec: Vec<ForInsert> = Vec::with_capacity(list.len());
-    for mut obj in list.into_iter() {
-        ensure!(obj.is_new(), "The obj is not new.");
-        obj.__validate()?;
-        obj.__set_default_value(conn).await?;
-        vec.push(obj.into());
-    }
-    __bulk_insert(conn, &vec, ignore, replace, false).await
+    let prev_shard_id = _route_shard(conn, list.iter().map(InnerPrimary::from))?;
+    let result: Result<_> = async {
+        let mut vec: Vec<ForInsert> = Vec::with_capacity(list.len());
+        for mut obj in list.into_iter() {
+            ensure!(obj.is_new(), "The obj is not new.");
+            obj.__validate()?;
+            obj.__set_default_value(conn).await?;
+            vec.push(obj.into());
+        }
+        __bulk_insert(conn, &vec, ignore, replace, false).await
+    }.await;
+    conn.set_shard_id(prev_shard_id as usize);
+    result
 }
 
 async fn __bulk_insert(conn: &mut DbConn, list: &[ForInsert], ignore: bool, replace: bool, overwrite: bool) -> Result<()> {
-    let result = ___bulk_insert(conn, list, ignore, replace, overwrite).await?;
-    @%- if !config.force_disable_cache %@
-    @%- if def.act_as_job_queue() %@
-    conn.push_cache_op(CacheOp::Queued.wrap()).await;
-    @%- else if def.clear_all_cache_on_update() %@
-    conn.clear_all_cache = true;
-    @%- else %@
-    @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
-    if !conn.clear_all_cache {
-        for list in &result {
-            for obj in list.iter() {
-                @{- def.cache_owners|fmt_cache_owners("
-                _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
+    let prev_shard_id = _route_shard(conn, list.iter().map(|v| InnerPrimary::from(&v._data)))?;
+    let result: Result<_> = async {
+        let result = ___bulk_insert(conn, list, ignore, replace, overwrite).await?;
+        @%- if !config.force_disable_cache %@
+        @%- if def.act_as_job_queue() %@
+        conn.push_cache_op(CacheOp::Queued.wrap()).await;
+        @%- else if def.clear_all_cache_on_update() %@
+        conn.clear_all_cache = true;
+        @%- else %@
+        @%- if !config.force_disable_cache && !def.clear_all_cache_on_update() && def.cache_owners.len() > 0 %@
+        if !conn.clear_all_cache {
+            for list in &result {
+                for obj in list.iter() {
+                    @{- def.cache_owners|fmt_cache_owners("
+                    _invalidate_{raw_rel_name}(conn, &obj._data).await;") }@
+                }
             }
         }
-    }
-    @%- endif %@
-    if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
-        for list in result {
-            let cache_msg = CacheOp::BulkInsert {
-                replace,
-                overwrite,
-                ignore, 
-                shard_id: conn.shard_id(),
-                list,
-            };
-            conn.push_cache_op_to(cache_msg.wrap(), @{ def.disable_insert_cache_propagation }@).await;
+        @%- endif %@
+        if !conn.clear_all_cache && (USE_CACHE || ENABLE_ALL_ROWS_CACHE || ENABLE_UPDATE_NOTICE) {
+            for list in result {
+                let cache_msg = CacheOp::BulkInsert {
+                    replace,
+                    overwrite,
+                    ignore, 
+                    shard_id: conn.shard_id(),
+                    list,
+                };
+                conn.push_cache_op_to(cache_msg.wrap(), @{ def.disable_insert_cache_propagation }@).await;
+            }
         }
-    }
-    @%- endif %@
-    @%- endif %@
-    Ok(())
+        @%- endif %@
+        @%- endif %@
+        Ok(())
+    }.await;
+    conn.set_shard_id(prev_shard_id as usize);
+    result
 }
 
 pub async fn ___bulk_insert(conn: &mut DbConn, list: &[ForInsert], ignore: bool, replace: bool, overwrite: bool) -> Result<Vec<Vec<ForInsert>>> {
@@ -4785,7 +5643,9 @@ pub async fn ___bulk_insert(conn: &mut DbConn, list: &[ForInsert], ignore: bool,
     let chunks = crate::misc::split_by_weight(list, *BULK_INSERT_MAX_SIZE.get().unwrap(), @{ 15000 / def.all_fields_except_read_only_and_auto_inc().len() }@, |v| v._data._size());
     let mut result = Vec::new();
     for chunk in chunks {
-        result.push(____bulk_insert(conn, chunk, ignore, replace, overwrite).await?);
+        let list = ____bulk_insert(conn, chunk, ignore, replace, overwrite).await?;
+        _dual_write(conn, list.iter().map(|v| InnerPrimary::from(&v._data))).await;
+        result.push(list);
     }
     Ok(result)
 }
@@ -4966,6 +5826,7 @@ async fn __bulk_upsert(conn: &mut DbConn, list: &[Data], obj: &__Updater__) -> R
     let chunks = crate::misc::split_by_weight(list, *BULK_INSERT_MAX_SIZE.get().unwrap(), @{ 15000 / def.all_fields_except_read_only_and_auto_inc().len() }@, |v| v._size());
     for chunk in chunks {
         ___bulk_upsert(conn, chunk, obj).await?;
+        _dual_write(conn, chunk.iter().map(InnerPrimary::from)).await;
     }
     Ok(())
 }
@@ -5073,7 +5934,7 @@ fn make_force_indexes(filter_digest: &str) -> Vec<&'static str> {
 }
 
 #[allow(clippy::needless_borrow)]
-fn bind_to_query<'a>(mut query: Query<'a, DbType, DbArguments>, data: &'a Data) -> Query<'a, DbType, DbArguments> {
+fn bind_to_query<'a>(mut query: Query<'a, DbType, DbArguments<'a>>, data: &'a Data) -> Query<'a, DbType, DbArguments<'a>> {
     @{- def.all_fields_except_read_only_and_auto_inc()|fmt_join("
     query = query.bind(data.{ident}{bind_as});", "") }@
     query
@@ -5083,8 +5944,13 @@ pub(crate) async fn _seed(seed: &serde_yaml::Value, conns: &mut [DbConn]) -> Res
     if let Some(mapping) = seed.as_mapping() {
         for (name, factory) in mapping {
             let seed: _@{ pascal_name }@Factory = serde_yaml::from_str(&serde_yaml::to_string(&factory)?)?;
-            let conn = &mut conns[0];
             let obj = seed.create();
+            @%- if def.shard_key_index().is_some() %@
+            let shard_id = _shard_id_of([InnerPrimary::from(&obj)])?.unwrap_or_default();
+            let conn = &mut conns[shard_id as usize];
+            @%- else %@
+            let conn = &mut conns[0];
+            @%- endif %@
             if let Some(obj) = _repo_::save(conn, obj).await? {
                 @{- def.auto_inc_or_seq()|fmt_join("
                 let id = obj._{raw_name}();
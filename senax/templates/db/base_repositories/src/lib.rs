@@ -4,7 +4,9 @@
 
 use anyhow::Result;
 use senax_common::ShardId;
+use senax_common::shard::ReshardProgress;
 use std::path::Path;
+use std::time::Duration;
 
 #[rustfmt::skip]
 #[allow(clippy::module_inception)]
@@ -146,4 +148,16 @@ pub async fn check(shard_id: ShardId) -> Vec<Vec<Result<()>>> {
         @%- endfor %@
     ]
 }
+pub async fn warm_up(shard_id: ShardId) -> Result<()> {
+    @%- for (name, defs) in groups %@
+    repositories::@{ name|snake|ident }@::warm_up(shard_id).await?;
+    @%- endfor %@
+    Ok(())
+}
+pub async fn reshard(progress: &mut ReshardProgress, batch_size: usize, interval: Duration) -> Result<()> {
+    @%- for (name, defs) in groups %@
+    repositories::@{ name|snake|ident }@::reshard(progress, batch_size, interval).await?;
+    @%- endfor %@
+    Ok(())
+}
 @{-"\n"}@
\ No newline at end of file
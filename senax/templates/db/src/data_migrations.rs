@@ -0,0 +1,22 @@
+//! Data migrations that run in order with the SQL migrations.
+//! Use `senax gen-migrate --data` to add a new one.
+#[allow(unused_imports)]
+use anyhow::{Result, bail};
+
+use crate::DbConn;
+
+// Do not modify this line. (data_migration_mod)
+
+/// Versions of the SQL files that record the data migrations as applied
+pub(crate) const VERSIONS: &[i64] = &[
+    // Do not modify this line. (data_migration_version)
+];
+
+#[allow(unused_variables)]
+pub(crate) async fn run(version: i64, conn: &mut DbConn) -> Result<()> {
+    match version {
+        // Do not modify this line. (data_migration_run)
+        _ => bail!("Unknown data migration: {}", version),
+    }
+}
+@{-"\n"}@
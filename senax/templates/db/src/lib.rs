@@ -23,6 +23,7 @@ pub mod impl_domain;
 pub mod models;
 #[rustfmt::skip]
 pub mod seeder;
+pub mod data_migrations;
 
 #[rustfmt::skip]
 pub mod repositories {
@@ -53,6 +54,8 @@ pub async fn start(
     )
     .await?;
     models::start(db_dir).await?;
+    _base::_load_cache_dump(is_hot_deploy).await;
+    models::warm_up().await?;
     Ok(())
 }
 
@@ -98,6 +101,31 @@ pub async fn migrate(
     Ok(())
 }
 
+pub async fn migrate_down(use_test: bool, steps: u32) -> Result<()> {
+    if use_test {
+        connection::init_test().await?;
+    } else {
+        connection::init().await?;
+    }
+    let mut join_set = tokio::task::JoinSet::new();
+    for shard_id in DbConn::shard_num_range() {
+        join_set.spawn_local(async move { models::exec_migrate_down(shard_id, steps).await });
+    }
+    let mut error = None;
+    while let Some(res) = join_set.join_next().await {
+        if let Err(e) = res?
+            && let Some(e) = error.replace(e)
+        {
+            log::error!(target: "db_@{ db|snake }@", "{}", e);
+        }
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    models::_clear_cache(&DbConn::inc_all_cache_sync().await, true).await;
+    Ok(())
+}
+
 pub async fn check(use_test: bool) -> Result<()> {
     if use_test {
         connection::init_test().await?;
@@ -107,4 +135,81 @@ pub async fn check(use_test: bool) -> Result<()> {
     models::check().await?;
     Ok(())
 }
+
+/// Copies rows to the shard layout of @{ db|upper_snake }@_RESHARD_DB_URL,
+/// or deletes the rows left on their old shards when `cleanup` is set.
+pub async fn reshard(
+    progress_dir: &Path,
+    batch_size: usize,
+    interval: Duration,
+    swap: bool,
+    cleanup: bool,
+) -> Result<()> {
+    use senax_common::shard::{ReshardPhase, ReshardProgress, progress_path, replace_env_var};
+    connection::init().await?;
+    let path = progress_path(progress_dir, "@{ db|snake }@");
+    if cleanup {
+        anyhow::ensure!(
+            DbConn::reshard_num() == 0,
+            "@{ db|upper_snake }@_RESHARD_DB_URL must be removed before the cleanup."
+        );
+        let mut progress = ReshardProgress::load(&path, ReshardPhase::Cleanup, DbConn::shard_num())?;
+        return models::reshard(&mut progress, batch_size, interval).await;
+    }
+    anyhow::ensure!(
+        DbConn::reshard_num() > 0,
+        "@{ db|upper_snake }@_RESHARD_DB_URL is required in the .env file."
+    );
+    let reshard_replica_url = std::env::var("@{ db|upper_snake }@_RESHARD_REPLICA_DB_URL").ok();
+    if swap {
+        #[cfg(feature = "etcd")]
+        {
+            // The URLs in etcd take precedence over the .env file, so the swap would not take effect.
+            let names = [
+                "@{ db|upper_snake }@_DB_URL",
+                "@{ db|upper_snake }@_REPLICA_DB_URL",
+                "@{ db|upper_snake }@_RESHARD_DB_URL",
+                "@{ db|upper_snake }@_RESHARD_REPLICA_DB_URL",
+            ];
+            let etcd = senax_common::etcd::map("db/").await?;
+            let key = etcd.keys().find(|k| {
+                names
+                    .iter()
+                    .any(|name| k == name || k.starts_with(&format!("{}/", name)))
+            });
+            if let Some(key) = key {
+                anyhow::bail!("{} is configured in etcd. Replace the URLs in etcd instead of --swap.", key);
+            }
+        }
+        anyhow::ensure!(
+            reshard_replica_url.is_some() || std::env::var("@{ db|upper_snake }@_REPLICA_DB_URL").is_err(),
+            "@{ db|upper_snake }@_RESHARD_REPLICA_DB_URL is required to replace @{ db|upper_snake }@_REPLICA_DB_URL."
+        );
+    }
+    let mut progress = ReshardProgress::load(&path, ReshardPhase::Copy, DbConn::reshard_num())?;
+    progress.merge_failures()?;
+    models::reshard(&mut progress, batch_size, interval).await?;
+    if swap {
+        progress.merge_failures()?;
+        anyhow::ensure!(
+            progress.failure_count() == 0,
+            "The dual write of {} rows failed after the copy. Run reshard again before the swap.",
+            progress.failure_count()
+        );
+        let env_path = Path::new(".env");
+        let mut content = std::fs::read_to_string(env_path)
+            .with_context(|| format!("Failed to read {}", env_path.display()))?;
+        let reshard_url = std::env::var("@{ db|upper_snake }@_RESHARD_DB_URL")?;
+        content = replace_env_var(&content, "@{ db|upper_snake }@_DB_URL", Some(&reshard_url));
+        content = replace_env_var(&content, "@{ db|upper_snake }@_RESHARD_DB_URL", None);
+        if let Some(url) = reshard_replica_url {
+            content = replace_env_var(&content, "@{ db|upper_snake }@_REPLICA_DB_URL", Some(&url));
+            content = replace_env_var(&content, "@{ db|upper_snake }@_RESHARD_REPLICA_DB_URL", None);
+        }
+        std::fs::write(env_path, content)?;
+        progress.remove_failure_log()?;
+        log::info!(target: "db_@{ db|snake }@", "The shard layout in {} has been replaced.", env_path.display());
+    }
+    Ok(())
+}
 @{-"\n"}@
\ No newline at end of file
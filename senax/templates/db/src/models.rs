@@ -2,9 +2,10 @@
 // Senax v@{ ""|senax_version }@
 
 use crate::connection::{DbConn, DbType};
-use ::anyhow::Result;
+use ::anyhow::{Context as _, Result};
 use ::futures::TryStreamExt;
 use ::senax_common::ShardId;
+use ::senax_common::shard::ReshardProgress;
 use ::std::collections::BTreeMap;
 use ::std::path::Path;
 use ::std::sync::Arc;
@@ -35,6 +36,22 @@ pub(crate) async fn start_test() -> Result<()> {
     Ok(())
 }
 
+pub(crate) async fn warm_up() -> Result<()> {
+    for shard_id in DbConn::shard_num_range() {
+        @%- for name in unified_joinable %@
+        _base_repo_@{ name }@::warm_up(shard_id).await?;
+        @%- endfor %@
+    }
+    Ok(())
+}
+
+pub(crate) async fn reshard(progress: &mut ReshardProgress, batch_size: usize, interval: ::std::time::Duration) -> Result<()> {
+    @%- for name in unified_joinable %@
+    _base_repo_@{ name }@::reshard(progress, batch_size, interval).await?;
+    @%- endfor %@
+    Ok(())
+}
+
 #[rustfmt::skip]
 pub(crate) async fn check() -> Result<()> {
     for shard_id in DbConn::shard_num_range() {
@@ -142,6 +159,35 @@ pub(crate) async fn exec_migrate(
     .await?;
     @%- endif %@
     @%- endif %@
+    @%- if config.is_mysql() %@
+    exec_ddl(
+        r#"
+            CREATE TABLE IF NOT EXISTS _lock (
+                name VARCHAR(255) NOT NULL PRIMARY KEY,
+                holder VARCHAR(64) NOT NULL,
+                token BIGINT UNSIGNED NOT NULL,
+                expires_at BIGINT NOT NULL
+            );
+        "#,
+        writer.as_mut(),
+    )
+    .await?;
+    @%- else %@
+    exec_ddl(
+        r#"
+            CREATE TABLE IF NOT EXISTS "_lock" (
+                "name" VARCHAR(255) NOT NULL PRIMARY KEY,
+                "holder" VARCHAR(64) NOT NULL,
+                "token" BIGINT NOT NULL,
+                "expires_at" BIGINT NOT NULL
+            );
+        "#,
+        writer.as_mut(),
+    )
+    .await?;
+    @%- endif %@
+    replace_squashed_migrations(writer.as_mut()).await?;
+    run_data_migrations(shard_id, writer.as_mut()).await?;
     loop {
         match sqlx::migrate!()
             .set_ignore_missing(ignore_missing)
@@ -167,4 +213,150 @@ pub(crate) async fn exec_migrate(
     }
     Ok(())
 }
+
+/// Replaces the records of the migrations squashed by `senax squash-migrations`
+/// with the baseline that took over the version of the last one.
+async fn replace_squashed_migrations(
+    conn: &mut <DbType as sqlx::Database>::Connection,
+) -> Result<()> {
+    use sqlx::migrate::Migrate as _;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+    for migration in sqlx::migrate!().iter() {
+        if migration.migration_type.is_down_migration()
+            || migration.description != "squashed baseline"
+        {
+            continue;
+        }
+        let squashed = applied.iter().any(|m| m.version < migration.version);
+        match applied.iter().find(|m| m.version == migration.version) {
+            Some(baseline) if baseline.checksum != migration.checksum => {
+                @%- if config.is_mysql() %@
+                let sql =
+                    "UPDATE _sqlx_migrations SET description = ?, checksum = ? WHERE version = ?";
+                @%- else %@
+                let sql =
+                    "UPDATE _sqlx_migrations SET description = $1, checksum = $2 WHERE version = $3";
+                @%- endif %@
+                sqlx::query(sql)
+                    .bind(&*migration.description)
+                    .bind(&*migration.checksum)
+                    .bind(migration.version)
+                    .execute(&mut *conn)
+                    .await?;
+                exec_ddl(
+                    &format!(
+                        "DELETE FROM _sqlx_migrations WHERE version < {};",
+                        migration.version
+                    ),
+                    &mut *conn,
+                )
+                .await?;
+            }
+            None if squashed => {
+                anyhow::bail!(
+                    "The migrations squashed into {} are partially applied.",
+                    migration.version
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Runs each pending data migration after the SQL migrations before it,
+/// and records its SQL file as applied in the same transaction.
+/// The data migrations at or below a squashed baseline are retired,
+/// since the baseline has taken over their records.
+async fn run_data_migrations(
+    shard_id: ShardId,
+    conn: &mut <DbType as sqlx::Database>::Connection,
+) -> Result<()> {
+    use sqlx::migrate::Migrate as _;
+    let applied: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    let mut migrator = sqlx::migrate!();
+    migrator.set_ignore_missing(true);
+    let migrations = migrator.migrations.clone();
+    let baseline = migrations
+        .iter()
+        .filter(|m| m.description == "squashed baseline")
+        .map(|m| m.version)
+        .max()
+        .unwrap_or_default();
+    for &version in crate::data_migrations::VERSIONS {
+        if version <= baseline || applied.contains(&version) {
+            continue;
+        }
+        let marker = migrations
+            .iter()
+            .find(|m| m.version == version && !m.migration_type.is_down_migration())
+            .with_context(|| format!("The SQL file of data migration {} is missing.", version))?;
+        migrator.migrations = migrations
+            .iter()
+            .filter(|m| m.version < version)
+            .cloned()
+            .collect::<Vec<_>>()
+            .into();
+        migrator.run(&mut *conn).await?;
+        let mut db_conn = DbConn::_new(shard_id);
+        db_conn.begin().await?;
+        crate::data_migrations::run(version, &mut db_conn)
+            .await
+            .with_context(|| format!("Data migration {} failed.", version))?;
+        @%- if config.is_mysql() %@
+        let sql = "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES (?, ?, TRUE, ?, -1)";
+        @%- else %@
+        let sql = "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) VALUES ($1, $2, TRUE, $3, -1)";
+        @%- endif %@
+        sqlx::query(sql)
+            .bind(marker.version)
+            .bind(&*marker.description)
+            .bind(&*marker.checksum)
+            .execute(db_conn.get_tx().await?.as_mut())
+            .await?;
+        db_conn.commit().await?;
+    }
+    Ok(())
+}
+
+/// Down file of a following file in a change set, which the first file reverts
+const CHANGE_SET_CONTINUATION: &str = "-- Reverted by the first file of this change set.";
+
+/// Reverts the last `steps` change sets.
+/// The files of a change set generated together are reverted as a whole.
+pub(crate) async fn exec_migrate_down(shard_id: ShardId, steps: u32) -> Result<()> {
+    let conn = DbConn::_new(shard_id);
+    let mut writer = conn.acquire_writer().await?;
+    let versions: Vec<i64> = sqlx::query_scalar(
+        "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version DESC",
+    )
+    .fetch_all(writer.as_mut())
+    .await?;
+    let continuations: Vec<i64> = sqlx::migrate!()
+        .iter()
+        .filter(|m| {
+            m.migration_type.is_down_migration() && m.sql.starts_with(CHANGE_SET_CONTINUATION)
+        })
+        .map(|m| m.version)
+        .collect();
+    let mut remaining = steps;
+    let mut target = 0;
+    for version in versions {
+        if remaining == 0 {
+            target = version;
+            break;
+        }
+        if !continuations.contains(&version) {
+            remaining -= 1;
+        }
+    }
+    sqlx::migrate!().undo(writer.as_mut(), target).await?;
+    Ok(())
+}
 @{-"\n"}@
\ No newline at end of file
@@ -2,6 +2,8 @@ use anyhow::{Result, ensure};
 use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
 use mimalloc::MiMalloc;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
@@ -32,6 +34,9 @@ enum Command {
         /// Note: Does not revert any applied DDL changes.
         #[clap(long)]
         remove_missing: bool,
+        /// Revert the last N applied migrations using their down scripts
+        #[clap(long, value_name = "N", conflicts_with = "clean")]
+        down: Option<u32>,
         /// Use test environment
         #[clap(short, long)]
         test: bool,
@@ -64,6 +69,26 @@ enum Command {
         #[clap(short, long)]
         test: bool,
     },
+    /// Copy rows to the shard layout of *_RESHARD_DB_URL
+    Reshard {
+        #[clap(long)]
+        db: Option<String>,
+        /// Number of rows read per batch
+        #[clap(long, default_value_t = 1000)]
+        batch_size: usize,
+        /// Wait time in milliseconds between batches
+        #[clap(long, default_value_t = 0)]
+        interval: u64,
+        /// Directory of the progress files used to resume an interrupted run
+        #[clap(long, default_value = ".")]
+        progress_dir: PathBuf,
+        /// Replace *_DB_URL in the .env file with the new layout after copying
+        #[clap(long)]
+        swap: bool,
+        /// Delete the rows left on their old shards after the new layout is deployed
+        #[clap(long, conflicts_with = "swap")]
+        cleanup: bool,
+    },
 }
 
 #[tokio::main]
@@ -79,6 +104,7 @@ async fn main() -> Result<()> {
             force_drop_db,
             ignore_missing,
             remove_missing,
+            down,
             test,
         } => {
             if clean {
@@ -88,6 +114,12 @@ async fn main() -> Result<()> {
                 );
             }
             let local = tokio::task::LocalSet::new();
+            if let Some(steps) = down {
+                local
+                    .run_until(async move { migrate_down(db.as_deref(), test, steps).await })
+                    .await?;
+                return Ok(());
+            }
             local
                 .run_until(async move {
                     migrate(
@@ -141,6 +173,25 @@ async fn main() -> Result<()> {
         Command::Check { test } => {
             check(test).await?;
         }
+        Command::Reshard {
+            db,
+            batch_size,
+            interval,
+            progress_dir,
+            swap,
+            cleanup,
+        } => {
+            ensure!(batch_size > 0, "batch_size must be greater than 0.");
+            reshard(
+                db.as_deref(),
+                &progress_dir,
+                batch_size,
+                Duration::from_millis(interval),
+                swap,
+                cleanup,
+            )
+            .await?;
+        }
     }
     Ok(())
 }
@@ -155,7 +206,24 @@ pub async fn migrate(db: Option<&str>, use_test: bool, clean: bool, ignore_missi
     // Do not modify this line. (migrate)
     let mut error = None;
     while let Some(res) = join_set.join_next().await {
-        if let Err(e) = res? 
+        if let Err(e) = res?
+            && let Some(e) = error.replace(e) {
+                log::error!("{}", e);
+            }
+    }
+    if let Some(e) = error {
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[rustfmt::skip]
+pub async fn migrate_down(db: Option<&str>, use_test: bool, steps: u32) -> Result<()> {
+    let mut join_set = tokio::task::JoinSet::new();
+    // Do not modify this line. (migrate_down)
+    let mut error = None;
+    while let Some(res) = join_set.join_next().await {
+        if let Err(e) = res?
             && let Some(e) = error.replace(e) {
                 log::error!("{}", e);
             }
@@ -183,4 +251,10 @@ pub async fn check(use_test: bool) -> Result<()> {
     )?;
     Ok(())
 }
+
+#[rustfmt::skip]
+pub async fn reshard(db: Option<&str>, progress_dir: &Path, batch_size: usize, interval: Duration, swap: bool, cleanup: bool) -> Result<()> {
+    // Do not modify this line. (reshard)
+    Ok(())
+}
 @{-"\n"}@
\ No newline at end of file
@@ -4,7 +4,9 @@ use anyhow::Result;
 use async_trait::async_trait;
 use domain::repository::Repository;
 use once_cell::sync::OnceCell;
+use senax_common::cache::db_cache::CacheMetrics;
 use std::sync::Arc;
+use std::time::SystemTime;
 use tokio::sync::Mutex;
 
 use crate::context::Ctx;
@@ -18,6 +20,13 @@ pub async fn clear_all_cache() {
     // Do not modify this line. (DbClearCache)
 }
 
+pub fn cache_metrics() -> Vec<(&'static str, CacheMetrics)> {
+    #[allow(unused_mut)]
+    let mut list = Vec::new();
+    // Do not modify this line. (DbCacheMetrics)
+    list
+}
+
 #[derive(Clone)]
 pub struct RepositoryImpl {
     _lock: Arc<Mutex<()>>,
@@ -45,6 +54,18 @@ impl RepositoryImpl {
     pub async fn lock(&self) -> tokio::sync::MutexGuard<'_, ()> {
         self._lock.lock().await
     }
+    /// Time until which reads go to the writer after a commit. Save it in the session to read your own writes.
+    #[allow(unused_mut)]
+    pub async fn writer_until(&self) -> Option<SystemTime> {
+        let mut until = None;
+        // Do not modify this line. (RepoWriterUntil)
+        until
+    }
+    /// Restores the time saved by `writer_until`.
+    #[allow(unused_variables)]
+    pub async fn set_writer_until(&self, until: Option<SystemTime>) {
+        // Do not modify this line. (RepoSetWriterUntil)
+    }
 }
 
 // Do not modify this line. (RepoStatic)
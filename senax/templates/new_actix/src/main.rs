@@ -36,6 +36,7 @@ mod auto_api;
 #[rustfmt::skip]
 mod db;
 mod gql_log;
+mod metrics;
 mod routes {
     pub mod root;
 }
@@ -49,6 +50,7 @@ const DEFAULT_HOST_PORT: &str = "0.0.0.0:8080";
 const DEFAULT_WORK_DIR: &str = "temp";
 const LINKER_PORT: &str = "LINKER_PORT";
 const LINKER_PASSWORD: &str = "LINKER_PASSWORD";
+const METRICS_PATH: &str = "METRICS_PATH";
 const SECRET_KEY: &str = "SECRET_KEY";
 #[cfg(feature = "session")]
 const SESSION_SECRET_KEY: &str = "SESSION_SECRET_KEY";
@@ -282,6 +284,7 @@ async fn main() -> Result<()> {
     let linker_port = env::var(LINKER_PORT).ok();
     info!("LINKER_PORT: {:?}", linker_port);
     let linker_pw = env::var(LINKER_PASSWORD).ok();
+    let metrics_path = env::var(METRICS_PATH).ok();
     let secret_key = env::var(SECRET_KEY).with_context(|| format!("{} required", SECRET_KEY))?;
     auth::SECRET
         .set(format!("{}{}", auth::INNER_KEY.as_str(), secret_key))
@@ -393,6 +396,15 @@ async fn main() -> Result<()> {
                     .app_data(Data::new(schema.clone()))
                     .to(auto_api::graphiql),
             );
+        let app = if let Some(path) = &metrics_path {
+            app.service(
+                web::resource(path.as_str())
+                    .guard(guard::Get())
+                    .to(metrics::handler),
+            )
+        } else {
+            app
+        };
         #[cfg(debug_assertions)]
         let app = app.service(
             utoipa_swagger_ui::SwaggerUi::new("/swagger-ui/{_:.*}")
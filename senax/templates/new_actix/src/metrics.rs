@@ -0,0 +1,130 @@
+use actix_web::{HttpResponse, Responder};
+use senax_common::cache::db_cache::{CacheMetrics, TypeCacheMetrics};
+use std::fmt::Write;
+
+/// Returns the cache metrics in the Prometheus text format.
+pub async fn handler() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(render(&crate::_base::db::cache_metrics()))
+}
+
+pub fn render(list: &[(&str, CacheMetrics)]) -> String {
+    let mut buf = String::new();
+    type Getter = fn(&CacheMetrics) -> u64;
+    let counters: [(&str, &str, &str, Getter); 12] = [
+        ("requests_total", "counter", "Cache requests", |m| {
+            m.cache_request_count
+        }),
+        ("fast_cache_hit_total", "counter", "Fast cache hits", |m| {
+            m.fast_cache_hit
+        }),
+        ("long_cache_hit_total", "counter", "Long cache hits", |m| {
+            m.long_cache_hit
+        }),
+        (
+            "short_cache_hit_total",
+            "counter",
+            "Short cache hits",
+            |m| m.short_cache_hit,
+        ),
+        (
+            "version_cache_hit_total",
+            "counter",
+            "Version cache hits",
+            |m| m.version_cache_hit,
+        ),
+        (
+            "storage_cache_hit_total",
+            "counter",
+            "Disk cache hits",
+            |m| m.storage_cache_hit,
+        ),
+        (
+            "remote_cache_hit_total",
+            "counter",
+            "Remote cache hits",
+            |m| m.remote_cache_hit,
+        ),
+        (
+            "long_cache_evicted_total",
+            "counter",
+            "Long cache evictions",
+            |m| m.long_cache_evicted,
+        ),
+        (
+            "short_cache_evicted_total",
+            "counter",
+            "Short cache evictions",
+            |m| m.short_cache_evicted,
+        ),
+        (
+            "version_cache_evicted_total",
+            "counter",
+            "Version cache evictions",
+            |m| m.version_cache_evicted,
+        ),
+        (
+            "long_cache_bytes",
+            "gauge",
+            "Weighted size of the long cache",
+            |m| m.long_cache_size,
+        ),
+        (
+            "short_cache_bytes",
+            "gauge",
+            "Weighted size of the short cache",
+            |m| m.short_cache_size,
+        ),
+    ];
+    for (name, kind, help, getter) in counters {
+        let _ = writeln!(buf, "# HELP senax_cache_{name} {help}");
+        let _ = writeln!(buf, "# TYPE senax_cache_{name} {kind}");
+        for (db, metrics) in list {
+            let _ = writeln!(buf, "senax_cache_{name}{{db=\"{db}\"}} {}", getter(metrics));
+        }
+    }
+
+    type TypeGetter = fn(&TypeCacheMetrics) -> u64;
+    let types: [(&str, &str, &str, TypeGetter); 5] = [
+        ("model_hit_total", "counter", "Cache hits by model", |m| {
+            m.hit
+        }),
+        (
+            "model_miss_total",
+            "counter",
+            "Cache misses by model",
+            |m| m.miss,
+        ),
+        (
+            "model_evicted_total",
+            "counter",
+            "Cache evictions by model",
+            |m| m.evicted,
+        ),
+        ("model_entries", "gauge", "Cached entries by model", |m| {
+            m.entries
+        }),
+        ("model_bytes", "gauge", "Cached bytes by model", |m| m.bytes),
+    ];
+    for (name, kind, help, getter) in types {
+        let _ = writeln!(buf, "# HELP senax_cache_{name} {help}");
+        let _ = writeln!(buf, "# TYPE senax_cache_{name} {kind}");
+        for (db, metrics) in list {
+            for m in &metrics.types {
+                let model = match m.name {
+                    Some(name) => name.to_string(),
+                    None => format!("{:x}", m.type_id),
+                };
+                let _ = writeln!(
+                    buf,
+                    "senax_cache_{name}{{db=\"{db}\",model=\"{model}\",shard=\"{}\"}} {}",
+                    m.shard_id,
+                    getter(m)
+                );
+            }
+        }
+    }
+    buf
+}
+@{-"\n"}@